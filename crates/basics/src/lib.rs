@@ -1,6 +1,9 @@
+#[cfg(windows)]
 pub mod d3d12;
+#[cfg(windows)]
 pub mod framework;
 pub mod math;
+#[cfg(windows)]
 pub mod renderer;
 
 pub struct Config {
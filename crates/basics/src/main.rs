@@ -1,6 +1,6 @@
 use basics::*;
 
-fn main() -> windows::core::Result<()> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     // change cwd so opening HLSL files will not fail
     let dir = std::env::current_exe()
         .inspect_err(|e| println!("Failed to get the path of this program: {e}"))
@@ -13,7 +13,17 @@ fn main() -> windows::core::Result<()> {
     }
 
     let config = crate::parse_args(std::env::args());
-    framework::run(&config)?;
+    run(&config)
+}
+
+#[cfg(windows)]
+fn run(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    framework::run(config)?;
     d3d12::device::report_live_objects()?;
     Ok(())
 }
+
+#[cfg(not(windows))]
+fn run(_config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    Err("the renderer requires Windows".into())
+}
@@ -92,7 +92,7 @@ extern "system" fn wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM
             return LRESULT::default();
         }
         WM_KEYUP => {
-            if wparam.0 == VK_SPACE.0.into() {
+            if wparam.0 == usize::from(VK_SPACE.0) {
                 let mut framework = get_framework_ptr(hwnd);
                 if let Some(framework) = unsafe { framework.as_mut() } {
                     framework.renderer.toggle_rendering_mode();
@@ -1,3 +1,4 @@
+use sandbox_core::mesh::primitives;
 use windows::Win32::Graphics::{Direct3D12::*, Dxgi::Common::*};
 
 use super::d3d12::{device::*, resource, view::Srv};
@@ -23,14 +24,25 @@ pub struct Mesh {
 impl Mesh {
     pub fn load(device: &mut Device) -> windows::core::Result<Self> {
         const VERTEX_SIZE: usize = std::mem::size_of::<f32>() * 3;
-        let vertex_buffer_size = std::mem::size_of_val(&MESH_VERTICES);
+
+        // a 2x2x2 cube whose faces have their own vertices, colored by their positions
+        let cube = primitives::cuboid(Vec3::splat(2.0));
+        let vertex_count = cube.positions().len();
+        let mesh_vertices: Vec<[f32; 3]> = cube
+            .positions()
+            .iter()
+            .map(|p| p.to_array())
+            .chain(cube.positions().iter().map(|&p| (p * 0.5 + 0.5).to_array()))
+            .collect();
+        let mesh_indices = cube.indices();
+        let vertex_buffer_size = std::mem::size_of_val(mesh_vertices.as_slice());
 
         let vertices = resource::create_buffer_with_data(
             device,
             D3D12_HEAP_TYPE_UPLOAD,
             D3D12_RESOURCE_FLAG_NONE,
             D3D12_RESOURCE_STATE_COMMON,
-            &MESH_VERTICES,
+            &mesh_vertices,
             "Intermediate vertex buffer",
         )?;
 
@@ -51,13 +63,13 @@ impl Mesh {
         let vertex_buffer_address = unsafe { vertex_buffer.GetGPUVirtualAddress() };
         let position_vbv = D3D12_VERTEX_BUFFER_VIEW {
             BufferLocation: vertex_buffer_address,
-            SizeInBytes: (VERTEX_SIZE * MESH_VERTEX_COUNT) as u32,
+            SizeInBytes: (VERTEX_SIZE * vertex_count) as u32,
             StrideInBytes: VERTEX_SIZE as u32,
         };
 
         let color_vbv = D3D12_VERTEX_BUFFER_VIEW {
-            BufferLocation: vertex_buffer_address + (VERTEX_SIZE * MESH_VERTEX_COUNT) as u64,
-            SizeInBytes: (VERTEX_SIZE * MESH_VERTEX_COUNT) as u32,
+            BufferLocation: vertex_buffer_address + (VERTEX_SIZE * vertex_count) as u64,
+            SizeInBytes: (VERTEX_SIZE * vertex_count) as u32,
             StrideInBytes: VERTEX_SIZE as u32,
         };
 
@@ -67,8 +79,8 @@ impl Mesh {
             Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
             Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
                 Buffer: D3D12_BUFFER_SRV {
-                    FirstElement: MESH_VERTEX_COUNT as u64,
-                    NumElements: MESH_VERTEX_COUNT as u32,
+                    FirstElement: vertex_count as u64,
+                    NumElements: vertex_count as u32,
                     StructureByteStride: (std::mem::size_of::<f32>() * 3) as u32,
                     Flags: D3D12_BUFFER_SRV_FLAG_NONE,
                 },
@@ -76,14 +88,14 @@ impl Mesh {
         };
         let color_srv = device.create_srv(Some(&vertex_buffer), Some(&color_srv_desc));
 
-        let index_buffer_size = std::mem::size_of_val(&MESH_INDICES);
-        let index_size = std::mem::size_of_val(&MESH_INDICES[0]);
+        let index_buffer_size = std::mem::size_of_val(mesh_indices);
+        let index_size = std::mem::size_of_val(&mesh_indices[0]);
         let indices = resource::create_buffer_with_data(
             device,
             D3D12_HEAP_TYPE_UPLOAD,
             D3D12_RESOURCE_FLAG_NONE,
             D3D12_RESOURCE_STATE_COMMON,
-            &MESH_INDICES,
+            mesh_indices,
             "intermediate index buffer",
         )?;
 
@@ -116,7 +128,7 @@ impl Mesh {
             Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
                 Buffer: D3D12_BUFFER_SRV {
                     FirstElement: 0,
-                    NumElements: MESH_INDICES.len() as u32,
+                    NumElements: mesh_indices.len() as u32,
                     StructureByteStride: index_size as u32,
                     Flags: D3D12_BUFFER_SRV_FLAG_NONE,
                 },
@@ -130,14 +142,14 @@ impl Mesh {
         command_queue.wait_fence(fence_value);
 
         Ok(Mesh {
-            vertex_count: MESH_VERTEX_COUNT,
+            vertex_count,
             vertex_format: DXGI_FORMAT_R32G32B32_FLOAT,
             vertex_buffer,
             position_vbv,
             color_vbv,
             color_srv,
 
-            index_count: MESH_INDICES.len(),
+            index_count: mesh_indices.len(),
             index_buffer,
             ibv,
             index_srv,
@@ -176,37 +188,3 @@ impl Mesh {
         &self.color_srv
     }
 }
-
-const MESH_VERTEX_COUNT: usize = 8;
-#[rustfmt::skip]
-const MESH_VERTICES: [[f32; 3]; MESH_VERTEX_COUNT * 2] = [
-    // position
-    [-1.0, -1.0, -1.0],
-    [-1.0,  1.0, -1.0],
-    [ 1.0,  1.0, -1.0],
-    [ 1.0, -1.0, -1.0],
-    [-1.0, -1.0,  1.0],
-    [-1.0,  1.0,  1.0],
-    [ 1.0,  1.0,  1.0],
-    [ 1.0, -1.0,  1.0],
-    // color
-    [0.0, 0.0, 0.0],
-    [0.0, 1.0, 0.0],
-    [1.0, 1.0, 0.0],
-    [1.0, 0.0, 0.0],
-    [0.0, 0.0, 1.0],
-    [0.0, 1.0, 1.0],
-    [1.0, 1.0, 1.0],
-    [1.0, 0.0, 1.0] 
-];
-
-// winding order is clockwise
-#[rustfmt::skip]
-const MESH_INDICES: [u32; 36] = [
-    0, 1, 2, 0, 2, 3,
-    4, 6, 5, 4, 7, 6,
-    4, 5, 1, 4, 1, 0,
-    3, 2, 6, 3, 6, 7,
-    1, 5, 6, 1, 6, 2,
-    4, 0, 3, 4, 3, 7,
-];
@@ -1,4 +1,6 @@
+#[cfg(windows)]
 pub mod framework;
+#[cfg(windows)]
 pub mod gfx;
 
 pub struct Config {
@@ -9,10 +9,14 @@ fn main() {
     println!("cargo::rerun-if-changed=shaders/");
 
     copy_assets("bunny.obj");
+    copy_assets("scene.json");
 
-    copy_dll("dxcompiler.dll");
-    copy_dll("dxil.dll");
-    copy_dll("WinPixEventRuntime.dll");
+    // the DLLs are only loaded by the D3D12 renderer
+    if std::env::var_os("CARGO_CFG_WINDOWS").is_some() {
+        copy_dll("dxcompiler.dll");
+        copy_dll("dxil.dll");
+        copy_dll("WinPixEventRuntime.dll");
+    }
 
     copy_shaders("rasterization.hlsl");
     copy_shaders("raytracing.hlsl");
@@ -2,19 +2,19 @@
 #include "light.hlsl"
 #include "brdf.hlsl"
 
-// must match MAX_MESH_DATA_COUNT in raytracing.rs
-#define MAX_MESH_DATA_COUNT 2
-
+// must match MeshData in raytracing.rs
 struct MeshData {
     uint index_buffer_id;
     uint position_buffer_id;
     uint normal_buffer_id;
-    uint1 reserved;
+    uint uv_buffer_id; // 0xffffffff if the mesh has no UVs, nor tangents
+    uint tangent_buffer_id;
+    uint index_size; // 2 or 4 bytes
+    uint first_index; // of the LOD in the BLAS
+    uint reserved;
 };
 
 cbuffer ResourceHandles : register(b0) {
-    MeshData mesh_data_list[MAX_MESH_DATA_COUNT];
-
     SpotLight light;
 
     uint camera_id;
@@ -23,9 +23,30 @@ cbuffer ResourceHandles : register(b0) {
     uint transform_buffer_id;
 
     uint material_buffer_id;
-    uint3 pad;
+    uint mesh_data_buffer_id; // MeshData of each geometry of the BLAS
+    uint2 pad;
 };
 
+// The index buffer is a raw buffer of 16-bit or 32-bit indices
+uint3 load_indices(MeshData mesh_data, uint primitive_index) {
+    ByteAddressBuffer index_buffer = ResourceDescriptorHeap[mesh_data.index_buffer_id];
+
+    uint first_index = mesh_data.first_index + 3 * primitive_index;
+
+    if (mesh_data.index_size == 4) {
+        return index_buffer.Load3(4 * first_index);
+    }
+
+    // loads the two aligned words that contain the three 16-bit indices
+    uint offset = 2 * first_index;
+    uint2 words = index_buffer.Load2(offset & ~3);
+    if ((offset & 3) == 0) {
+        return uint3(words.x & 0xffff, words.x >> 16, words.y & 0xffff);
+    } else {
+        return uint3(words.x >> 16, words.y & 0xffff, words.y >> 16);
+    }
+}
+
 RayDesc generate_primary_ray(Camera camera, uint2 id) {
     float2 uv = (id + 0.5) / float2(camera.viewport_size);
     float2 dst = uv * float2(2, -2) + float2(-1, 1);
@@ -59,13 +80,12 @@ bool trace_ray(RaytracingAccelerationStructure raytracing_scene, RayDesc ray, ou
     }
 
     uint geometry_index = query.CommittedGeometryIndex();
-    MeshData mesh_data = mesh_data_list[geometry_index];
+    StructuredBuffer<MeshData> mesh_data_buffer = ResourceDescriptorHeap[mesh_data_buffer_id];
+    MeshData mesh_data = mesh_data_buffer[geometry_index];
 
     uint primitive_index = query.CommittedPrimitiveIndex();
 
-    StructuredBuffer<uint> index_buffer = ResourceDescriptorHeap[mesh_data.index_buffer_id];
-    uint index_buf_offset = 3 * primitive_index;
-    uint3 indices = uint3(index_buffer[index_buf_offset], index_buffer[index_buf_offset+1], index_buffer[index_buf_offset+2]);
+    uint3 indices = load_indices(mesh_data, primitive_index);
     
     hitpoint.position = ray.Origin + query.CommittedRayT() * ray.Direction;
 
@@ -5,9 +5,13 @@ use windows::Win32::{
     UI::WindowsAndMessaging::*,
 };
 
-use super::gfx::{renderer::Renderer, scene::Scene};
+use super::gfx::{
+    renderer::Renderer,
+    resource,
+    scene::{Scene, SceneDesc},
+};
 
-pub fn run(config: &crate::Config) -> windows::core::Result<()> {
+pub fn run(config: &crate::Config, scene: &SceneDesc) -> windows::core::Result<()> {
     let name = windows::core::s!("window");
 
     let instance = unsafe { GetModuleHandleA(None)? };
@@ -51,11 +55,7 @@ pub fn run(config: &crate::Config) -> windows::core::Result<()> {
         )?
     };
 
-    _framework = Some(Framework::new(
-        hwnd,
-        config.client_width(),
-        config.client_height(),
-    ));
+    _framework = Some(Framework::new(hwnd, config, scene));
 
     unsafe {
         let _ = ShowWindow(hwnd, SW_SHOW);
@@ -71,9 +71,23 @@ pub fn run(config: &crate::Config) -> windows::core::Result<()> {
         }
     }
 
+    // while the scene and the renderer are still alive
+    write_memory_report(config);
+
     Ok(())
 }
 
+fn write_memory_report(config: &crate::Config) {
+    let report = resource::memory_report();
+    print!("{report}");
+
+    if let Some(path) = config.memory_report_output() {
+        if let Err(e) = std::fs::write(path, report.to_json()) {
+            println!("Failed to write {}: {e}", path.display());
+        }
+    }
+}
+
 extern "system" fn wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     match msg {
         WM_CREATE => {
@@ -92,11 +106,13 @@ extern "system" fn wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM
             return LRESULT::default();
         }
         WM_KEYUP => {
-            if wparam.0 == VK_SPACE.0.into() {
+            if wparam.0 == usize::from(VK_SPACE.0) {
                 let mut framework = get_framework_ptr(hwnd);
                 if let Some(framework) = unsafe { framework.as_mut() } {
                     framework.renderer.toggle_rendering_mode();
                 }
+            } else if wparam.0 == usize::from(VK_M.0) {
+                print!("{}", resource::memory_report());
             }
 
             return LRESULT::default();
@@ -122,9 +138,12 @@ struct Framework {
 }
 
 impl Framework {
-    fn new(hwnd: HWND, screen_width: u32, screen_height: u32) -> Self {
-        let mut renderer = Renderer::new(hwnd, screen_width, screen_height);
-        let scene = Scene::build(renderer.device_mut(), screen_width, screen_height).unwrap();
+    fn new(hwnd: HWND, config: &crate::Config, scene: &SceneDesc) -> Self {
+        let mut renderer = Renderer::new(hwnd, config);
+        let screen_width = config.client_width();
+        let screen_height = config.client_height();
+        let scene =
+            Scene::build(renderer.device_mut(), scene, screen_width, screen_height).unwrap();
         Self { scene, renderer }
     }
 
@@ -136,3 +155,10 @@ impl Framework {
         self.renderer.render(&mut self.scene)
     }
 }
+
+impl Drop for Framework {
+    fn drop(&mut self) {
+        // the device waits for the GPU before it is dropped after the scene
+        self.scene.release(self.renderer.device_mut());
+    }
+}
@@ -1,10 +1,18 @@
+pub mod frame;
+pub mod graph;
+pub mod reference;
+#[cfg(windows)]
 pub mod renderer;
 pub mod scene;
 
 mod d3d12;
+#[cfg(windows)]
 pub use d3d12::device::report_live_objects;
+pub use d3d12::{backend, memory, release, resource, ring, staging, state, timeline, view};
 
-mod light;
-mod math;
+mod brdf;
 mod mesh;
+#[cfg(windows)]
 mod shadow_map;
+
+use sandbox_core::{light, math};
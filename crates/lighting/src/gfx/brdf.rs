@@ -0,0 +1,236 @@
+// CPU counterpart of crates/lighting/shaders/brdf.hlsl
+// Keep the two in sync so that the reference renderer matches the GPU output
+
+use super::math::*;
+use super::mesh::Material;
+
+fn sqr(x: f32) -> f32 {
+    x * x
+}
+
+fn saturate(v: Vec3) -> Vec3 {
+    v.clamp(Vec3::ZERO, Vec3::ONE)
+}
+
+fn microfacet_distribution(cos_theta: f32, alpha: f32) -> f32 {
+    let alpha2 = sqr(alpha);
+    let cos2_theta = sqr(cos_theta);
+    let cos4_theta = sqr(cos2_theta);
+    let tan2_theta = (1.0 / cos2_theta) - 1.0;
+    alpha2 / (std::f32::consts::PI * cos4_theta * sqr(alpha2 + tan2_theta))
+}
+
+fn pow5(x: f32) -> f32 {
+    let x2 = x * x;
+    x2 * x2 * x
+}
+
+fn pow6(x: f32) -> f32 {
+    // same as pow6 in brdf.hlsl, which actually evaluates x^5
+    let x2 = x * x;
+    x2 * x2 * x
+}
+
+fn fresnel(cos_theta: f32, r: Vec3, h: Vec3) -> Vec3 {
+    //  Lazanyi-Schlick approximation with Naty Hoffman's reparametrization
+    // https://doi.org/10.2312/mam.20191305
+    // https://renderwonk.com/publications/mam2019/
+    let a = (823543.0 / 46656.0) * (r - h) + (49.0 / 6.0) * (1.0 - r);
+    let f = r + (1.0 - r) * pow5(1.0 - cos_theta) - a * cos_theta * pow6(1.0 - cos_theta);
+    saturate(f)
+}
+
+fn lambda(cos_theta: f32, alpha: f32) -> f32 {
+    // Understanding the Masking-Shadowing Function in Microfacet-Based BRDFs [Heitz et al. 2014]
+    // https://jcgt.org/published/0003/02/03/
+    // Equation (72)
+    let tan2_theta = (1.0 / sqr(cos_theta)) - 1.0;
+    ((1.0 + sqr(alpha) * tan2_theta).sqrt() - 1.0) / 2.0
+}
+
+fn shadowing_factor(cos_theta_i: f32, cos_theta_o: f32, alpha: f32) -> f32 {
+    // Understanding the Masking-Shadowing Function in Microfacet-Based BRDFs [Heitz et al. 2014]
+    // https://jcgt.org/published/0003/02/03/
+    // Equation (99)
+    1.0 / (lambda(cos_theta_o, alpha) + lambda(cos_theta_i, alpha) + 1.0)
+}
+
+pub fn eval_specular(incoming: Vec3, outgoing: Vec3, normal: Vec3, material: &Material) -> Vec3 {
+    let cos_theta_i = incoming.dot(normal).clamp(0.0, 1.0);
+    if cos_theta_i == 0.0 {
+        return Vec3::ZERO;
+    }
+
+    let cos_theta_o = outgoing.dot(normal).clamp(0.0, 1.0);
+    if cos_theta_o == 0.0 {
+        return Vec3::ZERO;
+    }
+
+    let half_vector = (incoming + outgoing).normalize();
+
+    let cos_theta_m = half_vector.dot(normal).clamp(0.0, 1.0);
+    if cos_theta_m == 0.0 {
+        return Vec3::ZERO;
+    }
+
+    let alpha = sqr(material.roughness);
+
+    let f = fresnel(
+        cos_theta_i,
+        material.specular_reflectance,
+        material.specular_tint,
+    );
+    let g = shadowing_factor(cos_theta_i, cos_theta_o, alpha);
+    let d = microfacet_distribution(cos_theta_m, alpha);
+
+    (f * g * d) / (4.0 * cos_theta_i * cos_theta_o)
+}
+
+pub fn eval_diffuse(material: &Material) -> Vec3 {
+    material.base_color / std::f32::consts::PI
+}
+
+pub fn eval_brdf(incoming: Vec3, outgoing: Vec3, normal: Vec3, material: &Material) -> Vec3 {
+    material.metallic * eval_specular(incoming, outgoing, normal, material)
+        + (1.0 - material.metallic) * eval_diffuse(material)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    fn material(metallic: f32, roughness: f32) -> Material {
+        Material {
+            base_color: Vec3::new(0.8, 0.5, 0.2),
+            metallic,
+            specular_reflectance: Vec3::new(0.9, 0.6, 0.3),
+            roughness,
+            specular_tint: Vec3::ONE,
+            pad: 0,
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} != {expected}"
+        );
+    }
+
+    // The reflected energy toward the normal, the integral of f * cos(theta_i) over the
+    // hemisphere, which does not depend on the azimuth of the incoming direction then
+    fn albedo_at_normal_incidence(material: &Material) -> Vec3 {
+        let steps = 20000;
+        let d_theta = 0.5 * PI / steps as f32;
+        (0..steps)
+            .map(|i| {
+                let theta = (i as f32 + 0.5) * d_theta;
+                let (sin, cos) = theta.sin_cos();
+                let incoming = Vec3::new(sin, cos, 0.0);
+                eval_brdf(incoming, Vec3::Y, Vec3::Y, material) * cos * sin * 2.0 * PI * d_theta
+            })
+            .sum()
+    }
+
+    #[test]
+    fn lambert() {
+        let material = material(0.0, 0.5);
+        let expected = material.base_color / PI;
+        assert_eq!(eval_diffuse(&material), expected);
+
+        // the same in every direction
+        let incoming = Vec3::new(0.6, 0.8, 0.0);
+        let outgoing = Vec3::new(0.0, 0.6, -0.8);
+        assert_eq!(eval_brdf(incoming, outgoing, Vec3::Y, &material), expected);
+
+        let albedo = albedo_at_normal_incidence(&material);
+        assert!(albedo.abs_diff_eq(material.base_color, 1e-4), "{albedo}");
+    }
+
+    #[test]
+    fn ggx_distribution() {
+        // 1 / (pi * alpha^2) facing the normal
+        for alpha in [0.1, 0.25, 0.5, 1.0] {
+            assert_close(
+                microfacet_distribution(1.0, alpha),
+                1.0 / (PI * alpha * alpha),
+                1e-3,
+            );
+        }
+
+        // the projected microfacet area is that of the surface
+        let alpha = 0.25;
+        let steps = 20000;
+        let d_theta = 0.5 * PI / steps as f32;
+        let projected_area: f32 = (0..steps)
+            .map(|i| {
+                let theta = (i as f32 + 0.5) * d_theta;
+                let (sin, cos) = theta.sin_cos();
+                microfacet_distribution(cos, alpha) * cos * sin * 2.0 * PI * d_theta
+            })
+            .sum();
+        assert_close(projected_area, 1.0, 1e-3);
+    }
+
+    #[test]
+    fn fresnel_and_shadowing() {
+        let r = Vec3::new(0.9, 0.6, 0.3);
+        // the reflectance at normal incidence, and white at grazing angles
+        assert!(fresnel(1.0, r, Vec3::ONE).abs_diff_eq(r, 1e-6));
+        assert!(fresnel(0.0, r, Vec3::ONE).abs_diff_eq(Vec3::ONE, 1e-6));
+
+        assert_eq!(shadowing_factor(1.0, 1.0, 0.5), 1.0);
+        let grazing = shadowing_factor(0.1, 1.0, 0.5);
+        assert!(grazing > 0.0 && grazing < 1.0, "{grazing}");
+    }
+
+    #[test]
+    fn specular_at_normal_incidence() {
+        // F = reflectance, G = 1 and D = 1 / (pi * alpha^2) in the mirror direction
+        let material = material(1.0, 0.5);
+        let alpha = 0.25;
+        let expected = material.specular_reflectance / (4.0 * PI * alpha * alpha);
+        let actual = eval_brdf(Vec3::Y, Vec3::Y, Vec3::Y, &material);
+        assert!(actual.abs_diff_eq(expected, 1e-4), "{actual} != {expected}");
+
+        // nothing below the surface
+        let below = Vec3::new(0.6, -0.8, 0.0);
+        assert_eq!(
+            eval_specular(below, Vec3::Y, Vec3::Y, &material),
+            Vec3::ZERO
+        );
+        assert_eq!(
+            eval_specular(Vec3::Y, below, Vec3::Y, &material),
+            Vec3::ZERO
+        );
+    }
+
+    #[test]
+    fn specular_energy() {
+        // a white metal reflects everything when it is smooth, and loses the light scattered more
+        // than once between the microfacets as it gets rougher, which is 8.4% at a roughness of
+        // 0.5 by integrating the same GGX and height-correlated Smith terms separately
+        let albedo = |roughness| {
+            let mut material = material(1.0, roughness);
+            material.specular_reflectance = Vec3::ONE;
+            albedo_at_normal_incidence(&material).x
+        };
+        assert_close(albedo(0.1), 1.0, 2e-3);
+        assert_close(albedo(0.5), 0.9158, 1e-3);
+
+        let albedos = [0.1, 0.3, 0.5, 0.7, 1.0].map(albedo);
+        assert!(albedos.windows(2).all(|w| w[1] < w[0]), "{albedos:?}");
+    }
+
+    #[test]
+    fn metallic_mix() {
+        let incoming = Vec3::new(0.6, 0.8, 0.0);
+        let outgoing = Vec3::new(-0.6, 0.8, 0.0);
+        let m = material(0.25, 0.5);
+        let expected =
+            0.25 * eval_specular(incoming, outgoing, Vec3::Y, &m) + 0.75 * eval_diffuse(&m);
+        assert!(eval_brdf(incoming, outgoing, Vec3::Y, &m).abs_diff_eq(expected, 1e-6));
+    }
+}
@@ -1,10 +1,26 @@
+pub mod backend;
+#[cfg(windows)]
 pub mod barrier;
+#[cfg(windows)]
 pub mod command;
+#[cfg(windows)]
 pub mod device;
+pub mod fence;
+pub mod memory;
+#[cfg(windows)]
 pub mod pix;
+#[cfg(windows)]
 pub mod pso;
+#[cfg(windows)]
 pub mod raytracing;
+pub mod release;
 pub mod resource;
+pub mod ring;
+#[cfg(windows)]
 pub mod shader;
+pub mod staging;
+pub mod state;
+pub mod timeline;
+#[cfg(windows)]
 pub mod util;
 pub mod view;
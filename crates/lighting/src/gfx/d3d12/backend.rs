@@ -0,0 +1,295 @@
+// Abstraction over Device/Queue so that the frame logic can be driven without a GPU
+// `D3D12Backend` forwards to the actual device, and `MockBackend` records every call
+
+#[cfg(windows)]
+pub mod d3d12;
+pub mod mock;
+
+use std::ops::{BitAnd, BitOr, BitOrAssign};
+
+use super::fence::FenceValue;
+use super::view::{Cbv, Srv, Uav};
+
+// The values of the following types are identical to the D3D12/DXGI counterparts
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ResourceStates(pub i32);
+
+impl ResourceStates {
+    pub const COMMON: Self = Self(0);
+    pub const VERTEX_AND_CONSTANT_BUFFER: Self = Self(0x1);
+    pub const INDEX_BUFFER: Self = Self(0x2);
+    pub const RENDER_TARGET: Self = Self(0x4);
+    pub const UNORDERED_ACCESS: Self = Self(0x8);
+    pub const DEPTH_WRITE: Self = Self(0x10);
+    pub const DEPTH_READ: Self = Self(0x20);
+    pub const NON_PIXEL_SHADER_RESOURCE: Self = Self(0x40);
+    pub const PIXEL_SHADER_RESOURCE: Self = Self(0x80);
+    pub const INDIRECT_ARGUMENT: Self = Self(0x200);
+    pub const COPY_DEST: Self = Self(0x400);
+    pub const COPY_SOURCE: Self = Self(0x800);
+    pub const RAYTRACING_ACCELERATION_STRUCTURE: Self = Self(0x40_0000);
+    pub const ALL_SHADER_RESOURCE: Self = Self(0xc0);
+    pub const GENERIC_READ: Self = Self(0xac3);
+    pub const PRESENT: Self = Self(0);
+
+    // states in which the GPU only reads the resource, which can be combined with each other
+    const READ: Self = Self(0xae3);
+
+    pub fn contains(self, other: Self) -> bool {
+        (self.0 & other.0) == other.0
+    }
+
+    pub fn is_read_only(self) -> bool {
+        self.0 != 0 && (self.0 & !Self::READ.0) == 0
+    }
+}
+
+impl BitOr for ResourceStates {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for ResourceStates {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for ResourceStates {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ResourceFlags(pub i32);
+
+impl ResourceFlags {
+    pub const NONE: Self = Self(0);
+    pub const ALLOW_RENDER_TARGET: Self = Self(0x1);
+    pub const ALLOW_DEPTH_STENCIL: Self = Self(0x2);
+    pub const ALLOW_UNORDERED_ACCESS: Self = Self(0x4);
+    pub const DENY_SHADER_RESOURCE: Self = Self(0x8);
+    pub const RAYTRACING_ACCELERATION_STRUCTURE: Self = Self(0x100);
+
+    pub fn contains(self, other: Self) -> bool {
+        (self.0 & other.0) == other.0
+    }
+}
+
+impl BitOr for ResourceFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Format(pub i32);
+
+impl Format {
+    pub const UNKNOWN: Self = Self(0);
+    pub const R32G32B32A32_FLOAT: Self = Self(2);
+    pub const R32G32B32_FLOAT: Self = Self(6);
+    pub const R32G32_FLOAT: Self = Self(16);
+    pub const R8G8B8A8_UNORM: Self = Self(28);
+    pub const D32_FLOAT: Self = Self(40);
+    pub const R32_FLOAT: Self = Self(41);
+    pub const R32_UINT: Self = Self(42);
+    pub const D16_UNORM: Self = Self(55);
+    pub const R16_UNORM: Self = Self(56);
+    pub const R16_UINT: Self = Self(57);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HeapType {
+    Default,
+    Upload,
+    Readback,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueueType {
+    Graphics,
+    Copy,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferDesc {
+    pub size: u64,
+    pub heap_type: HeapType,
+    pub flags: ResourceFlags,
+    pub init_state: ResourceStates,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture2dDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: Format,
+    pub flags: ResourceFlags,
+    pub init_state: ResourceStates,
+    pub clear_value: Option<ClearValue>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClearValue {
+    Color([f32; 4]),
+    Depth(f32),
+}
+
+/// Structured buffer view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferView {
+    pub first_element: u64,
+    pub element_count: u32,
+    pub stride: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexBufferView {
+    pub location: u64,
+    pub size: u32,
+    pub stride: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexBufferView {
+    pub location: u64,
+    pub size: u32,
+    pub format: Format,
+}
+
+/// Selects every subresource in a transition barrier
+pub const ALL_SUBRESOURCES: u32 = 0xffffffff;
+
+#[derive(Debug)]
+pub enum Barrier<'a, R> {
+    Transition {
+        resource: &'a R,
+        /// Subresource index, or `ALL_SUBRESOURCES`
+        subresource: u32,
+        before: ResourceStates,
+        after: ResourceStates,
+    },
+    Uav {
+        resource: &'a R,
+    },
+}
+
+pub trait Backend {
+    type Error: std::fmt::Debug;
+
+    type Resource: Clone;
+    type Rtv: Clone;
+    type Dsv;
+    type Pipeline;
+    type RootSignature;
+    type CommandList;
+
+    // resources
+    fn create_buffer(
+        &mut self,
+        desc: &BufferDesc,
+        name: &str,
+    ) -> Result<Self::Resource, Self::Error>;
+
+    fn create_texture2d(
+        &mut self,
+        desc: &Texture2dDesc,
+        name: &str,
+    ) -> Result<Self::Resource, Self::Error>;
+
+    /// Writes `data` to the beginning of a buffer in an UPLOAD heap
+    fn write_buffer<T: Copy>(
+        &mut self,
+        buffer: &Self::Resource,
+        data: &[T],
+    ) -> Result<(), Self::Error>;
+
+    // descriptor views
+    fn create_buffer_srv(&mut self, buffer: &Self::Resource, view: &BufferView) -> Srv;
+    fn create_texture2d_srv(&mut self, texture: &Self::Resource, format: Format) -> Srv;
+    fn create_texture2d_uav(&mut self, texture: &Self::Resource, format: Format) -> Uav;
+    fn create_cbv(&mut self, buffer: &Self::Resource, size: u32) -> Cbv;
+    fn create_rtv(&mut self, texture: &Self::Resource) -> Self::Rtv;
+    fn create_dsv(&mut self, texture: &Self::Resource, format: Format) -> Self::Dsv;
+
+    // command recording
+    fn begin_commands(&mut self, queue: QueueType) -> Result<Self::CommandList, Self::Error>;
+    fn begin_event(&mut self, cmd: &Self::CommandList, name: &str);
+    fn end_event(&mut self, cmd: &Self::CommandList);
+    fn resource_barrier(&mut self, cmd: &Self::CommandList, barriers: &[Barrier<Self::Resource>]);
+    /// Binds the CBV/SRV/UAV heap used for bindless access
+    fn bind_view_heap(&mut self, cmd: &Self::CommandList);
+    fn clear_render_target(&mut self, cmd: &Self::CommandList, rtv: &Self::Rtv, color: [f32; 4]);
+    fn clear_depth(&mut self, cmd: &Self::CommandList, dsv: &Self::Dsv, depth: f32);
+    fn set_render_targets(
+        &mut self,
+        cmd: &Self::CommandList,
+        rtv: Option<&Self::Rtv>,
+        dsv: Option<&Self::Dsv>,
+    );
+    /// Sets both the viewport and the scissor rect to cover `width` x `height` pixels
+    fn set_viewport(&mut self, cmd: &Self::CommandList, width: u32, height: u32);
+    fn set_graphics_pipeline(
+        &mut self,
+        cmd: &Self::CommandList,
+        pipeline: &Self::Pipeline,
+        root_signature: &Self::RootSignature,
+    );
+    fn set_compute_pipeline(
+        &mut self,
+        cmd: &Self::CommandList,
+        pipeline: &Self::Pipeline,
+        root_signature: &Self::RootSignature,
+    );
+    fn set_graphics_constants(&mut self, cmd: &Self::CommandList, index: u32, constants: &[u32]);
+    fn set_compute_constants(&mut self, cmd: &Self::CommandList, index: u32, constants: &[u32]);
+    fn set_vertex_buffers(&mut self, cmd: &Self::CommandList, views: &[VertexBufferView]);
+    fn set_index_buffer(&mut self, cmd: &Self::CommandList, view: &IndexBufferView);
+    fn draw(&mut self, cmd: &Self::CommandList, vertex_count: u32, instance_count: u32);
+    fn draw_indexed(&mut self, cmd: &Self::CommandList, index_count: u32, instance_count: u32);
+    fn dispatch(&mut self, cmd: &Self::CommandList, x: u32, y: u32, z: u32);
+    fn copy_resource(
+        &mut self,
+        cmd: &Self::CommandList,
+        dst: &Self::Resource,
+        src: &Self::Resource,
+    );
+    fn copy_buffer_region(
+        &mut self,
+        cmd: &Self::CommandList,
+        dst: &Self::Resource,
+        dst_offset: u64,
+        src: &Self::Resource,
+        src_offset: u64,
+        size: u64,
+    );
+
+    // fences
+    fn execute(
+        &mut self,
+        queue: QueueType,
+        cmd: Self::CommandList,
+    ) -> Result<FenceValue, Self::Error>;
+    fn signal(&mut self, queue: QueueType) -> FenceValue;
+    fn is_fence_completed(&self, queue: QueueType, fence_value: FenceValue) -> bool;
+    fn wait_fence(&mut self, queue: QueueType, fence_value: FenceValue);
+    /// Makes `queue` wait for `fence_value` of `other` before executing the commands submitted after
+    /// this, without blocking the CPU
+    fn wait_queue(&mut self, queue: QueueType, other: QueueType, fence_value: FenceValue);
+
+    // presentation
+    fn back_buffer(&self) -> Self::Resource;
+    fn back_buffer_rtv(&self) -> Self::Rtv;
+    /// Executes `cmd` on the graphics queue and presents the back buffer
+    fn present(&mut self, cmd: Self::CommandList) -> Result<(), Self::Error>;
+}
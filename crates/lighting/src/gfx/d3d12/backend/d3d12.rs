@@ -0,0 +1,516 @@
+use windows::Win32::Foundation::{FALSE, RECT};
+use windows::Win32::Graphics::{Direct3D::*, Direct3D12::*, Dxgi::Common::*};
+
+use super::*;
+use crate::gfx::d3d12::{
+    barrier,
+    command::Context,
+    device::Device,
+    pix::{pix_color, Pix},
+    resource,
+    view::{Dsv, Rtv},
+};
+
+pub struct D3D12Backend<'a> {
+    device: &'a mut Device,
+    pix: Option<&'a Pix>,
+}
+
+impl<'a> D3D12Backend<'a> {
+    pub fn new(device: &'a mut Device, pix: Option<&'a Pix>) -> Self {
+        Self { device, pix }
+    }
+
+    pub fn device(&self) -> &Device {
+        self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut Device {
+        self.device
+    }
+
+    fn queue(&self, queue: QueueType) -> &crate::gfx::d3d12::command::Queue {
+        match queue {
+            QueueType::Graphics => self.device.gfx_queue(),
+            QueueType::Copy => self.device.copy_queue(),
+        }
+    }
+
+    fn queue_mut(&mut self, queue: QueueType) -> &mut crate::gfx::d3d12::command::Queue {
+        match queue {
+            QueueType::Graphics => self.device.gfx_queue_mut(),
+            QueueType::Copy => self.device.copy_queue_mut(),
+        }
+    }
+}
+
+impl From<ResourceStates> for D3D12_RESOURCE_STATES {
+    fn from(states: ResourceStates) -> Self {
+        D3D12_RESOURCE_STATES(states.0)
+    }
+}
+
+impl From<ResourceFlags> for D3D12_RESOURCE_FLAGS {
+    fn from(flags: ResourceFlags) -> Self {
+        D3D12_RESOURCE_FLAGS(flags.0)
+    }
+}
+
+impl From<Format> for DXGI_FORMAT {
+    fn from(format: Format) -> Self {
+        DXGI_FORMAT(format.0)
+    }
+}
+
+impl From<DXGI_FORMAT> for Format {
+    fn from(format: DXGI_FORMAT) -> Self {
+        Format(format.0)
+    }
+}
+
+impl From<HeapType> for D3D12_HEAP_TYPE {
+    fn from(heap_type: HeapType) -> Self {
+        match heap_type {
+            HeapType::Default => D3D12_HEAP_TYPE_DEFAULT,
+            HeapType::Upload => D3D12_HEAP_TYPE_UPLOAD,
+            HeapType::Readback => D3D12_HEAP_TYPE_READBACK,
+        }
+    }
+}
+
+impl From<D3D12_RESOURCE_FLAGS> for ResourceFlags {
+    fn from(flags: D3D12_RESOURCE_FLAGS) -> Self {
+        ResourceFlags(flags.0)
+    }
+}
+
+/// Fails for CUSTOM and GPU_UPLOAD heaps, which the backend does not create
+impl TryFrom<D3D12_HEAP_TYPE> for HeapType {
+    type Error = D3D12_HEAP_TYPE;
+
+    fn try_from(heap_type: D3D12_HEAP_TYPE) -> Result<Self, Self::Error> {
+        match heap_type {
+            D3D12_HEAP_TYPE_DEFAULT => Ok(HeapType::Default),
+            D3D12_HEAP_TYPE_UPLOAD => Ok(HeapType::Upload),
+            D3D12_HEAP_TYPE_READBACK => Ok(HeapType::Readback),
+            _ => Err(heap_type),
+        }
+    }
+}
+
+impl From<&D3D12_VERTEX_BUFFER_VIEW> for VertexBufferView {
+    fn from(view: &D3D12_VERTEX_BUFFER_VIEW) -> Self {
+        VertexBufferView {
+            location: view.BufferLocation,
+            size: view.SizeInBytes,
+            stride: view.StrideInBytes,
+        }
+    }
+}
+
+impl From<&VertexBufferView> for D3D12_VERTEX_BUFFER_VIEW {
+    fn from(view: &VertexBufferView) -> Self {
+        D3D12_VERTEX_BUFFER_VIEW {
+            BufferLocation: view.location,
+            SizeInBytes: view.size,
+            StrideInBytes: view.stride,
+        }
+    }
+}
+
+impl From<&D3D12_INDEX_BUFFER_VIEW> for IndexBufferView {
+    fn from(view: &D3D12_INDEX_BUFFER_VIEW) -> Self {
+        IndexBufferView {
+            location: view.BufferLocation,
+            size: view.SizeInBytes,
+            format: view.Format.into(),
+        }
+    }
+}
+
+impl From<&IndexBufferView> for D3D12_INDEX_BUFFER_VIEW {
+    fn from(view: &IndexBufferView) -> Self {
+        D3D12_INDEX_BUFFER_VIEW {
+            BufferLocation: view.location,
+            SizeInBytes: view.size,
+            Format: view.format.into(),
+        }
+    }
+}
+
+impl Backend for D3D12Backend<'_> {
+    type Error = windows::core::Error;
+
+    type Resource = ID3D12Resource;
+    type Rtv = Rtv;
+    type Dsv = Dsv;
+    type Pipeline = ID3D12PipelineState;
+    type RootSignature = ID3D12RootSignature;
+    type CommandList = Context;
+
+    fn create_buffer(
+        &mut self,
+        desc: &BufferDesc,
+        name: &str,
+    ) -> windows::core::Result<ID3D12Resource> {
+        resource::create_buffer(
+            self.device,
+            desc.size,
+            desc.heap_type.into(),
+            desc.flags.into(),
+            desc.init_state.into(),
+            name,
+        )
+    }
+
+    fn create_texture2d(
+        &mut self,
+        desc: &Texture2dDesc,
+        name: &str,
+    ) -> windows::core::Result<ID3D12Resource> {
+        let clear_value = desc.clear_value.map(|value| match value {
+            ClearValue::Color(color) => D3D12_CLEAR_VALUE {
+                Format: desc.format.into(),
+                Anonymous: D3D12_CLEAR_VALUE_0 { Color: color },
+            },
+            ClearValue::Depth(depth) => D3D12_CLEAR_VALUE {
+                Format: desc.format.into(),
+                Anonymous: D3D12_CLEAR_VALUE_0 {
+                    DepthStencil: D3D12_DEPTH_STENCIL_VALUE {
+                        Depth: depth,
+                        Stencil: 0,
+                    },
+                },
+            },
+        });
+
+        resource::create_texture2d(
+            self.device,
+            (desc.width, desc.height),
+            desc.format.into(),
+            desc.flags.into(),
+            desc.init_state.into(),
+            clear_value.as_ref().map(|v| v as *const _),
+            name,
+        )
+    }
+
+    fn write_buffer<T: Copy>(
+        &mut self,
+        buffer: &ID3D12Resource,
+        data: &[T],
+    ) -> windows::core::Result<()> {
+        let mut dst = std::ptr::null_mut();
+        unsafe {
+            buffer.Map(0, None, Some(&mut dst))?;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), dst as *mut T, data.len());
+            buffer.Unmap(0, None);
+        }
+
+        Ok(())
+    }
+
+    fn create_buffer_srv(&mut self, buffer: &ID3D12Resource, view: &BufferView) -> Srv {
+        let desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: DXGI_FORMAT_UNKNOWN,
+            ViewDimension: D3D12_SRV_DIMENSION_BUFFER,
+            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                Buffer: D3D12_BUFFER_SRV {
+                    FirstElement: view.first_element,
+                    NumElements: view.element_count,
+                    StructureByteStride: view.stride,
+                    Flags: D3D12_BUFFER_SRV_FLAG_NONE,
+                },
+            },
+        };
+        self.device.create_srv(Some(buffer), Some(&desc))
+    }
+
+    fn create_texture2d_srv(&mut self, texture: &ID3D12Resource, format: Format) -> Srv {
+        let desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: format.into(),
+            ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                Texture2D: D3D12_TEX2D_SRV {
+                    MostDetailedMip: 0,
+                    MipLevels: 1,
+                    PlaneSlice: 0,
+                    ResourceMinLODClamp: 0.0,
+                },
+            },
+        };
+        self.device.create_srv(Some(texture), Some(&desc))
+    }
+
+    fn create_texture2d_uav(&mut self, texture: &ID3D12Resource, format: Format) -> Uav {
+        let desc = D3D12_UNORDERED_ACCESS_VIEW_DESC {
+            Format: format.into(),
+            ViewDimension: D3D12_UAV_DIMENSION_TEXTURE2D,
+            Anonymous: D3D12_UNORDERED_ACCESS_VIEW_DESC_0 {
+                Texture2D: D3D12_TEX2D_UAV {
+                    MipSlice: 0,
+                    PlaneSlice: 0,
+                },
+            },
+        };
+        self.device.create_uav(texture, Some(&desc))
+    }
+
+    fn create_cbv(&mut self, buffer: &ID3D12Resource, size: u32) -> Cbv {
+        let desc = D3D12_CONSTANT_BUFFER_VIEW_DESC {
+            BufferLocation: unsafe { buffer.GetGPUVirtualAddress() },
+            SizeInBytes: size,
+        };
+        self.device.create_cbv(Some(&desc))
+    }
+
+    fn create_rtv(&mut self, texture: &ID3D12Resource) -> Rtv {
+        self.device.create_rtv(texture)
+    }
+
+    fn create_dsv(&mut self, texture: &ID3D12Resource, format: Format) -> Dsv {
+        let desc = D3D12_DEPTH_STENCIL_VIEW_DESC {
+            Format: format.into(),
+            ViewDimension: D3D12_DSV_DIMENSION_TEXTURE2D,
+            Flags: D3D12_DSV_FLAG_NONE,
+            Anonymous: D3D12_DEPTH_STENCIL_VIEW_DESC_0 {
+                Texture2D: D3D12_TEX2D_DSV { MipSlice: 0 },
+            },
+        };
+        self.device.create_dsv(texture, Some(&desc))
+    }
+
+    fn begin_commands(&mut self, queue: QueueType) -> windows::core::Result<Context> {
+        self.queue_mut(queue).request_command_ctx()
+    }
+
+    fn begin_event(&mut self, cmd: &Context, name: &str) {
+        if let Some(pix) = self.pix {
+            pix.push_event(cmd.command_list(), pix_color(0, 255, 0), name);
+        }
+    }
+
+    fn end_event(&mut self, cmd: &Context) {
+        if let Some(pix) = self.pix {
+            pix.pop_event(cmd.command_list());
+        }
+    }
+
+    fn resource_barrier(&mut self, cmd: &Context, barriers: &[Barrier<ID3D12Resource>]) {
+        let barriers: Vec<_> = barriers
+            .iter()
+            .map(|b| match b {
+                Barrier::Transition {
+                    resource,
+                    subresource,
+                    before,
+                    after,
+                } => barrier::transition_subresource(
+                    resource,
+                    *subresource,
+                    (*before).into(),
+                    (*after).into(),
+                ),
+                Barrier::Uav { resource } => barrier::uav(resource),
+            })
+            .collect();
+
+        unsafe { cmd.command_list().ResourceBarrier(&barriers) };
+    }
+
+    fn bind_view_heap(&mut self, cmd: &Context) {
+        let heaps = [Some(self.device.view_heap().clone())];
+        unsafe { cmd.command_list().SetDescriptorHeaps(&heaps) };
+    }
+
+    fn clear_render_target(&mut self, cmd: &Context, rtv: &Rtv, color: [f32; 4]) {
+        unsafe {
+            cmd.command_list()
+                .ClearRenderTargetView(rtv.cpu_handle(), &color, None)
+        };
+    }
+
+    fn clear_depth(&mut self, cmd: &Context, dsv: &Dsv, depth: f32) {
+        let rects = [];
+        unsafe {
+            cmd.command_list().ClearDepthStencilView(
+                dsv.cpu_handle(),
+                D3D12_CLEAR_FLAG_DEPTH,
+                depth,
+                0,
+                &rects,
+            )
+        };
+    }
+
+    fn set_render_targets(&mut self, cmd: &Context, rtv: Option<&Rtv>, dsv: Option<&Dsv>) {
+        let rtv = rtv.map(|rtv| rtv.cpu_handle());
+        let dsv = dsv.map(|dsv| dsv.cpu_handle());
+        unsafe {
+            cmd.command_list().OMSetRenderTargets(
+                rtv.is_some() as u32,
+                rtv.as_ref().map(|h| h as *const _),
+                FALSE,
+                dsv.as_ref().map(|h| h as *const _),
+            )
+        };
+    }
+
+    fn set_viewport(&mut self, cmd: &Context, width: u32, height: u32) {
+        let rect = RECT {
+            left: 0,
+            top: 0,
+            right: width.try_into().unwrap(),
+            bottom: height.try_into().unwrap(),
+        };
+
+        let viewport = D3D12_VIEWPORT {
+            TopLeftX: 0.0,
+            TopLeftY: 0.0,
+            Width: width as f32,
+            Height: height as f32,
+            MinDepth: D3D12_MIN_DEPTH,
+            MaxDepth: D3D12_MAX_DEPTH,
+        };
+
+        unsafe {
+            cmd.command_list().RSSetViewports(&[viewport]);
+            cmd.command_list().RSSetScissorRects(&[rect]);
+        }
+    }
+
+    fn set_graphics_pipeline(
+        &mut self,
+        cmd: &Context,
+        pipeline: &ID3D12PipelineState,
+        root_signature: &ID3D12RootSignature,
+    ) {
+        unsafe {
+            cmd.command_list().SetPipelineState(pipeline);
+            cmd.command_list().SetGraphicsRootSignature(root_signature);
+            // every graphics pipeline in this crate draws triangle lists
+            cmd.command_list()
+                .IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+        }
+    }
+
+    fn set_compute_pipeline(
+        &mut self,
+        cmd: &Context,
+        pipeline: &ID3D12PipelineState,
+        root_signature: &ID3D12RootSignature,
+    ) {
+        unsafe {
+            cmd.command_list().SetPipelineState(pipeline);
+            cmd.command_list().SetComputeRootSignature(root_signature);
+        }
+    }
+
+    fn set_graphics_constants(&mut self, cmd: &Context, index: u32, constants: &[u32]) {
+        unsafe {
+            cmd.command_list().SetGraphicsRoot32BitConstants(
+                index,
+                constants.len() as u32,
+                constants.as_ptr() as _,
+                0,
+            )
+        };
+    }
+
+    fn set_compute_constants(&mut self, cmd: &Context, index: u32, constants: &[u32]) {
+        unsafe {
+            cmd.command_list().SetComputeRoot32BitConstants(
+                index,
+                constants.len() as u32,
+                constants.as_ptr() as _,
+                0,
+            )
+        };
+    }
+
+    fn set_vertex_buffers(&mut self, cmd: &Context, views: &[VertexBufferView]) {
+        let views: Vec<D3D12_VERTEX_BUFFER_VIEW> = views.iter().map(|v| v.into()).collect();
+        let views = if views.is_empty() {
+            None
+        } else {
+            Some(views.as_slice())
+        };
+        unsafe { cmd.command_list().IASetVertexBuffers(0, views) };
+    }
+
+    fn set_index_buffer(&mut self, cmd: &Context, view: &IndexBufferView) {
+        let view: D3D12_INDEX_BUFFER_VIEW = view.into();
+        unsafe { cmd.command_list().IASetIndexBuffer(Some(&view)) };
+    }
+
+    fn draw(&mut self, cmd: &Context, vertex_count: u32, instance_count: u32) {
+        unsafe {
+            cmd.command_list()
+                .DrawInstanced(vertex_count, instance_count, 0, 0)
+        };
+    }
+
+    fn draw_indexed(&mut self, cmd: &Context, index_count: u32, instance_count: u32) {
+        unsafe {
+            cmd.command_list()
+                .DrawIndexedInstanced(index_count, instance_count, 0, 0, 0)
+        };
+    }
+
+    fn dispatch(&mut self, cmd: &Context, x: u32, y: u32, z: u32) {
+        unsafe { cmd.command_list().Dispatch(x, y, z) };
+    }
+
+    fn copy_resource(&mut self, cmd: &Context, dst: &ID3D12Resource, src: &ID3D12Resource) {
+        unsafe { cmd.command_list().CopyResource(dst, src) };
+    }
+
+    fn copy_buffer_region(
+        &mut self,
+        cmd: &Context,
+        dst: &ID3D12Resource,
+        dst_offset: u64,
+        src: &ID3D12Resource,
+        src_offset: u64,
+        size: u64,
+    ) {
+        unsafe {
+            cmd.command_list()
+                .CopyBufferRegion(dst, dst_offset, src, src_offset, size)
+        };
+    }
+
+    fn execute(&mut self, queue: QueueType, cmd: Context) -> windows::core::Result<FenceValue> {
+        self.queue_mut(queue).execute_commands(cmd)
+    }
+
+    fn signal(&mut self, queue: QueueType) -> FenceValue {
+        self.queue_mut(queue).signal()
+    }
+
+    fn is_fence_completed(&self, queue: QueueType, fence_value: FenceValue) -> bool {
+        self.queue(queue).is_fence_completed(fence_value)
+    }
+
+    fn wait_fence(&mut self, queue: QueueType, fence_value: FenceValue) {
+        self.queue(queue).wait_fence(fence_value);
+    }
+
+    fn wait_queue(&mut self, queue: QueueType, other: QueueType, fence_value: FenceValue) {
+        self.queue(queue).wait_queue(self.queue(other), fence_value);
+    }
+
+    fn back_buffer(&self) -> ID3D12Resource {
+        self.device.back_buffer().clone()
+    }
+
+    fn back_buffer_rtv(&self) -> Rtv {
+        self.device.back_buffer_rtv().clone()
+    }
+
+    fn present(&mut self, cmd: Context) -> windows::core::Result<()> {
+        self.device.present_frame(cmd)
+    }
+}
@@ -0,0 +1,800 @@
+use std::convert::Infallible;
+
+use super::super::timeline::{SyncPoint, Timeline};
+use super::*;
+
+const BACK_BUFFER_COUNT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MockResource {
+    id: u32,
+}
+
+impl MockResource {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MockRtv {
+    pub resource: MockResource,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MockDsv {
+    pub resource: MockResource,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockPipeline {
+    pub name: String,
+}
+
+impl MockPipeline {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.into() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockRootSignature {
+    pub name: String,
+}
+
+impl MockRootSignature {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.into() }
+    }
+}
+
+// intentionally not Clone, a command list is consumed by `execute` like `Context`
+#[derive(Debug, PartialEq, Eq)]
+pub struct MockCommandList {
+    id: u32,
+    queue: QueueType,
+}
+
+impl MockCommandList {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn queue(&self) -> QueueType {
+        self.queue
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordedBarrier {
+    Transition {
+        resource: MockResource,
+        subresource: u32,
+        before: ResourceStates,
+        after: ResourceStates,
+    },
+    Uav {
+        resource: MockResource,
+    },
+}
+
+/// A call to `MockBackend`, `cmd` is the id of the command list it was recorded into
+#[derive(Debug, Clone, PartialEq)]
+pub enum Call {
+    CreateBuffer {
+        resource: MockResource,
+        desc: BufferDesc,
+        name: String,
+    },
+    CreateTexture2d {
+        resource: MockResource,
+        desc: Texture2dDesc,
+        name: String,
+    },
+    WriteBuffer {
+        buffer: MockResource,
+        size: usize,
+    },
+    CreateBufferSrv {
+        buffer: MockResource,
+        view: BufferView,
+        handle: u32,
+    },
+    CreateTexture2dSrv {
+        texture: MockResource,
+        format: Format,
+        handle: u32,
+    },
+    CreateTexture2dUav {
+        texture: MockResource,
+        format: Format,
+        handle: u32,
+    },
+    CreateCbv {
+        buffer: MockResource,
+        size: u32,
+        handle: u32,
+    },
+    CreateRtv {
+        texture: MockResource,
+    },
+    CreateDsv {
+        texture: MockResource,
+        format: Format,
+    },
+    BeginCommands {
+        cmd: u32,
+        queue: QueueType,
+    },
+    BeginEvent {
+        cmd: u32,
+        name: String,
+    },
+    EndEvent {
+        cmd: u32,
+    },
+    ResourceBarrier {
+        cmd: u32,
+        barriers: Vec<RecordedBarrier>,
+    },
+    BindViewHeap {
+        cmd: u32,
+        heap: u32,
+    },
+    ClearRenderTarget {
+        cmd: u32,
+        target: MockResource,
+        color: [f32; 4],
+    },
+    ClearDepth {
+        cmd: u32,
+        target: MockResource,
+        depth: f32,
+    },
+    SetRenderTargets {
+        cmd: u32,
+        rtv: Option<MockResource>,
+        dsv: Option<MockResource>,
+    },
+    SetViewport {
+        cmd: u32,
+        width: u32,
+        height: u32,
+    },
+    SetGraphicsPipeline {
+        cmd: u32,
+        pipeline: String,
+        root_signature: String,
+    },
+    SetComputePipeline {
+        cmd: u32,
+        pipeline: String,
+        root_signature: String,
+    },
+    SetGraphicsConstants {
+        cmd: u32,
+        index: u32,
+        constants: Vec<u32>,
+    },
+    SetComputeConstants {
+        cmd: u32,
+        index: u32,
+        constants: Vec<u32>,
+    },
+    SetVertexBuffers {
+        cmd: u32,
+        views: Vec<VertexBufferView>,
+    },
+    SetIndexBuffer {
+        cmd: u32,
+        view: IndexBufferView,
+    },
+    Draw {
+        cmd: u32,
+        vertex_count: u32,
+        instance_count: u32,
+    },
+    DrawIndexed {
+        cmd: u32,
+        index_count: u32,
+        instance_count: u32,
+    },
+    Dispatch {
+        cmd: u32,
+        x: u32,
+        y: u32,
+        z: u32,
+    },
+    CopyResource {
+        cmd: u32,
+        dst: MockResource,
+        src: MockResource,
+    },
+    CopyBufferRegion {
+        cmd: u32,
+        dst: MockResource,
+        dst_offset: u64,
+        src: MockResource,
+        src_offset: u64,
+        size: u64,
+    },
+    Execute {
+        cmd: u32,
+        queue: QueueType,
+        fence_value: FenceValue,
+    },
+    Signal {
+        queue: QueueType,
+        fence_value: FenceValue,
+    },
+    WaitFence {
+        queue: QueueType,
+        fence_value: FenceValue,
+    },
+    WaitQueue {
+        queue: QueueType,
+        other: QueueType,
+        fence_value: FenceValue,
+    },
+    Present {
+        cmd: u32,
+        back_buffer: MockResource,
+    },
+}
+
+#[derive(Default)]
+struct MockQueue {
+    submitted: u64,
+    completed: u64,
+}
+
+/// Backend that records every call instead of talking to a GPU
+///
+/// Submitted work never completes on its own, call `complete_fence` or `wait_fence` to simulate
+/// the GPU catching up
+/// The waits between queues are recorded in `timeline`, so the order of the work on different
+/// queues can be checked
+/// Like the D3D12 view heap, the view heap is replaced by one twice as large when it is full
+pub struct MockBackend {
+    calls: Vec<Call>,
+
+    resource_names: Vec<String>,
+    view_count: u32,
+    view_capacity: u32,
+    view_heap: u32,
+    command_list_count: u32,
+
+    gfx_queue: MockQueue,
+    copy_queue: MockQueue,
+    timeline: Timeline,
+
+    back_buffers: [MockResource; BACK_BUFFER_COUNT],
+    back_buffer_index: usize,
+    frame_fences: [FenceValue; BACK_BUFFER_COUNT],
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::with_view_capacity(u32::MAX)
+    }
+
+    pub fn with_view_capacity(view_capacity: u32) -> Self {
+        let resource_names = (0..BACK_BUFFER_COUNT)
+            .map(|i| format!("MockBackend::back_buffers[{i}]"))
+            .collect();
+
+        Self {
+            calls: Vec::new(),
+            resource_names,
+            view_count: 0,
+            view_capacity,
+            view_heap: 0,
+            command_list_count: 0,
+            gfx_queue: Default::default(),
+            copy_queue: Default::default(),
+            timeline: Timeline::new(),
+            back_buffers: std::array::from_fn(|i| MockResource { id: i as u32 }),
+            back_buffer_index: 0,
+            frame_fences: Default::default(),
+        }
+    }
+
+    /// The number of times the view heap has been replaced to grow
+    pub fn view_heap(&self) -> u32 {
+        self.view_heap
+    }
+
+    pub fn calls(&self) -> &[Call] {
+        &self.calls
+    }
+
+    pub fn take_calls(&mut self) -> Vec<Call> {
+        std::mem::take(&mut self.calls)
+    }
+
+    pub fn resource_name(&self, resource: MockResource) -> &str {
+        &self.resource_names[resource.id as usize]
+    }
+
+    /// All barriers in the order they were recorded
+    pub fn barriers(&self) -> impl Iterator<Item = &RecordedBarrier> {
+        self.calls.iter().flat_map(|call| match call {
+            Call::ResourceBarrier { barriers, .. } => barriers.as_slice(),
+            _ => &[],
+        })
+    }
+
+    /// Simulates the GPU finishing the work up to `fence_value`
+    /// The fences the queue waits for before that are completed first
+    pub fn complete_fence(&mut self, queue: QueueType, fence_value: FenceValue) {
+        let point = SyncPoint { queue, fence_value };
+        let dependencies: Vec<_> = self.timeline.dependencies(point).collect();
+        for dependency in dependencies {
+            if !self.is_fence_completed(dependency.queue, dependency.fence_value) {
+                self.complete_fence(dependency.queue, dependency.fence_value);
+            }
+        }
+
+        let queue = self.queue_mut(queue);
+        assert!(
+            fence_value.v <= queue.submitted,
+            "The fence value {} has not been signaled",
+            fence_value.v
+        );
+        queue.completed = queue.completed.max(fence_value.v);
+    }
+
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+
+    pub fn completed_fence(&self, queue: QueueType) -> FenceValue {
+        FenceValue {
+            v: self.queue(queue).completed,
+        }
+    }
+
+    fn queue(&self, queue: QueueType) -> &MockQueue {
+        match queue {
+            QueueType::Graphics => &self.gfx_queue,
+            QueueType::Copy => &self.copy_queue,
+        }
+    }
+
+    fn queue_mut(&mut self, queue: QueueType) -> &mut MockQueue {
+        match queue {
+            QueueType::Graphics => &mut self.gfx_queue,
+            QueueType::Copy => &mut self.copy_queue,
+        }
+    }
+
+    fn new_resource(&mut self, name: &str) -> MockResource {
+        let resource = MockResource {
+            id: self.resource_names.len() as u32,
+        };
+        self.resource_names.push(name.into());
+        resource
+    }
+
+    fn new_view_handle(&mut self) -> u32 {
+        if self.view_count == self.view_capacity {
+            self.view_capacity *= 2;
+            self.view_heap += 1;
+        }
+
+        let handle = self.view_count;
+        self.view_count += 1;
+        handle
+    }
+}
+
+impl Backend for MockBackend {
+    type Error = Infallible;
+
+    type Resource = MockResource;
+    type Rtv = MockRtv;
+    type Dsv = MockDsv;
+    type Pipeline = MockPipeline;
+    type RootSignature = MockRootSignature;
+    type CommandList = MockCommandList;
+
+    fn create_buffer(&mut self, desc: &BufferDesc, name: &str) -> Result<MockResource, Infallible> {
+        let resource = self.new_resource(name);
+        self.calls.push(Call::CreateBuffer {
+            resource,
+            desc: desc.clone(),
+            name: name.into(),
+        });
+        Ok(resource)
+    }
+
+    fn create_texture2d(
+        &mut self,
+        desc: &Texture2dDesc,
+        name: &str,
+    ) -> Result<MockResource, Infallible> {
+        let resource = self.new_resource(name);
+        self.calls.push(Call::CreateTexture2d {
+            resource,
+            desc: desc.clone(),
+            name: name.into(),
+        });
+        Ok(resource)
+    }
+
+    fn write_buffer<T: Copy>(
+        &mut self,
+        buffer: &MockResource,
+        data: &[T],
+    ) -> Result<(), Infallible> {
+        self.calls.push(Call::WriteBuffer {
+            buffer: *buffer,
+            size: std::mem::size_of_val(data),
+        });
+        Ok(())
+    }
+
+    fn create_buffer_srv(&mut self, buffer: &MockResource, view: &BufferView) -> Srv {
+        let handle = self.new_view_handle();
+        self.calls.push(Call::CreateBufferSrv {
+            buffer: *buffer,
+            view: *view,
+            handle,
+        });
+        Srv {
+            handle,
+            generation: 0,
+        }
+    }
+
+    fn create_texture2d_srv(&mut self, texture: &MockResource, format: Format) -> Srv {
+        let handle = self.new_view_handle();
+        self.calls.push(Call::CreateTexture2dSrv {
+            texture: *texture,
+            format,
+            handle,
+        });
+        Srv {
+            handle,
+            generation: 0,
+        }
+    }
+
+    fn create_texture2d_uav(&mut self, texture: &MockResource, format: Format) -> Uav {
+        let handle = self.new_view_handle();
+        self.calls.push(Call::CreateTexture2dUav {
+            texture: *texture,
+            format,
+            handle,
+        });
+        Uav {
+            handle,
+            generation: 0,
+        }
+    }
+
+    fn create_cbv(&mut self, buffer: &MockResource, size: u32) -> Cbv {
+        let handle = self.new_view_handle();
+        self.calls.push(Call::CreateCbv {
+            buffer: *buffer,
+            size,
+            handle,
+        });
+        Cbv {
+            handle,
+            generation: 0,
+        }
+    }
+
+    fn create_rtv(&mut self, texture: &MockResource) -> MockRtv {
+        self.calls.push(Call::CreateRtv { texture: *texture });
+        MockRtv { resource: *texture }
+    }
+
+    fn create_dsv(&mut self, texture: &MockResource, format: Format) -> MockDsv {
+        self.calls.push(Call::CreateDsv {
+            texture: *texture,
+            format,
+        });
+        MockDsv { resource: *texture }
+    }
+
+    fn begin_commands(&mut self, queue: QueueType) -> Result<MockCommandList, Infallible> {
+        let id = self.command_list_count;
+        self.command_list_count += 1;
+        self.calls.push(Call::BeginCommands { cmd: id, queue });
+        Ok(MockCommandList { id, queue })
+    }
+
+    fn begin_event(&mut self, cmd: &MockCommandList, name: &str) {
+        self.calls.push(Call::BeginEvent {
+            cmd: cmd.id,
+            name: name.into(),
+        });
+    }
+
+    fn end_event(&mut self, cmd: &MockCommandList) {
+        self.calls.push(Call::EndEvent { cmd: cmd.id });
+    }
+
+    fn resource_barrier(&mut self, cmd: &MockCommandList, barriers: &[Barrier<MockResource>]) {
+        let barriers = barriers
+            .iter()
+            .map(|b| match b {
+                Barrier::Transition {
+                    resource,
+                    subresource,
+                    before,
+                    after,
+                } => RecordedBarrier::Transition {
+                    resource: **resource,
+                    subresource: *subresource,
+                    before: *before,
+                    after: *after,
+                },
+                Barrier::Uav { resource } => RecordedBarrier::Uav {
+                    resource: **resource,
+                },
+            })
+            .collect();
+
+        self.calls.push(Call::ResourceBarrier {
+            cmd: cmd.id,
+            barriers,
+        });
+    }
+
+    fn bind_view_heap(&mut self, cmd: &MockCommandList) {
+        self.calls.push(Call::BindViewHeap {
+            cmd: cmd.id,
+            heap: self.view_heap,
+        });
+    }
+
+    fn clear_render_target(&mut self, cmd: &MockCommandList, rtv: &MockRtv, color: [f32; 4]) {
+        self.calls.push(Call::ClearRenderTarget {
+            cmd: cmd.id,
+            target: rtv.resource,
+            color,
+        });
+    }
+
+    fn clear_depth(&mut self, cmd: &MockCommandList, dsv: &MockDsv, depth: f32) {
+        self.calls.push(Call::ClearDepth {
+            cmd: cmd.id,
+            target: dsv.resource,
+            depth,
+        });
+    }
+
+    fn set_render_targets(
+        &mut self,
+        cmd: &MockCommandList,
+        rtv: Option<&MockRtv>,
+        dsv: Option<&MockDsv>,
+    ) {
+        self.calls.push(Call::SetRenderTargets {
+            cmd: cmd.id,
+            rtv: rtv.map(|v| v.resource),
+            dsv: dsv.map(|v| v.resource),
+        });
+    }
+
+    fn set_viewport(&mut self, cmd: &MockCommandList, width: u32, height: u32) {
+        self.calls.push(Call::SetViewport {
+            cmd: cmd.id,
+            width,
+            height,
+        });
+    }
+
+    fn set_graphics_pipeline(
+        &mut self,
+        cmd: &MockCommandList,
+        pipeline: &MockPipeline,
+        root_signature: &MockRootSignature,
+    ) {
+        self.calls.push(Call::SetGraphicsPipeline {
+            cmd: cmd.id,
+            pipeline: pipeline.name.clone(),
+            root_signature: root_signature.name.clone(),
+        });
+    }
+
+    fn set_compute_pipeline(
+        &mut self,
+        cmd: &MockCommandList,
+        pipeline: &MockPipeline,
+        root_signature: &MockRootSignature,
+    ) {
+        self.calls.push(Call::SetComputePipeline {
+            cmd: cmd.id,
+            pipeline: pipeline.name.clone(),
+            root_signature: root_signature.name.clone(),
+        });
+    }
+
+    fn set_graphics_constants(&mut self, cmd: &MockCommandList, index: u32, constants: &[u32]) {
+        self.calls.push(Call::SetGraphicsConstants {
+            cmd: cmd.id,
+            index,
+            constants: constants.to_vec(),
+        });
+    }
+
+    fn set_compute_constants(&mut self, cmd: &MockCommandList, index: u32, constants: &[u32]) {
+        self.calls.push(Call::SetComputeConstants {
+            cmd: cmd.id,
+            index,
+            constants: constants.to_vec(),
+        });
+    }
+
+    fn set_vertex_buffers(&mut self, cmd: &MockCommandList, views: &[VertexBufferView]) {
+        self.calls.push(Call::SetVertexBuffers {
+            cmd: cmd.id,
+            views: views.to_vec(),
+        });
+    }
+
+    fn set_index_buffer(&mut self, cmd: &MockCommandList, view: &IndexBufferView) {
+        self.calls.push(Call::SetIndexBuffer {
+            cmd: cmd.id,
+            view: *view,
+        });
+    }
+
+    fn draw(&mut self, cmd: &MockCommandList, vertex_count: u32, instance_count: u32) {
+        self.calls.push(Call::Draw {
+            cmd: cmd.id,
+            vertex_count,
+            instance_count,
+        });
+    }
+
+    fn draw_indexed(&mut self, cmd: &MockCommandList, index_count: u32, instance_count: u32) {
+        self.calls.push(Call::DrawIndexed {
+            cmd: cmd.id,
+            index_count,
+            instance_count,
+        });
+    }
+
+    fn dispatch(&mut self, cmd: &MockCommandList, x: u32, y: u32, z: u32) {
+        self.calls.push(Call::Dispatch {
+            cmd: cmd.id,
+            x,
+            y,
+            z,
+        });
+    }
+
+    fn copy_resource(&mut self, cmd: &MockCommandList, dst: &MockResource, src: &MockResource) {
+        self.calls.push(Call::CopyResource {
+            cmd: cmd.id,
+            dst: *dst,
+            src: *src,
+        });
+    }
+
+    fn copy_buffer_region(
+        &mut self,
+        cmd: &MockCommandList,
+        dst: &MockResource,
+        dst_offset: u64,
+        src: &MockResource,
+        src_offset: u64,
+        size: u64,
+    ) {
+        self.calls.push(Call::CopyBufferRegion {
+            cmd: cmd.id,
+            dst: *dst,
+            dst_offset,
+            src: *src,
+            src_offset,
+            size,
+        });
+    }
+
+    fn execute(
+        &mut self,
+        queue: QueueType,
+        cmd: MockCommandList,
+    ) -> Result<FenceValue, Infallible> {
+        assert_eq!(
+            cmd.queue, queue,
+            "The command list {} was requested for {:?}",
+            cmd.id, cmd.queue
+        );
+
+        let mock_queue = self.queue_mut(queue);
+        mock_queue.submitted += 1;
+        let fence_value = FenceValue {
+            v: mock_queue.submitted,
+        };
+
+        self.calls.push(Call::Execute {
+            cmd: cmd.id,
+            queue,
+            fence_value,
+        });
+        Ok(fence_value)
+    }
+
+    fn signal(&mut self, queue: QueueType) -> FenceValue {
+        let mock_queue = self.queue_mut(queue);
+        mock_queue.submitted += 1;
+        let fence_value = FenceValue {
+            v: mock_queue.submitted,
+        };
+
+        self.calls.push(Call::Signal { queue, fence_value });
+        fence_value
+    }
+
+    fn is_fence_completed(&self, queue: QueueType, fence_value: FenceValue) -> bool {
+        self.queue(queue).completed >= fence_value.v
+    }
+
+    fn wait_fence(&mut self, queue: QueueType, fence_value: FenceValue) {
+        self.calls.push(Call::WaitFence { queue, fence_value });
+        self.complete_fence(queue, fence_value);
+    }
+
+    fn wait_queue(&mut self, queue: QueueType, other: QueueType, fence_value: FenceValue) {
+        // D3D12 accepts it, but nothing would be executed on `queue` until it is signaled
+        assert!(
+            fence_value.v <= self.queue(other).submitted,
+            "{queue:?} waits for the fence value {} of {other:?}, which has not been signaled",
+            fence_value.v
+        );
+
+        self.calls.push(Call::WaitQueue {
+            queue,
+            other,
+            fence_value,
+        });
+
+        let after = FenceValue {
+            v: self.queue(queue).submitted,
+        };
+        let point = SyncPoint {
+            queue: other,
+            fence_value,
+        };
+        self.timeline.wait(queue, after, point);
+    }
+
+    fn back_buffer(&self) -> MockResource {
+        self.back_buffers[self.back_buffer_index]
+    }
+
+    fn back_buffer_rtv(&self) -> MockRtv {
+        MockRtv {
+            resource: self.back_buffer(),
+        }
+    }
+
+    fn present(&mut self, cmd: MockCommandList) -> Result<(), Infallible> {
+        self.calls.push(Call::Present {
+            cmd: cmd.id,
+            back_buffer: self.back_buffer(),
+        });
+        self.frame_fences[self.back_buffer_index] = self.execute(QueueType::Graphics, cmd)?;
+
+        // wait for the frame that used the next back buffer, same as Device::present_frame
+        self.back_buffer_index = (self.back_buffer_index + 1) % BACK_BUFFER_COUNT;
+        self.wait_fence(
+            QueueType::Graphics,
+            self.frame_fences[self.back_buffer_index],
+        );
+
+        Ok(())
+    }
+}
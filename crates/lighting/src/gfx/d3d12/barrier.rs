@@ -4,6 +4,20 @@ pub fn transition(
     resource: &ID3D12Resource,
     old_state: D3D12_RESOURCE_STATES,
     new_state: D3D12_RESOURCE_STATES,
+) -> D3D12_RESOURCE_BARRIER {
+    transition_subresource(
+        resource,
+        D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
+        old_state,
+        new_state,
+    )
+}
+
+pub fn transition_subresource(
+    resource: &ID3D12Resource,
+    subresource: u32,
+    old_state: D3D12_RESOURCE_STATES,
+    new_state: D3D12_RESOURCE_STATES,
 ) -> D3D12_RESOURCE_BARRIER {
     // https://github.com/microsoft/windows-rs/blob/master/crates/samples/windows/direct3d12/src/main.rs#L486
     D3D12_RESOURCE_BARRIER {
@@ -12,7 +26,7 @@ pub fn transition(
         Anonymous: D3D12_RESOURCE_BARRIER_0 {
             Transition: std::mem::ManuallyDrop::new(D3D12_RESOURCE_TRANSITION_BARRIER {
                 pResource: unsafe { std::mem::transmute_copy(resource) },
-                Subresource: D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
+                Subresource: subresource,
                 StateBefore: old_state,
                 StateAfter: new_state,
             }),
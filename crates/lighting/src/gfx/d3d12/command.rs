@@ -1,3 +1,4 @@
+use super::fence::FenceValue;
 use super::util::*;
 use std::collections::VecDeque;
 use windows::{
@@ -173,6 +174,12 @@ impl Queue {
         }
     }
 
+    /// Makes the GPU wait for `fence_value` of `other` before executing the commands submitted after
+    /// this, the CPU does not block
+    pub fn wait_queue(&self, other: &Queue, fence_value: FenceValue) {
+        unsafe { self.queue.Wait(&other.fence, fence_value.v) }.unwrap();
+    }
+
     pub fn flush(&mut self) {
         let v = self.signal();
         self.wait_fence(v);
@@ -186,12 +193,6 @@ impl Drop for Queue {
     }
 }
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
-#[must_use]
-pub struct FenceValue {
-    v: u64,
-}
-
 #[must_use]
 pub struct Context {
     command_list: ID3D12GraphicsCommandList7,
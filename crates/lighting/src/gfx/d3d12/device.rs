@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
 use windows::core::Interface;
 use windows::Win32::Foundation::{self, E_FAIL, HWND};
 use windows::Win32::Graphics::{
@@ -7,11 +10,14 @@ use windows::Win32::Graphics::{
 };
 
 use super::command::Context;
+use super::memory::{GpuAllocation, GpuAllocator, MemoryStats};
+use super::release::ReleaseQueue;
+use super::ring::{UploadAllocation, UploadRing};
+use super::staging::UploadManager;
 use super::view::*;
-use super::{
-    command::{self, FenceValue},
-    util,
-};
+use super::{command, fence::FenceValue, util};
+use crate::Config;
+
 pub struct Device {
     // D3D12 Device: considered as a memory context that tracks allocations in GPU memory
     device: ID3D12Device5,
@@ -28,15 +34,27 @@ pub struct Device {
 
     view_heap: CbvSrvUavHeap,
     rtv_heap: RtvHeap,
+    upload_ring: UploadRing,
     dsv_heap: DsvHeap,
 
+    // uploads to DEFAULT heap resources, copied on the copy queue
+    upload_manager: UploadManager,
+
+    // resources replaced while the GPU may still use them
+    release_queue: ReleaseQueue<ID3D12Resource>,
+
+    // heaps shared by the placed resources, whose memory is freed after the resources are released
+    memory: GpuAllocator,
+    placed_resources: HashMap<PlacedResource, GpuAllocation>,
+    freed_memory: ReleaseQueue<GpuAllocation>,
+
     // controls whether the swap chain's present method should wait for the next vertical fresh before presenting the rendered image
     vsync_enabled: bool,
     tearing_supported: bool,
 }
 
 impl Device {
-    pub fn build(hwnd: HWND, screen_width: u32, screen_height: u32) -> windows::core::Result<Self> {
+    pub fn build(hwnd: HWND, config: &Config) -> windows::core::Result<Self> {
         let factory = create_factory(true, true)?;
 
         let device = create_device(&factory)?;
@@ -52,8 +70,8 @@ impl Device {
             &factory,
             gfx_queue.get(),
             hwnd,
-            screen_width,
-            screen_height,
+            config.client_width(),
+            config.client_height(),
             FRAME_BUFFER_COUNT as u32,
         )
         .unwrap();
@@ -62,17 +80,23 @@ impl Device {
 
         let back_buffer_index = unsafe { swap_chain.GetCurrentBackBufferIndex() } as usize;
 
-        //TODO make the capacity configurable
-        let view_heap = CbvSrvUavHeap::build(&device, 100, "Device::view_heap")?;
+        // grows when it is full
+        let view_heap =
+            CbvSrvUavHeap::build(&device, config.view_heap_capacity(), "Device::view_heap")?;
 
-        let mut rtv_heap =
-            RtvHeap::build(&device, FRAME_BUFFER_COUNT as u32 + 1, "Device::rtv_heap")?;
+        // the frame buffers always have RTVs
+        let rtv_capacity = config.rtv_heap_capacity().max(FRAME_BUFFER_COUNT as u32);
+        let mut rtv_heap = RtvHeap::build(&device, rtv_capacity, "Device::rtv_heap")?;
 
         let frame_buffer_rtvs = frame_buffers
             .each_ref()
             .map(|buf| rtv_heap.create_rtv(&device, buf));
 
-        let dsv_heap = DsvHeap::build(&device, 2, "Device::dsv_heap")?;
+        let dsv_heap = DsvHeap::build(&device, config.dsv_heap_capacity(), "Device::dsv_heap")?;
+
+        let upload_ring = UploadRing::build(&device, UPLOAD_RING_SIZE, "Device::upload_ring")?;
+        let upload_manager =
+            UploadManager::build(&device, STAGING_BUFFER_SIZE, "Device::upload_manager")?;
 
         let copy_queue = command::Queue::build(
             &device,
@@ -81,6 +105,8 @@ impl Device {
         )
         .unwrap();
 
+        let memory = GpuAllocator::new(&device, MEMORY_BLOCK_SIZE, "Device::memory");
+
         let tearing_supported = check_tearing_support(&factory);
 
         let inline_raytracing_supported = check_inline_raytracing_support(&device);
@@ -115,6 +141,14 @@ impl Device {
             view_heap,
             rtv_heap,
             dsv_heap,
+            upload_ring,
+            upload_manager,
+
+            release_queue: ReleaseQueue::new(),
+
+            memory,
+            placed_resources: HashMap::new(),
+            freed_memory: ReleaseQueue::new(),
 
             tearing_supported,
             vsync_enabled: true,
@@ -122,6 +156,9 @@ impl Device {
     }
 
     pub fn present_frame(&mut self, ctx: Context) -> windows::core::Result<()> {
+        // the frame may use resources whose uploads have not been submitted yet
+        self.submit_uploads()?;
+
         self.frame_fences[self.back_buffer_index] = self.gfx_queue.execute_commands(ctx)?;
 
         let sync_interval = if self.vsync_enabled { 1 } else { 0 };
@@ -139,10 +176,30 @@ impl Device {
         // insert fence for the current frame
         self.frame_fences[self.back_buffer_index] = self.gfx_queue.signal();
 
+        // views freed, data uploaded and resources released in this frame may be used until the fence
+        let fence_value = self.frame_fences[self.back_buffer_index];
+        self.view_heap.retire(fence_value);
+        self.upload_ring.retire(fence_value);
+        self.release_queue.retire(fence_value);
+        self.freed_memory.retire(fence_value);
+
         // wait for the previous frame
         let i = unsafe { self.swap_chain.GetCurrentBackBufferIndex() } as usize;
         self.gfx_queue.wait_fence(self.frame_fences[i]);
 
+        let gfx_queue = &self.gfx_queue;
+        let is_completed = |fence_value| gfx_queue.is_fence_completed(fence_value);
+        self.view_heap.reclaim(is_completed);
+        self.upload_ring.reclaim(is_completed);
+        self.release_queue.reclaim(is_completed);
+        for allocation in self.freed_memory.reclaim(is_completed) {
+            self.memory.free(allocation);
+        }
+
+        let copy_queue = &self.copy_queue;
+        self.upload_manager
+            .reclaim(|fence_value| copy_queue.is_fence_completed(fence_value));
+
         self.back_buffer_index = i;
 
         Ok(())
@@ -176,6 +233,12 @@ impl Device {
         &mut self.copy_queue
     }
 
+    /// Makes the graphics queue wait for `fence_value` of the copy queue on the GPU, so that the
+    /// following frames can use the copied resources without blocking the CPU
+    pub fn wait_for_copy(&mut self, fence_value: FenceValue) {
+        self.gfx_queue.wait_queue(&self.copy_queue, fence_value);
+    }
+
     pub fn view_heap(&self) -> &ID3D12DescriptorHeap {
         self.view_heap.get()
     }
@@ -200,6 +263,131 @@ impl Device {
         self.view_heap.create_uav(&self.device, resource, desc)
     }
 
+    pub fn free_cbv(&mut self, cbv: Cbv) {
+        self.view_heap.free_cbv(cbv);
+    }
+
+    pub fn free_srv(&mut self, srv: Srv) {
+        self.view_heap.free_srv(srv);
+    }
+
+    pub fn free_uav(&mut self, uav: Uav) {
+        self.view_heap.free_uav(uav);
+    }
+
+    /// The index of a view in `view_heap` for the shaders, which asserts in debug builds that
+    /// the view has not been freed
+    pub fn view_handle(&self, slot: DescriptorSlot) -> u32 {
+        self.view_heap.allocator().index(slot)
+    }
+
+    /// Copies `data` into the upload ring, which is valid until the GPU finishes the current frame
+    /// Waits for the frames in flight if the ring is full
+    pub fn upload<T: Copy>(
+        &mut self,
+        data: &[T],
+        alignment: u64,
+    ) -> windows::core::Result<UploadAllocation> {
+        loop {
+            let allocation = self.upload_ring.upload(data, alignment);
+            let oldest_fence = self.upload_ring.allocator().oldest_fence();
+
+            match (allocation, oldest_fence) {
+                (Ok(allocation), _) => return Ok(allocation),
+                // larger than the whole ring
+                (Err(e), None) => return Err(e),
+                (Err(_), Some(fence_value)) => {
+                    self.gfx_queue.wait_fence(fence_value);
+
+                    let gfx_queue = &self.gfx_queue;
+                    self.upload_ring
+                        .reclaim(|fence_value| gfx_queue.is_fence_completed(fence_value));
+                }
+            }
+        }
+    }
+
+    /// Drops `resource` once the GPU has finished the current frame
+    pub fn release(&mut self, resource: ID3D12Resource) {
+        let key = PlacedResource(resource.clone());
+        if let Some(allocation) = self.placed_resources.remove(&key) {
+            self.freed_memory.defer(allocation);
+        }
+
+        self.release_queue.defer(resource);
+    }
+
+    /// Creates a buffer in a heap shared with other resources
+    /// Its memory is reused after it is passed to `release`
+    pub fn create_placed_buffer(
+        &mut self,
+        size: u64,
+        heap_type: D3D12_HEAP_TYPE,
+        flags: D3D12_RESOURCE_FLAGS,
+        init_state: D3D12_RESOURCE_STATES,
+        name: &str,
+    ) -> windows::core::Result<ID3D12Resource> {
+        let (buffer, allocation) = self
+            .memory
+            .create_buffer(size, heap_type, flags, init_state, name)?;
+        self.placed_resources
+            .insert(PlacedResource(buffer.clone()), allocation);
+
+        Ok(buffer)
+    }
+
+    /// The memory used by the placed resources in heaps of `heap_type`
+    pub fn memory_stats(&self, heap_type: D3D12_HEAP_TYPE) -> MemoryStats {
+        self.memory.stats(heap_type)
+    }
+
+    /// Copies `data` to `dst`, which must be in the COMMON state, when `submit_uploads` is called
+    pub fn upload_buffer<T: Copy>(
+        &mut self,
+        dst: &ID3D12Resource,
+        dst_offset: u64,
+        data: &[T],
+    ) -> windows::core::Result<()> {
+        self.upload_manager
+            .upload_buffer(&mut self.copy_queue, dst, dst_offset, data)
+    }
+
+    /// Copies the tightly packed rows of `data` to a subresource of `dst`, which must be in the COMMON
+    /// state, when `submit_uploads` is called
+    pub fn upload_texture2d(
+        &mut self,
+        dst: &ID3D12Resource,
+        subresource: u32,
+        data: &[u8],
+    ) -> windows::core::Result<()> {
+        self.upload_manager.upload_texture2d(
+            &mut self.copy_queue,
+            &self.device,
+            dst,
+            subresource,
+            data,
+        )
+    }
+
+    /// Submits the pending uploads in one command list, and makes the graphics queue wait for them
+    /// Returns the fence of the copy queue, or None if there was nothing to upload
+    pub fn submit_uploads(&mut self) -> windows::core::Result<Option<FenceValue>> {
+        let fence_value = self.upload_manager.submit(&mut self.copy_queue)?;
+        if let Some(fence_value) = fence_value {
+            self.wait_for_copy(fence_value);
+        }
+
+        Ok(fence_value)
+    }
+
+    pub fn upload_manager(&self) -> &UploadManager {
+        &self.upload_manager
+    }
+
+    pub fn upload_ring(&self) -> &UploadRing {
+        &self.upload_ring
+    }
+
     pub fn create_rtv(&mut self, resource: &ID3D12Resource) -> Rtv {
         self.rtv_heap.create_rtv(&self.device, resource)
     }
@@ -233,8 +421,38 @@ impl Device {
     }
 }
 
+impl Drop for Device {
+    fn drop(&mut self) {
+        // the resources waiting for release may still be used by the GPU
+        self.gfx_queue.flush();
+        self.copy_queue.flush();
+        self.release_queue.flush();
+        self.freed_memory.flush();
+    }
+}
+
+// Holds a reference to the resource, so that no other resource is created at its address while it
+// is a key
+#[derive(Clone, PartialEq, Eq)]
+struct PlacedResource(ID3D12Resource);
+
+impl Hash for PlacedResource {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_raw().hash(state);
+    }
+}
+
 pub const FRAME_BUFFER_COUNT: usize = 3;
 
+// shared by the per-frame data of all the frames in flight
+const UPLOAD_RING_SIZE: u64 = 4 * 1024 * 1024;
+
+// larger data is split into several submissions
+const STAGING_BUFFER_SIZE: u64 = 32 * 1024 * 1024;
+
+// the size of the heaps for placed resources, larger resources get a heap of their own
+const MEMORY_BLOCK_SIZE: u64 = 64 * 1024 * 1024;
+
 pub const FRAME_BUFFER_FORMAT: DXGI_FORMAT = DXGI_FORMAT_R8G8B8A8_UNORM;
 
 pub fn report_live_objects() -> windows::core::Result<()> {
@@ -0,0 +1,6 @@
+// A value signaled by a queue when the GPU finishes the commands submitted before it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[must_use]
+pub struct FenceValue {
+    pub(super) v: u64,
+}
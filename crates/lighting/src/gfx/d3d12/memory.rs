@@ -0,0 +1,466 @@
+// Sub-allocation of large heap blocks for placed resources
+// Each block is managed by a buddy allocator: blocks are split in halves down to the requested size,
+// and merged again with their buddy when both are free. A block of size 2^n is always placed at a
+// multiple of 2^n, so larger alignments only need larger blocks
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The placement alignment of buffers and single-sample textures
+pub const DEFAULT_RESOURCE_PLACEMENT_ALIGNMENT: u64 = 64 * 1024;
+/// The placement alignment of multi-sample textures
+pub const DEFAULT_MSAA_RESOURCE_PLACEMENT_ALIGNMENT: u64 = 4 * 1024 * 1024;
+/// Acceleration structures must be placed at multiples of this in a buffer
+pub const RAYTRACING_ACCELERATION_STRUCTURE_BYTE_ALIGNMENT: u64 = 256;
+
+pub struct BuddyAllocator {
+    size: u64,
+    min_block_size: u64,
+    // the offsets of the free blocks of each order, whose size is `min_block_size << order`
+    free_blocks: Vec<BTreeSet<u64>>,
+    // the order of each allocated block by offset
+    allocated: BTreeMap<u64, usize>,
+    used: u64,
+}
+
+impl BuddyAllocator {
+    /// `size` and `min_block_size` must be powers of two
+    pub fn new(size: u64, min_block_size: u64) -> Self {
+        assert!(size.is_power_of_two() && min_block_size.is_power_of_two());
+        assert!(
+            size >= min_block_size,
+            "the size {size} is smaller than the minimum block size {min_block_size}"
+        );
+
+        let max_order = (size / min_block_size).trailing_zeros() as usize;
+        let mut free_blocks = vec![BTreeSet::new(); max_order + 1];
+        free_blocks[max_order].insert(0);
+
+        Self {
+            size,
+            min_block_size,
+            free_blocks,
+            allocated: BTreeMap::new(),
+            used: 0,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The bytes of the allocated blocks, including the rounding up to powers of two
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allocated.is_empty()
+    }
+
+    /// The size of the block that would be allocated for `size` bytes aligned to `alignment`
+    pub fn block_size(&self, size: u64, alignment: u64) -> u64 {
+        size.max(alignment)
+            .max(self.min_block_size)
+            .next_power_of_two()
+    }
+
+    /// The largest size that can be allocated without freeing any block
+    pub fn largest_free_block(&self) -> u64 {
+        let order = self
+            .free_blocks
+            .iter()
+            .rposition(|blocks| !blocks.is_empty());
+        order.map_or(0, |order| self.min_block_size << order)
+    }
+
+    /// Returns the offset of a block of at least `size` bytes, which is a multiple of `alignment`
+    /// None if there is no free block large enough
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        let block_size = self.block_size(size, alignment);
+        if block_size > self.size {
+            return None;
+        }
+
+        let order = (block_size / self.min_block_size).trailing_zeros() as usize;
+
+        // the smallest free block that fits, with the lowest offset to keep the blocks packed
+        let mut current =
+            (order..self.free_blocks.len()).find(|o| !self.free_blocks[*o].is_empty())?;
+        let offset = self.free_blocks[current].pop_first().unwrap();
+
+        // the upper halves of the split blocks stay free
+        while current > order {
+            current -= 1;
+            let buddy = offset + (self.min_block_size << current);
+            self.free_blocks[current].insert(buddy);
+        }
+
+        self.allocated.insert(offset, order);
+        self.used += block_size;
+
+        Some(offset)
+    }
+
+    /// Frees the block at `offset`, which must have been returned by `allocate`
+    pub fn free(&mut self, offset: u64) {
+        let Some(mut order) = self.allocated.remove(&offset) else {
+            panic!("no block is allocated at {offset}");
+        };
+
+        self.used -= self.min_block_size << order;
+
+        let mut offset = offset;
+        while order + 1 < self.free_blocks.len() {
+            let buddy = offset ^ (self.min_block_size << order);
+            if !self.free_blocks[order].remove(&buddy) {
+                break;
+            }
+
+            offset = offset.min(buddy);
+            order += 1;
+        }
+
+        self.free_blocks[order].insert(offset);
+    }
+}
+
+/// A range of a block of a `MemoryPool`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemoryAllocation {
+    pub block: usize,
+    pub offset: u64,
+    /// The size of the buddy block, which can be larger than the requested size
+    pub size: u64,
+    pub requested_size: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    pub block_count: usize,
+    pub allocation_count: usize,
+    /// The bytes of all the blocks
+    pub reserved: u64,
+    /// The bytes of the allocated buddy blocks
+    pub allocated: u64,
+    /// The bytes requested by the allocations, the rest of `allocated` is lost in rounding
+    pub requested: u64,
+}
+
+impl MemoryStats {
+    pub fn merge(&mut self, other: &MemoryStats) {
+        self.block_count += other.block_count;
+        self.allocation_count += other.allocation_count;
+        self.reserved += other.reserved;
+        self.allocated += other.allocated;
+        self.requested += other.requested;
+    }
+}
+
+/// Blocks of the same kind of memory, which are added as they fill up
+pub struct MemoryPool {
+    block_size: u64,
+    min_block_size: u64,
+    // None once an empty block has been released, so that the indices of the others do not change
+    blocks: Vec<Option<BuddyAllocator>>,
+    allocation_count: usize,
+    requested: u64,
+}
+
+impl MemoryPool {
+    /// `block_size` is the size of the blocks added by `add_block`, unless a larger one is needed
+    pub fn new(block_size: u64, min_block_size: u64) -> Self {
+        assert!(block_size.is_power_of_two() && block_size >= min_block_size);
+
+        Self {
+            block_size,
+            min_block_size,
+            blocks: Vec::new(),
+            allocation_count: 0,
+            requested: 0,
+        }
+    }
+
+    /// The size of a block that can hold `size` bytes aligned to `alignment`
+    pub fn new_block_size(&self, size: u64, alignment: u64) -> u64 {
+        let required = size
+            .max(alignment)
+            .max(self.min_block_size)
+            .next_power_of_two();
+        required.max(self.block_size)
+    }
+
+    /// Adds a block that can hold `size` bytes aligned to `alignment`, and returns its index
+    /// The memory of the block must be created with `block_size(index)` bytes
+    pub fn add_block(&mut self, size: u64, alignment: u64) -> usize {
+        let block = BuddyAllocator::new(self.new_block_size(size, alignment), self.min_block_size);
+
+        match self.blocks.iter().position(|b| b.is_none()) {
+            Some(i) => {
+                self.blocks[i] = Some(block);
+                i
+            }
+            None => {
+                self.blocks.push(Some(block));
+                self.blocks.len() - 1
+            }
+        }
+    }
+
+    /// Removes an empty block, such as one whose memory failed to be created
+    pub fn remove_block(&mut self, block: usize) {
+        let is_empty = self.blocks[block].as_ref().is_some_and(|b| b.is_empty());
+        assert!(is_empty, "the block {block} is in use");
+
+        self.blocks[block] = None;
+    }
+
+    pub fn block_size(&self, block: usize) -> Option<u64> {
+        self.blocks.get(block)?.as_ref().map(|b| b.size())
+    }
+
+    /// Returns None if no block has enough space, `add_block` has to be called then
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<MemoryAllocation> {
+        let (block, offset) = self.blocks.iter_mut().enumerate().find_map(|(i, block)| {
+            let offset = block.as_mut()?.allocate(size, alignment)?;
+            Some((i, offset))
+        })?;
+
+        self.allocation_count += 1;
+        self.requested += size;
+
+        Some(MemoryAllocation {
+            block,
+            offset,
+            size: self.blocks[block]
+                .as_ref()
+                .unwrap()
+                .block_size(size, alignment),
+            requested_size: size,
+        })
+    }
+
+    /// Returns the index of the block if it has become empty and was released, in which case the
+    /// memory of the block can be released too
+    /// The last block is kept even when empty, so that allocating and freeing repeatedly does not
+    /// create a block every time
+    pub fn free(&mut self, allocation: MemoryAllocation) -> Option<usize> {
+        let Some(Some(block)) = self.blocks.get_mut(allocation.block) else {
+            panic!("the block {} has been released", allocation.block);
+        };
+
+        block.free(allocation.offset);
+        let is_empty = block.is_empty();
+
+        self.allocation_count -= 1;
+        self.requested -= allocation.requested_size;
+
+        let live_blocks = self.blocks.iter().flatten().count();
+        if !is_empty || live_blocks == 1 {
+            return None;
+        }
+
+        self.blocks[allocation.block] = None;
+        Some(allocation.block)
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        let blocks = self.blocks.iter().flatten();
+
+        MemoryStats {
+            block_count: blocks.clone().count(),
+            allocation_count: self.allocation_count,
+            reserved: blocks.clone().map(|b| b.size()).sum(),
+            allocated: blocks.map(|b| b.used()).sum(),
+            requested: self.requested,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod heap;
+#[cfg(windows)]
+pub use heap::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_are_split_to_the_requested_size() {
+        let mut buddy = BuddyAllocator::new(1024, 64);
+
+        assert_eq!(buddy.allocate(64, 1), Some(0));
+        // 128 bytes for 100 bytes, from the upper half of the first split
+        assert_eq!(buddy.allocate(100, 1), Some(128));
+        assert_eq!(buddy.allocate(1, 1), Some(64));
+        assert_eq!(buddy.allocate(256, 1), Some(256));
+
+        assert_eq!(buddy.used(), 512);
+        assert_eq!(buddy.largest_free_block(), 512);
+    }
+
+    #[test]
+    fn blocks_are_aligned() {
+        let mut buddy = BuddyAllocator::new(1024, 64);
+
+        assert_eq!(buddy.block_size(64, 256), 256);
+        assert_eq!(buddy.block_size(300, 1), 512);
+        assert_eq!(buddy.block_size(1, 1), 64);
+
+        assert_eq!(buddy.allocate(64, 1), Some(0));
+        assert_eq!(buddy.allocate(64, 512), Some(512));
+        assert_eq!(buddy.allocate(64, 256), Some(256));
+        assert_eq!(buddy.allocate(64, 128), Some(128));
+    }
+
+    #[test]
+    fn freed_buddies_are_merged() {
+        let mut buddy = BuddyAllocator::new(1024, 64);
+
+        let offsets: Vec<u64> = (0..16).map(|_| buddy.allocate(64, 1).unwrap()).collect();
+        assert_eq!(buddy.largest_free_block(), 0);
+
+        // every other block first, so that no buddies can merge until the second pass
+        for offset in offsets.iter().step_by(2) {
+            buddy.free(*offset);
+        }
+        assert_eq!(buddy.largest_free_block(), 64);
+        assert_eq!(buddy.allocate(128, 1), None);
+
+        for offset in offsets.iter().skip(1).step_by(2) {
+            buddy.free(*offset);
+        }
+        assert!(buddy.is_empty());
+        assert_eq!(buddy.used(), 0);
+        assert_eq!(buddy.largest_free_block(), 1024);
+        assert_eq!(buddy.allocate(1024, 1), Some(0));
+    }
+
+    #[test]
+    fn exhaustion() {
+        let mut buddy = BuddyAllocator::new(1024, 64);
+
+        assert_eq!(buddy.allocate(2048, 1), None);
+        assert_eq!(buddy.allocate(64, 2048), None);
+
+        assert_eq!(buddy.allocate(512, 1), Some(0));
+        assert_eq!(buddy.allocate(513, 1), None);
+        assert_eq!(buddy.allocate(512, 1), Some(512));
+        assert_eq!(buddy.allocate(1, 1), None);
+
+        buddy.free(0);
+        assert_eq!(buddy.allocate(1, 1), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "no block is allocated at 64")]
+    fn free_unallocated_block() {
+        let mut buddy = BuddyAllocator::new(1024, 64);
+        let _ = buddy.allocate(128, 1);
+        buddy.free(64);
+    }
+
+    #[test]
+    fn pool_adds_blocks_on_demand() {
+        let mut pool = MemoryPool::new(1024, 64);
+        assert_eq!(pool.allocate(100, 1), None);
+
+        assert_eq!(pool.add_block(100, 1), 0);
+        assert_eq!(pool.block_size(0), Some(1024));
+        let allocation = pool.allocate(100, 1).unwrap();
+        assert_eq!(
+            allocation,
+            MemoryAllocation {
+                block: 0,
+                offset: 0,
+                size: 128,
+                requested_size: 100,
+            }
+        );
+
+        // larger than the block size
+        assert_eq!(pool.allocate(2000, 1), None);
+        assert_eq!(pool.new_block_size(2000, 1), 2048);
+        assert_eq!(pool.add_block(2000, 1), 1);
+        assert_eq!(pool.block_size(1), Some(2048));
+        assert_eq!(pool.allocate(2000, 1).map(|a| a.block), Some(1));
+
+        // the first block still has space
+        assert_eq!(pool.allocate(64, 1).map(|a| a.block), Some(0));
+
+        assert_eq!(
+            pool.stats(),
+            MemoryStats {
+                block_count: 2,
+                allocation_count: 3,
+                reserved: 3072,
+                allocated: 128 + 2048 + 64,
+                requested: 100 + 2000 + 64,
+            }
+        );
+    }
+
+    #[test]
+    fn pool_releases_empty_blocks_but_the_last() {
+        let mut pool = MemoryPool::new(1024, 64);
+        pool.add_block(1024, 1);
+        let a = pool.allocate(1024, 1).unwrap();
+        pool.add_block(1024, 1);
+        let b = pool.allocate(512, 1).unwrap();
+        let c = pool.allocate(512, 1).unwrap();
+
+        assert_eq!(pool.free(a), Some(0));
+        assert_eq!(pool.block_size(0), None);
+        assert_eq!(pool.free(b), None);
+        // the last block is kept for the next allocations
+        assert_eq!(pool.free(c), None);
+        assert_eq!(pool.block_size(1), Some(1024));
+
+        assert_eq!(
+            pool.stats(),
+            MemoryStats {
+                block_count: 1,
+                allocation_count: 0,
+                reserved: 1024,
+                allocated: 0,
+                requested: 0,
+            }
+        );
+
+        // the index of the released block is reused
+        assert_eq!(pool.add_block(64, 1), 0);
+    }
+
+    #[test]
+    fn remove_block_whose_memory_failed() {
+        let mut pool = MemoryPool::new(1024, 64);
+        let block = pool.add_block(64, 1);
+
+        pool.remove_block(block);
+        assert_eq!(pool.block_size(block), None);
+        assert_eq!(pool.allocate(64, 1), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "the block 0 is in use")]
+    fn remove_block_in_use() {
+        let mut pool = MemoryPool::new(1024, 64);
+        pool.add_block(64, 1);
+        let _ = pool.allocate(64, 1);
+
+        pool.remove_block(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "the block 0 has been released")]
+    fn free_in_released_block() {
+        let mut pool = MemoryPool::new(1024, 64);
+        pool.add_block(64, 1);
+        let a = pool.allocate(64, 1).unwrap();
+        pool.add_block(64, 1);
+        let _ = pool.allocate(1024, 1).unwrap();
+
+        pool.free(a);
+        pool.free(a);
+    }
+}
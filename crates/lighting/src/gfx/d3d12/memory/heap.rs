@@ -0,0 +1,245 @@
+use windows::Win32::Graphics::Direct3D12::*;
+
+use super::super::{resource, util::set_name_str};
+use super::{MemoryAllocation, MemoryPool, MemoryStats};
+use super::{DEFAULT_MSAA_RESOURCE_PLACEMENT_ALIGNMENT, DEFAULT_RESOURCE_PLACEMENT_ALIGNMENT};
+
+/// The kinds of resources that can share a heap on devices of resource heap tier 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    Buffer,
+    Texture,
+    RenderTargetOrDepthStencil,
+}
+
+impl ResourceKind {
+    pub fn of(desc: &D3D12_RESOURCE_DESC) -> Self {
+        let rt_ds =
+            D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET | D3D12_RESOURCE_FLAG_ALLOW_DEPTH_STENCIL;
+
+        if desc.Dimension == D3D12_RESOURCE_DIMENSION_BUFFER {
+            Self::Buffer
+        } else if (desc.Flags & rt_ds).0 != 0 {
+            Self::RenderTargetOrDepthStencil
+        } else {
+            Self::Texture
+        }
+    }
+
+    fn heap_flags(self) -> D3D12_HEAP_FLAGS {
+        match self {
+            Self::Buffer => D3D12_HEAP_FLAG_ALLOW_ONLY_BUFFERS,
+            Self::Texture => D3D12_HEAP_FLAG_ALLOW_ONLY_NON_RT_DS_TEXTURES,
+            Self::RenderTargetOrDepthStencil => D3D12_HEAP_FLAG_ALLOW_ONLY_RT_DS_TEXTURES,
+        }
+    }
+}
+
+/// A placed resource's range of memory, which is returned to `GpuAllocator::free`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GpuAllocation {
+    pool: usize,
+    allocation: MemoryAllocation,
+}
+
+impl GpuAllocation {
+    pub fn allocation(&self) -> &MemoryAllocation {
+        &self.allocation
+    }
+}
+
+struct Pool {
+    heap_type: D3D12_HEAP_TYPE,
+    kind: ResourceKind,
+    // the blocks of memory that `pool` allocates from, indexed by the block
+    heaps: Vec<Option<ID3D12Heap>>,
+    pool: MemoryPool,
+}
+
+/// Creates placed resources in heaps shared by many resources, instead of a heap for each resource
+pub struct GpuAllocator {
+    device: ID3D12Device5,
+    block_size: u64,
+    pools: Vec<Pool>,
+    name: String,
+}
+
+impl GpuAllocator {
+    /// `block_size` is the size of the heaps, resources larger than it get a heap of their own
+    /// It must be a power of two and a multiple of the MSAA placement alignment
+    pub fn new(device: &ID3D12Device5, block_size: u64, name: &str) -> Self {
+        assert!(
+            block_size.is_power_of_two() && block_size >= DEFAULT_MSAA_RESOURCE_PLACEMENT_ALIGNMENT
+        );
+
+        Self {
+            device: device.clone(),
+            block_size,
+            pools: Vec::new(),
+            name: name.to_string(),
+        }
+    }
+
+    pub fn create_buffer(
+        &mut self,
+        size: u64,
+        heap_type: D3D12_HEAP_TYPE,
+        flags: D3D12_RESOURCE_FLAGS,
+        init_state: D3D12_RESOURCE_STATES,
+        name: &str,
+    ) -> windows::core::Result<(ID3D12Resource, GpuAllocation)> {
+        let desc = resource::buffer_desc(size, flags);
+        self.create_resource(&desc, heap_type, init_state, None, name)
+    }
+
+    pub fn create_resource(
+        &mut self,
+        desc: &D3D12_RESOURCE_DESC,
+        heap_type: D3D12_HEAP_TYPE,
+        init_state: D3D12_RESOURCE_STATES,
+        clear_value: Option<*const D3D12_CLEAR_VALUE>,
+        name: &str,
+    ) -> windows::core::Result<(ID3D12Resource, GpuAllocation)> {
+        // 64 KiB, or 4 MiB for MSAA textures
+        let info = unsafe { self.device.GetResourceAllocationInfo(0, &[*desc]) };
+
+        let kind = ResourceKind::of(desc);
+        let allocation = self.allocate(heap_type, kind, info.SizeInBytes, info.Alignment)?;
+
+        let pool = &self.pools[allocation.pool];
+        let heap = pool.heaps[allocation.allocation.block].as_ref().unwrap();
+
+        let mut resource: Option<ID3D12Resource> = None;
+        let result = unsafe {
+            self.device.CreatePlacedResource(
+                heap,
+                allocation.allocation.offset,
+                desc,
+                init_state,
+                clear_value,
+                &mut resource,
+            )
+        };
+
+        if let Err(e) = result {
+            self.free(allocation);
+            return Err(e);
+        }
+
+        let resource = resource.expect("Failed to create a placed resource");
+        set_name_str(&resource, name)?;
+        resource::track(&self.device, &resource, heap_type, name)?;
+
+        Ok((resource, allocation))
+    }
+
+    /// Makes the memory available to other resources, the resource must not be used by the GPU anymore
+    pub fn free(&mut self, allocation: GpuAllocation) {
+        let pool = &mut self.pools[allocation.pool];
+
+        if let Some(block) = pool.pool.free(allocation.allocation) {
+            pool.heaps[block] = None;
+        }
+    }
+
+    /// The statistics of all the pools of `heap_type`
+    pub fn stats(&self, heap_type: D3D12_HEAP_TYPE) -> MemoryStats {
+        let mut stats = MemoryStats::default();
+        for pool in self.pools.iter().filter(|p| p.heap_type == heap_type) {
+            stats.merge(&pool.pool.stats());
+        }
+
+        stats
+    }
+
+    fn allocate(
+        &mut self,
+        heap_type: D3D12_HEAP_TYPE,
+        kind: ResourceKind,
+        size: u64,
+        alignment: u64,
+    ) -> windows::core::Result<GpuAllocation> {
+        let index = self.pool_index(heap_type, kind);
+        let pool = &mut self.pools[index];
+
+        if let Some(allocation) = pool.pool.allocate(size, alignment) {
+            return Ok(GpuAllocation {
+                pool: index,
+                allocation,
+            });
+        }
+
+        let block = pool.pool.add_block(size, alignment);
+        let block_size = pool.pool.block_size(block).unwrap();
+
+        let name = format!("{}::heaps[{heap_type:?}, {kind:?}][{block}]", self.name);
+        let heap = match create_heap(&self.device, heap_type, kind, block_size, &name) {
+            Ok(heap) => heap,
+            Err(e) => {
+                pool.pool.remove_block(block);
+                return Err(e);
+            }
+        };
+
+        if pool.heaps.len() <= block {
+            pool.heaps.resize(block + 1, None);
+        }
+        pool.heaps[block] = Some(heap);
+
+        let allocation = pool
+            .pool
+            .allocate(size, alignment)
+            .expect("a new block must have enough space");
+
+        Ok(GpuAllocation {
+            pool: index,
+            allocation,
+        })
+    }
+
+    fn pool_index(&mut self, heap_type: D3D12_HEAP_TYPE, kind: ResourceKind) -> usize {
+        let index = self
+            .pools
+            .iter()
+            .position(|p| p.heap_type == heap_type && p.kind == kind);
+
+        index.unwrap_or_else(|| {
+            self.pools.push(Pool {
+                heap_type,
+                kind,
+                heaps: Vec::new(),
+                pool: MemoryPool::new(self.block_size, DEFAULT_RESOURCE_PLACEMENT_ALIGNMENT),
+            });
+            self.pools.len() - 1
+        })
+    }
+}
+
+fn create_heap(
+    device: &ID3D12Device5,
+    heap_type: D3D12_HEAP_TYPE,
+    kind: ResourceKind,
+    size: u64,
+    name: &str,
+) -> windows::core::Result<ID3D12Heap> {
+    // MSAA textures can only be placed in heaps aligned to 4 MiB
+    let alignment = match kind {
+        ResourceKind::Buffer => DEFAULT_RESOURCE_PLACEMENT_ALIGNMENT,
+        _ => DEFAULT_MSAA_RESOURCE_PLACEMENT_ALIGNMENT,
+    };
+
+    let desc = D3D12_HEAP_DESC {
+        SizeInBytes: size,
+        Properties: resource::heap_properties(heap_type),
+        Alignment: alignment,
+        Flags: kind.heap_flags(),
+    };
+
+    let mut heap: Option<ID3D12Heap> = None;
+    unsafe { device.CreateHeap(&desc, &mut heap) }?;
+
+    let heap = heap.expect("Failed to create a heap");
+    set_name_str(&heap, name)?;
+
+    Ok(heap)
+}
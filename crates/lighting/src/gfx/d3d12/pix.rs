@@ -58,16 +58,26 @@ impl Pix {
         color: u64,
         name: &'a str,
     ) -> PixEvent<'a> {
+        self.push_event(command_list, color, name);
+        PixEvent {
+            pix: self,
+            command_list,
+        }
+    }
+
+    // Unlike `begin_event`, the caller is responsible for calling `pop_event`
+    pub fn push_event(&self, command_list: &ID3D12GraphicsCommandList, color: u64, name: &str) {
         let begin_event = self.begin_event;
         let name: String = name.chars().chain(std::iter::once('\0')).collect();
 
         // seems like the command list must be cloned, or BeginEventOnCommandList crashes
         // https://www.polymonster.co.uk/blog/bulding-new-engine-in-rust-2
         unsafe { begin_event(command_list.clone().as_raw(), color, PCSTR(name.as_ptr())) };
-        PixEvent {
-            pix: self,
-            command_list,
-        }
+    }
+
+    pub fn pop_event(&self, command_list: &ID3D12GraphicsCommandList) {
+        let end_event = self.end_event;
+        unsafe { end_event(command_list.as_raw()) };
     }
 
     pub fn _set_marker(&self, command_list: &ID3D12GraphicsCommandList, color: u64, name: &str) {
@@ -84,8 +94,7 @@ pub struct PixEvent<'a> {
 
 impl<'a> Drop for PixEvent<'a> {
     fn drop(&mut self) {
-        let end_event = self.pix.end_event;
-        unsafe { end_event(self.command_list.as_raw()) };
+        self.pix.pop_event(self.command_list);
     }
 }
 
@@ -15,7 +15,10 @@ pub struct RaytracingScene {
     tlas: Tlas,
     srv: Option<Srv>,
 
-    mesh_data: [MeshData; MAX_MESH_DATA_COUNT],
+    // indexed by the geometry index, as the meshes are the geometries of a single BLAS
+    mesh_data: Vec<MeshData>,
+    mesh_data_buffer: Option<ID3D12Resource>,
+    mesh_data_srv: Option<Srv>,
 
     name: String,
 }
@@ -30,7 +33,9 @@ impl RaytracingScene {
             blas_list: Vec::new(),
             tlas,
             srv: None,
-            mesh_data: Default::default(),
+            mesh_data: Vec::new(),
+            mesh_data_buffer: None,
+            mesh_data_srv: None,
             name,
         }
     }
@@ -46,13 +51,15 @@ impl RaytracingScene {
         id
     }
 
-    pub fn add_mesh(&mut self, blas_id: BlasId, mesh: &Mesh, transform_address: Option<u64>) {
-        let total_geometry_count = self
-            .blas_list
-            .iter()
-            .fold(0, |acc, g| acc + g.geometries.len());
-        debug_assert!(total_geometry_count < MAX_MESH_DATA_COUNT);
-
+    /// The BLAS keeps the LOD selected by the mesh at this point, as updates cannot change the
+    /// triangle count
+    pub fn add_mesh(
+        &mut self,
+        device: &Device,
+        blas_id: BlasId,
+        mesh: &Mesh,
+        transform_address: Option<u64>,
+    ) {
         let geometry = D3D12_RAYTRACING_GEOMETRY_DESC {
             Type: D3D12_RAYTRACING_GEOMETRY_TYPE_TRIANGLES,
             Flags: D3D12_RAYTRACING_GEOMETRY_FLAG_OPAQUE,
@@ -73,12 +80,31 @@ impl RaytracingScene {
         };
         self.blas_list[blas_id.v].add_geometry(geometry);
 
-        self.mesh_data[total_geometry_count] = MeshData {
-            index_buffer_handle: mesh.index_srv().handle(),
-            position_buffer_handle: mesh.position_srv().handle(),
-            normal_buffer_handle: mesh.normal_srv().handle(),
+        let handle = |srv: &Srv| device.view_handle(srv.slot());
+        self.mesh_data.push(MeshData {
+            index_buffer_handle: handle(mesh.index_srv()),
+            position_buffer_handle: handle(mesh.position_srv()),
+            normal_buffer_handle: handle(mesh.normal_srv()),
+            uv_buffer_handle: mesh.uv_srv(0).map_or(u32::MAX, handle),
+            tangent_buffer_handle: mesh.tangent_srv().map_or(u32::MAX, handle),
+            index_size: match mesh.index_buffer_view().Format {
+                DXGI_FORMAT_R16_UINT => 2,
+                _ => 4,
+            },
+            first_index: mesh.first_index(),
             ..Default::default()
-        };
+        });
+    }
+
+    /// Replaces the transform addresses passed to `add_mesh` in the same order, used by the next update
+    pub fn set_transforms(&mut self, transform_addresses: impl IntoIterator<Item = u64>) {
+        let geometries = self
+            .blas_list
+            .iter_mut()
+            .flat_map(|b| b.geometries.iter_mut());
+        for (geometry, address) in geometries.zip(transform_addresses) {
+            geometry.Anonymous.Triangles.Transform3x4 = address;
+        }
     }
 
     pub fn build(&mut self, device: &mut Device) -> windows::core::Result<()> {
@@ -130,9 +156,10 @@ impl RaytracingScene {
             self.init_srv(device);
         }
 
-        let command_queue = device.gfx_queue_mut();
-        let fence_value = command_queue.execute_commands(ctx).unwrap();
-        command_queue.wait_fence(fence_value);
+        self.init_mesh_data(device)?;
+
+        // the frames are executed after the build on the same queue, so the CPU does not wait
+        let _ = device.gfx_queue_mut().execute_commands(ctx)?;
 
         Ok(())
     }
@@ -166,6 +193,42 @@ impl RaytracingScene {
         Ok(())
     }
 
+    // The meshes do not change after the build, so their data is written once
+    fn init_mesh_data(&mut self, device: &mut Device) -> windows::core::Result<()> {
+        let buffer = resource::create_buffer_with_data(
+            device,
+            D3D12_HEAP_TYPE_UPLOAD,
+            D3D12_RESOURCE_FLAG_NONE,
+            D3D12_RESOURCE_STATE_ALL_SHADER_RESOURCE,
+            &self.mesh_data,
+            &format!("{}::mesh_data_buffer", self.name),
+        )?;
+
+        let desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: DXGI_FORMAT_UNKNOWN,
+            ViewDimension: D3D12_SRV_DIMENSION_BUFFER,
+            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                Buffer: D3D12_BUFFER_SRV {
+                    FirstElement: 0,
+                    NumElements: self.mesh_data.len() as u32,
+                    StructureByteStride: std::mem::size_of::<MeshData>() as u32,
+                    Flags: D3D12_BUFFER_SRV_FLAG_NONE,
+                },
+            },
+        };
+        let srv = device.create_srv(Some(&buffer), Some(&desc));
+
+        if let Some(old_srv) = self.mesh_data_srv.replace(srv) {
+            device.free_srv(old_srv);
+        }
+        if let Some(old_buffer) = self.mesh_data_buffer.replace(buffer) {
+            device.release(old_buffer);
+        }
+
+        Ok(())
+    }
+
     pub fn init_srv(&mut self, device: &mut Device) {
         let desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
             Format: DXGI_FORMAT_UNKNOWN,
@@ -181,15 +244,21 @@ impl RaytracingScene {
         // The first arg must be None: passing the actual buffer would cause an error:
         // ID3D12Device::CreateShaderResourceView: When ViewDimension is D3D12_SRV_DIMENSION_RAYTRACING_ACCELERATION_STRUCTURE,
         // pResource must be NULL, since the resource location comes from a GPUVA in pDesc.
-        self.srv = Some(device.create_srv(None, Some(&desc)));
+        let srv = device.create_srv(None, Some(&desc));
+
+        // the TLAS buffer has been reallocated
+        if let Some(old_srv) = self.srv.replace(srv) {
+            device.free_srv(old_srv);
+        }
     }
 
     pub fn srv(&self) -> Option<&Srv> {
         self.srv.as_ref()
     }
 
-    pub fn mesh_data(&self) -> &[MeshData; MAX_MESH_DATA_COUNT] {
-        &self.mesh_data
+    /// The `MeshData` of each geometry, None until the scene is built
+    pub fn mesh_data_srv(&self) -> Option<&Srv> {
+        self.mesh_data_srv.as_ref()
     }
 }
 
@@ -205,18 +274,23 @@ pub struct TlasId {
     v: usize,
 }
 
+// must match MeshData in raytracing.hlsl
 #[derive(Debug, Clone)]
 #[repr(C)]
 pub struct MeshData {
     index_buffer_handle: u32,
     position_buffer_handle: u32,
     normal_buffer_handle: u32,
+    /// u32::MAX if the mesh has no UVs, in which case it has no tangents either
+    uv_buffer_handle: u32,
+    tangent_buffer_handle: u32,
+    /// 2 or 4 bytes
+    index_size: u32,
+    /// The first index of the LOD in the BLAS, as the index buffer holds every LOD
+    first_index: u32,
     _reserved: u32,
 }
 
-// must match MAX_MESH_DATA_COUNT in raytracing.hlsl
-pub const MAX_MESH_DATA_COUNT: usize = 2;
-
 #[derive(Debug, PartialEq, Eq)]
 enum BuildMode {
     FullBuild,
@@ -251,7 +325,7 @@ impl Blas {
 
     fn allocate_buffers(
         &mut self,
-        device: &device::Device,
+        device: &mut device::Device,
         name: &str,
     ) -> windows::core::Result<()> {
         let inputs = self.inputs(BuildMode::FullBuild);
@@ -264,8 +338,7 @@ impl Blas {
         };
 
         let scratch_buffer_name = "Scratch buffer for ".to_string() + name;
-        let scratch_buffer = resource::create_buffer(
-            device,
+        let scratch_buffer = device.create_placed_buffer(
             info.ScratchDataSizeInBytes,
             D3D12_HEAP_TYPE_DEFAULT,
             D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
@@ -273,8 +346,7 @@ impl Blas {
             &scratch_buffer_name,
         )?;
 
-        let buffer = resource::create_buffer(
-            device,
+        let buffer = device.create_placed_buffer(
             info.ResultDataMaxSizeInBytes,
             D3D12_HEAP_TYPE_DEFAULT,
             D3D12_RESOURCE_FLAG_RAYTRACING_ACCELERATION_STRUCTURE
@@ -285,8 +357,13 @@ impl Blas {
 
         // note that the state of scratch buffer is COMMON, and needs to change to UNORDERED_ACCESS
         // before buidling
-        self.buffer = Some(buffer);
-        self.scratch_buffer = Some(scratch_buffer);
+        let old_buffers = [
+            self.buffer.replace(buffer),
+            self.scratch_buffer.replace(scratch_buffer),
+        ];
+        for old_buffer in old_buffers.into_iter().flatten() {
+            device.release(old_buffer);
+        }
 
         Ok(())
     }
@@ -432,7 +509,7 @@ impl Tlas {
     }
 
     #[must_use]
-    fn allocate_buffers(&mut self, device: &Device, name: &str) -> bool {
+    fn allocate_buffers(&mut self, device: &mut Device, name: &str) -> bool {
         let inputs = self.inputs();
 
         let mut info = Default::default();
@@ -447,17 +524,20 @@ impl Tlas {
             .take_if(|buf| unsafe { buf.GetDesc() }.Width >= info.ScratchDataSizeInBytes)
             .unwrap_or_else(|| {
                 let scratch_buffer_name = "Scratch buffer for ".to_string() + name;
-                resource::create_buffer(
-                    device,
-                    info.ScratchDataSizeInBytes,
-                    D3D12_HEAP_TYPE_DEFAULT,
-                    D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
-                    D3D12_RESOURCE_STATE_COMMON,
-                    &scratch_buffer_name,
-                )
-                .unwrap()
+                device
+                    .create_placed_buffer(
+                        info.ScratchDataSizeInBytes,
+                        D3D12_HEAP_TYPE_DEFAULT,
+                        D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
+                        D3D12_RESOURCE_STATE_COMMON,
+                        &scratch_buffer_name,
+                    )
+                    .unwrap()
             });
-        self.scratch_buffer = Some(scratch_buffer);
+        // the GPU may still use the smaller buffers in the frames in flight
+        if let Some(old_buffer) = self.scratch_buffer.replace(scratch_buffer) {
+            device.release(old_buffer);
+        }
 
         let prev_address = self
             .buffer
@@ -468,18 +548,20 @@ impl Tlas {
             .buffer
             .take_if(|buf| unsafe { buf.GetDesc() }.Width >= info.ResultDataMaxSizeInBytes)
             .unwrap_or_else(|| {
-                resource::create_buffer(
-                    device,
-                    info.ResultDataMaxSizeInBytes,
-                    D3D12_HEAP_TYPE_DEFAULT,
-                    D3D12_RESOURCE_FLAG_RAYTRACING_ACCELERATION_STRUCTURE
-                        | D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
-                    D3D12_RESOURCE_STATE_RAYTRACING_ACCELERATION_STRUCTURE,
-                    name,
-                )
-                .unwrap()
+                device
+                    .create_placed_buffer(
+                        info.ResultDataMaxSizeInBytes,
+                        D3D12_HEAP_TYPE_DEFAULT,
+                        D3D12_RESOURCE_FLAG_RAYTRACING_ACCELERATION_STRUCTURE
+                            | D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
+                        D3D12_RESOURCE_STATE_RAYTRACING_ACCELERATION_STRUCTURE,
+                        name,
+                    )
+                    .unwrap()
             });
-        self.buffer = Some(buffer);
+        if let Some(old_buffer) = self.buffer.replace(buffer) {
+            device.release(old_buffer);
+        }
 
         prev_address != unsafe { self.buffer.as_ref().unwrap().GetGPUVirtualAddress() }
     }
@@ -538,6 +620,10 @@ impl Default for MeshData {
             index_buffer_handle: u32::MAX,
             position_buffer_handle: u32::MAX,
             normal_buffer_handle: u32::MAX,
+            uv_buffer_handle: u32::MAX,
+            tangent_buffer_handle: u32::MAX,
+            index_size: 4,
+            first_index: 0,
             _reserved: u32::MAX,
         }
     }
@@ -0,0 +1,117 @@
+// Keeps objects alive until the GPU has finished the commands that may reference them
+// Generic over the payload, so that it works for resources, heaps or anything else owned by the CPU
+// The fences are those of the graphics queue, retired in increasing order once per frame
+
+use std::collections::VecDeque;
+
+use super::fence::FenceValue;
+
+pub struct ReleaseQueue<T> {
+    // released in the current frame, waiting for its fence
+    pending: Vec<T>,
+    retired: VecDeque<(FenceValue, T)>,
+}
+
+impl<T> Default for ReleaseQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ReleaseQueue<T> {
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            retired: VecDeque::new(),
+        }
+    }
+
+    /// The number of objects that are not dropped yet
+    pub fn len(&self) -> usize {
+        self.pending.len() + self.retired.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops `payload` once the fence passed to the next `retire` has completed
+    pub fn defer(&mut self, payload: T) {
+        self.pending.push(payload);
+    }
+
+    /// Associates the objects deferred since the last call with `fence_value`
+    pub fn retire(&mut self, fence_value: FenceValue) {
+        let pending = self.pending.drain(..).map(|payload| (fence_value, payload));
+        self.retired.extend(pending);
+    }
+
+    /// Returns the objects whose fence has completed, which are dropped unless the caller keeps them
+    pub fn reclaim(&mut self, is_completed: impl Fn(FenceValue) -> bool) -> Vec<T> {
+        let mut completed = Vec::new();
+        while let Some((fence_value, _)) = self.retired.front() {
+            if !is_completed(*fence_value) {
+                break;
+            }
+            let (_, payload) = self.retired.pop_front().unwrap();
+            completed.push(payload);
+        }
+        completed
+    }
+
+    /// Drops everything, the GPU must be idle
+    pub fn flush(&mut self) {
+        self.pending.clear();
+        self.retired.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    fn fence(v: u64) -> FenceValue {
+        FenceValue { v }
+    }
+
+    #[test]
+    fn deferred_objects_take_the_next_retired_fence() {
+        let mut queue = ReleaseQueue::new();
+        queue.defer(1);
+        queue.defer(2);
+        assert_eq!(queue.len(), 2);
+
+        // not retired yet, so never reclaimed
+        assert_eq!(queue.reclaim(|_| true), Vec::<i32>::new());
+
+        queue.retire(fence(1));
+        queue.defer(3);
+        queue.retire(fence(2));
+
+        assert_eq!(queue.reclaim(|f| f <= fence(1)), [1, 2]);
+        assert_eq!(queue.reclaim(|f| f <= fence(2)), [3]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn payloads_are_dropped() {
+        let payload = Rc::new(());
+        let mut queue = ReleaseQueue::new();
+
+        queue.defer(payload.clone());
+        queue.retire(fence(1));
+        queue.defer(payload.clone());
+        queue.retire(fence(2));
+        queue.defer(payload.clone());
+        assert_eq!(Rc::strong_count(&payload), 4);
+
+        drop(queue.reclaim(|f| f <= fence(1)));
+        assert_eq!(Rc::strong_count(&payload), 3);
+
+        queue.flush();
+        assert_eq!(Rc::strong_count(&payload), 1);
+        assert!(queue.is_empty());
+    }
+}
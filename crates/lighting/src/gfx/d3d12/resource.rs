@@ -1,147 +1,375 @@
-use windows::Win32::Foundation::E_FAIL;
-use windows::Win32::Graphics::Direct3D12::*;
-use windows::Win32::Graphics::Dxgi::Common::*;
-
-use super::device::Device;
-use super::util::set_name_str;
-
-pub fn create_buffer(
-    device: &Device,
-    size: u64,
-    heap_type: D3D12_HEAP_TYPE,
-    flags: D3D12_RESOURCE_FLAGS,
-    init_state: D3D12_RESOURCE_STATES,
-    name: &str,
-) -> windows::core::Result<ID3D12Resource> {
-    let properties = heap_properties(heap_type);
-    let desc = buffer_desc(size, flags);
-    let mut buffer: Option<ID3D12Resource> = None;
-    unsafe {
-        device.get().CreateCommittedResource(
-            &properties,
-            D3D12_HEAP_FLAG_NONE,
-            &desc,
-            init_state,
-            None,
-            &mut buffer,
-        )
-    }?;
+// Tracks the memory of the resources created by the renderer, so that it can be reported by name
+// The records are kept separate from D3D12, so that the report can be built from any list of them
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use sandbox_core::json;
 
-    let buffer: ID3D12Resource = buffer.expect("Failed to create a buffer");
+use super::backend::{HeapType, ResourceFlags};
 
-    super::util::set_name_str(&buffer, name)?;
+const HEAP_TYPES: [HeapType; 3] = [HeapType::Default, HeapType::Upload, HeapType::Readback];
 
-    Ok(buffer)
+const FLAG_NAMES: [(ResourceFlags, &str); 5] = [
+    (ResourceFlags::ALLOW_RENDER_TARGET, "ALLOW_RENDER_TARGET"),
+    (ResourceFlags::ALLOW_DEPTH_STENCIL, "ALLOW_DEPTH_STENCIL"),
+    (
+        ResourceFlags::ALLOW_UNORDERED_ACCESS,
+        "ALLOW_UNORDERED_ACCESS",
+    ),
+    (ResourceFlags::DENY_SHADER_RESOURCE, "DENY_SHADER_RESOURCE"),
+    (
+        ResourceFlags::RAYTRACING_ACCELERATION_STRUCTURE,
+        "RAYTRACING_ACCELERATION_STRUCTURE",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceRecord {
+    pub name: String,
+    /// The bytes of memory the resource occupies, including the padding required by the device
+    pub size: u64,
+    pub heap_type: HeapType,
+    pub flags: ResourceFlags,
 }
 
-pub fn create_buffer_with_data<T>(
-    device: &Device,
-    heap_type: D3D12_HEAP_TYPE,
-    flags: D3D12_RESOURCE_FLAGS,
-    init_state: D3D12_RESOURCE_STATES,
-    init_data: &[T],
-    name: &str,
-) -> windows::core::Result<ID3D12Resource> {
-    let size = std::mem::size_of_val(init_data);
-    let buffer = create_buffer(device, size as u64, heap_type, flags, init_state, name)?;
-
-    let mut data = std::ptr::null_mut();
-    unsafe {
-        buffer.Map(0, None, Some(&mut data))?;
-        std::ptr::copy_nonoverlapping(init_data.as_ptr(), data as *mut T, init_data.len());
-        buffer.Unmap(0, None);
-    }
-
-    Ok(buffer)
+impl ResourceRecord {
+    /// The names of the known flags, unknown bits are ignored
+    pub fn flag_names(&self) -> Vec<&'static str> {
+        FLAG_NAMES
+            .iter()
+            .filter(|(flag, _)| self.flags.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect()
+    }
 }
 
-pub fn create_texture2d(
-    device: &Device,
-    size: (u32, u32),
-    format: DXGI_FORMAT,
-    resource_flags: D3D12_RESOURCE_FLAGS,
-    init_state: D3D12_RESOURCE_STATES,
-    clear_value: Option<*const D3D12_CLEAR_VALUE>,
-    name: &str,
-) -> windows::core::Result<ID3D12Resource> {
-    let (width, height) = size;
-
-    if width == 0 || height == 0 {
-        return Err(windows::core::Error::new(
-            E_FAIL,
-            "The width and the height must be grater than zero",
-        ));
-    }
-
-    let properties = heap_properties(D3D12_HEAP_TYPE_DEFAULT);
-
-    let desc = texture2d_desc(format, width.into(), height, resource_flags);
-
-    let mut texture: Option<ID3D12Resource> = None;
-    unsafe {
-        device.get().CreateCommittedResource(
-            &properties,
-            D3D12_HEAP_FLAG_NONE,
-            &desc,
-            init_state,
-            clear_value,
-            &mut texture,
-        )?;
-    };
-    let texture = texture.unwrap();
+/// The live resources by an identifier, such as the address of the resource
+#[derive(Debug, Default)]
+pub struct ResourceTracker {
+    records: BTreeMap<usize, ResourceRecord>,
+}
 
-    set_name_str(&texture, name)?;
+impl ResourceTracker {
+    pub const fn new() -> Self {
+        Self {
+            records: BTreeMap::new(),
+        }
+    }
 
-    Ok(texture)
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Replaces the record of `key` if it is already tracked
+    pub fn track(&mut self, key: usize, record: ResourceRecord) {
+        self.records.insert(key, record);
+    }
+
+    pub fn untrack(&mut self, key: usize) -> Option<ResourceRecord> {
+        self.records.remove(&key)
+    }
+
+    pub fn report(&self) -> MemoryReport {
+        MemoryReport::new(self.records.values().cloned())
+    }
 }
 
-fn heap_properties(heap_type: D3D12_HEAP_TYPE) -> D3D12_HEAP_PROPERTIES {
-    D3D12_HEAP_PROPERTIES {
-        Type: heap_type,
-        CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
-        MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
-        CreationNodeMask: 1,
-        VisibleNodeMask: 1,
+/// The memory used by each type of heap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapUsage {
+    pub heap_type: HeapType,
+    pub resource_count: usize,
+    pub size: u64,
+}
+
+/// The resources sorted from the largest, and their totals by heap type
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    records: Vec<ResourceRecord>,
+}
+
+impl MemoryReport {
+    pub fn new(records: impl IntoIterator<Item = ResourceRecord>) -> Self {
+        let mut records: Vec<_> = records.into_iter().collect();
+        records.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+
+        Self { records }
+    }
+
+    pub fn records(&self) -> &[ResourceRecord] {
+        &self.records
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.records.iter().map(|r| r.size).sum()
+    }
+
+    /// The usage of every heap type, including those without any resource
+    pub fn heap_usages(&self) -> Vec<HeapUsage> {
+        HEAP_TYPES
+            .iter()
+            .map(|heap_type| {
+                let records = self.records.iter().filter(|r| r.heap_type == *heap_type);
+                HeapUsage {
+                    heap_type: *heap_type,
+                    resource_count: records.clone().count(),
+                    size: records.map(|r| r.size).sum(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        let heaps: Vec<_> = self
+            .heap_usages()
+            .iter()
+            .map(|usage| {
+                format!(
+                    "{{\"heap_type\":{},\"resource_count\":{},\"size\":{}}}",
+                    json::quote(&format!("{:?}", usage.heap_type)),
+                    usage.resource_count,
+                    usage.size
+                )
+            })
+            .collect();
+
+        let resources: Vec<_> = self
+            .records
+            .iter()
+            .map(|record| {
+                let flags: Vec<_> = record.flag_names().into_iter().map(json::quote).collect();
+                format!(
+                    "{{\"name\":{},\"size\":{},\"heap_type\":{},\"flags\":[{}]}}",
+                    json::quote(&record.name),
+                    record.size,
+                    json::quote(&format!("{:?}", record.heap_type)),
+                    flags.join(",")
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"total_size\":{},\"heaps\":[{}],\"resources\":[{}]}}",
+            self.total_size(),
+            heaps.join(","),
+            resources.join(",")
+        )
     }
 }
 
-fn buffer_desc(buffer_size: u64, flags: D3D12_RESOURCE_FLAGS) -> D3D12_RESOURCE_DESC {
-    D3D12_RESOURCE_DESC {
-        Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
-        Alignment: 0,
-        Width: buffer_size,
-        Height: 1,
-        DepthOrArraySize: 1,
-        MipLevels: 1,
-        Format: DXGI_FORMAT_UNKNOWN,
-        SampleDesc: DXGI_SAMPLE_DESC {
-            Count: 1,
-            Quality: 0,
-        },
-        Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
-        Flags: flags,
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "GPU memory: {} in {} resources",
+            format_size(self.total_size()),
+            self.records.len()
+        )?;
+
+        for usage in self.heap_usages() {
+            writeln!(
+                f,
+                "  {:<10} {:>12} in {} resources",
+                format!("{:?}", usage.heap_type),
+                format_size(usage.size),
+                usage.resource_count
+            )?;
+        }
+
+        for record in &self.records {
+            write!(
+                f,
+                "  {:>12}  {:<10} {}",
+                format_size(record.size),
+                format!("{:?}", record.heap_type),
+                record.name
+            )?;
+
+            let flags = record.flag_names();
+            if !flags.is_empty() {
+                write!(f, " [{}]", flags.join(" | "))?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats `bytes` with the largest binary unit that keeps the number above 1
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
     }
+
+    format!("{size:.2} {}", UNITS[unit])
 }
 
-fn texture2d_desc(
-    format: DXGI_FORMAT,
-    width: u64,
-    height: u32,
-    flags: D3D12_RESOURCE_FLAGS,
-) -> D3D12_RESOURCE_DESC {
-    D3D12_RESOURCE_DESC {
-        Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
-        Alignment: 0,
-        Width: width,
-        Height: height,
-        DepthOrArraySize: 1,
-        MipLevels: 1,
-        Format: format,
-        SampleDesc: DXGI_SAMPLE_DESC {
-            Count: 1,
-            Quality: 0,
-        },
-        Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
-        Flags: flags,
+#[cfg(windows)]
+mod d3d12;
+#[cfg(windows)]
+pub use d3d12::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn record(name: &str, size: u64, heap_type: HeapType, flags: ResourceFlags) -> ResourceRecord {
+        ResourceRecord {
+            name: name.to_string(),
+            size,
+            heap_type,
+            flags,
+        }
+    }
+
+    // in no particular order
+    fn report() -> MemoryReport {
+        MemoryReport::new([
+            record("constants", 1000, HeapType::Upload, ResourceFlags::NONE),
+            record(
+                "upload \"ring\"",
+                4 * MIB,
+                HeapType::Upload,
+                ResourceFlags::NONE,
+            ),
+            record(
+                "depth buffer",
+                4 * MIB,
+                HeapType::Default,
+                ResourceFlags::ALLOW_DEPTH_STENCIL | ResourceFlags::DENY_SHADER_RESOURCE,
+            ),
+            record(
+                "color buffer",
+                8 * MIB,
+                HeapType::Default,
+                ResourceFlags::ALLOW_RENDER_TARGET,
+            ),
+        ])
+    }
+
+    #[test]
+    fn records_are_sorted_by_size_and_name() {
+        let report = report();
+        let names: Vec<_> = report.records().iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "color buffer",
+                "depth buffer",
+                "upload \"ring\"",
+                "constants"
+            ]
+        );
+        assert_eq!(report.total_size(), 16 * MIB + 1000);
+    }
+
+    #[test]
+    fn heap_usages_include_empty_heaps() {
+        assert_eq!(
+            report().heap_usages(),
+            [
+                HeapUsage {
+                    heap_type: HeapType::Default,
+                    resource_count: 2,
+                    size: 12 * MIB,
+                },
+                HeapUsage {
+                    heap_type: HeapType::Upload,
+                    resource_count: 2,
+                    size: 4 * MIB + 1000,
+                },
+                HeapUsage {
+                    heap_type: HeapType::Readback,
+                    resource_count: 0,
+                    size: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn display() {
+        let expected = concat!(
+            "GPU memory: 16.00 MiB in 4 resources\n",
+            "  Default       12.00 MiB in 2 resources\n",
+            "  Upload         4.00 MiB in 2 resources\n",
+            "  Readback            0 B in 0 resources\n",
+            "      8.00 MiB  Default    color buffer [ALLOW_RENDER_TARGET]\n",
+            "      4.00 MiB  Default    depth buffer [ALLOW_DEPTH_STENCIL | DENY_SHADER_RESOURCE]\n",
+            "      4.00 MiB  Upload     upload \"ring\"\n",
+            "        1000 B  Upload     constants\n",
+        );
+        assert_eq!(report().to_string(), expected);
+    }
+
+    #[test]
+    fn to_json() {
+        let expected = concat!(
+            r#"{"total_size":16778216,"heaps":["#,
+            r#"{"heap_type":"Default","resource_count":2,"size":12582912},"#,
+            r#"{"heap_type":"Upload","resource_count":2,"size":4195304},"#,
+            r#"{"heap_type":"Readback","resource_count":0,"size":0}"#,
+            r#"],"resources":["#,
+            r#"{"name":"color buffer","size":8388608,"heap_type":"Default","flags":["ALLOW_RENDER_TARGET"]},"#,
+            r#"{"name":"depth buffer","size":4194304,"heap_type":"Default","#,
+            r#""flags":["ALLOW_DEPTH_STENCIL","DENY_SHADER_RESOURCE"]},"#,
+            r#"{"name":"upload \"ring\"","size":4194304,"heap_type":"Upload","flags":[]},"#,
+            r#"{"name":"constants","size":1000,"heap_type":"Upload","flags":[]}"#,
+            r#"]}"#,
+        );
+        assert_eq!(report().to_json(), expected);
+    }
+
+    #[test]
+    fn empty_report() {
+        let report = ResourceTracker::new().report();
+        assert_eq!(
+            report.to_json(),
+            concat!(
+                r#"{"total_size":0,"heaps":[{"heap_type":"Default","resource_count":0,"size":0},"#,
+                r#"{"heap_type":"Upload","resource_count":0,"size":0},"#,
+                r#"{"heap_type":"Readback","resource_count":0,"size":0}],"resources":[]}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn tracker_replaces_records() {
+        let mut tracker = ResourceTracker::new();
+        tracker.track(1, record("a", 1, HeapType::Default, ResourceFlags::NONE));
+        tracker.track(1, record("b", 2, HeapType::Default, ResourceFlags::NONE));
+        tracker.track(2, record("c", 3, HeapType::Upload, ResourceFlags::NONE));
+        assert_eq!(tracker.len(), 2);
+
+        assert_eq!(tracker.untrack(1).map(|r| r.name), Some("b".to_string()));
+        assert_eq!(tracker.untrack(1), None);
+        assert_eq!(tracker.report().total_size(), 3);
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.00 KiB");
+        assert_eq!(format_size(1536 * 1024), "1.50 MiB");
+        assert_eq!(format_size(3 << 40), "3.00 TiB");
+        assert_eq!(format_size(2048 << 40), "2048.00 TiB");
     }
 }
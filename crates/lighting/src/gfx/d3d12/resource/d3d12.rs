@@ -0,0 +1,200 @@
+use std::ffi::c_void;
+use std::sync::Mutex;
+
+use windows::core::Interface;
+use windows::Win32::Foundation::E_FAIL;
+use windows::Win32::Graphics::Direct3D::ID3DDestructionNotifier;
+use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+use super::super::backend::HeapType;
+use super::super::device::Device;
+use super::super::util::set_name_str;
+use super::{MemoryReport, ResourceRecord, ResourceTracker};
+
+static TRACKER: Mutex<ResourceTracker> = Mutex::new(ResourceTracker::new());
+
+pub fn create_buffer(
+    device: &Device,
+    size: u64,
+    heap_type: D3D12_HEAP_TYPE,
+    flags: D3D12_RESOURCE_FLAGS,
+    init_state: D3D12_RESOURCE_STATES,
+    name: &str,
+) -> windows::core::Result<ID3D12Resource> {
+    let properties = heap_properties(heap_type);
+    let desc = buffer_desc(size, flags);
+    let mut buffer: Option<ID3D12Resource> = None;
+    unsafe {
+        device.get().CreateCommittedResource(
+            &properties,
+            D3D12_HEAP_FLAG_NONE,
+            &desc,
+            init_state,
+            None,
+            &mut buffer,
+        )
+    }?;
+
+    let buffer: ID3D12Resource = buffer.expect("Failed to create a buffer");
+
+    set_name_str(&buffer, name)?;
+    track(device.get(), &buffer, heap_type, name)?;
+
+    Ok(buffer)
+}
+
+pub fn create_buffer_with_data<T>(
+    device: &Device,
+    heap_type: D3D12_HEAP_TYPE,
+    flags: D3D12_RESOURCE_FLAGS,
+    init_state: D3D12_RESOURCE_STATES,
+    init_data: &[T],
+    name: &str,
+) -> windows::core::Result<ID3D12Resource> {
+    let size = std::mem::size_of_val(init_data);
+    let buffer = create_buffer(device, size as u64, heap_type, flags, init_state, name)?;
+
+    let mut data = std::ptr::null_mut();
+    unsafe {
+        buffer.Map(0, None, Some(&mut data))?;
+        std::ptr::copy_nonoverlapping(init_data.as_ptr(), data as *mut T, init_data.len());
+        buffer.Unmap(0, None);
+    }
+
+    Ok(buffer)
+}
+
+pub fn create_texture2d(
+    device: &Device,
+    size: (u32, u32),
+    format: DXGI_FORMAT,
+    resource_flags: D3D12_RESOURCE_FLAGS,
+    init_state: D3D12_RESOURCE_STATES,
+    clear_value: Option<*const D3D12_CLEAR_VALUE>,
+    name: &str,
+) -> windows::core::Result<ID3D12Resource> {
+    let (width, height) = size;
+
+    if width == 0 || height == 0 {
+        return Err(windows::core::Error::new(
+            E_FAIL,
+            "The width and the height must be grater than zero",
+        ));
+    }
+
+    let properties = heap_properties(D3D12_HEAP_TYPE_DEFAULT);
+
+    let desc = texture2d_desc(format, width.into(), height, resource_flags);
+
+    let mut texture: Option<ID3D12Resource> = None;
+    unsafe {
+        device.get().CreateCommittedResource(
+            &properties,
+            D3D12_HEAP_FLAG_NONE,
+            &desc,
+            init_state,
+            clear_value,
+            &mut texture,
+        )?;
+    };
+    let texture = texture.unwrap();
+
+    set_name_str(&texture, name)?;
+    track(device.get(), &texture, D3D12_HEAP_TYPE_DEFAULT, name)?;
+
+    Ok(texture)
+}
+
+pub(in super::super) fn heap_properties(heap_type: D3D12_HEAP_TYPE) -> D3D12_HEAP_PROPERTIES {
+    D3D12_HEAP_PROPERTIES {
+        Type: heap_type,
+        CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
+        MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
+        CreationNodeMask: 1,
+        VisibleNodeMask: 1,
+    }
+}
+
+pub(in super::super) fn buffer_desc(
+    buffer_size: u64,
+    flags: D3D12_RESOURCE_FLAGS,
+) -> D3D12_RESOURCE_DESC {
+    D3D12_RESOURCE_DESC {
+        Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+        Alignment: 0,
+        Width: buffer_size,
+        Height: 1,
+        DepthOrArraySize: 1,
+        MipLevels: 1,
+        Format: DXGI_FORMAT_UNKNOWN,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+        Flags: flags,
+    }
+}
+
+fn texture2d_desc(
+    format: DXGI_FORMAT,
+    width: u64,
+    height: u32,
+    flags: D3D12_RESOURCE_FLAGS,
+) -> D3D12_RESOURCE_DESC {
+    D3D12_RESOURCE_DESC {
+        Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+        Alignment: 0,
+        Width: width,
+        Height: height,
+        DepthOrArraySize: 1,
+        MipLevels: 1,
+        Format: format,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+        Flags: flags,
+    }
+}
+
+/// Records the memory of `resource` in the report until the resource is destroyed
+pub fn track(
+    device: &ID3D12Device5,
+    resource: &ID3D12Resource,
+    heap_type: D3D12_HEAP_TYPE,
+    name: &str,
+) -> windows::core::Result<()> {
+    let desc = unsafe { resource.GetDesc() };
+    let info = unsafe { device.GetResourceAllocationInfo(0, &[desc]) };
+
+    let record = ResourceRecord {
+        name: name.to_string(),
+        size: info.SizeInBytes,
+        heap_type: HeapType::try_from(heap_type).expect("Unsupported heap type"),
+        flags: desc.Flags.into(),
+    };
+
+    // the resource may outlive every owner in the renderer, e.g. while the GPU still uses it
+    let key = resource.as_raw() as usize;
+    let notifier: ID3DDestructionNotifier = resource.cast()?;
+    unsafe { notifier.RegisterDestructionCallback(Some(on_destroyed), key as *const c_void) }?;
+
+    TRACKER.lock().unwrap().track(key, record);
+
+    Ok(())
+}
+
+/// The resources that are alive now
+pub fn memory_report() -> MemoryReport {
+    TRACKER.lock().unwrap().report()
+}
+
+unsafe extern "system" fn on_destroyed(data: *mut c_void) {
+    // must not panic across the FFI boundary
+    if let Ok(mut tracker) = TRACKER.lock() {
+        tracker.untrack(data as usize);
+    }
+}
@@ -0,0 +1,202 @@
+// Linear allocator over a ring buffer for data written by the CPU every frame
+// The allocations of a frame are released together once the GPU has passed the fence of the frame
+
+use std::collections::VecDeque;
+
+use super::fence::FenceValue;
+
+/// Constant buffers must be placed at multiples of this
+pub const CONSTANT_BUFFER_ALIGNMENT: u64 = 256;
+
+pub struct RingAllocator {
+    capacity: u64,
+    // positions increase monotonically, and the offset in the buffer is position % capacity
+    head: u64,
+    tail: u64,
+    // the head at the end of each retired frame, oldest first
+    frames: VecDeque<(FenceValue, u64)>,
+}
+
+impl RingAllocator {
+    pub fn new(capacity: u64) -> Self {
+        assert!(capacity > 0);
+
+        Self {
+            capacity,
+            head: 0,
+            tail: 0,
+            frames: VecDeque::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// The number of bytes used by the frames in flight, including the padding
+    pub fn used(&self) -> u64 {
+        self.head - self.tail
+    }
+
+    /// Returns the offset in the buffer, or None if there is not enough space until older frames retire
+    /// An allocation never wraps around the end of the buffer
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        assert!(alignment > 0);
+
+        if size > self.capacity {
+            return None;
+        }
+
+        let offset = self.head % self.capacity;
+        let aligned = offset.next_multiple_of(alignment);
+
+        let start = if aligned + size <= self.capacity {
+            self.head + (aligned - offset)
+        } else {
+            // the beginning of the buffer is aligned to anything
+            self.head + (self.capacity - offset)
+        };
+
+        let end = start + size;
+        if end - self.tail > self.capacity {
+            return None;
+        }
+
+        self.head = end;
+
+        Some(start % self.capacity)
+    }
+
+    /// Associates the allocations since the last call with `fence_value`
+    pub fn retire(&mut self, fence_value: FenceValue) {
+        let last_end = self.frames.back().map_or(self.tail, |(_, end)| *end);
+        if self.head != last_end {
+            self.frames.push_back((fence_value, self.head));
+        }
+    }
+
+    /// Releases the frames whose fence has completed
+    pub fn reclaim(&mut self, is_completed: impl Fn(FenceValue) -> bool) {
+        while let Some((fence_value, end)) = self.frames.front() {
+            if !is_completed(*fence_value) {
+                break;
+            }
+
+            self.tail = *end;
+            self.frames.pop_front();
+        }
+
+        // restart from the beginning of the buffer when nothing is in use, which avoids wrapping
+        if self.frames.is_empty() && self.head == self.tail {
+            self.head = 0;
+            self.tail = 0;
+        }
+    }
+
+    /// The fence to wait for when the ring is full
+    pub fn oldest_fence(&self) -> Option<FenceValue> {
+        self.frames.front().map(|(fence_value, _)| *fence_value)
+    }
+}
+
+#[cfg(windows)]
+mod upload;
+#[cfg(windows)]
+pub use upload::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fence(v: u64) -> FenceValue {
+        FenceValue { v }
+    }
+
+    #[test]
+    fn allocations_are_aligned() {
+        let mut ring = RingAllocator::new(4096);
+
+        assert_eq!(ring.allocate(100, CONSTANT_BUFFER_ALIGNMENT), Some(0));
+        assert_eq!(ring.allocate(4, 4), Some(100));
+        assert_eq!(ring.allocate(64, CONSTANT_BUFFER_ALIGNMENT), Some(256));
+        assert_eq!(ring.allocate(1, CONSTANT_BUFFER_ALIGNMENT), Some(512));
+        // the padding is in use until the frame retires
+        assert_eq!(ring.used(), 513);
+    }
+
+    #[test]
+    fn allocations_wrap_around_instead_of_splitting() {
+        let mut ring = RingAllocator::new(1024);
+
+        assert_eq!(ring.allocate(512, CONSTANT_BUFFER_ALIGNMENT), Some(0));
+        ring.retire(fence(1));
+        assert_eq!(ring.allocate(384, CONSTANT_BUFFER_ALIGNMENT), Some(512));
+        ring.retire(fence(2));
+
+        // the 128 bytes at the end are too small, and the beginning is still in use
+        assert_eq!(ring.allocate(256, CONSTANT_BUFFER_ALIGNMENT), None);
+
+        ring.reclaim(|f| f <= fence(1));
+        assert_eq!(ring.allocate(256, CONSTANT_BUFFER_ALIGNMENT), Some(0));
+        // 384 bytes of the second frame, the skipped 128 bytes and the new allocation
+        assert_eq!(ring.used(), 768);
+    }
+
+    #[test]
+    fn frames_are_released_in_order_of_their_fences() {
+        let mut ring = RingAllocator::new(1024);
+
+        for frame in 1..=4 {
+            assert!(ring.allocate(256, CONSTANT_BUFFER_ALIGNMENT).is_some());
+            ring.retire(fence(frame));
+        }
+        assert_eq!(ring.allocate(1, 1), None);
+        assert_eq!(ring.oldest_fence(), Some(fence(1)));
+
+        ring.reclaim(|f| f <= fence(2));
+        assert_eq!(ring.used(), 512);
+        assert_eq!(ring.oldest_fence(), Some(fence(3)));
+
+        // a completed fence behind an incomplete one is not released
+        ring.reclaim(|f| f == fence(4));
+        assert_eq!(ring.used(), 512);
+
+        ring.reclaim(|_| true);
+        assert_eq!(ring.used(), 0);
+        assert_eq!(ring.oldest_fence(), None);
+    }
+
+    #[test]
+    fn empty_ring_restarts_at_the_beginning() {
+        let mut ring = RingAllocator::new(1024);
+
+        assert_eq!(ring.allocate(768, CONSTANT_BUFFER_ALIGNMENT), Some(0));
+        ring.retire(fence(1));
+        ring.reclaim(|_| true);
+
+        // fits only because the ring is empty
+        assert_eq!(ring.allocate(512, CONSTANT_BUFFER_ALIGNMENT), Some(0));
+    }
+
+    #[test]
+    fn retire_without_allocations_is_ignored() {
+        let mut ring = RingAllocator::new(1024);
+
+        ring.retire(fence(1));
+        assert_eq!(ring.oldest_fence(), None);
+
+        assert!(ring.allocate(16, 16).is_some());
+        ring.retire(fence(2));
+        ring.retire(fence(3));
+        assert_eq!(ring.oldest_fence(), Some(fence(2)));
+        ring.reclaim(|f| f <= fence(2));
+        assert_eq!(ring.oldest_fence(), None);
+    }
+
+    #[test]
+    fn oversized_allocation() {
+        let mut ring = RingAllocator::new(1024);
+        assert_eq!(ring.allocate(1025, 1), None);
+        assert_eq!(ring.allocate(1024, 1), Some(0));
+    }
+}
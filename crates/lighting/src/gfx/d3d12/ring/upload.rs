@@ -0,0 +1,132 @@
+use windows::Win32::Foundation::E_OUTOFMEMORY;
+use windows::Win32::Graphics::Direct3D12::*;
+
+use super::super::{fence::FenceValue, resource, util::set_name_str};
+use super::RingAllocator;
+
+/// A block of `UploadRing` that the CPU writes and the GPU reads in the same frame
+pub struct UploadAllocation {
+    pub cpu_address: *mut u8,
+    pub gpu_address: u64,
+    /// Offset from the beginning of `UploadRing::buffer`
+    pub offset: u64,
+    pub size: u64,
+}
+
+pub struct UploadRing {
+    buffer: ID3D12Resource,
+    // UPLOAD heaps can stay mapped while the GPU reads them
+    cpu_address: *mut u8,
+    gpu_address: u64,
+    allocator: RingAllocator,
+}
+
+impl UploadRing {
+    pub fn build(device: &ID3D12Device5, size: u64, name: &str) -> windows::core::Result<Self> {
+        let (buffer, cpu_address) = create_mapped_buffer(device, size, name)?;
+        let gpu_address = unsafe { buffer.GetGPUVirtualAddress() };
+
+        Ok(Self {
+            buffer,
+            cpu_address,
+            gpu_address,
+            allocator: RingAllocator::new(size),
+        })
+    }
+
+    pub fn buffer(&self) -> &ID3D12Resource {
+        &self.buffer
+    }
+
+    pub fn allocator(&self) -> &RingAllocator {
+        &self.allocator
+    }
+
+    /// Returns an error if there is not enough space until the frames in flight retire
+    pub fn allocate(
+        &mut self,
+        size: u64,
+        alignment: u64,
+    ) -> windows::core::Result<UploadAllocation> {
+        let Some(offset) = self.allocator.allocate(size, alignment) else {
+            return Err(windows::core::Error::new(
+                E_OUTOFMEMORY,
+                format!(
+                    "Failed to allocate {size} bytes from the upload ring of {} bytes",
+                    self.allocator.capacity()
+                ),
+            ));
+        };
+
+        Ok(UploadAllocation {
+            cpu_address: unsafe { self.cpu_address.add(offset as usize) },
+            gpu_address: self.gpu_address + offset,
+            offset,
+            size,
+        })
+    }
+
+    /// Allocates a block and copies `data` into it
+    pub fn upload<T: Copy>(
+        &mut self,
+        data: &[T],
+        alignment: u64,
+    ) -> windows::core::Result<UploadAllocation> {
+        let size = std::mem::size_of_val(data) as u64;
+        let allocation = self.allocate(size, alignment)?;
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                allocation.cpu_address as *mut T,
+                data.len(),
+            )
+        };
+
+        Ok(allocation)
+    }
+
+    pub fn retire(&mut self, fence_value: FenceValue) {
+        self.allocator.retire(fence_value);
+    }
+
+    pub fn reclaim(&mut self, is_completed: impl Fn(FenceValue) -> bool) {
+        self.allocator.reclaim(is_completed);
+    }
+}
+
+impl Drop for UploadRing {
+    fn drop(&mut self) {
+        unsafe { self.buffer.Unmap(0, None) };
+    }
+}
+
+/// Creates a buffer in an UPLOAD heap that stays mapped, which must be unmapped before it is released
+pub(in super::super) fn create_mapped_buffer(
+    device: &ID3D12Device5,
+    size: u64,
+    name: &str,
+) -> windows::core::Result<(ID3D12Resource, *mut u8)> {
+    let properties = resource::heap_properties(D3D12_HEAP_TYPE_UPLOAD);
+    let desc = resource::buffer_desc(size, D3D12_RESOURCE_FLAG_NONE);
+    let mut buffer: Option<ID3D12Resource> = None;
+    unsafe {
+        device.CreateCommittedResource(
+            &properties,
+            D3D12_HEAP_FLAG_NONE,
+            &desc,
+            D3D12_RESOURCE_STATE_GENERIC_READ,
+            None,
+            &mut buffer,
+        )
+    }?;
+
+    let buffer: ID3D12Resource = buffer.expect("Failed to create a buffer");
+    set_name_str(&buffer, name)?;
+    resource::track(device, &buffer, D3D12_HEAP_TYPE_UPLOAD, name)?;
+
+    let mut cpu_address = std::ptr::null_mut();
+    unsafe { buffer.Map(0, None, Some(&mut cpu_address)) }?;
+
+    Ok((buffer, cpu_address as *mut u8))
+}
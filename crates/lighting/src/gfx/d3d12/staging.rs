@@ -0,0 +1,294 @@
+// Packs many uploads into one staging ring, so that they are copied to the GPU in a single submission
+// The staging memory of a submission is reused once the fence of the copy queue has completed
+
+use super::fence::FenceValue;
+use super::ring::RingAllocator;
+
+/// Rows of texture data in a buffer must be placed at multiples of this
+pub const TEXTURE_DATA_PITCH_ALIGNMENT: u64 = 256;
+/// Texture data in a buffer must start at multiples of this
+pub const TEXTURE_DATA_PLACEMENT_ALIGNMENT: u64 = 512;
+// not required by CopyBufferRegion, but keeps the CPU writes aligned
+const BUFFER_DATA_ALIGNMENT: u64 = 16;
+
+/// Layout of a 2D subresource in a staging buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureFootprint {
+    pub width: u32,
+    pub height: u32,
+    /// The bytes of a row in the source data, which is tightly packed
+    pub row_size: u64,
+    /// The distance between rows in the staging buffer
+    pub row_pitch: u64,
+}
+
+impl TextureFootprint {
+    pub fn new(width: u32, height: u32, bytes_per_pixel: u32) -> Self {
+        let row_size = width as u64 * bytes_per_pixel as u64;
+
+        Self {
+            width,
+            height,
+            row_size,
+            row_pitch: row_size.next_multiple_of(TEXTURE_DATA_PITCH_ALIGNMENT),
+        }
+    }
+
+    /// The bytes used in the staging buffer
+    pub fn size(&self) -> u64 {
+        self.row_pitch * self.height as u64
+    }
+}
+
+/// A copy from the staging buffer, recorded when the batch is submitted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StagedCopy<K> {
+    Buffer {
+        dst: K,
+        dst_offset: u64,
+        src_offset: u64,
+        size: u64,
+    },
+    Texture {
+        dst: K,
+        subresource: u32,
+        src_offset: u64,
+        footprint: TextureFootprint,
+    },
+}
+
+pub struct StagingBatch<K> {
+    allocator: RingAllocator,
+    // staged since the last submission
+    copies: Vec<StagedCopy<K>>,
+}
+
+impl<K> StagingBatch<K> {
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            allocator: RingAllocator::new(capacity),
+            copies: Vec::new(),
+        }
+    }
+
+    pub fn allocator(&self) -> &RingAllocator {
+        &self.allocator
+    }
+
+    pub fn copies(&self) -> &[StagedCopy<K>] {
+        &self.copies
+    }
+
+    /// Returns the offset in the staging buffer to write `size` bytes at, or None if the staging
+    /// buffer is full
+    pub fn stage_buffer(&mut self, dst: K, dst_offset: u64, size: u64) -> Option<u64> {
+        let src_offset = self.allocator.allocate(size, BUFFER_DATA_ALIGNMENT)?;
+
+        self.copies.push(StagedCopy::Buffer {
+            dst,
+            dst_offset,
+            src_offset,
+            size,
+        });
+
+        Some(src_offset)
+    }
+
+    /// Returns the offset in the staging buffer to write the rows at with `footprint.row_pitch`, or
+    /// None if the staging buffer is full
+    pub fn stage_texture(
+        &mut self,
+        dst: K,
+        subresource: u32,
+        footprint: TextureFootprint,
+    ) -> Option<u64> {
+        let src_offset = self
+            .allocator
+            .allocate(footprint.size(), TEXTURE_DATA_PLACEMENT_ALIGNMENT)?;
+
+        self.copies.push(StagedCopy::Texture {
+            dst,
+            subresource,
+            src_offset,
+            footprint,
+        });
+
+        Some(src_offset)
+    }
+
+    /// Returns the copies staged since the last call, which are recorded in one submission
+    pub fn take_copies(&mut self) -> Vec<StagedCopy<K>> {
+        std::mem::take(&mut self.copies)
+    }
+
+    /// Associates the staging memory of the copies taken since the last call with `fence_value`,
+    /// which is signaled after the submission
+    pub fn retire(&mut self, fence_value: FenceValue) {
+        self.allocator.retire(fence_value);
+    }
+
+    pub fn reclaim(&mut self, is_completed: impl Fn(FenceValue) -> bool) {
+        self.allocator.reclaim(is_completed);
+    }
+
+    /// Frees the staging memory of the oldest submission after `wait` has blocked until its fence
+    /// has completed, the staged copies must have been submitted before
+    /// Returns false if there is no submission to wait for
+    pub fn make_room(&mut self, wait: impl FnOnce(FenceValue)) -> bool {
+        let Some(oldest) = self.allocator.oldest_fence() else {
+            return false;
+        };
+
+        wait(oldest);
+
+        // the submissions are on one queue, so the earlier ones have completed too
+        self.allocator.reclaim(|fence_value| fence_value <= oldest);
+        true
+    }
+}
+
+/// Splits `size` bytes into ranges of at most `max_size` bytes, so that data larger than the
+/// staging buffer can be uploaded in several submissions
+pub fn chunks(size: u64, max_size: u64) -> impl Iterator<Item = std::ops::Range<u64>> {
+    assert!(max_size > 0);
+
+    (0..size.div_ceil(max_size)).map(move |i| {
+        let start = i * max_size;
+        start..(start + max_size).min(size)
+    })
+}
+
+#[cfg(windows)]
+mod manager;
+#[cfg(windows)]
+pub use manager::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fence(v: u64) -> FenceValue {
+        FenceValue { v }
+    }
+
+    // what the upload manager does when it submits the staged copies
+    fn submit(
+        batch: &mut StagingBatch<&'static str>,
+        fence_value: FenceValue,
+    ) -> Vec<StagedCopy<&'static str>> {
+        let copies = batch.take_copies();
+        batch.retire(fence_value);
+        copies
+    }
+
+    #[test]
+    fn texture_footprint() {
+        let footprint = TextureFootprint::new(100, 3, 4);
+        assert_eq!(footprint.row_size, 400);
+        assert_eq!(footprint.row_pitch, 512);
+        assert_eq!(footprint.size(), 1536);
+
+        let footprint = TextureFootprint::new(64, 2, 4);
+        assert_eq!(footprint.row_pitch, 256);
+        assert_eq!(footprint.size(), 512);
+    }
+
+    #[test]
+    fn copies_are_packed_with_their_alignment() {
+        let mut batch = StagingBatch::new(4096);
+        let footprint = TextureFootprint::new(4, 4, 4);
+
+        assert_eq!(batch.stage_buffer("a", 0, 20), Some(0));
+        assert_eq!(batch.stage_buffer("b", 8, 4), Some(32));
+        assert_eq!(batch.stage_texture("c", 2, footprint), Some(512));
+        assert_eq!(batch.stage_buffer("d", 0, 1), Some(1536));
+
+        assert_eq!(
+            submit(&mut batch, fence(1)),
+            [
+                StagedCopy::Buffer {
+                    dst: "a",
+                    dst_offset: 0,
+                    src_offset: 0,
+                    size: 20
+                },
+                StagedCopy::Buffer {
+                    dst: "b",
+                    dst_offset: 8,
+                    src_offset: 32,
+                    size: 4
+                },
+                StagedCopy::Texture {
+                    dst: "c",
+                    subresource: 2,
+                    src_offset: 512,
+                    footprint
+                },
+                StagedCopy::Buffer {
+                    dst: "d",
+                    dst_offset: 0,
+                    src_offset: 1536,
+                    size: 1
+                },
+            ]
+        );
+        assert_eq!(batch.copies(), []);
+        assert_eq!(batch.allocator().used(), 1537);
+    }
+
+    #[test]
+    fn full_staging_buffer() {
+        let mut batch = StagingBatch::new(1024);
+
+        assert_eq!(batch.stage_buffer("a", 0, 1000), Some(0));
+        assert_eq!(batch.stage_buffer("b", 0, 100), None);
+        // the failed copy is not recorded
+        assert_eq!(batch.copies().len(), 1);
+    }
+
+    #[test]
+    fn chunks_cover_the_data() {
+        let ranges: Vec<_> = chunks(10, 4).collect();
+        assert_eq!(ranges, [0..4, 4..8, 8..10]);
+
+        let ranges: Vec<_> = chunks(8, 4).collect();
+        assert_eq!(ranges, [0..4, 4..8]);
+
+        let ranges: Vec<_> = chunks(3, 4).collect();
+        assert_eq!(ranges, vec![0..3]);
+        assert_eq!(chunks(0, 4).count(), 0);
+    }
+
+    #[test]
+    fn make_room_waits_for_the_oldest_submission() {
+        let mut batch = StagingBatch::new(1024);
+
+        assert!(batch.stage_buffer("a", 0, 256).is_some());
+        submit(&mut batch, fence(1));
+        assert!(batch.stage_buffer("b", 0, 256).is_some());
+        submit(&mut batch, fence(2));
+        assert!(batch.stage_buffer("c", 0, 512).is_some());
+        assert_eq!(batch.stage_buffer("d", 0, 256), None);
+
+        submit(&mut batch, fence(3));
+        let mut waited = Vec::new();
+        assert!(batch.make_room(|fence_value| waited.push(fence_value)));
+        assert_eq!(waited, [fence(1)]);
+        assert_eq!(batch.allocator().used(), 768);
+
+        assert_eq!(batch.stage_buffer("d", 0, 256), Some(0));
+    }
+
+    #[test]
+    fn make_room_without_submissions() {
+        let mut batch = StagingBatch::new(1024);
+        assert!(batch.stage_buffer("a", 0, 1024).is_some());
+
+        // the copy has not been submitted, so there is nothing to wait for
+        assert!(!batch.make_room(|_| panic!("nothing to wait for")));
+
+        submit(&mut batch, fence(1));
+        assert!(batch.make_room(|_| {}));
+        assert_eq!(batch.allocator().used(), 0);
+    }
+}
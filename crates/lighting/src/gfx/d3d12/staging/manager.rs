@@ -0,0 +1,254 @@
+use windows::Win32::Foundation::E_OUTOFMEMORY;
+use windows::Win32::Graphics::Direct3D12::*;
+
+use super::super::{command::Queue, fence::FenceValue, ring::create_mapped_buffer};
+use super::{chunks, StagedCopy, StagingBatch, TextureFootprint};
+
+/// Uploads data to DEFAULT heap resources through a persistently mapped staging buffer
+/// The copies are recorded on the copy queue when `submit` is called, or when the staging buffer is full
+pub struct UploadManager {
+    buffer: ID3D12Resource,
+    cpu_address: *mut u8,
+    batch: StagingBatch<ID3D12Resource>,
+}
+
+impl UploadManager {
+    pub fn build(device: &ID3D12Device5, size: u64, name: &str) -> windows::core::Result<Self> {
+        let (buffer, cpu_address) = create_mapped_buffer(device, size, name)?;
+
+        Ok(Self {
+            buffer,
+            cpu_address,
+            batch: StagingBatch::new(size),
+        })
+    }
+
+    pub fn batch(&self) -> &StagingBatch<ID3D12Resource> {
+        &self.batch
+    }
+
+    /// Copies `data` to `dst` at `dst_offset`, `dst` must be in the COMMON state
+    pub fn upload_buffer<T: Copy>(
+        &mut self,
+        queue: &mut Queue,
+        dst: &ID3D12Resource,
+        dst_offset: u64,
+        data: &[T],
+    ) -> windows::core::Result<()> {
+        let size = std::mem::size_of_val(data) as u64;
+        let bytes =
+            unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, size as usize) };
+
+        // a chunk never takes the whole staging buffer, which may not start at an aligned offset
+        let max_chunk_size = self.batch.allocator().capacity() / 2;
+
+        for range in chunks(size, max_chunk_size) {
+            let chunk_size = range.end - range.start;
+            let src_offset = loop {
+                let dst = dst.clone();
+                match self
+                    .batch
+                    .stage_buffer(dst, dst_offset + range.start, chunk_size)
+                {
+                    Some(src_offset) => break src_offset,
+                    None => self.make_room(queue)?,
+                }
+            };
+
+            let chunk = &bytes[range.start as usize..range.end as usize];
+            self.write(src_offset, chunk.as_ptr(), chunk.len());
+        }
+
+        Ok(())
+    }
+
+    /// Copies the tightly packed rows of `data` to `subresource` of `dst`, which must be in the
+    /// COMMON state
+    pub fn upload_texture2d(
+        &mut self,
+        queue: &mut Queue,
+        device: &ID3D12Device5,
+        dst: &ID3D12Resource,
+        subresource: u32,
+        data: &[u8],
+    ) -> windows::core::Result<()> {
+        let footprint = copyable_footprint(device, dst, subresource);
+
+        assert_eq!(
+            data.len() as u64,
+            footprint.row_size * footprint.height as u64,
+            "the data does not match the size of the texture"
+        );
+
+        if footprint.size() > self.batch.allocator().capacity() / 2 {
+            return Err(windows::core::Error::new(
+                E_OUTOFMEMORY,
+                format!(
+                    "The texture of {} bytes does not fit in the staging buffer of {} bytes",
+                    footprint.size(),
+                    self.batch.allocator().capacity()
+                ),
+            ));
+        }
+
+        let src_offset = loop {
+            match self
+                .batch
+                .stage_texture(dst.clone(), subresource, footprint)
+            {
+                Some(src_offset) => break src_offset,
+                None => self.make_room(queue)?,
+            }
+        };
+
+        for (row, src) in data.chunks(footprint.row_size as usize).enumerate() {
+            let offset = src_offset + row as u64 * footprint.row_pitch;
+            self.write(offset, src.as_ptr(), src.len());
+        }
+
+        Ok(())
+    }
+
+    /// Records the staged copies in one command list and executes it on `queue`
+    /// Returns None if nothing has been staged since the last submission
+    pub fn submit(&mut self, queue: &mut Queue) -> windows::core::Result<Option<FenceValue>> {
+        let copies = self.batch.take_copies();
+        if copies.is_empty() {
+            return Ok(None);
+        }
+
+        let ctx = queue.request_command_ctx()?;
+        let command_list = ctx.command_list();
+
+        for copy in &copies {
+            match copy {
+                StagedCopy::Buffer {
+                    dst,
+                    dst_offset,
+                    src_offset,
+                    size,
+                } => unsafe {
+                    command_list.CopyBufferRegion(
+                        dst,
+                        *dst_offset,
+                        &self.buffer,
+                        *src_offset,
+                        *size,
+                    )
+                },
+                StagedCopy::Texture {
+                    dst,
+                    subresource,
+                    src_offset,
+                    footprint,
+                } => self.copy_texture(command_list, dst, *subresource, *src_offset, footprint),
+            }
+        }
+
+        let fence_value = queue.execute_commands(ctx)?;
+        self.batch.retire(fence_value);
+
+        Ok(Some(fence_value))
+    }
+
+    pub fn reclaim(&mut self, is_completed: impl Fn(FenceValue) -> bool) {
+        self.batch.reclaim(is_completed);
+    }
+
+    // Submits the staged copies and waits for the oldest submission to free its staging memory
+    fn make_room(&mut self, queue: &mut Queue) -> windows::core::Result<()> {
+        // the copies staged so far may be what fills the staging buffer
+        let _ = self.submit(queue)?;
+
+        if !self
+            .batch
+            .make_room(|fence_value| queue.wait_fence(fence_value))
+        {
+            return Err(windows::core::Error::new(
+                E_OUTOFMEMORY,
+                "The staging buffer is full without any submission to wait for",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, offset: u64, src: *const u8, size: usize) {
+        unsafe {
+            std::ptr::copy_nonoverlapping(src, self.cpu_address.add(offset as usize), size);
+        }
+    }
+
+    fn copy_texture(
+        &self,
+        command_list: &ID3D12GraphicsCommandList7,
+        dst: &ID3D12Resource,
+        subresource: u32,
+        src_offset: u64,
+        footprint: &TextureFootprint,
+    ) {
+        let dst_location = D3D12_TEXTURE_COPY_LOCATION {
+            pResource: unsafe { std::mem::transmute_copy(dst) },
+            Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+            Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                SubresourceIndex: subresource,
+            },
+        };
+
+        let src_location = D3D12_TEXTURE_COPY_LOCATION {
+            pResource: unsafe { std::mem::transmute_copy(&self.buffer) },
+            Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
+            Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                PlacedFootprint: D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
+                    Offset: src_offset,
+                    Footprint: D3D12_SUBRESOURCE_FOOTPRINT {
+                        Format: unsafe { dst.GetDesc() }.Format,
+                        Width: footprint.width,
+                        Height: footprint.height,
+                        Depth: 1,
+                        RowPitch: footprint.row_pitch as u32,
+                    },
+                },
+            },
+        };
+
+        unsafe { command_list.CopyTextureRegion(&dst_location, 0, 0, 0, &src_location, None) };
+    }
+}
+
+impl Drop for UploadManager {
+    fn drop(&mut self) {
+        unsafe { self.buffer.Unmap(0, None) };
+    }
+}
+
+// The layout of `subresource` in a buffer, with the size of its mip level
+fn copyable_footprint(
+    device: &ID3D12Device5,
+    resource: &ID3D12Resource,
+    subresource: u32,
+) -> TextureFootprint {
+    let desc = unsafe { resource.GetDesc() };
+
+    let mut layout = D3D12_PLACED_SUBRESOURCE_FOOTPRINT::default();
+    let mut row_size = 0;
+    unsafe {
+        device.GetCopyableFootprints(
+            &desc,
+            subresource,
+            1,
+            0,
+            Some(&mut layout),
+            None,
+            Some(&mut row_size),
+            None,
+        )
+    };
+
+    TextureFootprint {
+        width: layout.Footprint.Width,
+        height: layout.Footprint.Height,
+        row_size,
+        row_pitch: layout.Footprint.RowPitch as u64,
+    }
+}
@@ -0,0 +1,380 @@
+// Tracks the current state of each (sub)resource and infers the barriers for the next usage
+// Resources are identified by keys chosen by the user, so that the state machine does not need a device
+
+use std::collections::BTreeMap;
+
+use super::backend::{ResourceStates, ALL_SUBRESOURCES};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateBarrier<K> {
+    Transition {
+        resource: K,
+        /// Subresource index, or `ALL_SUBRESOURCES`
+        subresource: u32,
+        before: ResourceStates,
+        after: ResourceStates,
+    },
+    /// Waits for the unordered accesses before it to finish, when the resource stays in
+    /// UNORDERED_ACCESS between two uses
+    Uav { resource: K },
+}
+
+struct TrackedResource {
+    initial: ResourceStates,
+    // one for each subresource, including the transitions that are not flushed yet
+    states: Vec<ResourceStates>,
+    // the states as of the last flush
+    flushed: Vec<ResourceStates>,
+    // the commands after the next flush access the resource as UAV
+    uav_required: bool,
+    // the commands since the last barrier on the resource accessed it as UAV
+    uav_unsynchronized: bool,
+}
+
+pub struct StateTracker<K> {
+    resources: BTreeMap<K, TrackedResource>,
+}
+
+impl<K: Ord + Copy> Default for StateTracker<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Copy> StateTracker<K> {
+    pub fn new() -> Self {
+        Self {
+            resources: BTreeMap::new(),
+        }
+    }
+
+    /// Starts tracking a resource whose subresources are all in `state`
+    /// `require_initial_states` transitions the resource back to `state`
+    pub fn register(&mut self, resource: K, subresource_count: u32, state: ResourceStates) {
+        assert!(
+            subresource_count > 0,
+            "a resource has at least one subresource"
+        );
+
+        let states = vec![state; subresource_count as usize];
+        let tracked = TrackedResource {
+            initial: state,
+            flushed: states.clone(),
+            states,
+            uav_required: false,
+            uav_unsynchronized: false,
+        };
+        self.resources.insert(resource, tracked);
+    }
+
+    pub fn unregister(&mut self, resource: K) {
+        self.resources.remove(&resource);
+    }
+
+    /// Returns None if the resource is not registered or `subresource` is out of range
+    pub fn state(&self, resource: K, subresource: u32) -> Option<ResourceStates> {
+        let tracked = self.resources.get(&resource)?;
+        tracked.states.get(subresource as usize).copied()
+    }
+
+    /// Declares that the next commands access `subresource` of `resource` in `state`
+    /// `subresource` can be `ALL_SUBRESOURCES`
+    /// Read-only states are combined with the current one, so alternating reads need no barriers
+    /// UNORDERED_ACCESS after UNORDERED_ACCESS needs a UAV barrier instead of a transition
+    pub fn require(&mut self, resource: K, subresource: u32, state: ResourceStates) {
+        let Some(tracked) = self.resources.get_mut(&resource) else {
+            panic!("the resource is not registered to the state tracker");
+        };
+
+        if state == ResourceStates::UNORDERED_ACCESS {
+            tracked.uav_required = true;
+        }
+
+        let states = if subresource == ALL_SUBRESOURCES {
+            &mut tracked.states[..]
+        } else {
+            let i = subresource as usize;
+            assert!(
+                i < tracked.states.len(),
+                "subresource {subresource} is out of range"
+            );
+            &mut tracked.states[i..i + 1]
+        };
+
+        for current in states {
+            *current = next_state(*current, state);
+        }
+    }
+
+    /// Transitions every resource back to the state it was registered with
+    pub fn require_initial_states(&mut self) {
+        for tracked in self.resources.values_mut() {
+            tracked.states.fill(tracked.initial);
+        }
+    }
+
+    /// Returns the minimal set of barriers that brings the resources from the states of the last
+    /// flush to the required ones, which must be recorded before the commands accessing them
+    pub fn flush(&mut self) -> Vec<StateBarrier<K>> {
+        let mut barriers = Vec::new();
+
+        for (resource, tracked) in &mut self.resources {
+            let uav_required = std::mem::take(&mut tracked.uav_required);
+
+            // a transition waits for the previous accesses, so only the subresources staying in
+            // UNORDERED_ACCESS need a UAV barrier
+            let uav_kept = tracked
+                .flushed
+                .iter()
+                .zip(&tracked.states)
+                .any(|(b, a)| *b == ResourceStates::UNORDERED_ACCESS && b == a);
+            let uav_barrier = uav_required && uav_kept && tracked.uav_unsynchronized;
+            if uav_barrier {
+                barriers.push(StateBarrier::Uav {
+                    resource: *resource,
+                });
+            }
+            let synchronized = uav_barrier || !uav_kept;
+            tracked.uav_unsynchronized =
+                uav_required || (tracked.uav_unsynchronized && !synchronized);
+
+            if tracked.states == tracked.flushed {
+                continue;
+            }
+
+            // a single barrier is enough if all the subresources move from and to the same states
+            if is_uniform(&tracked.states) && is_uniform(&tracked.flushed) {
+                barriers.push(StateBarrier::Transition {
+                    resource: *resource,
+                    subresource: ALL_SUBRESOURCES,
+                    before: tracked.flushed[0],
+                    after: tracked.states[0],
+                });
+            } else {
+                let changed = tracked.flushed.iter().zip(&tracked.states).enumerate();
+                for (i, (before, after)) in changed.filter(|(_, (b, a))| b != a) {
+                    barriers.push(StateBarrier::Transition {
+                        resource: *resource,
+                        subresource: i as u32,
+                        before: *before,
+                        after: *after,
+                    });
+                }
+            }
+
+            tracked.flushed.clone_from(&tracked.states);
+        }
+
+        barriers
+    }
+}
+
+fn is_uniform(states: &[ResourceStates]) -> bool {
+    states.iter().all(|s| *s == states[0])
+}
+
+fn next_state(current: ResourceStates, requested: ResourceStates) -> ResourceStates {
+    if current.is_read_only() && requested.is_read_only() {
+        return current | requested;
+    }
+
+    requested
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMON: ResourceStates = ResourceStates::COMMON;
+    const COPY_DEST: ResourceStates = ResourceStates::COPY_DEST;
+    const PIXEL: ResourceStates = ResourceStates::PIXEL_SHADER_RESOURCE;
+    const NON_PIXEL: ResourceStates = ResourceStates::NON_PIXEL_SHADER_RESOURCE;
+    const UAV: ResourceStates = ResourceStates::UNORDERED_ACCESS;
+
+    fn transition(
+        resource: u32,
+        subresource: u32,
+        before: ResourceStates,
+        after: ResourceStates,
+    ) -> StateBarrier<u32> {
+        StateBarrier::Transition {
+            resource,
+            subresource,
+            before,
+            after,
+        }
+    }
+
+    #[test]
+    fn read_states_are_combined() {
+        let mut states = StateTracker::new();
+        states.register(0, 1, COPY_DEST);
+
+        states.require(0, ALL_SUBRESOURCES, PIXEL);
+        assert_eq!(
+            states.flush(),
+            [transition(0, ALL_SUBRESOURCES, COPY_DEST, PIXEL)]
+        );
+
+        // a second read state is added to the first one
+        states.require(0, ALL_SUBRESOURCES, NON_PIXEL);
+        assert_eq!(
+            states.flush(),
+            [transition(0, ALL_SUBRESOURCES, PIXEL, PIXEL | NON_PIXEL)]
+        );
+        assert_eq!(states.state(0, 0), Some(PIXEL | NON_PIXEL));
+
+        // then alternating reads need no barriers
+        for state in [PIXEL, NON_PIXEL, PIXEL] {
+            states.require(0, ALL_SUBRESOURCES, state);
+            assert_eq!(states.flush(), []);
+        }
+
+        // a write replaces the combined read states
+        states.require(0, ALL_SUBRESOURCES, COPY_DEST);
+        assert_eq!(
+            states.flush(),
+            [transition(
+                0,
+                ALL_SUBRESOURCES,
+                PIXEL | NON_PIXEL,
+                COPY_DEST
+            )]
+        );
+    }
+
+    #[test]
+    fn subresource_transitions() {
+        let mut states = StateTracker::new();
+        states.register(0, 4, COMMON);
+
+        states.require(0, 1, PIXEL);
+        assert_eq!(states.flush(), [transition(0, 1, COMMON, PIXEL)]);
+        assert_eq!(states.state(0, 0), Some(COMMON));
+        assert_eq!(states.state(0, 1), Some(PIXEL));
+        assert_eq!(states.state(0, 4), None);
+
+        // the subresources come from different states, so each needs its own barrier
+        states.require(0, ALL_SUBRESOURCES, COPY_DEST);
+        assert_eq!(
+            states.flush(),
+            [
+                transition(0, 0, COMMON, COPY_DEST),
+                transition(0, 1, PIXEL, COPY_DEST),
+                transition(0, 2, COMMON, COPY_DEST),
+                transition(0, 3, COMMON, COPY_DEST),
+            ]
+        );
+
+        // and a single one is enough once they are in the same state
+        states.require(0, ALL_SUBRESOURCES, PIXEL);
+        assert_eq!(
+            states.flush(),
+            [transition(0, ALL_SUBRESOURCES, COPY_DEST, PIXEL)]
+        );
+
+        // requiring the current state of some subresources only moves the others
+        states.require(0, 2, COPY_DEST);
+        states.require(0, 3, COPY_DEST);
+        assert_eq!(
+            states.flush(),
+            [
+                transition(0, 2, PIXEL, COPY_DEST),
+                transition(0, 3, PIXEL, COPY_DEST),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn subresource_out_of_range() {
+        let mut states = StateTracker::new();
+        states.register(0, 2, COMMON);
+        states.require(0, 2, PIXEL);
+    }
+
+    #[test]
+    #[should_panic(expected = "not registered")]
+    fn unregistered_resource() {
+        let mut states = StateTracker::new();
+        states.register(0, 1, COMMON);
+        states.unregister(0);
+        states.require(0, ALL_SUBRESOURCES, PIXEL);
+    }
+
+    #[test]
+    fn initial_states_are_restored() {
+        let mut states = StateTracker::new();
+        states.register(0, 1, COMMON);
+        states.register(1, 2, UAV);
+        states.register(2, 1, PIXEL);
+
+        states.require(0, ALL_SUBRESOURCES, COPY_DEST);
+        states.require(1, 1, PIXEL);
+        assert_eq!(states.flush().len(), 2);
+
+        // the resource that was not used needs no barrier
+        states.require_initial_states();
+        assert_eq!(
+            states.flush(),
+            [
+                transition(0, ALL_SUBRESOURCES, COPY_DEST, COMMON),
+                transition(1, 1, PIXEL, UAV),
+            ]
+        );
+        assert_eq!(states.flush(), []);
+    }
+
+    #[test]
+    fn unordered_accesses_are_separated_by_uav_barriers() {
+        let mut states = StateTracker::new();
+        states.register(0, 1, UAV);
+        let uav = StateBarrier::Uav { resource: 0 };
+
+        // nothing accessed the resource as UAV before the first use
+        states.require(0, ALL_SUBRESOURCES, UAV);
+        assert_eq!(states.flush(), []);
+
+        states.require(0, ALL_SUBRESOURCES, UAV);
+        assert_eq!(states.flush(), [uav]);
+
+        // the commands that do not use the resource do not synchronize it
+        assert_eq!(states.flush(), []);
+        states.require(0, ALL_SUBRESOURCES, UAV);
+        assert_eq!(states.flush(), [uav]);
+
+        // a transition waits for the unordered accesses instead
+        states.require(0, ALL_SUBRESOURCES, PIXEL);
+        assert_eq!(
+            states.flush(),
+            [transition(0, ALL_SUBRESOURCES, UAV, PIXEL)]
+        );
+        states.require(0, ALL_SUBRESOURCES, UAV);
+        assert_eq!(
+            states.flush(),
+            [transition(0, ALL_SUBRESOURCES, PIXEL, UAV)]
+        );
+        states.require(0, ALL_SUBRESOURCES, UAV);
+        assert_eq!(states.flush(), [uav]);
+    }
+
+    #[test]
+    fn uav_barrier_with_other_subresources_in_transition() {
+        let mut states = StateTracker::new();
+        states.register(0, 2, UAV);
+
+        states.require(0, ALL_SUBRESOURCES, UAV);
+        assert_eq!(states.flush(), []);
+
+        // subresource 0 is still accessed as UAV while subresource 1 moves to a read
+        states.require(0, 0, UAV);
+        states.require(0, 1, PIXEL);
+        assert_eq!(
+            states.flush(),
+            [
+                StateBarrier::Uav { resource: 0 },
+                transition(0, 1, UAV, PIXEL)
+            ]
+        );
+    }
+}
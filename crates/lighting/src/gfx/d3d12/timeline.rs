@@ -0,0 +1,177 @@
+// Dependencies between the queues created by GPU-side waits
+// A queue that waits for a fence of another queue does not start the commands submitted after the
+// wait until the other queue reaches the fence, while the CPU keeps going
+
+use super::backend::QueueType;
+use super::fence::FenceValue;
+
+/// A fence value of a specific queue, which is reached when the queue finishes the commands
+/// submitted before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncPoint {
+    pub queue: QueueType,
+    pub fence_value: FenceValue,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Wait {
+    queue: QueueType,
+    // the last fence value signaled by `queue` before the wait
+    after: FenceValue,
+    point: SyncPoint,
+}
+
+#[derive(Default)]
+pub struct Timeline {
+    waits: Vec<Wait>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self { waits: Vec::new() }
+    }
+
+    /// Records that `queue` waits for `point` after signaling `after`
+    pub fn wait(&mut self, queue: QueueType, after: FenceValue, point: SyncPoint) {
+        assert_ne!(queue, point.queue, "a queue cannot wait for itself");
+
+        self.waits.push(Wait {
+            queue,
+            after,
+            point,
+        });
+    }
+
+    /// The points of other queues that `point` cannot be reached before
+    pub fn dependencies(&self, point: SyncPoint) -> impl Iterator<Item = SyncPoint> + '_ {
+        self.waits
+            .iter()
+            .filter(move |w| w.queue == point.queue && w.after < point.fence_value)
+            .map(|w| w.point)
+    }
+
+    /// True if the GPU is guaranteed to reach `before` no later than `after`
+    pub fn happens_before(&self, before: SyncPoint, after: SyncPoint) -> bool {
+        if before.queue == after.queue {
+            return before.fence_value <= after.fence_value;
+        }
+
+        // waits only refer to points that were signaled before them, so this terminates
+        self.dependencies(after)
+            .any(|point| self.happens_before(before, point))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::backend::mock::MockBackend;
+    use super::super::backend::Backend;
+    use super::*;
+
+    const GRAPHICS: QueueType = QueueType::Graphics;
+    const COPY: QueueType = QueueType::Copy;
+
+    fn point(queue: QueueType, fence_value: FenceValue) -> SyncPoint {
+        SyncPoint { queue, fence_value }
+    }
+
+    // an upload on the copy queue consumed by a frame on the graphics queue
+    fn upload_then_draw(backend: &mut MockBackend) -> (SyncPoint, SyncPoint, SyncPoint) {
+        let before = point(GRAPHICS, backend.signal(GRAPHICS));
+
+        let cmd = backend.begin_commands(COPY).unwrap();
+        let upload = point(COPY, backend.execute(COPY, cmd).unwrap());
+        backend.wait_queue(GRAPHICS, COPY, upload.fence_value);
+
+        let cmd = backend.begin_commands(GRAPHICS).unwrap();
+        let draw = point(GRAPHICS, backend.execute(GRAPHICS, cmd).unwrap());
+
+        (before, upload, draw)
+    }
+
+    #[test]
+    fn wait_queue_records_a_dependency() {
+        let mut backend = MockBackend::new();
+        let (before, upload, draw) = upload_then_draw(&mut backend);
+        let timeline = backend.timeline();
+
+        assert_eq!(timeline.dependencies(draw).collect::<Vec<_>>(), [upload]);
+        // the commands before the wait do not depend on the upload
+        assert_eq!(timeline.dependencies(before).count(), 0);
+        assert_eq!(timeline.dependencies(upload).count(), 0);
+
+        assert!(timeline.happens_before(upload, draw));
+        assert!(timeline.happens_before(before, draw));
+        assert!(!timeline.happens_before(upload, before));
+        assert!(!timeline.happens_before(draw, upload));
+    }
+
+    #[test]
+    fn dependencies_are_transitive() {
+        let mut backend = MockBackend::new();
+        let (_, upload, draw) = upload_then_draw(&mut backend);
+
+        // a second upload that waits for the frame
+        backend.wait_queue(COPY, GRAPHICS, draw.fence_value);
+        let cmd = backend.begin_commands(COPY).unwrap();
+        let readback = point(COPY, backend.execute(COPY, cmd).unwrap());
+
+        let timeline = backend.timeline();
+        assert_eq!(timeline.dependencies(readback).collect::<Vec<_>>(), [draw]);
+        assert!(timeline.happens_before(upload, readback));
+        assert!(timeline.happens_before(draw, readback));
+        assert!(!timeline.happens_before(readback, draw));
+    }
+
+    #[test]
+    fn completing_a_fence_completes_its_dependencies() {
+        let mut backend = MockBackend::new();
+        let (before, upload, draw) = upload_then_draw(&mut backend);
+
+        backend.complete_fence(GRAPHICS, before.fence_value);
+        assert_eq!(backend.completed_fence(GRAPHICS), before.fence_value);
+        assert!(!backend.is_fence_completed(COPY, upload.fence_value));
+
+        backend.complete_fence(GRAPHICS, draw.fence_value);
+        assert!(backend.is_fence_completed(COPY, upload.fence_value));
+        assert!(backend.is_fence_completed(GRAPHICS, draw.fence_value));
+    }
+
+    #[test]
+    fn independent_queues_complete_separately() {
+        let mut backend = MockBackend::new();
+        let copy = point(COPY, backend.signal(COPY));
+        let graphics = point(GRAPHICS, backend.signal(GRAPHICS));
+
+        assert!(!backend.timeline().happens_before(copy, graphics));
+        assert!(!backend.timeline().happens_before(graphics, copy));
+
+        backend.complete_fence(GRAPHICS, graphics.fence_value);
+        assert!(!backend.is_fence_completed(COPY, copy.fence_value));
+    }
+
+    #[test]
+    #[should_panic(expected = "has not been signaled")]
+    fn wait_for_unsignaled_fence() {
+        let mut backend = MockBackend::new();
+        let fence_value = backend.signal(COPY);
+        backend.wait_queue(
+            GRAPHICS,
+            COPY,
+            FenceValue {
+                v: fence_value.v + 1,
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "a queue cannot wait for itself")]
+    fn wait_for_itself() {
+        let mut timeline = Timeline::new();
+        timeline.wait(
+            GRAPHICS,
+            FenceValue::default(),
+            point(GRAPHICS, FenceValue { v: 1 }),
+        );
+    }
+}
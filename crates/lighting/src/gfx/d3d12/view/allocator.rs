@@ -0,0 +1,319 @@
+// Bookkeeping of the slots in a descriptor heap
+// A freed slot may still be referenced by commands in flight, so it is only reused after the fence
+// of the frame it was freed in has completed
+
+use std::collections::VecDeque;
+
+use super::super::fence::FenceValue;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DescriptorSlot {
+    pub index: u32,
+    /// Incremented every time the slot is freed, so stale handles can be detected
+    pub generation: u32,
+}
+
+pub struct DescriptorAllocator {
+    capacity: u32,
+    // the slots from this index have never been allocated
+    next_index: u32,
+    free_list: Vec<u32>,
+    generations: Vec<u32>,
+    // freed in the current frame, waiting for its fence
+    freed: Vec<u32>,
+    // waiting for the GPU, in the order of the fence values
+    retired: VecDeque<(FenceValue, Vec<u32>)>,
+}
+
+impl DescriptorAllocator {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            next_index: 0,
+            free_list: Vec::new(),
+            generations: Vec::new(),
+            freed: Vec::new(),
+            retired: VecDeque::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// The slots below this index have been allocated at least once, so they hold descriptors
+    pub fn watermark(&self) -> u32 {
+        self.next_index
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.free_list.is_empty() && self.next_index == self.capacity
+    }
+
+    /// The capacity a growable heap must reach before the next allocation, None while a slot is
+    /// available
+    pub fn required_capacity(&self) -> Option<u32> {
+        self.is_full().then(|| self.capacity * 2)
+    }
+
+    /// Adds slots after the current ones, the indices of the allocated slots do not change
+    pub fn grow(&mut self, capacity: u32) {
+        assert!(
+            capacity >= self.capacity,
+            "descriptor heap cannot shrink from {} to {capacity}",
+            self.capacity
+        );
+
+        self.capacity = capacity;
+    }
+
+    /// Returns None if every slot is in use
+    pub fn allocate(&mut self) -> Option<DescriptorSlot> {
+        let index = match self.free_list.pop() {
+            Some(index) => index,
+            None if self.next_index < self.capacity => {
+                self.next_index += 1;
+                self.generations.push(0);
+                self.next_index - 1
+            }
+            None => return None,
+        };
+
+        Some(DescriptorSlot {
+            index,
+            generation: self.generations[index as usize],
+        })
+    }
+
+    /// False once the slot has been freed, even if the index has been reused
+    pub fn is_valid(&self, slot: DescriptorSlot) -> bool {
+        self.generations.get(slot.index as usize) == Some(&slot.generation)
+    }
+
+    /// The index of a slot to read its descriptor at, asserting in debug builds that the slot has
+    /// not been freed, as its descriptor may have been replaced by that of another view
+    pub fn index(&self, slot: DescriptorSlot) -> u32 {
+        debug_assert!(
+            self.is_valid(slot),
+            "descriptor {} is used after it is freed",
+            slot.index
+        );
+        slot.index
+    }
+
+    /// The slot is reused after the fence passed to the next `retire` has completed
+    pub fn free(&mut self, slot: DescriptorSlot) {
+        assert!(
+            self.is_valid(slot),
+            "descriptor {} is already freed",
+            slot.index
+        );
+
+        let generation = &mut self.generations[slot.index as usize];
+        *generation = generation.wrapping_add(1);
+
+        self.freed.push(slot.index);
+    }
+
+    /// Associates the slots freed since the last call with `fence_value`, which is signaled after the
+    /// last commands that may reference them
+    pub fn retire(&mut self, fence_value: FenceValue) {
+        if self.freed.is_empty() {
+            return;
+        }
+
+        let freed = std::mem::take(&mut self.freed);
+        self.retired.push_back((fence_value, freed));
+    }
+
+    /// Makes the retired slots available again if `is_completed` returns true for their fence
+    pub fn reclaim(&mut self, is_completed: impl Fn(FenceValue) -> bool) {
+        while let Some((fence_value, _)) = self.retired.front() {
+            if !is_completed(*fence_value) {
+                break;
+            }
+
+            let (_, slots) = self.retired.pop_front().unwrap();
+            self.free_list.extend(slots);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fence(v: u64) -> FenceValue {
+        FenceValue { v }
+    }
+
+    #[test]
+    fn allocates_until_full() {
+        let mut allocator = DescriptorAllocator::new(3);
+
+        let indices: Vec<u32> = (0..3)
+            .map(|_| allocator.allocate().unwrap().index)
+            .collect();
+        assert_eq!(indices, [0, 1, 2]);
+        assert_eq!(allocator.watermark(), 3);
+        assert!(allocator.is_full());
+        assert_eq!(allocator.allocate(), None);
+
+        allocator.grow(4);
+        assert!(!allocator.is_full());
+        assert_eq!(allocator.allocate().map(|slot| slot.index), Some(3));
+    }
+
+    #[test]
+    fn growth_doubles_the_capacity_and_keeps_the_indices() {
+        let mut allocator = DescriptorAllocator::new(1);
+        let mut slots = Vec::new();
+        let mut capacities = Vec::new();
+
+        for _ in 0..5 {
+            if let Some(capacity) = allocator.required_capacity() {
+                allocator.grow(capacity);
+                capacities.push(capacity);
+            }
+            slots.push(allocator.allocate().unwrap());
+        }
+
+        assert_eq!(capacities, [2, 4, 8]);
+        assert_eq!(allocator.capacity(), 8);
+        assert_eq!(allocator.watermark(), 5);
+        let indices: Vec<u32> = slots.iter().map(|slot| slot.index).collect();
+        assert_eq!(indices, [0, 1, 2, 3, 4]);
+        assert!(slots.iter().all(|slot| allocator.is_valid(*slot)));
+    }
+
+    #[test]
+    fn freed_slots_avoid_growth() {
+        let mut allocator = DescriptorAllocator::new(1);
+        let slot = allocator.allocate().unwrap();
+        assert_eq!(allocator.required_capacity(), Some(2));
+
+        // a slot waiting for its fence cannot be allocated yet
+        allocator.free(slot);
+        allocator.retire(FenceValue { v: 1 });
+        assert_eq!(allocator.required_capacity(), Some(2));
+
+        allocator.reclaim(|_| true);
+        assert_eq!(allocator.required_capacity(), None);
+    }
+
+    #[test]
+    fn freed_slots_are_reused_after_their_fence() {
+        let mut allocator = DescriptorAllocator::new(2);
+        let a = allocator.allocate().unwrap();
+        let b = allocator.allocate().unwrap();
+
+        allocator.free(a);
+        allocator.retire(fence(1));
+        allocator.free(b);
+        allocator.retire(fence(2));
+
+        // the GPU may still use both
+        allocator.reclaim(|_| false);
+        assert_eq!(allocator.allocate(), None);
+
+        // only the first frame has completed
+        allocator.reclaim(|f| f <= fence(1));
+        let reused = allocator.allocate().unwrap();
+        assert_eq!(reused.index, a.index);
+        assert_eq!(allocator.allocate(), None);
+
+        allocator.reclaim(|f| f <= fence(2));
+        assert_eq!(allocator.allocate().map(|slot| slot.index), Some(b.index));
+        assert_eq!(allocator.watermark(), 2);
+    }
+
+    #[test]
+    fn free_list_reuses_the_last_freed_slot_first() {
+        let mut allocator = DescriptorAllocator::new(4);
+        let slots: Vec<DescriptorSlot> = (0..4).map(|_| allocator.allocate().unwrap()).collect();
+
+        allocator.free(slots[1]);
+        allocator.free(slots[3]);
+        allocator.retire(fence(1));
+        allocator.reclaim(|_| true);
+
+        assert_eq!(allocator.allocate().map(|slot| slot.index), Some(3));
+        assert_eq!(allocator.allocate().map(|slot| slot.index), Some(1));
+        assert_eq!(allocator.allocate(), None);
+    }
+
+    #[test]
+    fn retire_without_freed_slots_is_ignored() {
+        let mut allocator = DescriptorAllocator::new(1);
+        let slot = allocator.allocate().unwrap();
+
+        allocator.retire(fence(1));
+        allocator.free(slot);
+        allocator.retire(fence(2));
+
+        allocator.reclaim(|f| f <= fence(1));
+        assert_eq!(allocator.allocate(), None);
+        allocator.reclaim(|f| f <= fence(2));
+        assert!(allocator.allocate().is_some());
+    }
+
+    #[test]
+    fn stale_generations_are_detected() {
+        let mut allocator = DescriptorAllocator::new(1);
+        let old = allocator.allocate().unwrap();
+        assert!(allocator.is_valid(old));
+
+        allocator.free(old);
+        assert!(!allocator.is_valid(old));
+
+        allocator.retire(fence(1));
+        allocator.reclaim(|_| true);
+        let new = allocator.allocate().unwrap();
+        assert_eq!(new.index, old.index);
+        assert_eq!(new.generation, old.generation + 1);
+        assert!(allocator.is_valid(new));
+        assert!(!allocator.is_valid(old));
+
+        // never allocated
+        let unknown = DescriptorSlot {
+            index: 5,
+            generation: 0,
+        };
+        assert!(!allocator.is_valid(unknown));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "descriptor 0 is used after it is freed")]
+    fn reading_a_recycled_slot() {
+        let mut allocator = DescriptorAllocator::new(1);
+        let old = allocator.allocate().unwrap();
+        assert_eq!(allocator.index(old), 0);
+
+        allocator.free(old);
+        allocator.retire(fence(1));
+        allocator.reclaim(|_| true);
+        let new = allocator.allocate().unwrap();
+        assert_eq!(allocator.index(new), 0);
+
+        // the slot now holds the descriptor of `new`
+        allocator.index(old);
+    }
+
+    #[test]
+    #[should_panic(expected = "descriptor 0 is already freed")]
+    fn double_free() {
+        let mut allocator = DescriptorAllocator::new(1);
+        let slot = allocator.allocate().unwrap();
+
+        allocator.free(slot);
+        allocator.free(slot);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot shrink")]
+    fn shrink() {
+        let mut allocator = DescriptorAllocator::new(2);
+        allocator.grow(1);
+    }
+}
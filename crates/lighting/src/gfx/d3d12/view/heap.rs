@@ -0,0 +1,287 @@
+use windows::Win32::Graphics::Direct3D12::*;
+
+use super::super::{fence::FenceValue, release::ReleaseQueue};
+use super::{Cbv, DescriptorAllocator, DescriptorSlot, Srv, Uav};
+use crate::gfx::d3d12::util::set_name_str;
+
+pub const TYPE_CBV_SRV_UAV: i32 = D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV.0;
+pub const TYPE_SAMPLER: i32 = D3D12_DESCRIPTOR_HEAP_TYPE_SAMPLER.0;
+pub const TYPE_RTV: i32 = D3D12_DESCRIPTOR_HEAP_TYPE_RTV.0;
+pub const TYPE_DSV: i32 = D3D12_DESCRIPTOR_HEAP_TYPE_DSV.0;
+
+/// Render Target View
+#[derive(Clone)]
+pub struct Rtv {
+    cpu_handle: D3D12_CPU_DESCRIPTOR_HANDLE,
+}
+
+impl Rtv {
+    pub fn cpu_handle(&self) -> D3D12_CPU_DESCRIPTOR_HANDLE {
+        self.cpu_handle
+    }
+}
+
+/// Depth Stencil View
+pub struct Dsv {
+    cpu_handle: D3D12_CPU_DESCRIPTOR_HANDLE,
+}
+
+impl Dsv {
+    pub fn cpu_handle(&self) -> D3D12_CPU_DESCRIPTOR_HANDLE {
+        self.cpu_handle
+    }
+}
+
+pub struct DesciptorHeap<const T: i32> {
+    heap: ID3D12DescriptorHeap,
+    // Shader-visible heaps cannot be the source of a copy, so the views are created in this CPU-only
+    // heap and copied into `heap`, which lets the views move to a larger heap at the same indices
+    staging_heap: Option<ID3D12DescriptorHeap>,
+    name: String,
+    view_size: u32,
+    allocator: DescriptorAllocator,
+
+    // replaced by a larger heap, waiting for the GPU to finish the frames that bound them
+    old_heaps: ReleaseQueue<ID3D12DescriptorHeap>,
+}
+
+impl<const T: i32> DesciptorHeap<T> {
+    pub fn build(device: &ID3D12Device5, capacity: u32, name: &str) -> windows::core::Result<Self> {
+        assert!(capacity > 0);
+
+        let heap_type: D3D12_DESCRIPTOR_HEAP_TYPE = D3D12_DESCRIPTOR_HEAP_TYPE(T);
+
+        let is_shader_visible: bool = (T == TYPE_CBV_SRV_UAV) || (T == TYPE_SAMPLER);
+
+        let heap = create_heap(device, heap_type, capacity, is_shader_visible, name)?;
+
+        let staging_heap = if is_shader_visible {
+            let name = format!("{name}::staging");
+            Some(create_heap(device, heap_type, capacity, false, &name)?)
+        } else {
+            None
+        };
+
+        let view_size = unsafe { device.GetDescriptorHandleIncrementSize(heap_type) };
+
+        Ok(Self {
+            heap,
+            staging_heap,
+            name: name.to_string(),
+            view_size,
+            allocator: DescriptorAllocator::new(capacity),
+            old_heaps: ReleaseQueue::new(),
+        })
+    }
+
+    pub fn get(&self) -> &ID3D12DescriptorHeap {
+        &self.heap
+    }
+
+    pub fn allocator(&self) -> &DescriptorAllocator {
+        &self.allocator
+    }
+
+    /// Associates the views freed and the heaps replaced since the last call with `fence_value`,
+    /// which is signaled after the last commands that may use them
+    pub fn retire(&mut self, fence_value: FenceValue) {
+        self.allocator.retire(fence_value);
+        self.old_heaps.retire(fence_value);
+    }
+
+    /// Reuses the views and releases the heaps whose fence has completed
+    pub fn reclaim(&mut self, is_completed: impl Fn(FenceValue) -> bool) {
+        self.allocator.reclaim(&is_completed);
+        self.old_heaps.reclaim(&is_completed);
+    }
+
+    // Returns the slot and the CPU handle to create the view at, which must be published after that
+    fn allocate(
+        &mut self,
+        device: &ID3D12Device5,
+    ) -> (DescriptorSlot, D3D12_CPU_DESCRIPTOR_HANDLE) {
+        let required_capacity = self.allocator.required_capacity();
+        if let Some(capacity) = required_capacity.filter(|_| self.staging_heap.is_some()) {
+            if let Err(e) = self.grow(device, capacity) {
+                panic!("Failed to grow {} to {capacity}: {e}", self.name);
+            }
+        }
+
+        let Some(slot) = self.allocator.allocate() else {
+            panic!(
+                "{} is full, capacity: {}",
+                self.name,
+                self.allocator.capacity()
+            );
+        };
+
+        let heap = self.staging_heap.as_ref().unwrap_or(&self.heap);
+        let cpu_handle = self.cpu_handle(heap, slot.index);
+
+        (slot, cpu_handle)
+    }
+
+    // Copies the view from the staging heap into the shader-visible one
+    fn publish(&self, device: &ID3D12Device5, slot: DescriptorSlot) {
+        if let Some(staging_heap) = &self.staging_heap {
+            let src = self.cpu_handle(staging_heap, slot.index);
+            let dst = self.cpu_handle(&self.heap, slot.index);
+            let heap_type = D3D12_DESCRIPTOR_HEAP_TYPE(T);
+            unsafe { device.CopyDescriptorsSimple(1, dst, src, heap_type) };
+        }
+    }
+
+    fn grow(&mut self, device: &ID3D12Device5, capacity: u32) -> windows::core::Result<()> {
+        let heap_type = D3D12_DESCRIPTOR_HEAP_TYPE(T);
+
+        let staging_name = format!("{}::staging", self.name);
+        let staging_heap = create_heap(device, heap_type, capacity, false, &staging_name)?;
+        let heap = create_heap(device, heap_type, capacity, true, &self.name)?;
+
+        let count = self.allocator.watermark();
+        let old_staging_heap = self.staging_heap.as_ref().unwrap();
+        unsafe {
+            let src = old_staging_heap.GetCPUDescriptorHandleForHeapStart();
+            let dst = staging_heap.GetCPUDescriptorHandleForHeapStart();
+            device.CopyDescriptorsSimple(count, dst, src, heap_type);
+
+            let dst = heap.GetCPUDescriptorHandleForHeapStart();
+            device.CopyDescriptorsSimple(count, dst, src, heap_type);
+        }
+
+        // command lists recorded in this frame may have bound the old heap
+        let old_heap = std::mem::replace(&mut self.heap, heap);
+        self.old_heaps.defer(old_heap);
+        self.staging_heap = Some(staging_heap);
+
+        self.allocator.grow(capacity);
+
+        Ok(())
+    }
+
+    fn cpu_handle(&self, heap: &ID3D12DescriptorHeap, index: u32) -> D3D12_CPU_DESCRIPTOR_HANDLE {
+        let mut cpu_handle = unsafe { heap.GetCPUDescriptorHandleForHeapStart() };
+        cpu_handle.ptr += (self.view_size as usize) * (index as usize);
+        cpu_handle
+    }
+}
+
+impl DesciptorHeap<TYPE_CBV_SRV_UAV> {
+    pub fn create_cbv(
+        &mut self,
+        device: &ID3D12Device5,
+        desc: Option<*const D3D12_CONSTANT_BUFFER_VIEW_DESC>,
+    ) -> Cbv {
+        let (slot, cpu_handle) = self.allocate(device);
+
+        unsafe { device.CreateConstantBufferView(desc, cpu_handle) };
+        self.publish(device, slot);
+
+        Cbv {
+            handle: slot.index,
+            generation: slot.generation,
+        }
+    }
+
+    pub fn create_srv(
+        &mut self,
+        device: &ID3D12Device5,
+        resource: Option<&ID3D12Resource>,
+        desc: Option<*const D3D12_SHADER_RESOURCE_VIEW_DESC>,
+    ) -> Srv {
+        let (slot, cpu_handle) = self.allocate(device);
+
+        unsafe { device.CreateShaderResourceView(resource, desc, cpu_handle) };
+        self.publish(device, slot);
+
+        Srv {
+            handle: slot.index,
+            generation: slot.generation,
+        }
+    }
+
+    pub fn create_uav(
+        &mut self,
+        device: &ID3D12Device5,
+        resource: &ID3D12Resource,
+        desc: Option<*const D3D12_UNORDERED_ACCESS_VIEW_DESC>,
+    ) -> Uav {
+        let (slot, cpu_handle) = self.allocate(device);
+
+        unsafe { device.CreateUnorderedAccessView(resource, None, desc, cpu_handle) };
+        self.publish(device, slot);
+
+        Uav {
+            handle: slot.index,
+            generation: slot.generation,
+        }
+    }
+
+    /// The view may still be used by the GPU, so the slot is reused once it has finished the frame
+    pub fn free_cbv(&mut self, cbv: Cbv) {
+        self.allocator.free(cbv.slot());
+    }
+
+    pub fn free_srv(&mut self, srv: Srv) {
+        self.allocator.free(srv.slot());
+    }
+
+    pub fn free_uav(&mut self, uav: Uav) {
+        self.allocator.free(uav.slot());
+    }
+}
+
+impl DesciptorHeap<TYPE_RTV> {
+    pub fn create_rtv(&mut self, device: &ID3D12Device5, resource: &ID3D12Resource) -> Rtv {
+        let (_, cpu_handle) = self.allocate(device);
+
+        unsafe { device.CreateRenderTargetView(resource, None, cpu_handle) };
+
+        Rtv { cpu_handle }
+    }
+}
+
+impl DesciptorHeap<TYPE_DSV> {
+    pub fn create_dsv(
+        &mut self,
+        device: &ID3D12Device5,
+        resource: &ID3D12Resource,
+        desc: Option<*const D3D12_DEPTH_STENCIL_VIEW_DESC>,
+    ) -> Dsv {
+        let (_, cpu_handle) = self.allocate(device);
+
+        unsafe { device.CreateDepthStencilView(resource, desc, cpu_handle) };
+
+        Dsv { cpu_handle }
+    }
+}
+
+fn create_heap(
+    device: &ID3D12Device5,
+    heap_type: D3D12_DESCRIPTOR_HEAP_TYPE,
+    capacity: u32,
+    is_shader_visible: bool,
+    name: &str,
+) -> windows::core::Result<ID3D12DescriptorHeap> {
+    let flags = if is_shader_visible {
+        D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE
+    } else {
+        D3D12_DESCRIPTOR_HEAP_FLAG_NONE
+    };
+
+    let desc = D3D12_DESCRIPTOR_HEAP_DESC {
+        NumDescriptors: capacity,
+        Type: heap_type,
+        Flags: flags,
+        ..Default::default()
+    };
+
+    let heap: ID3D12DescriptorHeap = unsafe { device.CreateDescriptorHeap(&desc) }?;
+    set_name_str(&heap, name)?;
+
+    Ok(heap)
+}
+
+pub type CbvSrvUavHeap = DesciptorHeap<TYPE_CBV_SRV_UAV>;
+pub type RtvHeap = DesciptorHeap<TYPE_RTV>;
+pub type DsvHeap = DesciptorHeap<TYPE_DSV>;
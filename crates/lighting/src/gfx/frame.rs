@@ -0,0 +1,662 @@
+// Backend-independent recording of a frame
+// `Renderer` gathers the resources and root constants into a `FrameDesc`, and `record_frame` issues
+// the same commands against either the D3D12 backend or the mock one
+
+use super::d3d12::backend::*;
+use super::graph::{RenderGraph, ResourceVersion};
+use super::math::divide_and_round_up;
+
+const CLEAR_COLOR: [f32; 4] = [0.4, 0.6, 0.9, 1.0];
+
+// must match [numthreads] in raytracing.hlsl
+const NUM_THREAD_X: u32 = 8;
+const NUM_THREAD_Y: u32 = 8;
+
+pub enum RenderingMode {
+    Raytracing,
+    Rasterization,
+}
+
+pub struct DrawItem {
+    pub vertex_buffers: Vec<VertexBufferView>,
+    /// `DrawInstanced` is issued instead of `DrawIndexedInstanced` if None
+    pub index_buffer: Option<IndexBufferView>,
+    /// The number of indices, or vertices if there is no index buffer
+    pub count: u32,
+    /// Root constants at index 0
+    pub constants: Vec<u32>,
+}
+
+pub struct GraphicsPass<'a, B: Backend> {
+    pub pipeline: &'a B::Pipeline,
+    pub root_signature: &'a B::RootSignature,
+    /// Root constants at index 1 shared by all draws, not set if empty
+    pub constants: Vec<u32>,
+    pub draws: Vec<DrawItem>,
+}
+
+pub struct ComputePass<'a, B: Backend> {
+    pub pipeline: &'a B::Pipeline,
+    pub root_signature: &'a B::RootSignature,
+    /// Root constants at index 0
+    pub constants: Vec<u32>,
+}
+
+pub struct ShadowMapTarget<'a, B: Backend> {
+    pub texture: &'a B::Resource,
+    pub dsv: &'a B::Dsv,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub enum FramePasses<'a, B: Backend> {
+    Rasterization {
+        shadow_map: ShadowMapTarget<'a, B>,
+        shadow_map_pass: GraphicsPass<'a, B>,
+        draw_mesh_pass: GraphicsPass<'a, B>,
+    },
+    Raytracing {
+        raytracing_pass: ComputePass<'a, B>,
+        /// Written by the raytracing pass and sampled by the copy pass
+        color_buffer: &'a B::Resource,
+        copy_pass: GraphicsPass<'a, B>,
+    },
+}
+
+pub struct FrameDesc<'a, B: Backend> {
+    pub viewport_width: u32,
+    pub viewport_height: u32,
+    pub depth_buffer: &'a B::Resource,
+    pub depth_dsv: &'a B::Dsv,
+    pub passes: FramePasses<'a, B>,
+}
+
+/// Records the updates of the scene and binds the view heap for the frame
+/// The heap is bound after the updates, as the views they create may grow the heap and replace it
+pub fn record_updates<B: Backend, E>(
+    backend: &mut B,
+    cmd: &B::CommandList,
+    update: impl FnOnce(&mut B, &B::CommandList) -> Result<(), E>,
+) -> Result<(), E> {
+    backend.begin_event(cmd, "Update scene");
+    update(backend, cmd)?;
+    backend.end_event(cmd);
+
+    backend.bind_view_heap(cmd);
+    Ok(())
+}
+
+/// Records the commands to render a frame into the back buffer
+/// The back buffer is expected to be in PRESENT, the depth buffer and the shadow map in DEPTH_WRITE
+/// and the color buffer in UNORDERED_ACCESS, and they are left in the same states
+pub fn record_frame<B: Backend>(backend: &mut B, cmd: &B::CommandList, frame: &FrameDesc<B>) {
+    let back_buffer = backend.back_buffer();
+    let back_buffer_rtv = backend.back_buffer_rtv();
+
+    let mut graph = RenderGraph::new();
+    let output = add_frame_passes(&mut graph, frame, &back_buffer, &back_buffer_rtv);
+    graph.export(output);
+
+    backend.begin_event(cmd, "Render");
+    graph.execute(backend, cmd);
+    backend.end_event(cmd);
+}
+
+// Returns the back buffer with the frame rendered
+fn add_frame_passes<'a, B: Backend>(
+    graph: &mut RenderGraph<'a, B>,
+    frame: &'a FrameDesc<B>,
+    back_buffer: &'a B::Resource,
+    back_buffer_rtv: &'a B::Rtv,
+) -> ResourceVersion {
+    let output = graph.import("back buffer", back_buffer, ResourceStates::PRESENT);
+    let depth = graph.import(
+        "depth buffer",
+        frame.depth_buffer,
+        ResourceStates::DEPTH_WRITE,
+    );
+
+    let mut pass = graph.add_pass("Clear buffers", move |backend, cmd| {
+        backend.clear_render_target(cmd, back_buffer_rtv, CLEAR_COLOR);
+        backend.clear_depth(cmd, frame.depth_dsv, 1.0);
+    });
+    let output = pass.render_target(output);
+    let depth = pass.depth_stencil(depth);
+
+    match &frame.passes {
+        FramePasses::Rasterization {
+            shadow_map,
+            shadow_map_pass,
+            draw_mesh_pass,
+        } => {
+            let shadow_map_texture = graph.import(
+                "shadow map",
+                shadow_map.texture,
+                ResourceStates::DEPTH_WRITE,
+            );
+
+            let mut pass = graph.add_pass("Draw shadow maps", move |backend, cmd| {
+                backend.clear_depth(cmd, shadow_map.dsv, 1.0);
+                backend.set_viewport(cmd, shadow_map.width, shadow_map.height);
+                backend.set_render_targets(cmd, None, Some(shadow_map.dsv));
+                record_graphics_pass(backend, cmd, shadow_map_pass);
+            });
+            let shadow_map_texture = pass.depth_stencil(shadow_map_texture);
+
+            let mut pass = graph.add_pass("Draw mesh", move |backend, cmd| {
+                backend.set_viewport(cmd, frame.viewport_width, frame.viewport_height);
+                backend.set_render_targets(cmd, Some(back_buffer_rtv), Some(frame.depth_dsv));
+                record_graphics_pass(backend, cmd, draw_mesh_pass);
+            });
+            pass.read(shadow_map_texture, ResourceStates::ALL_SHADER_RESOURCE);
+            pass.depth_stencil(depth);
+            pass.render_target(output)
+        }
+        FramePasses::Raytracing {
+            raytracing_pass,
+            color_buffer,
+            copy_pass,
+        } => {
+            let color_buffer = graph.import(
+                "color buffer",
+                color_buffer,
+                ResourceStates::UNORDERED_ACCESS,
+            );
+
+            let mut pass = graph.add_pass("Raytrace", move |backend, cmd| {
+                backend.set_compute_pipeline(
+                    cmd,
+                    raytracing_pass.pipeline,
+                    raytracing_pass.root_signature,
+                );
+                backend.set_compute_constants(cmd, 0, &raytracing_pass.constants);
+
+                let x = divide_and_round_up(frame.viewport_width, NUM_THREAD_X);
+                let y = divide_and_round_up(frame.viewport_height, NUM_THREAD_Y);
+                backend.dispatch(cmd, x, y, 1);
+            });
+            let color_buffer = pass.write(color_buffer, ResourceStates::UNORDERED_ACCESS);
+
+            // copy the color buffer to the frame buffer
+            let mut pass = graph.add_pass("Copy", move |backend, cmd| {
+                backend.set_viewport(cmd, frame.viewport_width, frame.viewport_height);
+                backend.set_render_targets(cmd, Some(back_buffer_rtv), Some(frame.depth_dsv));
+                record_graphics_pass(backend, cmd, copy_pass);
+            });
+            pass.read(color_buffer, ResourceStates::PIXEL_SHADER_RESOURCE);
+            // bound with the render target, although the copy does not test the depth
+            pass.depth_stencil(depth);
+            pass.render_target(output)
+        }
+    }
+}
+
+fn record_graphics_pass<B: Backend>(backend: &mut B, cmd: &B::CommandList, pass: &GraphicsPass<B>) {
+    backend.set_graphics_pipeline(cmd, pass.pipeline, pass.root_signature);
+
+    if !pass.constants.is_empty() {
+        backend.set_graphics_constants(cmd, 1, &pass.constants);
+    }
+
+    for draw in &pass.draws {
+        backend.set_vertex_buffers(cmd, &draw.vertex_buffers);
+        backend.set_graphics_constants(cmd, 0, &draw.constants);
+
+        match &draw.index_buffer {
+            Some(index_buffer) => {
+                backend.set_index_buffer(cmd, index_buffer);
+                backend.draw_indexed(cmd, draw.count, 1);
+            }
+            None => backend.draw(cmd, draw.count, 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use super::super::d3d12::backend::mock::*;
+    use super::*;
+
+    const WIDTH: u32 = 1280;
+    const HEIGHT: u32 = 720;
+
+    struct Fixture {
+        depth_buffer: MockResource,
+        depth_dsv: MockDsv,
+        shadow_map: MockResource,
+        shadow_map_dsv: MockDsv,
+        color_buffer: MockResource,
+        pipeline: MockPipeline,
+        root_signature: MockRootSignature,
+    }
+
+    impl Fixture {
+        fn new(backend: &mut MockBackend) -> Self {
+            let texture = |backend: &mut MockBackend, format, state, name| {
+                let desc = Texture2dDesc {
+                    width: WIDTH,
+                    height: HEIGHT,
+                    format,
+                    flags: ResourceFlags::NONE,
+                    init_state: state,
+                    clear_value: None,
+                };
+                backend.create_texture2d(&desc, name).unwrap()
+            };
+
+            let depth_buffer = texture(
+                backend,
+                Format::D32_FLOAT,
+                ResourceStates::DEPTH_WRITE,
+                "depth buffer",
+            );
+            let shadow_map = texture(
+                backend,
+                Format::D32_FLOAT,
+                ResourceStates::DEPTH_WRITE,
+                "shadow map",
+            );
+            let color_buffer = texture(
+                backend,
+                Format::R8G8B8A8_UNORM,
+                ResourceStates::UNORDERED_ACCESS,
+                "color buffer",
+            );
+            let depth_dsv = backend.create_dsv(&depth_buffer, Format::D32_FLOAT);
+            let shadow_map_dsv = backend.create_dsv(&shadow_map, Format::D32_FLOAT);
+
+            Self {
+                depth_buffer,
+                depth_dsv,
+                shadow_map,
+                shadow_map_dsv,
+                color_buffer,
+                pipeline: MockPipeline::new("pipeline"),
+                root_signature: MockRootSignature::new("root signature"),
+            }
+        }
+
+        fn graphics_pass(&self, draws: Vec<DrawItem>) -> GraphicsPass<'_, MockBackend> {
+            GraphicsPass {
+                pipeline: &self.pipeline,
+                root_signature: &self.root_signature,
+                constants: vec![7],
+                draws,
+            }
+        }
+
+        fn frame<'a>(&'a self, passes: FramePasses<'a, MockBackend>) -> FrameDesc<'a, MockBackend> {
+            FrameDesc {
+                viewport_width: WIDTH,
+                viewport_height: HEIGHT,
+                depth_buffer: &self.depth_buffer,
+                depth_dsv: &self.depth_dsv,
+                passes,
+            }
+        }
+    }
+
+    // Records a frame and returns the calls recorded for it
+    fn record(backend: &mut MockBackend, frame: &FrameDesc<MockBackend>) -> Vec<Call> {
+        let cmd = backend.begin_commands(QueueType::Graphics).unwrap();
+        backend.take_calls();
+        record_frame(backend, &cmd, frame);
+        backend.take_calls()
+    }
+
+    fn indexed_draw() -> DrawItem {
+        DrawItem {
+            vertex_buffers: vec![VertexBufferView {
+                location: 0x1000,
+                size: 36,
+                stride: 12,
+            }],
+            index_buffer: Some(IndexBufferView {
+                location: 0x2000,
+                size: 12,
+                format: Format::R32_UINT,
+            }),
+            count: 3,
+            constants: vec![1, 2],
+        }
+    }
+
+    fn transition(
+        resource: MockResource,
+        before: ResourceStates,
+        after: ResourceStates,
+    ) -> RecordedBarrier {
+        RecordedBarrier::Transition {
+            resource,
+            subresource: ALL_SUBRESOURCES,
+            before,
+            after,
+        }
+    }
+
+    fn event(name: &str) -> Call {
+        Call::BeginEvent {
+            cmd: 0,
+            name: name.into(),
+        }
+    }
+
+    fn graphics_pass_calls() -> Vec<Call> {
+        vec![
+            Call::SetGraphicsPipeline {
+                cmd: 0,
+                pipeline: "pipeline".into(),
+                root_signature: "root signature".into(),
+            },
+            Call::SetGraphicsConstants {
+                cmd: 0,
+                index: 1,
+                constants: vec![7],
+            },
+        ]
+    }
+
+    fn indexed_draw_calls() -> Vec<Call> {
+        let draw = indexed_draw();
+        vec![
+            Call::SetVertexBuffers {
+                cmd: 0,
+                views: draw.vertex_buffers,
+            },
+            Call::SetGraphicsConstants {
+                cmd: 0,
+                index: 0,
+                constants: draw.constants,
+            },
+            Call::SetIndexBuffer {
+                cmd: 0,
+                view: draw.index_buffer.unwrap(),
+            },
+            Call::DrawIndexed {
+                cmd: 0,
+                index_count: 3,
+                instance_count: 1,
+            },
+        ]
+    }
+
+    #[test]
+    fn rasterization_frame() {
+        let mut backend = MockBackend::new();
+        let fixture = Fixture::new(&mut backend);
+        let back_buffer = backend.back_buffer();
+        let (depth_buffer, shadow_map) = (fixture.depth_buffer, fixture.shadow_map);
+
+        // the shadow map pass draws without an index buffer
+        let shadow_map_draw = DrawItem {
+            index_buffer: None,
+            ..indexed_draw()
+        };
+        let passes = FramePasses::Rasterization {
+            shadow_map: ShadowMapTarget {
+                texture: &fixture.shadow_map,
+                dsv: &fixture.shadow_map_dsv,
+                width: 1024,
+                height: 512,
+            },
+            shadow_map_pass: fixture.graphics_pass(vec![shadow_map_draw]),
+            draw_mesh_pass: fixture.graphics_pass(vec![indexed_draw()]),
+        };
+        let calls = record(&mut backend, &fixture.frame(passes));
+
+        let mut expected = vec![
+            event("Render"),
+            Call::ResourceBarrier {
+                cmd: 0,
+                barriers: vec![transition(
+                    back_buffer,
+                    ResourceStates::PRESENT,
+                    ResourceStates::RENDER_TARGET,
+                )],
+            },
+            event("Clear buffers"),
+            Call::ClearRenderTarget {
+                cmd: 0,
+                target: back_buffer,
+                color: CLEAR_COLOR,
+            },
+            Call::ClearDepth {
+                cmd: 0,
+                target: depth_buffer,
+                depth: 1.0,
+            },
+            Call::EndEvent { cmd: 0 },
+            // the shadow map is already in DEPTH_WRITE
+            event("Draw shadow maps"),
+            Call::ClearDepth {
+                cmd: 0,
+                target: shadow_map,
+                depth: 1.0,
+            },
+            Call::SetViewport {
+                cmd: 0,
+                width: 1024,
+                height: 512,
+            },
+            Call::SetRenderTargets {
+                cmd: 0,
+                rtv: None,
+                dsv: Some(shadow_map),
+            },
+        ];
+        expected.extend(graphics_pass_calls());
+        let mut shadow_map_draw_calls = indexed_draw_calls();
+        shadow_map_draw_calls.truncate(2);
+        expected.extend(shadow_map_draw_calls);
+        expected.extend([
+            Call::Draw {
+                cmd: 0,
+                vertex_count: 3,
+                instance_count: 1,
+            },
+            Call::EndEvent { cmd: 0 },
+            Call::ResourceBarrier {
+                cmd: 0,
+                barriers: vec![transition(
+                    shadow_map,
+                    ResourceStates::DEPTH_WRITE,
+                    ResourceStates::ALL_SHADER_RESOURCE,
+                )],
+            },
+            event("Draw mesh"),
+            Call::SetViewport {
+                cmd: 0,
+                width: WIDTH,
+                height: HEIGHT,
+            },
+            Call::SetRenderTargets {
+                cmd: 0,
+                rtv: Some(back_buffer),
+                dsv: Some(depth_buffer),
+            },
+        ]);
+        expected.extend(graphics_pass_calls());
+        expected.extend(indexed_draw_calls());
+        expected.extend([
+            Call::EndEvent { cmd: 0 },
+            Call::ResourceBarrier {
+                cmd: 0,
+                barriers: vec![
+                    transition(
+                        back_buffer,
+                        ResourceStates::RENDER_TARGET,
+                        ResourceStates::PRESENT,
+                    ),
+                    transition(
+                        shadow_map,
+                        ResourceStates::ALL_SHADER_RESOURCE,
+                        ResourceStates::DEPTH_WRITE,
+                    ),
+                ],
+            },
+            Call::EndEvent { cmd: 0 },
+        ]);
+
+        assert_eq!(calls, expected);
+    }
+
+    #[test]
+    fn raytracing_frame() {
+        let mut backend = MockBackend::new();
+        let fixture = Fixture::new(&mut backend);
+        let back_buffer = backend.back_buffer();
+        let (depth_buffer, color_buffer) = (fixture.depth_buffer, fixture.color_buffer);
+
+        let copy_draw = DrawItem {
+            vertex_buffers: Vec::new(),
+            index_buffer: None,
+            count: 3,
+            constants: vec![4],
+        };
+        let passes = FramePasses::Raytracing {
+            raytracing_pass: ComputePass {
+                pipeline: &fixture.pipeline,
+                root_signature: &fixture.root_signature,
+                constants: vec![5, 6],
+            },
+            color_buffer: &fixture.color_buffer,
+            copy_pass: fixture.graphics_pass(vec![copy_draw]),
+        };
+        let calls = record(&mut backend, &fixture.frame(passes));
+
+        let mut expected = vec![
+            event("Render"),
+            Call::ResourceBarrier {
+                cmd: 0,
+                barriers: vec![transition(
+                    back_buffer,
+                    ResourceStates::PRESENT,
+                    ResourceStates::RENDER_TARGET,
+                )],
+            },
+            event("Clear buffers"),
+            Call::ClearRenderTarget {
+                cmd: 0,
+                target: back_buffer,
+                color: CLEAR_COLOR,
+            },
+            Call::ClearDepth {
+                cmd: 0,
+                target: depth_buffer,
+                depth: 1.0,
+            },
+            Call::EndEvent { cmd: 0 },
+            // the color buffer is already in UNORDERED_ACCESS
+            event("Raytrace"),
+            Call::SetComputePipeline {
+                cmd: 0,
+                pipeline: "pipeline".into(),
+                root_signature: "root signature".into(),
+            },
+            Call::SetComputeConstants {
+                cmd: 0,
+                index: 0,
+                constants: vec![5, 6],
+            },
+            Call::Dispatch {
+                cmd: 0,
+                x: WIDTH / NUM_THREAD_X,
+                y: HEIGHT / NUM_THREAD_Y,
+                z: 1,
+            },
+            Call::EndEvent { cmd: 0 },
+            Call::ResourceBarrier {
+                cmd: 0,
+                barriers: vec![transition(
+                    color_buffer,
+                    ResourceStates::UNORDERED_ACCESS,
+                    ResourceStates::PIXEL_SHADER_RESOURCE,
+                )],
+            },
+            event("Copy"),
+            Call::SetViewport {
+                cmd: 0,
+                width: WIDTH,
+                height: HEIGHT,
+            },
+            Call::SetRenderTargets {
+                cmd: 0,
+                rtv: Some(back_buffer),
+                dsv: Some(depth_buffer),
+            },
+        ];
+        expected.extend(graphics_pass_calls());
+        expected.extend([
+            Call::SetVertexBuffers {
+                cmd: 0,
+                views: Vec::new(),
+            },
+            Call::SetGraphicsConstants {
+                cmd: 0,
+                index: 0,
+                constants: vec![4],
+            },
+            Call::Draw {
+                cmd: 0,
+                vertex_count: 3,
+                instance_count: 1,
+            },
+            Call::EndEvent { cmd: 0 },
+            Call::ResourceBarrier {
+                cmd: 0,
+                barriers: vec![
+                    transition(
+                        back_buffer,
+                        ResourceStates::RENDER_TARGET,
+                        ResourceStates::PRESENT,
+                    ),
+                    transition(
+                        color_buffer,
+                        ResourceStates::PIXEL_SHADER_RESOURCE,
+                        ResourceStates::UNORDERED_ACCESS,
+                    ),
+                ],
+            },
+            Call::EndEvent { cmd: 0 },
+        ]);
+
+        assert_eq!(calls, expected);
+    }
+
+    #[test]
+    fn view_heap_is_bound_after_growing() {
+        let mut backend = MockBackend::with_view_capacity(1);
+        let desc = BufferDesc {
+            size: 256,
+            heap_type: HeapType::Upload,
+            flags: ResourceFlags::NONE,
+            init_state: ResourceStates::GENERIC_READ,
+        };
+        let camera = backend.create_buffer(&desc, "camera").unwrap();
+        // fills the heap
+        backend.create_cbv(&camera, 256);
+
+        let cmd = backend.begin_commands(QueueType::Graphics).unwrap();
+        backend.take_calls();
+        record_updates(&mut backend, &cmd, |backend, _| {
+            backend.create_cbv(&camera, 256);
+            Ok::<_, Infallible>(())
+        })
+        .unwrap();
+        assert_eq!(backend.view_heap(), 1);
+
+        let expected = vec![
+            event("Update scene"),
+            Call::CreateCbv {
+                buffer: camera,
+                size: 256,
+                handle: 1,
+            },
+            Call::EndEvent { cmd: 0 },
+            Call::BindViewHeap { cmd: 0, heap: 1 },
+        ];
+        assert_eq!(backend.take_calls(), expected);
+    }
+}
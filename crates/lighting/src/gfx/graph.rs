@@ -0,0 +1,590 @@
+// Render graph: passes declare the resources they read and write, and the graph derives the execution
+// order, culls the passes whose results are never used, and records the barriers between them
+// Every write creates a new version of the resource, so the order follows the data flow rather than
+// the order the passes are added in
+
+use std::ops::Range;
+
+use super::d3d12::backend::{Backend, Barrier, ResourceStates, ALL_SUBRESOURCES};
+use super::d3d12::state::{StateBarrier, StateTracker};
+
+type Execute<'a, B> = Box<dyn FnOnce(&mut B, &<B as Backend>::CommandList) + 'a>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ResourceId(usize);
+
+/// A resource as of a write, passes reading it are executed after the writing pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResourceVersion {
+    id: ResourceId,
+    version: usize,
+}
+
+impl ResourceVersion {
+    pub fn id(&self) -> ResourceId {
+        self.id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PassId(usize);
+
+struct ResourceNode<'a, R> {
+    name: String,
+    resource: &'a R,
+    // the state before and after the graph is executed
+    state: ResourceStates,
+    // the pass that created each version, except the imported version 0
+    writers: Vec<PassId>,
+}
+
+struct PassNode<'a, B: Backend> {
+    name: String,
+    reads: Vec<(ResourceVersion, ResourceStates)>,
+    // the versions created by this pass
+    writes: Vec<(ResourceVersion, ResourceStates)>,
+    side_effects: bool,
+    execute: Option<Execute<'a, B>>,
+}
+
+pub struct RenderGraph<'a, B: Backend> {
+    resources: Vec<ResourceNode<'a, B::Resource>>,
+    passes: Vec<PassNode<'a, B>>,
+    exports: Vec<ResourceVersion>,
+}
+
+pub struct PassBuilder<'g, 'a, B: Backend> {
+    graph: &'g mut RenderGraph<'a, B>,
+    pass: PassId,
+}
+
+/// The result of compiling a graph, which only depends on the declared accesses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledGraph {
+    order: Vec<PassId>,
+    culled: Vec<PassId>,
+    // indexed by the position in `order`
+    barriers: Vec<Vec<StateBarrier<ResourceId>>>,
+    final_barriers: Vec<StateBarrier<ResourceId>>,
+    // indexed by ResourceId
+    lifetimes: Vec<Option<Range<usize>>>,
+}
+
+impl<'a, B: Backend> Default for RenderGraph<'a, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B: Backend> RenderGraph<'a, B> {
+    pub fn new() -> Self {
+        Self {
+            resources: Vec::new(),
+            passes: Vec::new(),
+            exports: Vec::new(),
+        }
+    }
+
+    /// Adds a resource created outside the graph, which is in `state` before and after the execution
+    pub fn import(
+        &mut self,
+        name: &str,
+        resource: &'a B::Resource,
+        state: ResourceStates,
+    ) -> ResourceVersion {
+        let id = ResourceId(self.resources.len());
+        self.resources.push(ResourceNode {
+            name: name.to_string(),
+            resource,
+            state,
+            writers: Vec::new(),
+        });
+
+        ResourceVersion { id, version: 0 }
+    }
+
+    /// Marks a version as an output of the graph, so that the passes producing it are not culled
+    pub fn export(&mut self, resource: ResourceVersion) {
+        self.exports.push(resource);
+    }
+
+    /// `execute` records the commands of the pass, which is wrapped in an event named `name`
+    pub fn add_pass<'g>(
+        &'g mut self,
+        name: &str,
+        execute: impl FnOnce(&mut B, &B::CommandList) + 'a,
+    ) -> PassBuilder<'g, 'a, B> {
+        let pass = PassId(self.passes.len());
+        self.passes.push(PassNode {
+            name: name.to_string(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            side_effects: false,
+            execute: Some(Box::new(execute)),
+        });
+
+        PassBuilder { graph: self, pass }
+    }
+
+    /// Declares more accesses of a pass that was already added
+    pub fn pass<'g>(&'g mut self, pass: PassId) -> PassBuilder<'g, 'a, B> {
+        PassBuilder { graph: self, pass }
+    }
+
+    pub fn pass_name(&self, pass: PassId) -> &str {
+        &self.passes[pass.0].name
+    }
+
+    pub fn resource_name(&self, resource: ResourceId) -> &str {
+        &self.resources[resource.0].name
+    }
+
+    pub fn compile(&self) -> CompiledGraph {
+        let dependencies: Vec<_> = (0..self.passes.len())
+            .map(|i| self.dependencies(PassId(i)))
+            .collect();
+
+        let live = self.live_passes();
+        let order = sort_passes(&dependencies, &live);
+        let culled = (0..self.passes.len())
+            .filter(|i| !live[*i])
+            .map(PassId)
+            .collect();
+
+        let mut lifetimes: Vec<Option<Range<usize>>> = vec![None; self.resources.len()];
+        let mut states = StateTracker::new();
+        for (i, resource) in self.resources.iter().enumerate() {
+            states.register(ResourceId(i), 1, resource.state);
+        }
+
+        let mut barriers = Vec::with_capacity(order.len());
+        for (position, pass) in order.iter().enumerate() {
+            let pass = &self.passes[pass.0];
+
+            for (resource, state) in pass.reads.iter().chain(&pass.writes) {
+                states.require(resource.id, ALL_SUBRESOURCES, *state);
+
+                let lifetime = &mut lifetimes[resource.id.0];
+                *lifetime = match lifetime.take() {
+                    Some(range) => Some(range.start..position + 1),
+                    None => Some(position..position + 1),
+                };
+            }
+
+            barriers.push(states.flush());
+        }
+
+        states.require_initial_states();
+        let final_barriers = states.flush();
+
+        CompiledGraph {
+            order,
+            culled,
+            barriers,
+            final_barriers,
+            lifetimes,
+        }
+    }
+
+    /// Compiles the graph and records the passes in the order
+    pub fn execute(mut self, backend: &mut B, cmd: &B::CommandList) {
+        let compiled = self.compile();
+
+        for (position, pass) in compiled.order.iter().enumerate() {
+            self.record_barriers(backend, cmd, &compiled.barriers[position]);
+
+            let pass = &mut self.passes[pass.0];
+            let execute = pass.execute.take().unwrap();
+
+            backend.begin_event(cmd, &pass.name);
+            execute(backend, cmd);
+            backend.end_event(cmd);
+        }
+
+        self.record_barriers(backend, cmd, &compiled.final_barriers);
+    }
+
+    fn record_barriers(
+        &self,
+        backend: &mut B,
+        cmd: &B::CommandList,
+        state_barriers: &[StateBarrier<ResourceId>],
+    ) {
+        if state_barriers.is_empty() {
+            return;
+        }
+
+        let barriers: Vec<_> = state_barriers
+            .iter()
+            .map(|b| match *b {
+                StateBarrier::Transition {
+                    resource,
+                    subresource,
+                    before,
+                    after,
+                } => Barrier::Transition {
+                    resource: self.resources[resource.0].resource,
+                    subresource,
+                    before,
+                    after,
+                },
+                StateBarrier::Uav { resource } => Barrier::Uav {
+                    resource: self.resources[resource.0].resource,
+                },
+            })
+            .collect();
+
+        backend.resource_barrier(cmd, &barriers);
+    }
+
+    // The passes whose results `pass` uses
+    fn inputs(&self, pass: PassId) -> Vec<PassId> {
+        let node = &self.passes[pass.0];
+
+        // a write modifies the previous version, so it is an input as well as the read ones
+        let reads = node.reads.iter().map(|(v, _)| *v);
+        let modified = node.writes.iter().map(|(v, _)| previous_version(v));
+
+        let mut inputs: Vec<_> = reads
+            .chain(modified)
+            .filter(|v| v.version > 0)
+            .map(|v| self.resources[v.id.0].writers[v.version - 1])
+            .collect();
+
+        inputs.sort();
+        inputs.dedup();
+        inputs
+    }
+
+    // The passes that must be executed before `pass`
+    fn dependencies(&self, pass: PassId) -> Vec<PassId> {
+        let mut dependencies = self.inputs(pass);
+
+        // the previous versions of what this pass writes must have been read
+        for (written, _) in &self.passes[pass.0].writes {
+            let previous = previous_version(written);
+            let readers = self
+                .passes
+                .iter()
+                .enumerate()
+                .filter(|(i, p)| *i != pass.0 && p.reads.iter().any(|(v, _)| *v == previous));
+            dependencies.extend(readers.map(|(i, _)| PassId(i)));
+        }
+
+        dependencies.sort();
+        dependencies.dedup();
+        dependencies
+    }
+
+    // A pass is live if it has side effects, or an export or a live pass uses its results
+    fn live_passes(&self) -> Vec<bool> {
+        let mut live = vec![false; self.passes.len()];
+
+        let mut stack: Vec<_> = self
+            .exports
+            .iter()
+            .filter(|v| v.version > 0)
+            .map(|v| self.resources[v.id.0].writers[v.version - 1])
+            .collect();
+        stack.extend(
+            (0..self.passes.len())
+                .filter(|i| self.passes[*i].side_effects)
+                .map(PassId),
+        );
+
+        while let Some(pass) = stack.pop() {
+            if !live[pass.0] {
+                live[pass.0] = true;
+                stack.extend(self.inputs(pass));
+            }
+        }
+
+        live
+    }
+}
+
+impl<'g, 'a, B: Backend> PassBuilder<'g, 'a, B> {
+    pub fn id(&self) -> PassId {
+        self.pass
+    }
+
+    pub fn read(&mut self, resource: ResourceVersion, state: ResourceStates) -> &mut Self {
+        self.graph.passes[self.pass.0].reads.push((resource, state));
+        self
+    }
+
+    /// Returns the new version of the resource that the following passes can read
+    /// Each version can only be written once
+    pub fn write(&mut self, resource: ResourceVersion, state: ResourceStates) -> ResourceVersion {
+        let node = &mut self.graph.resources[resource.id.0];
+        assert_eq!(
+            resource.version,
+            node.writers.len(),
+            "version {} of {} is already written",
+            resource.version,
+            node.name
+        );
+
+        node.writers.push(self.pass);
+
+        let written = ResourceVersion {
+            id: resource.id,
+            version: resource.version + 1,
+        };
+        self.graph.passes[self.pass.0].writes.push((written, state));
+
+        written
+    }
+
+    pub fn render_target(&mut self, resource: ResourceVersion) -> ResourceVersion {
+        self.write(resource, ResourceStates::RENDER_TARGET)
+    }
+
+    pub fn depth_stencil(&mut self, resource: ResourceVersion) -> ResourceVersion {
+        self.write(resource, ResourceStates::DEPTH_WRITE)
+    }
+
+    /// Keeps the pass even if nothing depends on it
+    pub fn side_effects(&mut self) -> &mut Self {
+        self.graph.passes[self.pass.0].side_effects = true;
+        self
+    }
+}
+
+impl CompiledGraph {
+    /// The passes to execute
+    pub fn order(&self) -> &[PassId] {
+        &self.order
+    }
+
+    pub fn culled(&self) -> &[PassId] {
+        &self.culled
+    }
+
+    /// Barriers recorded before the pass at `position` in `order`
+    pub fn barriers(&self, position: usize) -> &[StateBarrier<ResourceId>] {
+        &self.barriers[position]
+    }
+
+    /// Barriers that return the resources to their imported states
+    pub fn final_barriers(&self) -> &[StateBarrier<ResourceId>] {
+        &self.final_barriers
+    }
+
+    /// The range of positions in `order` between the first and the last pass accessing the resource
+    /// None if no executed pass accesses it
+    pub fn lifetime(&self, resource: ResourceId) -> Option<Range<usize>> {
+        self.lifetimes[resource.0].clone()
+    }
+}
+
+fn previous_version(resource: &ResourceVersion) -> ResourceVersion {
+    ResourceVersion {
+        id: resource.id,
+        version: resource.version - 1,
+    }
+}
+
+// Topological sort that keeps the order the passes were added in whenever possible
+fn sort_passes(dependencies: &[Vec<PassId>], live: &[bool]) -> Vec<PassId> {
+    let mut order = Vec::new();
+    let mut scheduled = vec![false; dependencies.len()];
+
+    let count = live.iter().filter(|l| **l).count();
+    while order.len() < count {
+        let ready = (0..dependencies.len()).find(|i| {
+            let satisfied = |d: &PassId| scheduled[d.0] || !live[d.0];
+            live[*i] && !scheduled[*i] && dependencies[*i].iter().all(satisfied)
+        });
+
+        let Some(i) = ready else {
+            panic!("the render graph has a cycle");
+        };
+
+        scheduled[i] = true;
+        order.push(PassId(i));
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::d3d12::backend::mock::{MockBackend, MockResource};
+    use super::super::d3d12::backend::{BufferDesc, HeapType, ResourceFlags};
+    use super::*;
+
+    const UAV: ResourceStates = ResourceStates::UNORDERED_ACCESS;
+    const SRV: ResourceStates = ResourceStates::NON_PIXEL_SHADER_RESOURCE;
+    const COPY_DEST: ResourceStates = ResourceStates::COPY_DEST;
+
+    fn buffers<const N: usize>() -> [MockResource; N] {
+        let mut backend = MockBackend::new();
+        let desc = BufferDesc {
+            size: 256,
+            heap_type: HeapType::Default,
+            flags: ResourceFlags::NONE,
+            init_state: ResourceStates::COMMON,
+        };
+        std::array::from_fn(|i| backend.create_buffer(&desc, &format!("{i}")).unwrap())
+    }
+
+    fn names(graph: &RenderGraph<MockBackend>, passes: &[PassId]) -> Vec<String> {
+        passes
+            .iter()
+            .map(|p| graph.pass_name(*p).to_string())
+            .collect()
+    }
+
+    fn transition(
+        resource: ResourceVersion,
+        before: ResourceStates,
+        after: ResourceStates,
+    ) -> StateBarrier<ResourceId> {
+        StateBarrier::Transition {
+            resource: resource.id(),
+            subresource: ALL_SUBRESOURCES,
+            before,
+            after,
+        }
+    }
+
+    #[test]
+    fn passes_follow_the_data_flow() {
+        let [a, b] = buffers();
+        let mut graph = RenderGraph::<MockBackend>::new();
+        let a = graph.import("a", &a, COPY_DEST);
+        let b = graph.import("b", &b, COPY_DEST);
+
+        // the reader is added before the writer of the version it reads
+        let reader = graph.add_pass("read", |_, _| {}).id();
+        let mut pass = graph.add_pass("write", |_, _| {});
+        let a1 = pass.write(a, UAV);
+        graph.pass(reader).read(a1, SRV);
+        let b1 = graph.pass(reader).write(b, UAV);
+
+        // a write after a read of the previous version waits for the read
+        let mut pass = graph.add_pass("overwrite", |_, _| {});
+        let a2 = pass.write(a1, COPY_DEST);
+
+        graph.export(a2);
+        graph.export(b1);
+        let compiled = graph.compile();
+
+        assert_eq!(
+            names(&graph, compiled.order()),
+            ["write", "read", "overwrite"]
+        );
+        assert_eq!(compiled.culled(), []);
+    }
+
+    #[test]
+    fn unused_passes_are_culled() {
+        let [a, b, c] = buffers();
+        let mut graph = RenderGraph::<MockBackend>::new();
+        let a = graph.import("a", &a, UAV);
+        let b = graph.import("b", &b, UAV);
+        let c = graph.import("c", &c, UAV);
+
+        let a1 = graph.add_pass("produce a", |_, _| {}).write(a, UAV);
+        let mut pass = graph.add_pass("a to b", |_, _| {});
+        pass.read(a1, SRV);
+        let b1 = pass.write(b, UAV);
+
+        // nothing uses the results of these, except each other
+        let c1 = graph.add_pass("produce c", |_, _| {}).write(c, UAV);
+        let mut pass = graph.add_pass("consume c", |_, _| {});
+        pass.read(c1, SRV);
+        pass.write(b1, UAV);
+
+        // but a pass with side effects is kept along with its inputs
+        let mut pass = graph.add_pass("present", |_, _| {});
+        pass.read(b1, SRV).side_effects();
+
+        let compiled = graph.compile();
+        assert_eq!(
+            names(&graph, compiled.order()),
+            ["produce a", "a to b", "present"]
+        );
+        assert_eq!(names(&graph, compiled.culled()), ["produce c", "consume c"]);
+        assert_eq!(compiled.lifetime(c.id()), None);
+    }
+
+    #[test]
+    fn exports_keep_the_passes_writing_them() {
+        let [a] = buffers();
+        let mut graph = RenderGraph::<MockBackend>::new();
+        let a = graph.import("a", &a, UAV);
+
+        let a1 = graph.add_pass("first", |_, _| {}).write(a, UAV);
+        let a2 = graph.add_pass("second", |_, _| {}).write(a1, UAV);
+        graph.add_pass("third", |_, _| {}).write(a2, UAV);
+
+        // the last write is not exported, so it is culled
+        graph.export(a2);
+        let compiled = graph.compile();
+        assert_eq!(names(&graph, compiled.order()), ["first", "second"]);
+        assert_eq!(names(&graph, compiled.culled()), ["third"]);
+    }
+
+    #[test]
+    fn lifetimes_span_the_accessing_passes() {
+        let [a, b, c] = buffers();
+        let mut graph = RenderGraph::<MockBackend>::new();
+        let a = graph.import("a", &a, UAV);
+        let b = graph.import("b", &b, UAV);
+        let c = graph.import("c", &c, UAV);
+
+        let a1 = graph.add_pass("0", |_, _| {}).write(a, UAV);
+        let mut pass = graph.add_pass("1", |_, _| {});
+        pass.read(a1, SRV);
+        let b1 = pass.write(b, UAV);
+        let mut pass = graph.add_pass("2", |_, _| {});
+        pass.read(b1, SRV);
+        let c1 = pass.write(c, UAV);
+        let c2 = graph.add_pass("3", |_, _| {}).write(c1, UAV);
+        graph.export(c2);
+
+        let compiled = graph.compile();
+        assert_eq!(compiled.lifetime(a.id()), Some(0..2));
+        assert_eq!(compiled.lifetime(b.id()), Some(1..3));
+        assert_eq!(compiled.lifetime(c.id()), Some(2..4));
+    }
+
+    #[test]
+    fn barriers_between_passes() {
+        let [a, b] = buffers();
+        let mut graph = RenderGraph::<MockBackend>::new();
+        let a = graph.import("a", &a, COPY_DEST);
+        let b = graph.import("b", &b, UAV);
+
+        let mut pass = graph.add_pass("upload", |_, _| {});
+        let a1 = pass.write(a, COPY_DEST);
+        let mut pass = graph.add_pass("simulate", |_, _| {});
+        pass.read(a1, SRV);
+        let b1 = pass.write(b, UAV);
+        let b2 = graph.add_pass("simulate again", |_, _| {}).write(b1, UAV);
+        graph.export(b2);
+
+        let compiled = graph.compile();
+        assert_eq!(compiled.barriers(0), []);
+        assert_eq!(compiled.barriers(1), [transition(a, COPY_DEST, SRV)]);
+        // the second pass writing the UAV waits for the first one
+        assert_eq!(
+            compiled.barriers(2),
+            [StateBarrier::Uav { resource: b.id() }]
+        );
+        assert_eq!(compiled.final_barriers(), [transition(a, SRV, COPY_DEST)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already written")]
+    fn versions_are_written_once() {
+        let [a] = buffers();
+        let mut graph = RenderGraph::<MockBackend>::new();
+        let a = graph.import("a", &a, UAV);
+
+        graph.add_pass("first", |_, _| {}).write(a, UAV);
+        graph.add_pass("second", |_, _| {}).write(a, UAV);
+    }
+}
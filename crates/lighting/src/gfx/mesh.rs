@@ -1,29 +1,47 @@
+#[cfg(windows)]
 use std::mem;
+#[cfg(windows)]
 use windows::Win32::Graphics::{Direct3D12::*, Dxgi::Common::*};
 
-use super::d3d12::{device::*, resource, view::Srv};
+#[cfg(windows)]
+use super::d3d12::{device::*, view::Srv};
+#[cfg(windows)]
 use super::math::*;
+#[cfg(windows)]
+use sandbox_core::{align, camera::Camera};
 
+pub use sandbox_core::mesh::*;
+
+#[cfg(windows)]
 type OnUpdate = dyn FnMut(f64) -> Mat4;
 
+// The coarsest LOD whose error is within this many pixels on the screen is drawn
+#[cfg(windows)]
+const MAX_LOD_PIXEL_ERROR: f32 = 1.0;
+
+#[cfg(windows)]
 pub struct Mesh {
     vertex_count: usize,
-    #[allow(unused)]
-    position_buffer: ID3D12Resource,
+    position: VertexBuffer,
     position_format: DXGI_FORMAT,
-    position_vbv: D3D12_VERTEX_BUFFER_VIEW,
-    position_srv: Srv,
 
-    #[allow(unused)]
-    normal_buffer: ID3D12Resource,
-    normal_vbv: D3D12_VERTEX_BUFFER_VIEW,
-    normal_srv: Srv,
+    normal: VertexBuffer,
+
+    uv_sets: Vec<VertexBuffer>,
+    /// None if the mesh has no UVs to compute tangents from
+    tangent: Option<VertexBuffer>,
 
-    index_count: usize,
-    #[allow(unused)]
     index_buffer: ID3D12Resource,
-    ibv: D3D12_INDEX_BUFFER_VIEW,
     index_srv: Srv,
+    /// The mesh itself and then its simplified LODs, whose indices follow each other in the index
+    /// buffer
+    lods: Vec<LodRange>,
+    lod: usize,
+    /// In the object space, to measure the LOD errors on the screen
+    bounding_sphere: (Vec3, f32),
+
+    /// Of the mesh itself, not of the LODs, None if the mesh has no triangles
+    meshlets: Option<MeshletBuffers>,
 
     transform: [f32; 12],
     transposed_inv_transform: [f32; 12],
@@ -33,170 +51,286 @@ pub struct Mesh {
     on_update: Box<OnUpdate>,
 }
 
-impl Mesh {
-    pub fn load(
-        device: &mut Device,
-        mesh: &MeshResource,
-        material: Material,
-        on_update: Box<OnUpdate>,
-    ) -> windows::core::Result<Self> {
-        let ctx = device.request_copy_command_ctx()?;
-        let command_list = ctx.command_list();
+#[cfg(windows)]
+struct LodRange {
+    ibv: D3D12_INDEX_BUFFER_VIEW,
+    first_index: u32,
+    index_count: u32,
+    error: f32,
+}
 
-        let position_buffer_size = mem::size_of_val(&mesh.positions[0]) * mesh.positions.len();
+// A vertex attribute, which is also readable as a structured buffer by raytracing shaders
+#[cfg(windows)]
+struct VertexBuffer {
+    buffer: ID3D12Resource,
+    vbv: D3D12_VERTEX_BUFFER_VIEW,
+    srv: Srv,
+}
 
-        let positions = resource::create_buffer_with_data(
-            device,
-            D3D12_HEAP_TYPE_UPLOAD,
-            D3D12_RESOURCE_FLAG_NONE,
-            D3D12_RESOURCE_STATE_COMMON,
-            &mesh.positions,
-            "Intermediate vertex buffer",
-        )?;
+#[cfg(windows)]
+impl VertexBuffer {
+    fn new<T: Copy>(device: &mut Device, data: &[T], name: &str) -> windows::core::Result<Self> {
+        let StructuredBuffer { buffer, srv } = StructuredBuffer::new(device, data, name)?;
 
-        let position_buffer = resource::create_buffer(
-            device,
-            position_buffer_size as u64,
+        let vbv = D3D12_VERTEX_BUFFER_VIEW {
+            BufferLocation: unsafe { buffer.GetGPUVirtualAddress() },
+            SizeInBytes: mem::size_of_val(data) as u32,
+            StrideInBytes: mem::size_of::<T>() as u32,
+        };
+
+        Ok(VertexBuffer { buffer, vbv, srv })
+    }
+
+    fn release(self, device: &mut Device) {
+        device.free_srv(self.srv);
+        device.release(self.buffer);
+    }
+}
+
+#[cfg(windows)]
+struct StructuredBuffer {
+    buffer: ID3D12Resource,
+    srv: Srv,
+}
+
+#[cfg(windows)]
+impl StructuredBuffer {
+    fn new<T: Copy>(device: &mut Device, data: &[T], name: &str) -> windows::core::Result<Self> {
+        let stride = mem::size_of::<T>();
+        let size = mem::size_of_val(data);
+
+        let buffer = device.create_placed_buffer(
+            size as u64,
             D3D12_HEAP_TYPE_DEFAULT,
             D3D12_RESOURCE_FLAG_NONE,
             D3D12_RESOURCE_STATE_COMMON,
-            &format!("{}::position_buffer", mesh.name),
+            name,
         )?;
 
-        unsafe { command_list.CopyResource(&position_buffer, &positions) };
-
-        let position_vbv = D3D12_VERTEX_BUFFER_VIEW {
-            BufferLocation: unsafe { position_buffer.GetGPUVirtualAddress() },
-            SizeInBytes: position_buffer_size as u32,
-            StrideInBytes: mem::size_of_val(&mesh.positions[0]) as u32,
-        };
+        device.upload_buffer(&buffer, 0, data)?;
 
-        let position_srv_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+        let srv_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
             Format: DXGI_FORMAT_UNKNOWN,
             ViewDimension: D3D12_SRV_DIMENSION_BUFFER,
             Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
             Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
                 Buffer: D3D12_BUFFER_SRV {
                     FirstElement: 0,
-                    NumElements: mesh.positions.len() as u32,
-                    StructureByteStride: mem::size_of_val(&mesh.positions[0]) as u32,
+                    NumElements: data.len() as u32,
+                    StructureByteStride: stride as u32,
                     Flags: D3D12_BUFFER_SRV_FLAG_NONE,
                 },
             },
         };
-        let position_srv = device.create_srv(Some(&position_buffer), Some(&position_srv_desc));
+        let srv = device.create_srv(Some(&buffer), Some(&srv_desc));
 
-        let normal_buffer_size = mem::size_of_val(&mesh.normals[0]) * mesh.normals.len();
+        Ok(StructuredBuffer { buffer, srv })
+    }
 
-        let normals = resource::create_buffer_with_data(
-            device,
-            D3D12_HEAP_TYPE_UPLOAD,
-            D3D12_RESOURCE_FLAG_NONE,
-            D3D12_RESOURCE_STATE_COMMON,
-            &mesh.normals,
-            "Intermediate normal buffer",
-        )?;
+    fn release(self, device: &mut Device) {
+        device.free_srv(self.srv);
+        device.release(self.buffer);
+    }
+}
 
-        let normal_buffer = resource::create_buffer(
-            device,
-            normal_buffer_size as u64,
-            D3D12_HEAP_TYPE_DEFAULT,
-            D3D12_RESOURCE_FLAG_NONE,
-            D3D12_RESOURCE_STATE_COMMON,
-            &format!("{}::normal_buffer", mesh.name),
-        )?;
+// The arrays of `Meshlets` for mesh shaders
+#[cfg(windows)]
+struct MeshletBuffers {
+    count: usize,
+    meshlets: StructuredBuffer,
+    bounds: StructuredBuffer,
+    vertices: StructuredBuffer,
+    triangles: StructuredBuffer,
+}
 
-        unsafe { command_list.CopyResource(&normal_buffer, &normals) };
+#[cfg(windows)]
+impl MeshletBuffers {
+    // None for a mesh without triangles, as buffers cannot be empty
+    fn new(device: &mut Device, mesh: &MeshResource) -> windows::core::Result<Option<Self>> {
+        let meshlets = mesh.build_meshlets();
+        if meshlets.meshlets.is_empty() {
+            return Ok(None);
+        }
+        let name = |buffer: &str| format!("{}::{buffer}", mesh.name());
+
+        Ok(Some(MeshletBuffers {
+            count: meshlets.meshlets.len(),
+            meshlets: StructuredBuffer::new(device, &meshlets.meshlets, &name("meshlet_buffer"))?,
+            bounds: StructuredBuffer::new(
+                device,
+                &meshlets.bounds,
+                &name("meshlet_bounds_buffer"),
+            )?,
+            vertices: StructuredBuffer::new(
+                device,
+                &meshlets.vertices,
+                &name("meshlet_vertex_buffer"),
+            )?,
+            triangles: StructuredBuffer::new(
+                device,
+                &meshlets.triangles,
+                &name("meshlet_triangle_buffer"),
+            )?,
+        }))
+    }
 
-        let normal_vbv = D3D12_VERTEX_BUFFER_VIEW {
-            BufferLocation: unsafe { normal_buffer.GetGPUVirtualAddress() } as u64,
-            SizeInBytes: normal_buffer_size as u32,
-            StrideInBytes: mem::size_of_val(&mesh.normals[0]) as u32,
-        };
+    fn release(self, device: &mut Device) {
+        for buffer in [self.meshlets, self.bounds, self.vertices, self.triangles] {
+            buffer.release(device);
+        }
+    }
+}
 
-        let normal_srv_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
-            Format: DXGI_FORMAT_UNKNOWN,
-            ViewDimension: D3D12_SRV_DIMENSION_BUFFER,
-            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
-            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
-                Buffer: D3D12_BUFFER_SRV {
-                    FirstElement: 0,
-                    NumElements: mesh.normals.len() as u32,
-                    StructureByteStride: mem::size_of_val(&mesh.normals[0]) as u32,
-                    Flags: D3D12_BUFFER_SRV_FLAG_NONE,
-                },
+// 16-bit or 32-bit indices by the size of T
+// The SRV is a raw buffer, as structured buffers cannot have 16-bit elements
+#[cfg(windows)]
+fn create_index_buffer<T: Copy>(
+    device: &mut Device,
+    indices: &[T],
+    name: &str,
+) -> windows::core::Result<(ID3D12Resource, D3D12_INDEX_BUFFER_VIEW, Srv)> {
+    let size = mem::size_of_val(indices);
+    // raw buffers are addressed in 4 bytes
+    let aligned_size = align!(size, 4);
+
+    let buffer = device.create_placed_buffer(
+        aligned_size as u64,
+        D3D12_HEAP_TYPE_DEFAULT,
+        D3D12_RESOURCE_FLAG_NONE,
+        D3D12_RESOURCE_STATE_COMMON,
+        name,
+    )?;
+
+    device.upload_buffer(&buffer, 0, indices)?;
+
+    let format = if mem::size_of::<T>() == mem::size_of::<u16>() {
+        DXGI_FORMAT_R16_UINT
+    } else {
+        DXGI_FORMAT_R32_UINT
+    };
+    let ibv = D3D12_INDEX_BUFFER_VIEW {
+        BufferLocation: unsafe { buffer.GetGPUVirtualAddress() },
+        Format: format,
+        SizeInBytes: size as u32,
+    };
+
+    let srv_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+        Format: DXGI_FORMAT_R32_TYPELESS,
+        ViewDimension: D3D12_SRV_DIMENSION_BUFFER,
+        Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+        Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+            Buffer: D3D12_BUFFER_SRV {
+                FirstElement: 0,
+                NumElements: (aligned_size / 4) as u32,
+                StructureByteStride: 0,
+                Flags: D3D12_BUFFER_SRV_FLAG_RAW,
             },
-        };
-        let normal_srv = device.create_srv(Some(&normal_buffer), Some(&normal_srv_desc));
+        },
+    };
+    let srv = device.create_srv(Some(&buffer), Some(&srv_desc));
+
+    Ok((buffer, ibv, srv))
+}
 
-        let index_buffer_size = mem::size_of_val(&mesh.indices[0]) * mesh.indices.len();
-        let index_size = mem::size_of_val(&mesh.indices[0]);
-        let indices = resource::create_buffer_with_data(
+#[cfg(windows)]
+impl Mesh {
+    /// The vertex and index buffers are filled when `Device::submit_uploads` is called
+    /// `lods` index the vertices of `mesh`, such as those of `MeshResource::lod_chain`
+    pub fn load(
+        device: &mut Device,
+        mesh: &MeshResource,
+        lods: &[Lod],
+        material: Material,
+        on_update: Box<OnUpdate>,
+    ) -> windows::core::Result<Self> {
+        let position = VertexBuffer::new(
             device,
-            D3D12_HEAP_TYPE_UPLOAD,
-            D3D12_RESOURCE_FLAG_NONE,
-            D3D12_RESOURCE_STATE_COMMON,
-            &mesh.indices,
-            "intermediate index buffer",
+            mesh.positions(),
+            &format!("{}::position_buffer", mesh.name()),
         )?;
 
-        let index_buffer = resource::create_buffer(
+        let normal = VertexBuffer::new(
             device,
-            index_buffer_size as u64,
-            D3D12_HEAP_TYPE_DEFAULT,
-            D3D12_RESOURCE_FLAG_NONE,
-            D3D12_RESOURCE_STATE_COMMON,
-            &format!("{}::_index_buffer", mesh.name),
+            mesh.normals(),
+            &format!("{}::normal_buffer", mesh.name()),
         )?;
 
-        unsafe { command_list.CopyResource(&index_buffer, &indices) };
-
-        let index_format = if index_size == mem::size_of::<u16>() {
-            DXGI_FORMAT_R16_UINT
-        } else {
-            DXGI_FORMAT_R32_UINT
-        };
-        let ibv = D3D12_INDEX_BUFFER_VIEW {
-            BufferLocation: unsafe { index_buffer.GetGPUVirtualAddress() },
-            Format: index_format,
-            SizeInBytes: index_buffer_size as u32,
+        let uv_sets = mesh
+            .uv_sets()
+            .iter()
+            .enumerate()
+            .map(|(i, uvs)| {
+                VertexBuffer::new(device, uvs, &format!("{}::uv_buffer[{i}]", mesh.name()))
+            })
+            .collect::<windows::core::Result<_>>()?;
+
+        let tangent = match mesh.tangents().is_empty() {
+            true => None,
+            false => Some(VertexBuffer::new(
+                device,
+                mesh.tangents(),
+                &format!("{}::tangent_buffer", mesh.name()),
+            )?),
         };
 
-        let index_srv_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
-            Format: DXGI_FORMAT_UNKNOWN,
-            ViewDimension: D3D12_SRV_DIMENSION_BUFFER,
-            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
-            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
-                Buffer: D3D12_BUFFER_SRV {
-                    FirstElement: 0,
-                    NumElements: mesh.indices.len() as u32,
-                    StructureByteStride: index_size as u32,
-                    Flags: D3D12_BUFFER_SRV_FLAG_NONE,
-                },
-            },
+        let lod_indices =
+            std::iter::once(mesh.indices()).chain(lods.iter().map(|lod| &lod.indices[..]));
+
+        // the LODs index the same vertices, so they fit in 16 bits whenever the mesh does
+        let name = format!("{}::_index_buffer", mesh.name());
+        let (index_buffer, ibv, index_srv) = match mesh.narrow_indices() {
+            Some(mut indices) => {
+                let lods = lod_indices.clone().skip(1).flatten();
+                indices.extend(lods.map(|&i| i as u16));
+                create_index_buffer(device, &indices, &name)?
+            }
+            None => {
+                let indices: Vec<u32> = lod_indices.clone().flatten().copied().collect();
+                create_index_buffer(device, &indices, &name)?
+            }
         };
-        let index_srv = device.create_srv(Some(&index_buffer), Some(&index_srv_desc));
 
-        // make sure vertex and index buffers are uploaded to the GPU memory
-        let command_queue = device.copy_queue_mut();
-        let fence_value = command_queue.execute_commands(ctx).unwrap();
-        command_queue.wait_fence(fence_value);
+        let index_size = match ibv.Format {
+            DXGI_FORMAT_R16_UINT => mem::size_of::<u16>(),
+            _ => mem::size_of::<u32>(),
+        };
+        let errors = std::iter::once(0.0).chain(lods.iter().map(|l| l.error));
+        let mut first_index = 0;
+        let lods = lod_indices
+            .zip(errors)
+            .map(|(indices, error)| {
+                let range = LodRange {
+                    ibv: D3D12_INDEX_BUFFER_VIEW {
+                        BufferLocation: ibv.BufferLocation + (first_index * index_size) as u64,
+                        Format: ibv.Format,
+                        SizeInBytes: (indices.len() * index_size) as u32,
+                    },
+                    first_index: first_index as u32,
+                    index_count: indices.len() as u32,
+                    error,
+                };
+                first_index += indices.len();
+                range
+            })
+            .collect();
 
         Ok(Mesh {
-            vertex_count: mesh.positions.len(),
+            vertex_count: mesh.positions().len(),
+            position,
             position_format: DXGI_FORMAT_R32G32B32_FLOAT,
-            position_buffer,
-            position_vbv,
-            position_srv,
 
-            normal_buffer,
-            normal_vbv,
-            normal_srv,
+            normal,
+
+            uv_sets,
+            tangent,
 
-            index_count: mesh.indices.len(),
             index_buffer,
-            ibv,
             index_srv,
+            lods,
+            lod: 0,
+            bounding_sphere: mesh.bounding_sphere(),
+
+            meshlets: MeshletBuffers::new(device, mesh)?,
 
             transform: mat4_to_row_marjor_float3x4(&Mat4::IDENTITY),
             transposed_inv_transform: mat4_to_row_marjor_float3x4(&Mat4::IDENTITY),
@@ -207,24 +341,45 @@ impl Mesh {
         })
     }
 
-    pub fn update(&mut self, time: f64) {
+    /// Releases the buffers and frees the views once the GPU has finished the current frame
+    /// The memory of the buffers is shared with other resources, so it is only reused after this
+    pub fn release(self, device: &mut Device) {
+        let vertex_buffers = [self.position, self.normal]
+            .into_iter()
+            .chain(self.uv_sets)
+            .chain(self.tangent);
+        for buffer in vertex_buffers {
+            buffer.release(device);
+        }
+
+        device.free_srv(self.index_srv);
+        device.release(self.index_buffer);
+
+        if let Some(meshlets) = self.meshlets {
+            meshlets.release(device);
+        }
+    }
+
+    /// Also selects the LOD for `camera`
+    pub fn update(&mut self, time: f64, camera: &Camera) {
         let transform = (self.on_update)(time);
         self.transform = mat4_to_row_marjor_float3x4(&transform);
+        self.lod = self.select_lod(&transform, camera);
 
         let transposed_inv_transform = transform.inverse().transpose();
         self.transposed_inv_transform = mat4_to_row_marjor_float3x4(&transposed_inv_transform);
     }
 
     pub fn vertex_buffer_views(&self) -> [D3D12_VERTEX_BUFFER_VIEW; 2] {
-        [self.position_vbv, self.normal_vbv]
+        [self.position.vbv, self.normal.vbv]
     }
 
     pub fn position_buffer_view(&self) -> &D3D12_VERTEX_BUFFER_VIEW {
-        &self.position_vbv
+        &self.position.vbv
     }
 
     pub fn position_srv(&self) -> &Srv {
-        &self.position_srv
+        &self.position.srv
     }
 
     pub fn vertex_count(&self) -> usize {
@@ -235,20 +390,56 @@ impl Mesh {
         self.position_format
     }
 
+    /// The indices of the selected LOD
     pub fn index_buffer_view(&self) -> &D3D12_INDEX_BUFFER_VIEW {
-        &self.ibv
+        &self.lods[self.lod].ibv
     }
 
     pub fn index_count(&self) -> usize {
-        self.index_count
+        self.lods[self.lod].index_count as usize
+    }
+
+    /// Where the selected LOD starts in `index_srv`
+    pub fn first_index(&self) -> u32 {
+        self.lods[self.lod].first_index
+    }
+
+    pub fn lod_count(&self) -> usize {
+        self.lods.len()
     }
 
+    /// 0 for the mesh itself, and larger for coarser LODs
+    pub fn lod(&self) -> usize {
+        self.lod
+    }
+
+    /// A raw buffer of the indices of every LOD in the format of `index_buffer_view`
     pub fn index_srv(&self) -> &Srv {
         &self.index_srv
     }
 
     pub fn normal_srv(&self) -> &Srv {
-        &self.normal_srv
+        &self.normal.srv
+    }
+
+    pub fn uv_set_count(&self) -> usize {
+        self.uv_sets.len()
+    }
+
+    pub fn uv_buffer_view(&self, set: usize) -> Option<&D3D12_VERTEX_BUFFER_VIEW> {
+        self.uv_sets.get(set).map(|uvs| &uvs.vbv)
+    }
+
+    pub fn uv_srv(&self, set: usize) -> Option<&Srv> {
+        self.uv_sets.get(set).map(|uvs| &uvs.srv)
+    }
+
+    pub fn tangent_buffer_view(&self) -> Option<&D3D12_VERTEX_BUFFER_VIEW> {
+        self.tangent.as_ref().map(|tangent| &tangent.vbv)
+    }
+
+    pub fn tangent_srv(&self) -> Option<&Srv> {
+        self.tangent.as_ref().map(|tangent| &tangent.srv)
     }
 
     pub fn transform(&self) -> &[f32; 12] {
@@ -262,80 +453,54 @@ impl Mesh {
     pub fn material(&self) -> &Material {
         &self.material
     }
-}
-
-pub struct MeshResource {
-    indices: Vec<u32>,
-    positions: Vec<Vec3>,
-    normals: Vec<Vec3>,
-    name: String,
-}
 
-impl MeshResource {
-    pub fn new(indices: &[u32], positions: &[Vec3], normals: &[Vec3], name: String) -> Self {
-        MeshResource {
-            indices: Vec::from(indices),
-            positions: Vec::from(positions),
-            normals: Vec::from(normals),
-            name,
-        }
+    pub fn meshlet_count(&self) -> usize {
+        self.meshlets.as_ref().map_or(0, |meshlets| meshlets.count)
     }
-}
 
-#[derive(Debug, Clone)]
-#[repr(C)]
-pub struct Material {
-    pub base_color: Vec3,
-    pub metallic: f32,
-    pub specular_reflectance: Vec3,
-    pub roughness: f32,
-    pub specular_tint: Vec3,
-    pub pad: u32,
-}
+    /// A structured buffer of `Meshlet`, None if there are no meshlets
+    pub fn meshlet_srv(&self) -> Option<&Srv> {
+        self.meshlets
+            .as_ref()
+            .map(|meshlets| &meshlets.meshlets.srv)
+    }
 
-pub mod obj {
-    use glam::Vec3;
+    /// A structured buffer of `MeshletBounds`
+    pub fn meshlet_bounds_srv(&self) -> Option<&Srv> {
+        self.meshlets.as_ref().map(|meshlets| &meshlets.bounds.srv)
+    }
 
-    use super::MeshResource;
+    /// A structured buffer of the vertex indices of the meshlets
+    pub fn meshlet_vertex_srv(&self) -> Option<&Srv> {
+        self.meshlets
+            .as_ref()
+            .map(|meshlets| &meshlets.vertices.srv)
+    }
 
-    pub fn load(path: &str) -> Result<Vec<super::MeshResource>, tobj::LoadError> {
-        let (models, _materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+    /// A structured buffer of the triangles of the meshlets, packed as in `Meshlets::triangles`
+    pub fn meshlet_triangle_srv(&self) -> Option<&Srv> {
+        self.meshlets
+            .as_ref()
+            .map(|meshlets| &meshlets.triangles.srv)
+    }
 
-        let resources = models
+    // The coarsest LOD whose error is not visible at the nearest point of the bounding sphere
+    fn select_lod(&self, transform: &Mat4, camera: &Camera) -> usize {
+        let (center, radius) = self.bounding_sphere;
+        let scale = [transform.x_axis, transform.y_axis, transform.z_axis]
             .iter()
-            .map(|model| {
-                let name = if model.name.is_empty() {
-                    path.to_string()
-                } else {
-                    format!("{path}:{}", model.name)
-                };
+            .map(|axis| axis.truncate().length())
+            .fold(0.0, f32::max);
 
-                let mesh = &model.mesh;
-                let mut positions = Vec::new();
-                for i in (0..mesh.positions.len()).step_by(3) {
-                    let p = Vec3::new(
-                        mesh.positions[i],
-                        mesh.positions[i + 1],
-                        mesh.positions[i + 2],
-                    );
-                    positions.push(p);
-                }
-
-                let mut normals = Vec::new();
-                for i in (0..mesh.normals.len()).step_by(3) {
-                    let n = Vec3::new(mesh.normals[i], mesh.normals[i + 1], mesh.normals[i + 2]);
-                    normals.push(n);
-                }
-
-                MeshResource {
-                    indices: model.mesh.indices.clone(),
-                    positions,
-                    normals,
-                    name,
-                }
-            })
-            .collect();
+        let depth = camera.depth(transform.transform_point3(center)) - radius * scale;
+        if depth <= 0.0 {
+            return 0;
+        }
 
-        Ok(resources)
+        // the errors grow with the LODs
+        self.lods
+            .iter()
+            .rposition(|lod| camera.projected_size(lod.error * scale, depth) <= MAX_LOD_PIXEL_ERROR)
+            .unwrap_or(0)
     }
 }
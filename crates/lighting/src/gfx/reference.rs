@@ -0,0 +1,394 @@
+// Headless CPU reference renderer
+// Mirrors crates/lighting/shaders/raytracing.hlsl so that shading changes can be checked without a
+// GPU
+
+mod bvh;
+
+use std::io::{self, Write};
+use std::{fs, path::Path, thread};
+
+use super::brdf::eval_brdf;
+use super::light::{eval_spot_light, LightParameters, SpotLight};
+use super::math::*;
+use super::mesh::Material;
+use super::scene::{self, Camera, SceneDesc, SceneError, SceneObject};
+use bvh::{Bvh, Triangle};
+
+// the color of rays that hit nothing, must match raytracing.hlsl
+const MISS_COLOR: Vec3 = Vec3::new(0.4, 0.6, 0.9);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+    pub t_min: f32,
+    pub t_max: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct HitPoint {
+    pub position: Vec3,
+    pub normal: Vec3,
+
+    pub material: Material,
+}
+
+struct Geometry {
+    indices: Vec<u32>,
+    normals: Vec<Vec3>,
+    // transposed inverse of the model transform, same as the odd entries of Scene::transform_buffer
+    normal_transform: Mat3,
+    material: Material,
+}
+
+pub struct ReferenceScene {
+    camera: Camera,
+    light: LightParameters,
+
+    geometries: Vec<Geometry>,
+    bvh: Bvh,
+}
+
+impl ReferenceScene {
+    pub fn build(objects: &[SceneObject], light: &SpotLight, camera: &Camera, time: f64) -> Self {
+        let mut triangles = Vec::new();
+        let mut geometries = Vec::with_capacity(objects.len());
+
+        for (geometry_index, object) in objects.iter().enumerate() {
+            let transform = object.transform_at(time);
+            let mesh = &object.resource;
+
+            let positions: Vec<_> = mesh
+                .positions()
+                .iter()
+                .map(|p| transform.transform_point3(*p))
+                .collect();
+
+            for (primitive_index, face) in mesh.indices().chunks_exact(3).enumerate() {
+                triangles.push(Triangle {
+                    vertices: [
+                        positions[face[0] as usize],
+                        positions[face[1] as usize],
+                        positions[face[2] as usize],
+                    ],
+                    geometry_index: geometry_index as u32,
+                    primitive_index: primitive_index as u32,
+                });
+            }
+
+            geometries.push(Geometry {
+                indices: mesh.indices().to_vec(),
+                normals: mesh.normals().to_vec(),
+                normal_transform: Mat3::from_mat4(transform.inverse().transpose()),
+                material: object.material.clone(),
+            });
+        }
+
+        Self {
+            camera: *camera,
+            light: light.create_parameters(),
+            geometries,
+            bvh: Bvh::build(triangles),
+        }
+    }
+}
+
+/// Linear HDR image, the CPU counterpart of Renderer::color_buffer
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<Vec4>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Vec4::ZERO; (width as usize) * (height as usize)],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Vec4 {
+        self.pixels[(y as usize) * (self.width as usize) + (x as usize)]
+    }
+
+    pub fn write_ppm(&self, path: &Path) -> io::Result<()> {
+        let mut data = Vec::with_capacity(3 * self.pixels.len() + 32);
+        write!(data, "P6\n{} {}\n255\n", self.width, self.height)?;
+
+        // same conversion as writing to the R8G8B8A8_UNORM frame buffer
+        for pixel in &self.pixels {
+            let rgb = pixel.truncate().clamp(Vec3::ZERO, Vec3::ONE) * 255.0 + 0.5;
+            data.extend(rgb.to_array().map(|c| c as u8));
+        }
+
+        fs::write(path, data)
+    }
+}
+
+pub fn render(scene: &ReferenceScene) -> Image {
+    let [width, height] = scene.camera.viewport_size();
+    let mut image = Image::new(width, height);
+
+    let thread_count = thread::available_parallelism().map_or(1, |n| n.get()) as u32;
+    let rows_per_thread = divide_and_round_up(height, thread_count);
+    let pixels_per_thread = (rows_per_thread * width) as usize;
+
+    thread::scope(|s| {
+        for (i, pixels) in image.pixels.chunks_mut(pixels_per_thread).enumerate() {
+            s.spawn(move || {
+                let offset = i * pixels_per_thread;
+                for (j, pixel) in pixels.iter_mut().enumerate() {
+                    let index = (offset + j) as u32;
+                    let id = UVec2::new(index % width, index / width);
+                    *pixel = shade(scene, id).extend(1.0);
+                }
+            });
+        }
+    });
+
+    image
+}
+
+/// Renders the same scene as `Scene::build` at the given time
+pub fn render_scene(
+    desc: &SceneDesc,
+    viewport_width: u32,
+    viewport_height: u32,
+    time: f64,
+) -> Result<Image, SceneError> {
+    let objects = scene::load_objects(desc)?;
+    let light = desc.light.spot_light();
+    let camera = desc.camera.camera(viewport_width, viewport_height);
+
+    let scene = ReferenceScene::build(&objects, &light, &camera, time);
+    Ok(render(&scene))
+}
+
+pub fn generate_primary_ray(camera: &Camera, id: UVec2) -> Ray {
+    let [width, height] = camera.viewport_size();
+    let uv = (id.as_vec2() + 0.5) / Vec2::new(width as f32, height as f32);
+    let dst = uv * Vec2::new(2.0, -2.0) + Vec2::new(-1.0, 1.0);
+    let ray_d = *camera.inv_view_proj() * Vec4::new(dst.x, dst.y, 1.0, 1.0);
+    let ray_o = *camera.inv_view_proj() * Vec4::new(0.0, 0.0, 0.0, 1.0);
+
+    let origin = ray_o.truncate() / ray_o.w;
+    Ray {
+        origin,
+        direction: (ray_d.truncate() / ray_d.w - origin).normalize(),
+        t_min: 1e-4,
+        t_max: 100.0,
+    }
+}
+
+pub fn trace_ray(scene: &ReferenceScene, ray: &Ray) -> Option<HitPoint> {
+    let hit = scene.bvh.closest_hit(ray)?;
+
+    let geometry = &scene.geometries[hit.geometry_index as usize];
+
+    let offset = 3 * hit.primitive_index as usize;
+    let indices = &geometry.indices[offset..offset + 3];
+
+    let position = ray.origin + hit.t * ray.direction;
+
+    let barycentrics = hit.barycentrics;
+    let weights = Vec3::new(
+        1.0 - barycentrics.x - barycentrics.y,
+        barycentrics.x,
+        barycentrics.y,
+    );
+
+    let vertex_normals = [
+        geometry.normals[indices[0] as usize],
+        geometry.normals[indices[1] as usize],
+        geometry.normals[indices[2] as usize],
+    ];
+    let local_normal = (vertex_normals[0] * weights.x
+        + vertex_normals[1] * weights.y
+        + vertex_normals[2] * weights.z)
+        .normalize();
+
+    // not normalized after the transform, as in raytracing.hlsl
+    let normal = geometry.normal_transform * local_normal;
+
+    Some(HitPoint {
+        position,
+        normal,
+        material: geometry.material.clone(),
+    })
+}
+
+pub fn trace_shadow_ray(scene: &ReferenceScene, ray: &Ray) -> bool {
+    scene.bvh.any_hit(ray)
+}
+
+fn shade(scene: &ReferenceScene, id: UVec2) -> Vec3 {
+    let ray = generate_primary_ray(&scene.camera, id);
+
+    let Some(hitpoint) = trace_ray(scene, &ray) else {
+        return MISS_COLOR;
+    };
+
+    let light = &scene.light;
+
+    let mut light_dir = light.position - hitpoint.position;
+    let distance_to_light = light_dir.length();
+    light_dir /= distance_to_light;
+
+    let camera_dir = (scene.camera.position() - hitpoint.position).normalize();
+
+    let incoming_radiance = eval_spot_light(light, hitpoint.position);
+    let brdf = eval_brdf(light_dir, camera_dir, hitpoint.normal, &hitpoint.material);
+    let mut contribution = incoming_radiance * brdf * hitpoint.normal.dot(light_dir);
+
+    if contribution.cmpgt(Vec3::ZERO).any() {
+        let shadow_ray = Ray {
+            origin: hitpoint.position,
+            direction: light_dir,
+            t_min: 1e-3,
+            t_max: distance_to_light,
+        };
+
+        if trace_shadow_ray(scene, &shadow_ray) {
+            contribution = Vec3::ZERO;
+        }
+    }
+
+    contribution
+}
+
+#[cfg(test)]
+mod tests {
+    use sandbox_core::light::SpotLight;
+    use sandbox_core::scene::{Animation, Transform};
+
+    use super::super::mesh::{primitives, MeshResource};
+    use super::*;
+
+    fn object(resource: MeshResource, translation: Vec3) -> SceneObject {
+        SceneObject {
+            resource,
+            material: Material {
+                base_color: Vec3::splat(0.5),
+                metallic: 0.0,
+                specular_reflectance: Vec3::ZERO,
+                roughness: 1.0,
+                specular_tint: Vec3::ZERO,
+                pad: 0,
+            },
+            transform: Transform {
+                translation,
+                ..Default::default()
+            },
+            animation: Animation::Static,
+            file_transform: Mat4::IDENTITY,
+            lods: Vec::new(),
+        }
+    }
+
+    // A plane of size 2 seen from 4 above, whose middle pixel is the origin and whose corner
+    // pixels miss it
+    fn render_plane(light: &SpotLight, occluders: Vec<SceneObject>) -> Image {
+        let mut objects = vec![object(primitives::plane(2.0, 1), Vec3::ZERO)];
+        objects.extend(occluders);
+
+        let mut camera = Camera::new(3, 3);
+        camera.look_at(Vec3::Y * 4.0, Vec3::ZERO, Vec3::Z, 90f32.to_radians());
+
+        render(&ReferenceScene::build(&objects, light, &camera, 0.0))
+    }
+
+    #[test]
+    fn primary_rays() {
+        let mut camera = Camera::new(3, 3);
+        camera.look_at(Vec3::Y * 4.0, Vec3::ZERO, Vec3::Z, 90f32.to_radians());
+
+        // the rays start on the near plane, 0.1 in front of the camera
+        let ray = generate_primary_ray(&camera, UVec2::new(1, 1));
+        assert!(
+            ray.origin.abs_diff_eq(Vec3::Y * 3.9, 1e-4),
+            "{}",
+            ray.origin
+        );
+        assert!(
+            ray.direction.abs_diff_eq(Vec3::NEG_Y, 1e-5),
+            "{}",
+            ray.direction
+        );
+
+        // the top of the image is +Z, the up direction, and the right is +X in the left-handed
+        // view looking down
+        let ray = generate_primary_ray(&camera, UVec2::new(1, 0));
+        assert!(ray.direction.z > 0.0 && ray.direction.x.abs() < 1e-5);
+        let ray = generate_primary_ray(&camera, UVec2::new(2, 1));
+        assert!(ray.direction.x > 0.0 && ray.direction.z.abs() < 1e-5);
+    }
+
+    #[test]
+    fn lit_plane() {
+        // straight above the middle at a distance of 2
+        let light = SpotLight::new(Vec3::Y * 2.0, 4.0, Vec3::NEG_Y, 90f32.to_radians());
+        let image = render_plane(&light, Vec::new());
+
+        // intensity / distance^2 * albedo / pi * cos
+        let expected = 4.0 / 4.0 * 0.5 / std::f32::consts::PI;
+        let middle = image.pixel(1, 1);
+        assert!(
+            middle.abs_diff_eq(Vec3::splat(expected).extend(1.0), 1e-4),
+            "{middle}"
+        );
+
+        for (x, y) in [(0, 0), (2, 0), (0, 2), (2, 2)] {
+            assert_eq!(image.pixel(x, y), MISS_COLOR.extend(1.0));
+        }
+
+        // outside the cone of the light
+        let light = SpotLight::new(Vec3::Y * 2.0, 4.0, Vec3::X, 90f32.to_radians());
+        let image = render_plane(&light, Vec::new());
+        assert_eq!(image.pixel(1, 1), Vec4::W);
+    }
+
+    #[test]
+    fn shadows() {
+        // a light at a grazing angle, blocked by a box beside the middle of the plane
+        let position = Vec3::new(2.0, 1.0, 0.0);
+        let light = SpotLight::new(position, 4.0, -position.normalize(), 90f32.to_radians());
+
+        let lit = render_plane(&light, Vec::new());
+        assert!(lit.pixel(1, 1).x > 0.0);
+
+        let occluder = object(
+            primitives::cuboid(Vec3::splat(0.2)),
+            Vec3::new(1.0, 0.5, 0.0),
+        );
+        let shadowed = render_plane(&light, vec![occluder]);
+        assert_eq!(shadowed.pixel(1, 1), Vec4::W);
+    }
+
+    #[test]
+    fn ppm() {
+        let mut image = Image::new(2, 1);
+        image.pixels = vec![
+            Vec4::new(1.0, 0.5, 0.0, 1.0),
+            Vec4::new(2.0, -1.0, 0.25, 1.0),
+        ];
+        let path =
+            std::env::temp_dir().join(format!("sandbox-reference-{}.ppm", std::process::id()));
+        image.write_ppm(&path).unwrap();
+        let data = fs::read(&path);
+        fs::remove_file(&path).unwrap();
+
+        let mut expected = b"P6\n2 1\n255\n".to_vec();
+        expected.extend([255, 128, 0, 255, 0, 64]);
+        assert_eq!(data.unwrap(), expected);
+    }
+}
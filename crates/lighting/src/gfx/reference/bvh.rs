@@ -0,0 +1,334 @@
+use super::Ray;
+use crate::gfx::math::*;
+
+const MAX_LEAF_SIZE: usize = 4;
+
+pub struct Triangle {
+    pub vertices: [Vec3; 3],
+    pub geometry_index: u32,
+    pub primitive_index: u32,
+}
+
+impl Triangle {
+    fn centroid(&self) -> Vec3 {
+        (self.vertices[0] + self.vertices[1] + self.vertices[2]) / 3.0
+    }
+
+    // Moller-Trumbore ray-triangle intersection
+    // The barycentrics follow the convention of RayQuery::CommittedTriangleBarycentrics
+    fn intersect(&self, ray: &Ray) -> Option<(f32, Vec2)> {
+        let [v0, v1, v2] = self.vertices;
+        let e1 = v1 - v0;
+        let e2 = v2 - v0;
+
+        let p = ray.direction.cross(e2);
+        let det = e1.dot(p);
+        if det.abs() < f32::EPSILON * f32::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let s = ray.origin - v0;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(e1);
+        let v = ray.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = e2.dot(q) * inv_det;
+        if t < ray.t_min || t > ray.t_max {
+            return None;
+        }
+
+        Some((t, Vec2::new(u, v)))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    pub t: f32,
+    pub barycentrics: Vec2,
+    pub geometry_index: u32,
+    pub primitive_index: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Aabb {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Aabb {
+    const EMPTY: Aabb = Aabb {
+        min: Vec3::INFINITY,
+        max: Vec3::NEG_INFINITY,
+    };
+
+    fn grow(&mut self, p: Vec3) {
+        self.min = self.min.min(p);
+        self.max = self.max.max(p);
+    }
+
+    fn intersect(&self, ray: &Ray, inv_dir: Vec3, t_max: f32) -> bool {
+        let t0 = (self.min - ray.origin) * inv_dir;
+        let t1 = (self.max - ray.origin) * inv_dir;
+        let t_near = t0.min(t1).max_element().max(ray.t_min);
+        let t_far = t0.max(t1).min_element().min(t_max);
+        t_near <= t_far
+    }
+}
+
+struct Node {
+    bounds: Aabb,
+    // index of the first triangle for leaves, or of the left child for interior nodes
+    first: u32,
+    // zero for interior nodes
+    count: u32,
+}
+
+/// Bounding volume hierarchy over world-space triangles, standing in for the TLAS/BLAS on the CPU
+pub struct Bvh {
+    nodes: Vec<Node>,
+    triangles: Vec<Triangle>,
+}
+
+impl Bvh {
+    pub fn build(mut triangles: Vec<Triangle>) -> Self {
+        let mut nodes = Vec::with_capacity(2 * triangles.len() / MAX_LEAF_SIZE + 1);
+        nodes.push(Node {
+            bounds: Aabb::EMPTY,
+            first: 0,
+            count: triangles.len() as u32,
+        });
+
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node: &mut Node = &mut nodes[node_index];
+            let first = node.first as usize;
+            let count = node.count as usize;
+            let range = first..first + count;
+
+            let mut centroid_bounds = Aabb::EMPTY;
+            for triangle in &triangles[range.clone()] {
+                for v in triangle.vertices {
+                    node.bounds.grow(v);
+                }
+                centroid_bounds.grow(triangle.centroid());
+            }
+
+            if count <= MAX_LEAF_SIZE {
+                continue;
+            }
+
+            // median split along the longest axis of the centroids
+            let extent = centroid_bounds.max - centroid_bounds.min;
+            let axis = if extent.x >= extent.y && extent.x >= extent.z {
+                0
+            } else if extent.y >= extent.z {
+                1
+            } else {
+                2
+            };
+            let half = count / 2;
+            triangles[range].select_nth_unstable_by(half, |a, b| {
+                a.centroid()[axis].total_cmp(&b.centroid()[axis])
+            });
+
+            let left = nodes.len();
+            nodes[node_index].first = left as u32;
+            nodes[node_index].count = 0;
+
+            nodes.push(Node {
+                bounds: Aabb::EMPTY,
+                first: first as u32,
+                count: half as u32,
+            });
+            nodes.push(Node {
+                bounds: Aabb::EMPTY,
+                first: (first + half) as u32,
+                count: (count - half) as u32,
+            });
+            stack.push(left);
+            stack.push(left + 1);
+        }
+
+        Self { nodes, triangles }
+    }
+
+    pub fn closest_hit(&self, ray: &Ray) -> Option<Hit> {
+        let mut closest: Option<Hit> = None;
+        self.traverse(ray, |triangle, t, barycentrics| {
+            closest = Some(Hit {
+                t,
+                barycentrics,
+                geometry_index: triangle.geometry_index,
+                primitive_index: triangle.primitive_index,
+            });
+            false
+        });
+        closest
+    }
+
+    pub fn any_hit(&self, ray: &Ray) -> bool {
+        let mut hit = false;
+        self.traverse(ray, |_, _, _| {
+            hit = true;
+            true
+        });
+        hit
+    }
+
+    // `on_hit` returns true to end the search
+    fn traverse(&self, ray: &Ray, mut on_hit: impl FnMut(&Triangle, f32, Vec2) -> bool) {
+        if self.triangles.is_empty() {
+            return;
+        }
+
+        let inv_dir = ray.direction.recip();
+        let mut t_max = ray.t_max;
+
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node.bounds.intersect(ray, inv_dir, t_max) {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack.push(node.first as usize);
+                stack.push(node.first as usize + 1);
+                continue;
+            }
+
+            let first = node.first as usize;
+            for triangle in &self.triangles[first..first + node.count as usize] {
+                let ray = Ray { t_max, ..*ray };
+                if let Some((t, barycentrics)) = triangle.intersect(&ray) {
+                    t_max = t;
+                    if on_hit(triangle, t, barycentrics) {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A linear congruential generator, so that the triangles and rays are the same every run
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn vec3(&mut self, min: f32, max: f32) -> Vec3 {
+            Vec3::new(self.next(), self.next(), self.next()) * (max - min) + min
+        }
+    }
+
+    fn random_triangles(random: &mut Random, count: u32) -> Vec<Triangle> {
+        (0..count)
+            .map(|i| {
+                let center = random.vec3(-5.0, 5.0);
+                Triangle {
+                    vertices: [0, 1, 2].map(|_| center + random.vec3(-0.5, 0.5)),
+                    geometry_index: i % 3,
+                    primitive_index: i,
+                }
+            })
+            .collect()
+    }
+
+    fn brute_force(triangles: &[Triangle], ray: &Ray) -> Option<(f32, u32)> {
+        triangles
+            .iter()
+            .filter_map(|t| {
+                t.intersect(ray)
+                    .map(|(distance, _)| (distance, t.primitive_index))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    #[test]
+    fn hits_match_brute_force() {
+        let mut random = Random(7);
+        let triangles = random_triangles(&mut random, 500);
+        let expected_triangles = random_triangles(&mut Random(7), 500);
+        let bvh = Bvh::build(triangles);
+
+        let mut hit_count = 0;
+        for _ in 0..2000 {
+            let ray = Ray {
+                origin: random.vec3(-8.0, 8.0),
+                direction: random.vec3(-1.0, 1.0).normalize(),
+                t_min: 1e-4,
+                t_max: 4.0 + 10.0 * random.next(),
+            };
+            let expected = brute_force(&expected_triangles, &ray);
+
+            let hit = bvh.closest_hit(&ray);
+            assert_eq!(hit.map(|h| (h.t, h.primitive_index)), expected);
+            assert_eq!(bvh.any_hit(&ray), expected.is_some());
+
+            if let Some(hit) = hit {
+                hit_count += 1;
+                let triangle = &expected_triangles[hit.primitive_index as usize];
+                assert_eq!(hit.geometry_index, triangle.geometry_index);
+                let (_, barycentrics) = triangle.intersect(&ray).unwrap();
+                assert_eq!(hit.barycentrics, barycentrics);
+            }
+        }
+        // both outcomes are covered
+        assert!(hit_count > 100 && hit_count < 1900, "{hit_count}");
+    }
+
+    #[test]
+    fn barycentrics() {
+        let triangle = Triangle {
+            vertices: [Vec3::ZERO, Vec3::X, Vec3::Y],
+            geometry_index: 0,
+            primitive_index: 0,
+        };
+        let ray = Ray {
+            origin: Vec3::new(0.25, 0.5, -1.0),
+            direction: Vec3::Z,
+            t_min: 0.0,
+            t_max: 10.0,
+        };
+        let (t, barycentrics) = triangle.intersect(&ray).unwrap();
+        assert_eq!(t, 1.0);
+        assert_eq!(barycentrics, Vec2::new(0.25, 0.5));
+
+        // outside the interval of the ray
+        assert!(triangle.intersect(&Ray { t_max: 0.5, ..ray }).is_none());
+        assert!(triangle.intersect(&Ray { t_min: 1.5, ..ray }).is_none());
+    }
+
+    #[test]
+    fn empty() {
+        let bvh = Bvh::build(Vec::new());
+        let ray = Ray {
+            origin: Vec3::ZERO,
+            direction: Vec3::X,
+            t_min: 0.0,
+            t_max: 10.0,
+        };
+        assert!(bvh.closest_hit(&ray).is_none());
+        assert!(!bvh.any_hit(&ray));
+    }
+}
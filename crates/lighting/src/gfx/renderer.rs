@@ -4,15 +4,16 @@ use std::path::PathBuf;
 
 use super::{
     d3d12::{
-        barrier,
+        backend::{d3d12::D3D12Backend, Backend},
         device::*,
         pix::*,
-        pso, raytracing,
+        pso,
         resource::*,
         shader::*,
         util::*,
         view::{self, Dsv, Srv, Uav},
     },
+    frame::*,
     light::LightParameters,
     mesh::Material,
     scene::Scene,
@@ -20,14 +21,10 @@ use super::{
 };
 
 use super::math::*;
+use crate::Config;
 
-use windows::Win32::Foundation::{FALSE, HWND};
-use windows::Win32::Graphics::{Direct3D::*, Direct3D12::*, Dxgi::Common::*};
-
-pub enum RenderingMode {
-    Raytracing,
-    Rasterization,
-}
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Graphics::{Direct3D12::*, Dxgi::Common::*};
 
 pub struct Renderer {
     device: Device,
@@ -36,7 +33,6 @@ pub struct Renderer {
     color_srv: Srv,
     color_uav: Uav,
 
-    #[allow(dead_code)]
     depth_buffer: ID3D12Resource,
     depth_dsv: Dsv,
 
@@ -61,8 +57,11 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub fn new(hwnd: HWND, viewport_width: u32, viewport_height: u32) -> Self {
-        let mut device = Device::build(hwnd, viewport_width, viewport_height).unwrap();
+    pub fn new(hwnd: HWND, config: &Config) -> Self {
+        let viewport_width = config.client_width();
+        let viewport_height = config.client_height();
+
+        let mut device = Device::build(hwnd, config).unwrap();
 
         let color_buffer_format = DXGI_FORMAT_R32G32B32A32_FLOAT;
         let color_buffer = create_texture2d(
@@ -186,102 +185,87 @@ impl Renderer {
 
     pub fn render(&mut self, scene: &mut Scene) -> windows::core::Result<()> {
         let ctx = self.device.request_gfx_command_ctx()?;
-        let cmd_list = ctx.command_list();
 
-        let pix = self.pix.as_ref();
-
-        let rect = windows::Win32::Foundation::RECT {
-            left: 0,
-            top: 0,
-            right: self.viewport_width.try_into().unwrap(),
-            bottom: self.viewport_height.try_into().unwrap(),
-        };
+        let mut backend = D3D12Backend::new(&mut self.device, self.pix.as_ref());
 
-        let viewport = D3D12_VIEWPORT {
-            TopLeftX: 0.0,
-            TopLeftY: 0.0,
-            Width: self.viewport_width as f32,
-            Height: self.viewport_height as f32,
-            MinDepth: D3D12_MIN_DEPTH,
-            MaxDepth: D3D12_MAX_DEPTH,
-        };
-
-        unsafe { cmd_list.SetDescriptorHeaps(&[Some(self.device.view_heap().clone())]) };
-
-        {
-            let color = pix_color(0, 255, 0);
-            let _event = pix.map(|p| p.begin_event(cmd_list, color, "Update scene"));
-            scene.update_buffers(&mut self.device, cmd_list)?;
-        }
+        record_updates(&mut backend, &ctx, |backend, ctx| {
+            scene.update_buffers(backend.device_mut(), ctx.command_list())
+        })?;
 
-        let back_buffer = self.device.back_buffer();
-
-        {
-            let color = pix_color(0, 255, 0);
-            let _event = pix.map(|p| p.begin_event(cmd_list, color, "Render"));
-
-            let barriers = [barrier::transition(
-                back_buffer,
-                D3D12_RESOURCE_STATE_PRESENT,
-                D3D12_RESOURCE_STATE_RENDER_TARGET,
-            )];
-            unsafe { cmd_list.ResourceBarrier(&barriers) };
-
-            self.clear_buffers(cmd_list, pix);
-
-            match &self.mode {
-                RenderingMode::Rasterization => {
-                    self.shadow_map_pass
-                        .draw(cmd_list, scene, &self.shadow_map, pix);
-
-                    let barriers = [barrier::transition(
-                        self.shadow_map.texture(),
-                        D3D12_RESOURCE_STATE_DEPTH_WRITE,
-                        D3D12_RESOURCE_STATE_ALL_SHADER_RESOURCE,
-                    )];
-
-                    unsafe {
-                        cmd_list.ResourceBarrier(&barriers);
-
-                        cmd_list.RSSetViewports(&[viewport]);
-                        cmd_list.RSSetScissorRects(&[rect]);
-                        cmd_list.OMSetRenderTargets(
-                            1,
-                            Some(&self.device.back_buffer_rtv().cpu_handle()),
-                            FALSE,
-                            Some(&self.depth_dsv.cpu_handle()),
-                        );
-                    }
-
-                    self.draw_mesh(cmd_list, scene, pix);
-
-                    let barriers = [barrier::transition(
-                        self.shadow_map.texture(),
-                        D3D12_RESOURCE_STATE_ALL_SHADER_RESOURCE,
-                        D3D12_RESOURCE_STATE_DEPTH_WRITE,
-                    )];
-
-                    unsafe {
-                        cmd_list.ResourceBarrier(&barriers);
-                    }
-                }
-                RenderingMode::Raytracing => {
-                    self.raytrace(cmd_list, scene, pix);
+        let device = backend.device();
+        let passes = match &self.mode {
+            RenderingMode::Rasterization => FramePasses::Rasterization {
+                shadow_map: ShadowMapTarget {
+                    texture: self.shadow_map.texture(),
+                    dsv: self.shadow_map.dsv(),
+                    width: self.shadow_map.width(),
+                    height: self.shadow_map.height(),
+                },
+                shadow_map_pass: self.shadow_map_pass.graphics_pass(
+                    device,
+                    scene,
+                    &self.shadow_map,
+                ),
+                draw_mesh_pass: GraphicsPass {
+                    pipeline: &self.draw_mesh_pso,
+                    root_signature: &self.draw_mesh_root_signature,
+                    constants: Vec::new(),
+                    draws: draw_mesh_items(device, scene, &self.shadow_map),
+                },
+            },
+            RenderingMode::Raytracing => {
+                let resources = RaytracingResourceHandles {
+                    light: scene.light().create_parameters(),
+                    camera: device.view_handle(scene.camera_cbv().slot()),
+                    output: device.view_handle(self.color_uav.slot()),
+                    raytracing_scene: device
+                        .view_handle(scene.raytracing_scene().srv().unwrap().slot()),
+                    transform_buffer: device.view_handle(scene.transform_srv().slot()),
+                    material_buffer: device.view_handle(scene.material_srv().slot()),
+                    mesh_data_buffer: device
+                        .view_handle(scene.raytracing_scene().mesh_data_srv().unwrap().slot()),
+                    pad: Default::default(),
+                };
+
+                let copy_resources = CopyResourceHandles {
+                    camera: device.view_handle(scene.camera_cbv().slot()),
+                    src_texture: device.view_handle(self.color_srv.slot()),
+                };
+
+                FramePasses::Raytracing {
+                    raytracing_pass: ComputePass {
+                        pipeline: &self.raytracing_pso,
+                        root_signature: &self.raytracing_root_signature,
+                        constants: resources.as_slice().to_vec(),
+                    },
+                    color_buffer: &self.color_buffer,
+                    copy_pass: GraphicsPass {
+                        pipeline: &self.copy_pso,
+                        root_signature: &self.copy_root_signature,
+                        constants: Vec::new(),
+                        // a fullscreen triangle without vertex buffers
+                        draws: vec![DrawItem {
+                            vertex_buffers: Vec::new(),
+                            index_buffer: None,
+                            count: 3,
+                            constants: copy_resources.as_slice().to_vec(),
+                        }],
+                    },
                 }
             }
-        }
-
-        let barriers = [barrier::transition(
-            back_buffer,
-            D3D12_RESOURCE_STATE_RENDER_TARGET,
-            D3D12_RESOURCE_STATE_PRESENT,
-        )];
+        };
 
-        unsafe { cmd_list.ResourceBarrier(&barriers) }
+        let frame = FrameDesc {
+            viewport_width: self.viewport_width,
+            viewport_height: self.viewport_height,
+            depth_buffer: &self.depth_buffer,
+            depth_dsv: &self.depth_dsv,
+            passes,
+        };
 
-        self.device.present_frame(ctx)?;
+        record_frame(&mut backend, &ctx, &frame);
 
-        Ok(())
+        backend.present(ctx)
     }
 
     pub fn device(&self) -> &Device {
@@ -304,183 +288,48 @@ impl Renderer {
             }
         };
     }
+}
 
-    fn clear_buffers(&self, cmd_list: &ID3D12GraphicsCommandList7, pix: Option<&Pix>) {
-        const CLEAR_COLOR: [f32; 4] = [0.4, 0.6, 0.9, 1.0];
-
-        let color = pix_color(0, 255, 0);
-        let _event = pix.map(|p| p.begin_event(cmd_list, color, "Clear buffers"));
-
-        unsafe {
-            cmd_list.ClearRenderTargetView(
-                self.device.back_buffer_rtv().cpu_handle(),
-                &CLEAR_COLOR,
-                None,
-            )
-        };
-
-        let rects = [];
-        unsafe {
-            cmd_list.ClearDepthStencilView(
-                self.depth_dsv.cpu_handle(),
-                D3D12_CLEAR_FLAG_DEPTH,
-                1.0,
-                0,
-                &rects,
-            )
-        };
-    }
-
-    fn draw_mesh(&self, cmd_list: &ID3D12GraphicsCommandList7, scene: &Scene, pix: Option<&Pix>) {
-        let color = pix_color(0, 255, 0);
-        let _event = pix.map(|p| p.begin_event(cmd_list, color, "Draw mesh"));
-
-        let coords_remap = Mat4::from_scale_rotation_translation(
-            Vec3::new(0.5, -0.5, 1.0),
-            Quat::from_rotation_x(0.0),
-            Vec3::new(0.5, 0.5, 0.0),
-        );
-
-        let aspect_ratio = (self.shadow_map.width() as f32) / (self.shadow_map.height() as f32);
-        let light_transform = coords_remap * scene.light().view_projection(aspect_ratio);
-
-        for (i, mesh) in scene.meshes().iter().enumerate() {
+fn draw_mesh_items(device: &Device, scene: &Scene, shadow_map: &ShadowMap) -> Vec<DrawItem> {
+    let coords_remap = Mat4::from_scale_rotation_translation(
+        Vec3::new(0.5, -0.5, 1.0),
+        Quat::from_rotation_x(0.0),
+        Vec3::new(0.5, 0.5, 0.0),
+    );
+
+    let aspect_ratio = (shadow_map.width() as f32) / (shadow_map.height() as f32);
+    let light_transform = coords_remap * scene.light().view_projection(aspect_ratio);
+
+    scene
+        .meshes()
+        .iter()
+        .enumerate()
+        .map(|(i, mesh)| {
             let resources = DrawMeshResourceHandles {
-                camera: scene.camera_cbv().handle(),
-                transform: scene.transform_srv().handle(),
+                camera: device.view_handle(scene.camera_cbv().slot()),
+                transform: device.view_handle(scene.transform_srv().slot()),
                 mesh_id: i as u32,
-                shadow_map_id: self.shadow_map.srv().handle(),
+                shadow_map_id: device.view_handle(shadow_map.srv().slot()),
                 light: scene.light().create_parameters(),
                 light_transform,
-                shadow_offset: 1.0 / (self.shadow_map.width() as f32),
+                shadow_offset: 1.0 / (shadow_map.width() as f32),
                 shadow_bias: 0.0001,
                 material: mesh.material().clone(),
                 pad: Default::default(),
             };
 
-            unsafe {
-                cmd_list.SetPipelineState(&self.draw_mesh_pso);
-                cmd_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
-
-                let vbvs = mesh.vertex_buffer_views();
-                cmd_list.IASetVertexBuffers(0, Some(&vbvs));
-
-                cmd_list.IASetIndexBuffer(Some(mesh.index_buffer_view()));
-
-                cmd_list.SetGraphicsRootSignature(&self.draw_mesh_root_signature);
-
-                cmd_list.SetGraphicsRoot32BitConstants(
-                    0,
-                    DrawMeshResourceHandles::COUNT,
-                    resources.as_ptr(),
-                    0,
-                );
-
-                cmd_list.DrawIndexedInstanced(mesh.index_count() as u32, 1, 0, 0, 0);
+            DrawItem {
+                vertex_buffers: mesh
+                    .vertex_buffer_views()
+                    .iter()
+                    .map(|v| v.into())
+                    .collect(),
+                index_buffer: Some(mesh.index_buffer_view().into()),
+                count: mesh.index_count() as u32,
+                constants: resources.as_slice().to_vec(),
             }
-        }
-    }
-
-    fn raytrace(&self, cmd_list: &ID3D12GraphicsCommandList7, scene: &Scene, pix: Option<&Pix>) {
-        let color = pix_color(0, 255, 0);
-        let _event = pix.map(|p| p.begin_event(cmd_list, color, "Raytrace"));
-
-        unsafe {
-            cmd_list.SetPipelineState(&self.raytracing_pso);
-
-            cmd_list.SetComputeRootSignature(&self.raytracing_root_signature);
-
-            let resources = RaytracingResourceHandles {
-                mesh_data: scene.raytracing_scene().mesh_data().clone(),
-                light: scene.light().create_parameters(),
-                camera: scene.camera_cbv().handle(),
-                output: self.color_uav.handle(),
-                raytracing_scene: scene.raytracing_scene().srv().unwrap().handle(),
-                transform_buffer: scene.transform_srv().handle(),
-                material_buffer: scene.material_srv().handle(),
-                pad: Default::default(),
-            };
-            cmd_list.SetComputeRoot32BitConstants(
-                0,
-                RaytracingResourceHandles::COUNT,
-                resources.as_ptr(),
-                0,
-            );
-
-            const NUM_THREAD_X: u32 = 8;
-            const NUM_THREAD_Y: u32 = 8;
-            let x = divide_and_round_up(self.viewport_width, NUM_THREAD_X);
-            let y = divide_and_round_up(self.viewport_height, NUM_THREAD_Y);
-            cmd_list.Dispatch(x, y, 1);
-        }
-
-        let barriers = [
-            barrier::uav(&self.color_buffer),
-            barrier::transition(
-                &self.color_buffer,
-                D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
-                D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
-            ),
-        ];
-
-        let rect = windows::Win32::Foundation::RECT {
-            left: 0,
-            top: 0,
-            right: self.viewport_width.try_into().unwrap(),
-            bottom: self.viewport_height.try_into().unwrap(),
-        };
-
-        let viewport = D3D12_VIEWPORT {
-            TopLeftX: 0.0,
-            TopLeftY: 0.0,
-            Width: self.viewport_width as f32,
-            Height: self.viewport_height as f32,
-            MinDepth: D3D12_MIN_DEPTH,
-            MaxDepth: D3D12_MAX_DEPTH,
-        };
-
-        unsafe {
-            cmd_list.ResourceBarrier(&barriers);
-
-            cmd_list.RSSetViewports(&[viewport]);
-            cmd_list.RSSetScissorRects(&[rect]);
-            cmd_list.OMSetRenderTargets(
-                1,
-                Some(&self.device.back_buffer_rtv().cpu_handle()),
-                FALSE,
-                Some(&self.depth_dsv.cpu_handle()),
-            );
-
-            // copy the color buffer to the frame buffer
-            cmd_list.SetPipelineState(&self.copy_pso);
-            cmd_list.SetGraphicsRootSignature(&self.copy_root_signature);
-
-            let resources = CopyResourceHandles {
-                camera: scene.camera_cbv().handle(),
-                src_texture: self.color_srv.handle(),
-            };
-            cmd_list.SetGraphicsRoot32BitConstants(
-                0,
-                CopyResourceHandles::COUNT,
-                resources.as_ptr(),
-                0,
-            );
-
-            cmd_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
-
-            cmd_list.IASetVertexBuffers(0, None);
-
-            cmd_list.DrawInstanced(3, 1, 0, 0);
-        }
-
-        let barriers = [barrier::transition(
-            &self.color_buffer,
-            D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
-            D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
-        )];
-
-        unsafe { cmd_list.ResourceBarrier(&barriers) };
-    }
+        })
+        .collect()
 }
 
 fn create_depth_buffer(
@@ -750,8 +599,6 @@ fn create_draw_mesh_pso(
 
 #[repr(C)]
 struct RaytracingResourceHandles {
-    mesh_data: [raytracing::MeshData; raytracing::MAX_MESH_DATA_COUNT],
-
     light: LightParameters,
 
     camera: u32,
@@ -760,7 +607,8 @@ struct RaytracingResourceHandles {
     transform_buffer: u32,
 
     material_buffer: u32,
-    pad: [u32; 3],
+    mesh_data_buffer: u32,
+    pad: [u32; 2],
 }
 view::impl_resource_handles!(RaytracingResourceHandles);
 
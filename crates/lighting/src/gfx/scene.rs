@@ -1,16 +1,34 @@
+#[cfg(windows)]
 use std::mem;
-use windows::Win32::Graphics::{Direct3D12::*, Dxgi::Common::*};
-
-use super::d3d12::{device::*, raytracing::*, resource, view::*};
+use std::path::Path;
+#[cfg(windows)]
+use windows::Win32::{
+    Foundation::E_FAIL,
+    Graphics::{Direct3D12::*, Dxgi::Common::*},
+};
+
+#[cfg(windows)]
+use super::d3d12::{device::*, raytracing::*, resource, ring::CONSTANT_BUFFER_ALIGNMENT, view::*};
+#[cfg(windows)]
 use super::light::SpotLight;
-use super::mesh::{Material, Mesh, MeshResource};
+#[cfg(windows)]
+use super::mesh::Mesh;
+#[cfg(windows)]
+use sandbox_core::align;
+
+use super::mesh::{cache::MeshCache, ImportedMesh, Lod, Material, MeshResource, ValidationPolicy};
 use super::{math::*, mesh};
+use sandbox_core::scene::{Animation, MeshDesc, Transform};
+
+pub use sandbox_core::camera::Camera;
+pub use sandbox_core::scene::{SceneDesc, SceneError};
 
+#[cfg(windows)]
 pub struct Scene {
     timer: std::time::Instant,
 
     camera: Camera,
-    camera_buffer: ID3D12Resource,
+    // points to the upload ring, so that it is recreated every frame
     camera_cbv: Cbv,
 
     meshes: Vec<Mesh>,
@@ -19,7 +37,7 @@ pub struct Scene {
 
     raytracing_scene: RaytracingScene,
 
-    transform_buffer: ID3D12Resource,
+    // points to the upload ring, so that it is recreated every frame
     transform_srv: Srv,
 
     #[allow(unused)]
@@ -27,72 +45,45 @@ pub struct Scene {
     material_srv: Srv,
 }
 
+#[cfg(windows)]
 impl Scene {
     pub fn build(
         device: &mut Device,
+        desc: &SceneDesc,
         viewport_width: u32,
         viewport_height: u32,
     ) -> windows::core::Result<Self> {
-        let mesh_resources = mesh::obj::load("assets/bunny.obj").expect("Cannot find the obj file");
-        let mesh_material = Material {
-            base_color: Vec3::new(1.0, 0.97, 0.73),
-            metallic: 0.75,
-            specular_reflectance: Vec3::new(0.95, 0.73, 0.37),
-            roughness: 0.5,
-            specular_tint: Vec3::new(1.0, 0.97, 0.73),
-            pad: Default::default(),
-        };
-        let meshes: windows::core::Result<Vec<Mesh>> = mesh_resources
+        let objects =
+            load_objects(desc).map_err(|e| windows::core::Error::new(E_FAIL, e.to_string()))?;
+        let meshes: windows::core::Result<Vec<Mesh>> = objects
             .iter()
-            .map(|mesh| Mesh::load(device, mesh, mesh_material.clone(), Box::new(rotate)))
+            .map(|object| {
+                let (transform, animation) = (object.transform, object.animation);
+                let file_transform = object.file_transform;
+                Mesh::load(
+                    device,
+                    &object.resource,
+                    &object.lods,
+                    object.material.clone(),
+                    Box::new(move |time| {
+                        model_transform(&transform, &animation, &file_transform, time)
+                    }),
+                )
+            })
             .collect();
         let mut meshes = meshes?;
 
-        let plane = MeshResource::new(
-            &PLANE_INDICES,
-            &PLANE_VERTEX_POSITIONS,
-            &PLANE_VERTEX_NORMALS,
-            "plane".to_string(),
-        );
-        let mesh_material = Material {
-            base_color: Vec3::new(0.75, 0.75, 0.75),
-            metallic: 0.0,
-            specular_reflectance: Vec3::ZERO,
-            roughness: 1.0,
-            specular_tint: Vec3::ZERO,
-            pad: Default::default(),
-        };
-        meshes.push(Mesh::load(device, &plane, mesh_material, Box::new(stay))?);
+        // the buffers of all the meshes are copied in one submission
+        let _ = device.submit_uploads()?;
 
-        let transforms: Vec<_> = meshes
-            .iter()
-            .flat_map(|mesh| [mesh.transform(), mesh.transposed_inv_transform()])
-            .cloned()
-            .collect();
+        let camera = desc.camera.camera(viewport_width, viewport_height);
 
-        let transform_buffer = resource::create_buffer_with_data(
-            device,
-            D3D12_HEAP_TYPE_UPLOAD,
-            D3D12_RESOURCE_FLAG_NONE,
-            D3D12_RESOURCE_STATE_ALL_SHADER_RESOURCE,
-            &transforms,
-            "Scene::transform_buffer",
-        )?;
+        // selects the LODs of the BLAS
+        for mesh in &mut meshes {
+            mesh.update(0.0, &camera);
+        }
 
-        let transform_srv_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
-            Format: DXGI_FORMAT_UNKNOWN,
-            ViewDimension: D3D12_SRV_DIMENSION_BUFFER,
-            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
-            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
-                Buffer: D3D12_BUFFER_SRV {
-                    FirstElement: 0,
-                    NumElements: transforms.len() as u32,
-                    StructureByteStride: std::mem::size_of_val(&transforms[0]) as u32,
-                    Flags: D3D12_BUFFER_SRV_FLAG_NONE,
-                },
-            },
-        };
-        let transform_srv = device.create_srv(Some(&transform_buffer), Some(&transform_srv_desc));
+        let (transform_srv, transform_address) = upload_transforms(device, &meshes)?;
 
         let materials: Vec<_> = meshes.iter().map(|mesh| mesh.material()).cloned().collect();
         let material_buffer = resource::create_buffer_with_data(
@@ -119,26 +110,7 @@ impl Scene {
         };
         let material_srv = device.create_srv(Some(&material_buffer), Some(&material_srv_desc));
 
-        let camera = Camera {
-            viewport_size: [viewport_width, viewport_height],
-            ..Default::default()
-        };
-
-        let camera_buf_size = align!(std::mem::size_of_val(&camera), 256);
-        let camera_buffer = resource::create_buffer(
-            device,
-            camera_buf_size as u64,
-            D3D12_HEAP_TYPE_UPLOAD,
-            D3D12_RESOURCE_FLAG_NONE,
-            D3D12_RESOURCE_STATE_COMMON,
-            "Scene::camera",
-        )?;
-
-        let camera_cbv_desc = D3D12_CONSTANT_BUFFER_VIEW_DESC {
-            BufferLocation: unsafe { camera_buffer.GetGPUVirtualAddress() },
-            SizeInBytes: camera_buf_size as u32,
-        };
-        let camera_cbv = device.create_cbv(Some(&camera_cbv_desc));
+        let camera_cbv = upload_camera(device, &camera)?;
 
         let mut raytracing_scene = RaytracingScene::new(
             D3D12_RAYTRACING_ACCELERATION_STRUCTURE_BUILD_FLAG_PREFER_FAST_TRACE,
@@ -149,25 +121,19 @@ impl Scene {
             | D3D12_RAYTRACING_ACCELERATION_STRUCTURE_BUILD_FLAG_ALLOW_UPDATE;
 
         let blas_id = raytracing_scene.add_blas(blas_flags);
-        let transform_address = unsafe { transform_buffer.GetGPUVirtualAddress() };
 
-        for (i, mesh) in meshes.iter().enumerate() {
-            // multiplied by 2 because odd number indices are for invertransposed inverse matrices
-            let transform = transform_address + (2 * mem::size_of::<[f32; 12]>() * i) as u64;
-            raytracing_scene.add_mesh(blas_id, mesh, Some(transform));
+        let transforms = blas_transforms(transform_address, meshes.len());
+        for (mesh, transform) in meshes.iter().zip(transforms) {
+            raytracing_scene.add_mesh(device, blas_id, mesh, Some(transform));
         }
 
-        let light_position = Vec3::new(3.0, 10.0, -3.0);
-        let light_dir = (vec3(0.0, 0.0, 0.0) - light_position).normalize();
-        let light_angle = 60.0_f32.to_radians();
-        let light = SpotLight::new(light_position, 500.0, light_dir, light_angle);
+        let light = desc.light.spot_light();
 
         raytracing_scene.build(device)?;
 
         Ok(Scene {
             timer: std::time::Instant::now(),
             camera,
-            camera_buffer,
             camera_cbv,
 
             meshes,
@@ -175,7 +141,6 @@ impl Scene {
 
             raytracing_scene,
 
-            transform_buffer,
             transform_srv,
 
             material_buffer,
@@ -187,25 +152,8 @@ impl Scene {
         let total_time = self.timer.elapsed().as_secs_f64();
 
         for mesh in &mut self.meshes {
-            mesh.update(total_time);
+            mesh.update(total_time, &self.camera);
         }
-
-        let eye = Vec3::new(0.0, 5.0, -10.0);
-        let center: Vec3 = Vec3::ZERO;
-        let up = Vec3::new(0.0, 1.0, 0.0);
-        let view = Mat4::look_at_lh(eye, center, up);
-
-        let fov = 20.0_f32.to_radians();
-        let viewport_size = &self.camera.viewport_size;
-        let aspect_ratio = (viewport_size[0] as f32) / (viewport_size[1] as f32);
-
-        let projection = Mat4::perspective_lh(fov, aspect_ratio, 0.1, 100.0);
-
-        let view_proj = projection * view;
-
-        self.camera.position = eye;
-        self.camera.view_proj = view_proj;
-        self.camera.inv_view_proj = view_proj.inverse();
     }
 
     pub fn update_buffers(
@@ -213,40 +161,24 @@ impl Scene {
         device: &mut Device,
         cmd_list: &ID3D12GraphicsCommandList7,
     ) -> windows::core::Result<()> {
-        self.update_camera_buffer()?;
-        self.update_transform()?;
-        self.raytracing_scene.update(device, cmd_list)?;
+        let camera_cbv = upload_camera(device, &self.camera)?;
+        device.free_cbv(mem::replace(&mut self.camera_cbv, camera_cbv));
 
-        Ok(())
-    }
+        let (transform_srv, transform_address) = upload_transforms(device, &self.meshes)?;
+        device.free_srv(mem::replace(&mut self.transform_srv, transform_srv));
 
-    fn update_camera_buffer(&self) -> windows::core::Result<()> {
-        let mut data = std::ptr::null_mut();
-        unsafe {
-            self.camera_buffer.Map(0, None, Some(&mut data))?;
-            std::ptr::copy_nonoverlapping(&self.camera, data as *mut _, 1);
-            self.camera_buffer.Unmap(0, None);
-        }
+        let transforms = blas_transforms(transform_address, self.meshes.len());
+        self.raytracing_scene.set_transforms(transforms);
+        self.raytracing_scene.update(device, cmd_list)?;
 
         Ok(())
     }
 
-    pub fn update_transform(&self) -> windows::core::Result<()> {
-        let transforms: Vec<[f32; 12]> = self
-            .meshes
-            .iter()
-            .flat_map(|mesh| [mesh.transform(), mesh.transposed_inv_transform()])
-            .cloned()
-            .collect();
-
-        let mut data = std::ptr::null_mut();
-        unsafe {
-            self.transform_buffer.Map(0, None, Some(&mut data))?;
-            std::ptr::copy_nonoverlapping(transforms.as_ptr(), data as *mut _, transforms.len());
-            self.transform_buffer.Unmap(0, None);
+    /// Releases the buffers of the meshes once the GPU has finished the current frame
+    pub fn release(&mut self, device: &mut Device) {
+        for mesh in self.meshes.drain(..) {
+            mesh.release(device);
         }
-
-        Ok(())
     }
 
     pub fn light(&self) -> &SpotLight {
@@ -274,46 +206,174 @@ impl Scene {
     }
 }
 
-#[derive(Debug, Default)]
-#[repr(C, align(16))]
-pub struct Camera {
-    view_proj: Mat4,
+#[cfg(windows)]
+fn upload_camera(device: &mut Device, camera: &Camera) -> windows::core::Result<Cbv> {
+    let camera_data = device.upload(std::slice::from_ref(camera), CONSTANT_BUFFER_ALIGNMENT)?;
+
+    let camera_cbv_desc = D3D12_CONSTANT_BUFFER_VIEW_DESC {
+        BufferLocation: camera_data.gpu_address,
+        SizeInBytes: align!(mem::size_of::<Camera>(), 256) as u32,
+    };
+
+    Ok(device.create_cbv(Some(&camera_cbv_desc)))
+}
+
+// Returns the SRV and the GPU virtual address of the transforms
+#[cfg(windows)]
+fn upload_transforms(device: &mut Device, meshes: &[Mesh]) -> windows::core::Result<(Srv, u64)> {
+    let transforms: Vec<_> = meshes
+        .iter()
+        .flat_map(|mesh| [mesh.transform(), mesh.transposed_inv_transform()])
+        .cloned()
+        .collect();
+
+    // the offset must be a multiple of the stride to be the first element of the SRV
+    let stride = mem::size_of_val(&transforms[0]);
+    let transform_data = device.upload(&transforms, stride as u64)?;
+
+    let transform_srv_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+        Format: DXGI_FORMAT_UNKNOWN,
+        ViewDimension: D3D12_SRV_DIMENSION_BUFFER,
+        Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+        Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+            Buffer: D3D12_BUFFER_SRV {
+                FirstElement: transform_data.offset / stride as u64,
+                NumElements: transforms.len() as u32,
+                StructureByteStride: stride as u32,
+                Flags: D3D12_BUFFER_SRV_FLAG_NONE,
+            },
+        },
+    };
+    let upload_buffer = device.upload_ring().buffer().clone();
+    let transform_srv = device.create_srv(Some(&upload_buffer), Some(&transform_srv_desc));
+
+    Ok((transform_srv, transform_data.gpu_address))
+}
+
+// The addresses of the transform of each mesh uploaded by `upload_transforms`
+#[cfg(windows)]
+fn blas_transforms(transform_address: u64, mesh_count: usize) -> impl Iterator<Item = u64> {
+    // multiplied by 2 because odd number indices are for invertransposed inverse matrices
+    let stride = 2 * mem::size_of::<[f32; 12]>() as u64;
+    (0..mesh_count as u64).map(move |i| transform_address + stride * i)
+}
 
-    inv_view_proj: Mat4,
+/// CPU-side description of a mesh in the scene
+pub struct SceneObject {
+    pub resource: MeshResource,
+    pub material: Material,
+    pub transform: Transform,
+    pub animation: Animation,
+    /// Places the mesh in the space of its file, such as the transform of a glTF node
+    pub file_transform: Mat4,
+    /// Simplified from `resource`, empty if the scene asks for no LODs
+    pub lods: Vec<Lod>,
+}
 
-    position: Vec3,
-    pad: u32,
+impl SceneObject {
+    /// The model transform at `time` seconds
+    pub fn transform_at(&self, time: f64) -> Mat4 {
+        model_transform(&self.transform, &self.animation, &self.file_transform, time)
+    }
+}
 
-    viewport_size: [u32; 2],
+// The animation is applied to the whole file, before placing it in the scene
+fn model_transform(
+    transform: &Transform,
+    animation: &Animation,
+    file_transform: &Mat4,
+    time: f64,
+) -> Mat4 {
+    transform.matrix() * animation.matrix(time) * *file_transform
 }
 
-#[rustfmt::skip]
-const PLANE_VERTEX_POSITIONS: [Vec3; 4] = [
-    Vec3{x: -10.0, y: -2.0, z: -10.0},
-    Vec3{x:  10.0, y: -2.0, z: -10.0},
-    Vec3{x: -10.0, y: -2.0, z:  10.0},
-    Vec3{x:  10.0, y: -2.0, z:  10.0},
-];
-
-#[rustfmt::skip]
-const PLANE_VERTEX_NORMALS: [Vec3; 4] = [
-    Vec3{x: 0.0, y: 1.0, z:  0.0},
-    Vec3{x: 0.0, y: 1.0, z:  0.0},
-    Vec3{x: 0.0, y: 1.0, z:  0.0},
-    Vec3{x: 0.0, y: 1.0, z:  0.0},
-];
-
-#[rustfmt::skip]
-const PLANE_INDICES: [u32; 6] = [
-    0, 2, 3, 0, 3, 1
-];
-
-fn rotate(time: f64) -> Mat4 {
-    let angle_deg = (time * 90.0) % 360.0;
-    let angle = angle_deg.to_radians();
-    Mat4::from_rotation_y(angle as f32)
+/// Loads the meshes of the objects in `desc`, a file with several meshes makes an object for each
+pub fn load_objects(desc: &SceneDesc) -> Result<Vec<SceneObject>, SceneError> {
+    let mut objects = Vec::new();
+    let cache = desc.mesh_cache.as_deref().map(MeshCache::new);
+
+    for (object_index, object) in desc.objects.iter().enumerate() {
+        let meshes = match &object.mesh {
+            MeshDesc::File(path) => {
+                let mut meshes = load_mesh_file(path, desc.mesh_validation, cache.as_ref())?;
+                // files are rarely exported in the order the GPU reads them
+                for mesh in &mut meshes {
+                    if !mesh.repaired.is_empty() {
+                        let name = mesh.resource.name();
+                        println!("Repaired {} problems of {name}", mesh.repaired.len());
+                    }
+                    mesh.resource.optimize();
+                }
+                meshes
+            }
+            MeshDesc::Primitive(primitive) => primitive.meshes(),
+        };
+
+        let mesh_count = meshes.len();
+        for (i, mut mesh) in meshes.into_iter().enumerate() {
+            if let Some(name) = &object.name {
+                match mesh_count {
+                    1 => mesh.resource.set_name(name.clone()),
+                    _ => mesh.resource.set_name(format!("{name}[{i}]")),
+                }
+            }
+
+            let material = object
+                .material
+                .clone()
+                .or(mesh.material.map(|m| m.material));
+            let Some(material) = material else {
+                return Err(SceneError::Invalid {
+                    path: format!("objects[{object_index}]"),
+                    message: "\"material\" is required because the mesh has none".to_string(),
+                });
+            };
+
+            let lods = match object.lod {
+                Some(lod) => mesh.resource.lod_chain(lod.max_error),
+                None => Vec::new(),
+            };
+
+            objects.push(SceneObject {
+                resource: mesh.resource,
+                material,
+                transform: object.transform,
+                animation: object.animation,
+                file_transform: mesh.transform,
+                lods,
+            });
+        }
+    }
+
+    Ok(objects)
 }
 
-fn stay(_time: f64) -> Mat4 {
-    Mat4::IDENTITY
+fn load_mesh_file(
+    path: &Path,
+    policy: ValidationPolicy,
+    cache: Option<&MeshCache>,
+) -> Result<Vec<ImportedMesh>, SceneError> {
+    let extension = path.extension().and_then(|e| e.to_str());
+
+    match extension.map(str::to_ascii_lowercase).as_deref() {
+        Some("gltf" | "glb") => {
+            mesh::gltf::load(path, policy).map_err(|e| SceneError::Gltf(path.to_path_buf(), e))
+        }
+        Some("ply") => {
+            let mesh = mesh::ply::load(path, policy)
+                .map_err(|e| SceneError::Ply(path.to_path_buf(), e))?;
+            Ok(vec![mesh])
+        }
+        _ => {
+            let file = mesh::obj::load(&path.to_string_lossy(), policy, cache)
+                .map_err(|e| SceneError::Obj(path.to_path_buf(), e))?;
+            if let Some(e) = file.material_error {
+                println!("Failed to load the materials of {}: {e}", path.display());
+            }
+            if let Some(e) = file.cache_error {
+                println!("Failed to use the mesh cache for {}: {e}", path.display());
+            }
+            Ok(file.meshes)
+        }
+    }
 }
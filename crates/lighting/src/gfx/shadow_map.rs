@@ -1,18 +1,18 @@
 use std::{mem, path::PathBuf};
 
 use windows::core as winapi;
-use windows::Win32::Foundation::FALSE;
-use windows::Win32::Graphics::{Direct3D::*, Direct3D12::*, Dxgi::Common::*};
+use windows::Win32::Graphics::{Direct3D12::*, Dxgi::Common::*};
 
 use super::d3d12::{
+    backend::d3d12::D3D12Backend,
     device::*,
-    pix::*,
     pso,
     resource::*,
     shader::*,
     view,
     view::{Dsv, Srv},
 };
+use super::frame::{DrawItem, GraphicsPass};
 use super::{math::*, scene::Scene};
 
 pub struct ShadowMap {
@@ -109,6 +109,10 @@ impl ShadowMap {
     pub fn srv(&self) -> &Srv {
         &self.srv
     }
+
+    pub fn dsv(&self) -> &Dsv {
+        &self.dsv
+    }
 }
 
 pub struct ShadowMapPass {
@@ -128,91 +132,40 @@ impl ShadowMapPass {
         })
     }
 
-    pub fn clear(&self, command_list: &ID3D12GraphicsCommandList7, shadow_map: &ShadowMap) {
-        let rects = [];
-        unsafe {
-            command_list.ClearDepthStencilView(
-                shadow_map.dsv.cpu_handle(),
-                D3D12_CLEAR_FLAG_DEPTH,
-                1.0,
-                0,
-                &rects,
-            )
-        };
-    }
-
-    pub fn draw(
-        &self,
-        command_list: &ID3D12GraphicsCommandList7,
+    /// The pass drawing all meshes in `scene` into `shadow_map`
+    pub fn graphics_pass<'a, 'b>(
+        &'a self,
+        device: &Device,
         scene: &Scene,
         shadow_map: &ShadowMap,
-        pix: Option<&Pix>,
-    ) {
-        let color = pix_color(0, 255, 0);
-        let _event = pix.map(|p| p.begin_event(command_list, color, "Draw shadow maps"));
-
-        self.clear(command_list, shadow_map);
-
-        let light = scene.light();
-
+    ) -> GraphicsPass<'a, D3D12Backend<'b>> {
         let aspect_ratio = (shadow_map.width as f32) / (shadow_map.height as f32);
-        let view_projection = light.view_projection(aspect_ratio);
-
-        let rect = windows::Win32::Foundation::RECT {
-            left: 0,
-            top: 0,
-            right: shadow_map.width.try_into().unwrap(),
-            bottom: shadow_map.height.try_into().unwrap(),
-        };
-
-        let viewport = D3D12_VIEWPORT {
-            TopLeftX: 0.0,
-            TopLeftY: 0.0,
-            Width: shadow_map.width as f32,
-            Height: shadow_map.height as f32,
-            MinDepth: D3D12_MIN_DEPTH,
-            MaxDepth: D3D12_MAX_DEPTH,
-        };
-
-        unsafe {
-            command_list.SetPipelineState(&self.pso);
-            command_list.SetGraphicsRootSignature(&self.root_signature);
-
-            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
-
-            command_list.RSSetViewports(&[viewport]);
-            command_list.RSSetScissorRects(&[rect]);
-
-            command_list.OMSetRenderTargets(0, None, FALSE, Some(&shadow_map.dsv.cpu_handle()));
-
-            command_list.SetGraphicsRoot32BitConstants(
-                1,
-                (mem::size_of_val(&view_projection) / mem::size_of::<f32>()) as u32,
-                &view_projection as *const _ as _,
-                0,
-            );
-        }
-
-        for (i, mesh) in scene.meshes().iter().enumerate() {
-            unsafe {
-                let vbv = mesh.position_buffer_view();
-                command_list.IASetVertexBuffers(0, Some(&[*vbv]));
-
-                command_list.IASetIndexBuffer(Some(mesh.index_buffer_view()));
+        let view_projection = scene.light().view_projection(aspect_ratio);
 
+        let draws = scene
+            .meshes()
+            .iter()
+            .enumerate()
+            .map(|(i, mesh)| {
                 let resources = ResourceHandles {
-                    mesh_transform: scene.transform_srv().handle(),
+                    mesh_transform: device.view_handle(scene.transform_srv().slot()),
                     mesh_id: i as u32,
                 };
-                command_list.SetGraphicsRoot32BitConstants(
-                    0,
-                    ResourceHandles::COUNT,
-                    resources.as_ptr(),
-                    0,
-                );
-
-                command_list.DrawIndexedInstanced(mesh.index_count() as u32, 1, 0, 0, 0);
-            }
+
+                DrawItem {
+                    vertex_buffers: vec![mesh.position_buffer_view().into()],
+                    index_buffer: Some(mesh.index_buffer_view().into()),
+                    count: mesh.index_count() as u32,
+                    constants: resources.as_slice().to_vec(),
+                }
+            })
+            .collect();
+
+        GraphicsPass {
+            pipeline: &self.pso,
+            root_signature: &self.root_signature,
+            constants: view_projection.to_cols_array().map(f32::to_bits).to_vec(),
+            draws,
         }
     }
 }
@@ -1,12 +1,32 @@
+use std::path::{Path, PathBuf};
+
+#[cfg(windows)]
 pub mod framework;
 pub mod gfx;
 
+// the depth buffer and the shadow map are created at startup and never freed
+const MIN_DSV_HEAP_CAPACITY: u32 = 2;
+
 pub struct Config {
     client_width: u32,
     client_height: u32,
 
     debug_layer_enabled: bool,
     gpu_validation_enabled: bool,
+
+    // relative to the directory of this program unless given by --scene
+    scene: PathBuf,
+
+    // renders a single frame on the CPU and writes it to this path instead of opening a window
+    reference_output: Option<PathBuf>,
+
+    // writes the GPU memory report as JSON to this path at shutdown
+    memory_report_output: Option<PathBuf>,
+
+    // initial number of CBV/SRV/UAV descriptors, the heap grows when it is full
+    view_heap_capacity: u32,
+    rtv_heap_capacity: u32,
+    dsv_heap_capacity: u32,
 }
 
 impl Config {
@@ -25,13 +45,144 @@ impl Config {
     pub fn gpu_validation_enabled(&self) -> bool {
         self.gpu_validation_enabled
     }
+
+    pub fn scene(&self) -> &Path {
+        &self.scene
+    }
+
+    pub fn reference_output(&self) -> Option<&Path> {
+        self.reference_output.as_deref()
+    }
+
+    pub fn memory_report_output(&self) -> Option<&Path> {
+        self.memory_report_output.as_deref()
+    }
+
+    pub fn view_heap_capacity(&self) -> u32 {
+        self.view_heap_capacity
+    }
+
+    pub fn rtv_heap_capacity(&self) -> u32 {
+        self.rtv_heap_capacity
+    }
+
+    pub fn dsv_heap_capacity(&self) -> u32 {
+        self.dsv_heap_capacity
+    }
 }
 
-pub fn parse_args(_args: impl Iterator<Item = String>) -> Config {
-    Config {
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Config {
+    let mut config = Config {
         client_width: 1280,
         client_height: 720,
         debug_layer_enabled: true,
         gpu_validation_enabled: true,
+        scene: PathBuf::from("assets/scene.json"),
+        reference_output: None,
+        memory_report_output: None,
+        view_heap_capacity: 100,
+        rtv_heap_capacity: 4,
+        dsv_heap_capacity: MIN_DSV_HEAP_CAPACITY,
+    };
+
+    // skip the path of this program
+    args.next();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scene" => match args.next() {
+                Some(path) => match std::path::absolute(path) {
+                    Ok(path) => config.scene = path,
+                    Err(e) => println!("Invalid scene path: {e}"),
+                },
+                None => println!("--scene requires a path"),
+            },
+            "--reference" => match args.next() {
+                Some(path) => config.reference_output = std::path::absolute(path).ok(),
+                None => println!("--reference requires an output path"),
+            },
+            "--memory-report" => match args.next() {
+                Some(path) => config.memory_report_output = std::path::absolute(path).ok(),
+                None => println!("--memory-report requires an output path"),
+            },
+            "--view-heap-capacity" => {
+                parse_capacity(&arg, args.next(), 1, &mut config.view_heap_capacity)
+            }
+            "--rtv-heap-capacity" => {
+                parse_capacity(&arg, args.next(), 1, &mut config.rtv_heap_capacity)
+            }
+            "--dsv-heap-capacity" => parse_capacity(
+                &arg,
+                args.next(),
+                MIN_DSV_HEAP_CAPACITY,
+                &mut config.dsv_heap_capacity,
+            ),
+            _ => println!("Unknown argument: {arg}"),
+        }
+    }
+
+    config
+}
+
+fn parse_capacity(arg: &str, value: Option<String>, min: u32, capacity: &mut u32) {
+    match value.as_deref().map(str::parse::<u32>) {
+        Some(Ok(v)) if v >= min => *capacity = v,
+        _ => println!("{arg} requires a number of at least {min}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Config {
+        let args = ["lighting"].iter().chain(args).map(|arg| arg.to_string());
+        parse_args(args)
+    }
+
+    #[test]
+    fn default_heap_capacities() {
+        let config = parse(&[]);
+        assert_eq!(config.view_heap_capacity(), 100);
+        assert_eq!(config.rtv_heap_capacity(), 4);
+        assert_eq!(config.dsv_heap_capacity(), 2);
+    }
+
+    #[test]
+    fn heap_capacities_from_arguments() {
+        let config = parse(&[
+            "--view-heap-capacity",
+            "1",
+            "--rtv-heap-capacity",
+            "8",
+            "--dsv-heap-capacity",
+            "3",
+        ]);
+        assert_eq!(config.view_heap_capacity(), 1);
+        assert_eq!(config.rtv_heap_capacity(), 8);
+        assert_eq!(config.dsv_heap_capacity(), 3);
+    }
+
+    #[test]
+    fn invalid_heap_capacities_are_ignored() {
+        let config = parse(&[
+            "--view-heap-capacity",
+            "0",
+            "--rtv-heap-capacity",
+            "-1",
+            "--dsv-heap-capacity",
+        ]);
+        assert_eq!(config.view_heap_capacity(), 100);
+        assert_eq!(config.rtv_heap_capacity(), 4);
+        assert_eq!(config.dsv_heap_capacity(), 2);
+    }
+
+    #[test]
+    fn dsv_heap_capacity_below_the_startup_views_is_ignored() {
+        let config = parse(&["--dsv-heap-capacity", "1"]);
+        assert_eq!(config.dsv_heap_capacity(), MIN_DSV_HEAP_CAPACITY);
+
+        let config = parse(&["--dsv-heap-capacity", "2"]);
+        assert_eq!(config.dsv_heap_capacity(), 2);
     }
 }
@@ -1,6 +1,9 @@
 use lighting::*;
 
-fn main() -> windows::core::Result<()> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // parse first so relative paths in the arguments are resolved against the original cwd
+    let config = crate::parse_args(std::env::args());
+
     // change cwd so opening HLSL files will not fail
     let dir = std::env::current_exe()
         .inspect_err(|e| println!("Failed to get the path of this program: {e}"))
@@ -12,8 +15,30 @@ fn main() -> windows::core::Result<()> {
         }
     }
 
-    let config = crate::parse_args(std::env::args());
-    framework::run(&config)?;
+    let scene = sandbox_core::scene::load(config.scene())
+        .inspect_err(|e| println!("Failed to load {}: {e}", config.scene().display()))?;
+
+    if let Some(path) = config.reference_output() {
+        let (width, height) = (config.client_width(), config.client_height());
+        let image = gfx::reference::render_scene(&scene, width, height, 0.0)
+            .inspect_err(|e| println!("{e}"))?;
+        if let Err(e) = image.write_ppm(path) {
+            println!("Failed to write {}: {e}", path.display());
+        }
+        return Ok(());
+    }
+
+    run(&config, &scene)
+}
+
+#[cfg(windows)]
+fn run(config: &Config, scene: &gfx::scene::SceneDesc) -> Result<(), Box<dyn std::error::Error>> {
+    framework::run(config, scene)?;
     gfx::report_live_objects()?;
     Ok(())
 }
+
+#[cfg(not(windows))]
+fn run(_config: &Config, _scene: &gfx::scene::SceneDesc) -> Result<(), Box<dyn std::error::Error>> {
+    Err("the renderer requires Windows; use --reference to render on the CPU".into())
+}
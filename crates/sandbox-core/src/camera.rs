@@ -0,0 +1,160 @@
+use super::math::*;
+
+// must match Camera in scene.hlsl
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C, align(16))]
+pub struct Camera {
+    view_proj: Mat4,
+
+    inv_view_proj: Mat4,
+
+    position: Vec3,
+    pad: u32,
+
+    viewport_size: [u32; 2],
+}
+
+impl Camera {
+    pub fn new(viewport_width: u32, viewport_height: u32) -> Self {
+        Self {
+            viewport_size: [viewport_width, viewport_height],
+            ..Default::default()
+        }
+    }
+
+    pub fn look_at(&mut self, eye: Vec3, center: Vec3, up: Vec3, fov: f32) {
+        let view = Mat4::look_at_lh(eye, center, up);
+
+        let viewport_size = &self.viewport_size;
+        let aspect_ratio = (viewport_size[0] as f32) / (viewport_size[1] as f32);
+
+        let projection = Mat4::perspective_lh(fov, aspect_ratio, 0.1, 100.0);
+
+        let view_proj = projection * view;
+
+        self.position = eye;
+        self.view_proj = view_proj;
+        self.inv_view_proj = view_proj.inverse();
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn inv_view_proj(&self) -> &Mat4 {
+        &self.inv_view_proj
+    }
+
+    pub fn viewport_size(&self) -> [u32; 2] {
+        self.viewport_size
+    }
+
+    /// The distance from the camera plane to `position`, negative behind the camera
+    pub fn depth(&self, position: Vec3) -> f32 {
+        // the perspective projection writes the view depth to w
+        self.view_proj.row(3).dot(position.extend(1.0))
+    }
+
+    /// The height in pixels of `length` seen at `depth`
+    pub fn projected_size(&self, length: f32, depth: f32) -> f32 {
+        // the Y row of the projection is the up vector of the view scaled by cot(fov / 2)
+        let cot_half_fov = self.view_proj.row(1).truncate().length();
+        length * cot_half_fov / depth * self.viewport_size[1] as f32 * 0.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EYE: Vec3 = Vec3::new(1.0, 2.0, 3.0);
+
+    // Looking along +X from `EYE` with a vertical field of view of 90 degrees
+    fn camera(width: u32, height: u32) -> Camera {
+        let mut camera = Camera::new(width, height);
+        camera.look_at(EYE, EYE + Vec3::X, Vec3::Y, 90f32.to_radians());
+        camera
+    }
+
+    fn project(camera: &Camera, position: Vec3) -> Vec3 {
+        camera.view_proj.project_point3(position)
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(actual.abs_diff_eq(expected, 1e-5), "{actual} != {expected}");
+    }
+
+    #[test]
+    fn look_direction_maps_to_depth() {
+        let camera = camera(100, 100);
+
+        // the near and far planes at 0.1 and 100 map to the depths 0 and 1
+        assert_close(project(&camera, EYE + Vec3::X * 0.1), Vec3::ZERO);
+        assert_close(project(&camera, EYE + Vec3::X * 100.0), Vec3::Z);
+        let middle = project(&camera, EYE + Vec3::X * 10.0);
+        assert!(middle.z > 0.0 && middle.z < 1.0);
+        assert_eq!(middle.truncate(), Vec2::ZERO);
+
+        // the view space is left-handed, with +Y up and +X on the right
+        assert_close(
+            project(&camera, EYE + Vec3::X + Vec3::Y)
+                .truncate()
+                .extend(0.0),
+            Vec3::Y,
+        );
+        let right = EYE + Vec3::X - Vec3::Z;
+        assert_close(project(&camera, right).truncate().extend(0.0), Vec3::X);
+    }
+
+    #[test]
+    fn aspect_ratio() {
+        // the field of view is vertical, so a wide viewport sees further to the sides
+        let camera = camera(200, 100);
+        let corner = EYE + Vec3::X + Vec3::Y - Vec3::Z * 2.0;
+        assert_close(
+            project(&camera, corner).truncate().extend(0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+        );
+        assert_eq!(camera.viewport_size(), [200, 100]);
+    }
+
+    #[test]
+    fn inverse() {
+        let camera = camera(160, 90);
+        let product = *camera.inv_view_proj() * camera.view_proj;
+        assert!(product.abs_diff_eq(Mat4::IDENTITY, 1e-4), "{product}");
+
+        // the clip-space origin is the eye projected onto the near plane
+        let near = camera.inv_view_proj().project_point3(Vec3::ZERO);
+        assert_close(near, EYE + Vec3::X * 0.1);
+    }
+
+    #[test]
+    fn look_at_moves_the_camera() {
+        let mut camera = camera(100, 100);
+        assert_eq!(camera.position(), EYE);
+
+        // turned around to look back from the other side of the target
+        let eye = EYE + Vec3::X * 2.0;
+        camera.look_at(eye, EYE + Vec3::X, Vec3::Y, 90f32.to_radians());
+        assert_eq!(camera.position(), eye);
+        assert_close(
+            project(&camera, EYE + Vec3::X * 0.5).truncate().extend(0.0),
+            Vec3::ZERO,
+        );
+        assert!(camera.depth(EYE) > 0.0);
+        assert!(camera.depth(eye + Vec3::X) < 0.0);
+    }
+
+    #[test]
+    fn depth_and_projected_size() {
+        let camera = camera(100, 50);
+        assert!((camera.depth(EYE + Vec3::new(4.0, 3.0, -2.0)) - 4.0).abs() < 1e-5);
+        assert!((camera.depth(EYE - Vec3::X) + 1.0).abs() < 1e-5);
+
+        // at a distance of 1 the 90 degree view is 2 high, so a length of 1 covers half of the
+        // 50 rows, and a quarter at twice the distance
+        assert!((camera.projected_size(1.0, 1.0) - 25.0).abs() < 1e-4);
+        assert!((camera.projected_size(1.0, 2.0) - 12.5).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,233 @@
+// JSON values of description files, parsed by serde_json
+// Objects keep the order of their members, and duplicated keys are rejected
+
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The member of an object, None for other values
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_object()?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    /// Used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "a boolean",
+            Value::Number(_) => "a number",
+            Value::String(_) => "a string",
+            Value::Array(_) => "an array",
+            Value::Object(_) => "an object",
+        }
+    }
+}
+
+/// Has the line and the column of the error
+pub type ParseError = serde_json::Error;
+
+pub fn parse(text: &str) -> Result<Value, ParseError> {
+    serde_json::from_str(text)
+}
+
+/// Writes `s` as a JSON string, including the quotes
+pub fn quote(s: &str) -> String {
+    serde_json::to_string(s).expect("a string is always valid JSON")
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Bool(b))
+    }
+
+    fn visit_i64<E>(self, n: i64) -> Result<Value, E> {
+        Ok(Value::Number(n as f64))
+    }
+
+    fn visit_u64<E>(self, n: u64) -> Result<Value, E> {
+        Ok(Value::Number(n as f64))
+    }
+
+    fn visit_f64<E>(self, n: f64) -> Result<Value, E> {
+        Ok(Value::Number(n))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::String(s.to_string()))
+    }
+
+    fn visit_string<E>(self, s: String) -> Result<Value, E> {
+        Ok(Value::String(s))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+
+        Ok(Value::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut members: Vec<(String, Value)> = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if members.iter().any(|(k, _)| *k == key) {
+                return Err(de::Error::custom(format!("duplicated key \"{key}\"")));
+            }
+
+            let value = map.next_value()?;
+            members.push((key, value));
+        }
+
+        Ok(Value::Object(members))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        let value = parse(r#"{"b": [1, -2.5, 1e3], "a": {"s": "x\ny", "t": true, "n": null}}"#);
+        let expected = Value::Object(vec![
+            (
+                "b".to_string(),
+                Value::Array(vec![
+                    Value::Number(1.0),
+                    Value::Number(-2.5),
+                    Value::Number(1000.0),
+                ]),
+            ),
+            (
+                "a".to_string(),
+                Value::Object(vec![
+                    ("s".to_string(), Value::String("x\ny".to_string())),
+                    ("t".to_string(), Value::Bool(true)),
+                    ("n".to_string(), Value::Null),
+                ]),
+            ),
+        ]);
+        assert_eq!(value.unwrap(), expected);
+    }
+
+    #[test]
+    fn members_keep_their_order() {
+        let value = parse(r#"{"z": 1, "a": 2, "m": 3}"#).unwrap();
+        let keys: Vec<_> = value
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(k, _)| k.as_str())
+            .collect();
+        assert_eq!(keys, ["z", "a", "m"]);
+        assert_eq!(value.get("a").and_then(Value::as_f64), Some(2.0));
+        assert_eq!(value.get("b"), None);
+    }
+
+    #[test]
+    fn duplicated_keys_are_rejected() {
+        let e = parse("{\"a\": 1,\n \"a\": 2}").unwrap_err();
+        assert!(e.to_string().contains("duplicated key \"a\""), "{e}");
+        assert_eq!(e.line(), 2);
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        let e = parse("{\n  \"a\": [1, 2,]\n}").unwrap_err();
+        assert_eq!((e.line(), e.column()), (2, 14));
+
+        assert!(parse("[1] 2").is_err());
+        assert!(parse("").is_err());
+        assert!(parse("1e400").is_err());
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let text = "[".repeat(1000) + &"]".repeat(1000);
+        assert!(parse(&text).is_err());
+    }
+
+    #[test]
+    fn quoted_strings_parse_back() {
+        for s in [
+            "plain",
+            "a \"quoted\" \\ path",
+            "tab\tand\nnew line",
+            "\u{1}",
+            "é✓",
+        ] {
+            let quoted = quote(s);
+            assert_eq!(parse(&quoted).unwrap(), Value::String(s.to_string()));
+        }
+        assert_eq!(quote("a\"b"), r#""a\"b""#);
+    }
+}
@@ -0,0 +1,8 @@
+// Platform-independent parts of the sandbox, which build and run without Windows or a GPU
+
+pub mod camera;
+pub mod json;
+pub mod light;
+pub mod math;
+pub mod mesh;
+pub mod scene;
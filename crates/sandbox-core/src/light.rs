@@ -1,5 +1,6 @@
 use super::math::*;
 
+// must match LightParameters in light.hlsl
 #[derive(Debug, Clone)]
 #[repr(C)]
 pub struct LightParameters {
@@ -49,3 +50,17 @@ impl SpotLight {
         projection * self.view_matrix()
     }
 }
+
+// same as eval_spot_light in light.hlsl
+pub fn eval_spot_light(spot_light: &LightParameters, shaded_point: Vec3) -> f32 {
+    let to_light = spot_light.position - shaded_point;
+    let distance2 = to_light.dot(to_light);
+    let distance_to_light = distance2.sqrt();
+    let light_dir = to_light / distance_to_light;
+
+    if (-light_dir).dot(spot_light.direction) > spot_light.cos_half_angle {
+        spot_light.intensity / distance2
+    } else {
+        0.0
+    }
+}
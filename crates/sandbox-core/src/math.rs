@@ -1,15 +1,14 @@
 pub use glam::*;
 
+#[macro_export]
 macro_rules! align {
     ($value:expr, $alignment:expr) => {
         ($value + $alignment - 1) & (!($alignment - 1))
     };
 }
 
-pub(crate) use align;
-
 pub fn divide_and_round_up(x: u32, y: u32) -> u32 {
-    (x + y - 1) / y
+    x.div_ceil(y)
 }
 
 pub fn mat4_to_row_marjor_float3x4(m: &Mat4) -> [f32; 12] {
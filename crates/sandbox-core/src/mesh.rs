@@ -0,0 +1,189 @@
+use std::path::PathBuf;
+
+use super::math::*;
+
+#[derive(Debug, Clone)]
+pub struct MeshResource {
+    indices: Vec<u32>,
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    /// Texture coordinates with the origin at the top left, a set has a UV for each position
+    uv_sets: Vec<Vec<Vec2>>,
+    /// The tangents along U of the first UV set, and the handedness in w
+    /// The bitangent is `w * cross(normal, tangent.xyz)`
+    tangents: Vec<Vec4>,
+    name: String,
+}
+
+impl MeshResource {
+    pub fn new(indices: &[u32], positions: &[Vec3], normals: &[Vec3], name: String) -> Self {
+        MeshResource {
+            indices: Vec::from(indices),
+            positions: Vec::from(positions),
+            normals: Vec::from(normals),
+            uv_sets: Vec::new(),
+            tangents: Vec::new(),
+            name,
+        }
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn positions(&self) -> &[Vec3] {
+        &self.positions
+    }
+
+    pub fn normals(&self) -> &[Vec3] {
+        &self.normals
+    }
+
+    pub fn uv_sets(&self) -> &[Vec<Vec2>] {
+        &self.uv_sets
+    }
+
+    /// Adds a UV set, whose index is the number of the sets before it
+    pub fn add_uv_set(&mut self, uvs: Vec<Vec2>) {
+        self.uv_sets.push(uvs);
+    }
+
+    pub fn tangents(&self) -> &[Vec4] {
+        &self.tangents
+    }
+
+    pub fn set_tangents(&mut self, tangents: Vec<Vec4>) {
+        self.tangents = tangents;
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    /// The center of the bounding box and the distance to the farthest position from it
+    pub fn bounding_sphere(&self) -> (Vec3, f32) {
+        bounding_sphere(self.positions.iter().copied())
+    }
+
+    // Appends a copy of every attribute of `vertex`, and returns the index of the copy
+    fn duplicate_vertex(&mut self, vertex: usize) -> u32 {
+        self.positions.push(self.positions[vertex]);
+        if let Some(&normal) = self.normals.get(vertex) {
+            self.normals.push(normal);
+        }
+        for uvs in &mut self.uv_sets {
+            if let Some(&uv) = uvs.get(vertex) {
+                uvs.push(uv);
+            }
+        }
+        if let Some(&tangent) = self.tangents.get(vertex) {
+            self.tangents.push(tangent);
+        }
+
+        (self.positions.len() - 1) as u32
+    }
+
+    // Replaces the vertices with copies of `vertices` in order, without changing the indices
+    fn gather_vertices(&mut self, vertices: &[u32]) {
+        fn gather<T: Copy>(attribute: &mut Vec<T>, vertices: &[u32]) {
+            if !attribute.is_empty() {
+                *attribute = vertices.iter().map(|&v| attribute[v as usize]).collect();
+            }
+        }
+
+        gather(&mut self.positions, vertices);
+        gather(&mut self.normals, vertices);
+        for uvs in &mut self.uv_sets {
+            gather(uvs, vertices);
+        }
+        gather(&mut self.tangents, vertices);
+    }
+}
+
+// The center of the bounding box of the positions and the distance to the farthest one from it
+fn bounding_sphere(positions: impl Iterator<Item = Vec3> + Clone) -> (Vec3, f32) {
+    let (min, max) = positions.clone().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), p| (min.min(p), max.max(p)),
+    );
+    if min.x > max.x {
+        return (Vec3::ZERO, 0.0);
+    }
+
+    let center = (min + max) * 0.5;
+    let radius = positions.map(|p| p.distance(center)).fold(0.0, f32::max);
+
+    (center, radius)
+}
+
+// must match Material in brdf.hlsl
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct Material {
+    pub base_color: Vec3,
+    pub metallic: f32,
+    pub specular_reflectance: Vec3,
+    pub roughness: f32,
+    pub specular_tint: Vec3,
+    pub pad: u32,
+}
+
+/// A mesh with what the file it is imported from says about rendering it
+#[derive(Debug, Clone)]
+pub struct ImportedMesh {
+    pub resource: MeshResource,
+    /// None if the file has no material for the mesh
+    pub material: Option<ImportedMaterial>,
+    /// Places the mesh in the space of the file, such as the world transform of a glTF node
+    pub transform: Mat4,
+    /// The problems of the file that the loader repaired with `ValidationPolicy::Repair`
+    pub repaired: Vec<MeshProblem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedMaterial {
+    pub name: String,
+    pub material: Material,
+    pub textures: MaterialTextures,
+}
+
+/// The image files a material refers to, which are not loaded by the importers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaterialTextures {
+    pub base_color: Option<PathBuf>,
+    pub specular: Option<PathBuf>,
+    pub roughness: Option<PathBuf>,
+    pub metallic: Option<PathBuf>,
+    pub normal: Option<PathBuf>,
+}
+
+pub mod binary;
+pub mod cache;
+pub mod gltf;
+
+mod meshlets;
+pub use meshlets::*;
+
+mod normals;
+pub use normals::*;
+
+mod optimize;
+pub use optimize::*;
+
+mod simplify;
+pub use simplify::*;
+
+mod tangents;
+
+mod validate;
+pub use validate::*;
+
+pub mod obj;
+
+pub mod ply;
+
+pub mod primitives;
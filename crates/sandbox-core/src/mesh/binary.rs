@@ -0,0 +1,836 @@
+// A compact binary format of imported meshes, whose attributes are read in place
+//
+// Every number is little endian
+// header:  magic "SBXMESH\0", version u32, reserved u32, payload length u64, checksum u64
+// payload: sections of a 4-byte tag, a reserved u32, a u64 length and the content, which is
+//          padded with zeros to a multiple of 16 bytes so that every content starts at a multiple
+//          of 16 bytes from the start of the file
+//   SRCE  the source files, a u32 count and then a path, a size u64 and a modification time
+//         of seconds u64 and nanoseconds u32 each
+//   MESH  starts a mesh: the name, the transform as 16 f32 in column major, and a u8 of whether a
+//         material follows, which is the name, 12 f32 of `Material` and 5 optional texture paths
+//   BNDS  the bounding sphere of the mesh as 4 f32
+//   INDX  u32 indices
+//   POSN  positions as 3 f32
+//   NORM  normals as 3 f32
+//   UVST  a UV set as 2 f32, one section for each set in order
+//   TANG  tangents as 4 f32
+// Strings are a u32 length and UTF-8, optional strings have a u8 of whether they are present
+// The checksum is FNV-1a of the payload
+// Unknown sections are skipped, so that sections can be added without a new version
+
+use std::fmt;
+use std::io::Read;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use super::{ImportedMaterial, ImportedMesh, Material, MaterialTextures, MeshResource};
+
+const MAGIC: &[u8; 8] = b"SBXMESH\0";
+/// Incremented when the meaning of a section changes
+pub const VERSION: u32 = 2;
+const HEADER_SIZE: usize = 32;
+/// Of the section contents, which is also the alignment `read` requires of the file in memory
+pub const ALIGNMENT: usize = 16;
+
+const SOURCES: &[u8; 4] = b"SRCE";
+const MESH: &[u8; 4] = b"MESH";
+const BOUNDS: &[u8; 4] = b"BNDS";
+const INDICES: &[u8; 4] = b"INDX";
+const POSITIONS: &[u8; 4] = b"POSN";
+const NORMALS: &[u8; 4] = b"NORM";
+const UV_SET: &[u8; 4] = b"UVST";
+const TANGENTS: &[u8; 4] = b"TANG";
+
+#[derive(Debug)]
+pub enum BinaryError {
+    Io(std::io::Error),
+    /// Not a file of this format
+    Magic,
+    Version(u32),
+    Checksum,
+    /// The file ends in the middle of the header or a section
+    Truncated,
+    /// The file is not aligned to `ALIGNMENT` in memory
+    Misaligned,
+    Invalid(String),
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryError::Io(e) => write!(f, "{e}"),
+            BinaryError::Magic => write!(f, "not a binary mesh file"),
+            BinaryError::Version(version) => {
+                write!(f, "the version {version} is not {VERSION}")
+            }
+            BinaryError::Checksum => write!(f, "the checksum does not match"),
+            BinaryError::Truncated => write!(f, "the file is truncated"),
+            BinaryError::Misaligned => {
+                write!(f, "the file is not aligned to {ALIGNMENT} bytes in memory")
+            }
+            BinaryError::Invalid(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for BinaryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BinaryError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A file the meshes are imported from, with what tells whether it has changed since
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Since the Unix epoch
+    pub modified: std::time::Duration,
+}
+
+impl SourceFile {
+    pub fn stat(path: &Path) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Ok(SourceFile {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            modified,
+        })
+    }
+
+    /// Whether the file still has the same size and modification time
+    pub fn is_current(&self) -> bool {
+        SourceFile::stat(&self.path).is_ok_and(|file| file == *self)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MeshFile {
+    pub sources: Vec<SourceFile>,
+    pub meshes: Vec<ImportedMesh>,
+    /// The `MeshResource::bounding_sphere` of each mesh, stored so that readers do not compute them
+    pub bounds: Vec<(Vec3, f32)>,
+}
+
+impl MeshFile {
+    pub fn new(sources: Vec<SourceFile>, meshes: Vec<ImportedMesh>) -> Self {
+        let bounds = meshes
+            .iter()
+            .map(|m| m.resource.bounding_sphere())
+            .collect();
+        MeshFile {
+            sources,
+            meshes,
+            bounds,
+        }
+    }
+}
+
+/// A mesh whose attributes are borrowed from the bytes of a file
+#[derive(Debug, Clone)]
+pub struct MeshView<'a> {
+    pub name: String,
+    pub transform: Mat4,
+    pub material: Option<ImportedMaterial>,
+    /// The `MeshResource::bounding_sphere` of the mesh
+    pub bounds: (Vec3, f32),
+    pub indices: &'a [u32],
+    pub positions: &'a [Vec3],
+    pub normals: &'a [Vec3],
+    pub uv_sets: Vec<&'a [Vec2]>,
+    pub tangents: &'a [Vec4],
+}
+
+impl MeshView<'_> {
+    pub fn to_imported(&self) -> ImportedMesh {
+        let mut resource = MeshResource::new(
+            self.indices,
+            self.positions,
+            self.normals,
+            self.name.clone(),
+        );
+        resource.uv_sets = self.uv_sets.iter().map(|uvs| uvs.to_vec()).collect();
+        resource.tangents = self.tangents.to_vec();
+
+        ImportedMesh {
+            resource,
+            material: self.material.clone(),
+            transform: self.transform,
+            repaired: Vec::new(),
+        }
+    }
+}
+
+/// A file read in place by `read`
+#[derive(Debug, Clone)]
+pub struct MeshFileView<'a> {
+    pub sources: Vec<SourceFile>,
+    pub meshes: Vec<MeshView<'a>>,
+}
+
+impl MeshFileView<'_> {
+    /// Copies the meshes, which are validated so that a file broken in a way the checksum misses
+    /// cannot reach the GPU
+    pub fn to_mesh_file(&self) -> Result<MeshFile, BinaryError> {
+        let meshes: Vec<ImportedMesh> = self.meshes.iter().map(MeshView::to_imported).collect();
+
+        for mesh in &meshes {
+            let resource = &mesh.resource;
+            resource
+                .validate()
+                .map_err(|e| BinaryError::Invalid(e.to_string()))?;
+            if !resource.tangents.is_empty() && !resource.has_tangents() {
+                return Err(BinaryError::Invalid(format!(
+                    "{} has {} tangents for {} positions",
+                    resource.name,
+                    resource.tangents.len(),
+                    resource.positions.len()
+                )));
+            }
+        }
+
+        Ok(MeshFile {
+            sources: self.sources.clone(),
+            meshes,
+            bounds: self.meshes.iter().map(|mesh| mesh.bounds).collect(),
+        })
+    }
+}
+
+/// The bytes of a file in memory aligned to `ALIGNMENT`, as `read` requires
+pub struct AlignedBytes {
+    blocks: Vec<Block>,
+    len: usize,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, align(16))]
+struct Block([u8; ALIGNMENT]);
+
+// an array of bytes without padding, as the alignment is its size
+unsafe impl bytemuck::Zeroable for Block {}
+unsafe impl bytemuck::Pod for Block {}
+
+impl AlignedBytes {
+    pub fn new(bytes: &[u8]) -> Self {
+        let mut aligned = AlignedBytes::zeroed(bytes.len());
+        aligned.as_mut_bytes().copy_from_slice(bytes);
+        aligned
+    }
+
+    /// Reads the file straight into aligned memory
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        let mut file = std::fs::File::open(path)?;
+        let mut aligned = AlignedBytes::zeroed(file.metadata()?.len() as usize);
+        file.read_exact(aligned.as_mut_bytes())?;
+        Ok(aligned)
+    }
+
+    fn zeroed(len: usize) -> Self {
+        AlignedBytes {
+            blocks: vec![Block([0; ALIGNMENT]); len.div_ceil(ALIGNMENT)],
+            len,
+        }
+    }
+
+    fn as_mut_bytes(&mut self) -> &mut [u8] {
+        &mut bytemuck::cast_slice_mut(&mut self.blocks)[..self.len]
+    }
+}
+
+impl Deref for AlignedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &bytemuck::cast_slice(&self.blocks)[..self.len]
+    }
+}
+
+/// Reads and validates the meshes of a file, whose attributes are copied once from the file
+/// into the meshes
+pub fn load(path: &Path) -> Result<MeshFile, BinaryError> {
+    let bytes = AlignedBytes::read(path).map_err(BinaryError::Io)?;
+    read(&bytes)?.to_mesh_file()
+}
+
+/// Writes to a temporary file that replaces `path`, so that readers never see a partial file
+pub fn save(path: &Path, file: &MeshFile) -> std::io::Result<()> {
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, write(file))?;
+    std::fs::rename(&temporary, path)
+}
+
+pub fn write(file: &MeshFile) -> Vec<u8> {
+    let mut payload = Writer::default();
+
+    payload.section(SOURCES, |w| {
+        w.u32(file.sources.len() as u32);
+        for source in &file.sources {
+            w.path(&source.path);
+            w.u64(source.size);
+            w.u64(source.modified.as_secs());
+            w.u32(source.modified.subsec_nanos());
+        }
+    });
+
+    for (mesh, (center, radius)) in file.meshes.iter().zip(&file.bounds) {
+        let resource = &mesh.resource;
+
+        payload.section(MESH, |w| {
+            w.string(&resource.name);
+            w.f32s(&mesh.transform.to_cols_array());
+            w.u8(mesh.material.is_some() as u8);
+            if let Some(material) = &mesh.material {
+                w.material(material);
+            }
+        });
+        payload.section(BOUNDS, |w| w.f32s(&center.extend(*radius).to_array()));
+        payload.section(INDICES, |w| {
+            for &index in &resource.indices {
+                w.u32(index);
+            }
+        });
+        payload.section(POSITIONS, |w| {
+            w.vectors(&resource.positions, Vec3::to_array)
+        });
+        payload.section(NORMALS, |w| w.vectors(&resource.normals, Vec3::to_array));
+        for uvs in &resource.uv_sets {
+            payload.section(UV_SET, |w| w.vectors(uvs, Vec2::to_array));
+        }
+        payload.section(TANGENTS, |w| w.vectors(&resource.tangents, Vec4::to_array));
+    }
+
+    let payload = payload.bytes;
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&checksum(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+/// Reads the meshes of `bytes` without copying their attributes, which are cast in place
+/// `bytes` must be aligned to `ALIGNMENT`, as `AlignedBytes` are. The attributes are not
+/// validated, which `MeshFileView::to_mesh_file` does
+pub fn read(bytes: &[u8]) -> Result<MeshFileView<'_>, BinaryError> {
+    // the attributes are cast as they are, so they would be byte swapped on big endian CPUs
+    if cfg!(target_endian = "big") {
+        return Err(BinaryError::Invalid(
+            "binary meshes are only read on little endian CPUs".to_string(),
+        ));
+    }
+    if !(bytes.as_ptr() as usize).is_multiple_of(ALIGNMENT) {
+        return Err(BinaryError::Misaligned);
+    }
+
+    let mut header = Reader { bytes };
+    if header.take(MAGIC.len())? != MAGIC {
+        return Err(BinaryError::Magic);
+    }
+    let version = header.u32()?;
+    if version != VERSION {
+        return Err(BinaryError::Version(version));
+    }
+    header.u32()?;
+    let payload_length = header.u64()?;
+    let expected_checksum = header.u64()?;
+
+    if (header.bytes.len() as u64) < payload_length {
+        return Err(BinaryError::Truncated);
+    }
+    let payload = &header.bytes[..payload_length as usize];
+    if checksum(payload) != expected_checksum {
+        return Err(BinaryError::Checksum);
+    }
+
+    let mut sources = Vec::new();
+    let mut meshes: Vec<MeshView> = Vec::new();
+
+    let mut reader = Reader { bytes: payload };
+    while !reader.bytes.is_empty() {
+        let tag: [u8; 4] = reader.take(4)?.try_into().unwrap();
+        reader.u32()?;
+        let length = reader.u64()?;
+        if (reader.bytes.len() as u64) < length {
+            return Err(BinaryError::Truncated);
+        }
+        let mut section = Reader {
+            bytes: reader.take(length as usize)?,
+        };
+        reader.take((length as usize).next_multiple_of(ALIGNMENT) - length as usize)?;
+
+        if &tag == SOURCES {
+            let count = section.u32()?;
+            for _ in 0..count {
+                let path = PathBuf::from(section.string()?);
+                let size = section.u64()?;
+                let seconds = section.u64()?;
+                let nanoseconds = section.u32()?;
+                sources.push(SourceFile {
+                    path,
+                    size,
+                    modified: std::time::Duration::new(seconds, nanoseconds),
+                });
+            }
+            continue;
+        }
+        if &tag == MESH {
+            let name = section.string()?;
+            let transform = Mat4::from_cols_slice(&section.f32s(16)?);
+            let material = match section.u8()? {
+                0 => None,
+                _ => Some(section.material()?),
+            };
+            meshes.push(MeshView {
+                name,
+                transform,
+                material,
+                bounds: (Vec3::ZERO, 0.0),
+                indices: &[],
+                positions: &[],
+                normals: &[],
+                uv_sets: Vec::new(),
+                tangents: &[],
+            });
+            continue;
+        }
+
+        // the other known sections belong to the last mesh
+        let known = [BOUNDS, INDICES, POSITIONS, NORMALS, UV_SET, TANGENTS];
+        if !known.contains(&&tag) {
+            continue;
+        }
+        let Some(mesh) = meshes.last_mut() else {
+            let tag = String::from_utf8_lossy(&tag);
+            return Err(BinaryError::Invalid(format!("{tag} is not in a mesh")));
+        };
+
+        match &tag {
+            BOUNDS => {
+                let sphere = Vec4::from_slice(&section.f32s(4)?);
+                mesh.bounds = (sphere.truncate(), sphere.w);
+            }
+            INDICES => mesh.indices = cast(section.bytes)?,
+            POSITIONS => mesh.positions = cast(section.bytes)?,
+            NORMALS => mesh.normals = cast(section.bytes)?,
+            UV_SET => mesh.uv_sets.push(cast(section.bytes)?),
+            _ => mesh.tangents = cast(section.bytes)?,
+        }
+    }
+
+    Ok(MeshFileView { sources, meshes })
+}
+
+// The content of a section as an array, which is aligned as the file is
+fn cast<T: bytemuck::Pod>(bytes: &[u8]) -> Result<&[T], BinaryError> {
+    bytemuck::try_cast_slice(bytes).map_err(|e| match e {
+        bytemuck::PodCastError::OutputSliceWouldHaveSlop => BinaryError::Truncated,
+        _ => BinaryError::Misaligned,
+    })
+}
+
+// FNV-1a, which is stable across platforms and compiler versions unlike `DefaultHasher`
+pub(super) fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    // The length is patched after the content is written, and then the content is padded
+    fn section(&mut self, tag: &[u8; 4], content: impl FnOnce(&mut Writer)) {
+        self.bytes.extend_from_slice(tag);
+        self.u32(0);
+        let length_offset = self.bytes.len();
+        self.u64(0);
+
+        content(self);
+
+        let length = (self.bytes.len() - length_offset - 8) as u64;
+        self.bytes[length_offset..length_offset + 8].copy_from_slice(&length.to_le_bytes());
+        let padded_length = self.bytes.len().next_multiple_of(ALIGNMENT);
+        self.bytes.resize(padded_length, 0);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32s(&mut self, values: &[f32]) {
+        for value in values {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn vectors<T: Copy, const N: usize>(&mut self, vectors: &[T], to_array: fn(&T) -> [f32; N]) {
+        self.bytes.reserve(vectors.len() * N * 4);
+        for vector in vectors {
+            self.f32s(&to_array(vector));
+        }
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn path(&mut self, path: &Path) {
+        self.string(&path.to_string_lossy());
+    }
+
+    fn material(&mut self, material: &ImportedMaterial) {
+        self.string(&material.name);
+
+        let m = &material.material;
+        self.f32s(&m.base_color.to_array());
+        self.f32s(&[m.metallic]);
+        self.f32s(&m.specular_reflectance.to_array());
+        self.f32s(&[m.roughness]);
+        self.f32s(&m.specular_tint.to_array());
+        self.f32s(&[0.0]);
+
+        let textures = &material.textures;
+        for texture in [
+            &textures.base_color,
+            &textures.specular,
+            &textures.roughness,
+            &textures.metallic,
+            &textures.normal,
+        ] {
+            self.u8(texture.is_some() as u8);
+            if let Some(texture) = texture {
+                self.path(texture);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], BinaryError> {
+        if self.bytes.len() < count {
+            return Err(BinaryError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, BinaryError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, BinaryError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, BinaryError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f32s(&mut self, count: usize) -> Result<Vec<f32>, BinaryError> {
+        let bytes = self.take(count * 4)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect())
+    }
+
+    fn string(&mut self) -> Result<String, BinaryError> {
+        let length = self.u32()? as usize;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| BinaryError::Invalid("a string is not UTF-8".to_string()))
+    }
+
+    fn optional_path(&mut self) -> Result<Option<PathBuf>, BinaryError> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => Ok(Some(PathBuf::from(self.string()?))),
+        }
+    }
+
+    fn material(&mut self) -> Result<ImportedMaterial, BinaryError> {
+        let name = self.string()?;
+        let m = self.f32s(12)?;
+        let material = Material {
+            base_color: Vec3::from_slice(&m[0..3]),
+            metallic: m[3],
+            specular_reflectance: Vec3::from_slice(&m[4..7]),
+            roughness: m[7],
+            specular_tint: Vec3::from_slice(&m[8..11]),
+            pad: 0,
+        };
+
+        let textures = MaterialTextures {
+            base_color: self.optional_path()?,
+            specular: self.optional_path()?,
+            roughness: self.optional_path()?,
+            metallic: self.optional_path()?,
+            normal: self.optional_path()?,
+        };
+
+        Ok(ImportedMaterial {
+            name,
+            material,
+            textures,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::primitives;
+
+    fn sample() -> MeshFile {
+        let material = ImportedMaterial {
+            name: "red".to_string(),
+            material: Material {
+                base_color: Vec3::new(1.0, 0.0, 0.0),
+                metallic: 0.5,
+                specular_reflectance: Vec3::splat(0.04),
+                roughness: 0.25,
+                specular_tint: Vec3::ONE,
+                pad: 0,
+            },
+            textures: MaterialTextures {
+                normal: Some(PathBuf::from("textures/normal.png")),
+                ..Default::default()
+            },
+        };
+        let mut sphere = primitives::uv_sphere(1.0, 8, 4);
+        sphere.add_uv_set(sphere.uv_sets()[0].clone());
+
+        let meshes = vec![
+            ImportedMesh {
+                resource: primitives::cuboid(Vec3::new(1.0, 2.0, 3.0)),
+                material: Some(material),
+                transform: Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0)),
+                repaired: Vec::new(),
+            },
+            ImportedMesh {
+                resource: sphere,
+                material: None,
+                transform: Mat4::IDENTITY,
+                repaired: Vec::new(),
+            },
+        ];
+        let sources = vec![SourceFile {
+            path: PathBuf::from("meshes/sample.obj"),
+            size: 1234,
+            modified: std::time::Duration::new(1_700_000_000, 123_456_789),
+        }];
+        MeshFile::new(sources, meshes)
+    }
+
+    fn assert_same_mesh(actual: &ImportedMesh, expected: &ImportedMesh) {
+        let (a, e) = (&actual.resource, &expected.resource);
+        assert_eq!(a.name(), e.name());
+        assert_eq!(a.indices(), e.indices());
+        assert_eq!(a.positions(), e.positions());
+        assert_eq!(a.normals(), e.normals());
+        assert_eq!(a.uv_sets(), e.uv_sets());
+        assert_eq!(a.tangents(), e.tangents());
+        assert_eq!(actual.material, expected.material);
+        assert_eq!(actual.transform, expected.transform);
+    }
+
+    // The bytes of the header checksum over `payload` written again
+    fn with_checksum(mut bytes: Vec<u8>) -> AlignedBytes {
+        let payload_checksum = checksum(&bytes[HEADER_SIZE..]);
+        bytes[24..32].copy_from_slice(&payload_checksum.to_le_bytes());
+        AlignedBytes::new(&bytes)
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = sample();
+        let bytes = AlignedBytes::new(&write(&file));
+        let read_file = read(&bytes).unwrap().to_mesh_file().unwrap();
+
+        assert_eq!(read_file.sources, file.sources);
+        assert_eq!(read_file.bounds, file.bounds);
+        assert_eq!(read_file.meshes.len(), 2);
+        for (actual, expected) in read_file.meshes.iter().zip(&file.meshes) {
+            assert_same_mesh(actual, expected);
+        }
+    }
+
+    #[test]
+    fn attributes_are_read_in_place() {
+        let bytes = AlignedBytes::new(&write(&sample()));
+        let view = read(&bytes).unwrap();
+
+        let range = bytes.as_ptr_range();
+        let mesh = &view.meshes[1];
+        assert_eq!(mesh.uv_sets.len(), 2);
+        for attribute in [
+            mesh.indices.as_ptr().cast::<u8>(),
+            mesh.positions.as_ptr().cast(),
+            mesh.normals.as_ptr().cast(),
+            mesh.uv_sets[1].as_ptr().cast(),
+            mesh.tangents.as_ptr().cast(),
+        ] {
+            assert!(range.contains(&attribute));
+            assert!((attribute as usize).is_multiple_of(ALIGNMENT));
+        }
+    }
+
+    #[test]
+    fn load_and_save() {
+        let path = std::env::temp_dir().join(format!("sandbox-binary-{}.mesh", std::process::id()));
+        let file = sample();
+        save(&path, &file).unwrap();
+        let loaded = load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.sources, file.sources);
+        assert_same_mesh(&loaded.meshes[0], &file.meshes[0]);
+    }
+
+    #[test]
+    fn corruption() {
+        let bytes = write(&sample());
+
+        // every byte of the payload is covered by the checksum
+        for offset in [
+            HEADER_SIZE,
+            HEADER_SIZE + 100,
+            bytes.len() / 2,
+            bytes.len() - 1,
+        ] {
+            let mut corrupted = bytes.clone();
+            corrupted[offset] ^= 0x10;
+            let e = read(&AlignedBytes::new(&corrupted)).unwrap_err();
+            assert!(matches!(e, BinaryError::Checksum), "{offset}: {e}");
+        }
+
+        let mut corrupted = bytes.clone();
+        corrupted[24] ^= 1;
+        let e = read(&AlignedBytes::new(&corrupted)).unwrap_err();
+        assert!(matches!(e, BinaryError::Checksum), "{e}");
+
+        let mut corrupted = bytes.clone();
+        corrupted[0] = b'X';
+        let e = read(&AlignedBytes::new(&corrupted)).unwrap_err();
+        assert!(matches!(e, BinaryError::Magic), "{e}");
+
+        let mut corrupted = bytes.clone();
+        corrupted[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let e = read(&AlignedBytes::new(&corrupted)).unwrap_err();
+        assert!(
+            matches!(e, BinaryError::Version(v) if v == VERSION + 1),
+            "{e}"
+        );
+    }
+
+    #[test]
+    fn truncation() {
+        let bytes = write(&sample());
+
+        for len in [
+            0,
+            4,
+            HEADER_SIZE - 1,
+            HEADER_SIZE,
+            HEADER_SIZE + 20,
+            bytes.len() - 1,
+        ] {
+            let e = read(&AlignedBytes::new(&bytes[..len])).unwrap_err();
+            assert!(matches!(e, BinaryError::Truncated), "{len}: {e}");
+        }
+    }
+
+    #[test]
+    fn truncated_sections() {
+        // a section longer than the payload, with a payload length and checksum that match
+        let mut bytes = write(&sample());
+        let first_length = HEADER_SIZE + 8;
+        bytes[first_length..first_length + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let e = read(&with_checksum(bytes)).unwrap_err();
+        assert!(matches!(e, BinaryError::Truncated), "{e}");
+
+        // positions that end in the middle of a vector
+        let mut writer = Writer::default();
+        writer.section(MESH, |w| {
+            w.string("mesh");
+            w.f32s(&Mat4::IDENTITY.to_cols_array());
+            w.u8(0);
+        });
+        writer.section(POSITIONS, |w| w.f32s(&[0.0; 4]));
+        let mut bytes = write(&MeshFile::new(Vec::new(), Vec::new()));
+        bytes.truncate(HEADER_SIZE);
+        bytes[16..24].copy_from_slice(&(writer.bytes.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&writer.bytes);
+        let e = read(&with_checksum(bytes)).unwrap_err();
+        assert!(matches!(e, BinaryError::Truncated), "{e}");
+    }
+
+    #[test]
+    fn misaligned() {
+        let bytes = write(&sample());
+        let shifted = AlignedBytes::new(&[&[0], &bytes[..]].concat());
+        let e = read(&shifted[1..]).unwrap_err();
+        assert!(matches!(e, BinaryError::Misaligned), "{e}");
+    }
+
+    #[test]
+    fn invalid_meshes() {
+        // an index out of range, which the checksum does not catch
+        let mut file = sample();
+        file.meshes[0].resource.indices[0] = 1000;
+        let bytes = AlignedBytes::new(&write(&file));
+        let e = read(&bytes).unwrap().to_mesh_file().unwrap_err();
+        assert!(matches!(e, BinaryError::Invalid(_)), "{e}");
+
+        let mut file = sample();
+        file.meshes[0].resource.tangents.pop();
+        let bytes = AlignedBytes::new(&write(&file));
+        let e = read(&bytes).unwrap().to_mesh_file().unwrap_err();
+        assert!(e.to_string().contains("tangents"), "{e}");
+    }
+
+    #[test]
+    fn unknown_sections_are_skipped() {
+        let mut writer = Writer::default();
+        writer.section(b"NEW!", |w| w.string("added in a later version"));
+        let mut bytes = write(&sample());
+        bytes.extend_from_slice(&writer.bytes);
+        let payload_length = (bytes.len() - HEADER_SIZE) as u64;
+        bytes[16..24].copy_from_slice(&payload_length.to_le_bytes());
+
+        let file = read(&with_checksum(bytes)).unwrap().to_mesh_file().unwrap();
+        assert_eq!(file.meshes.len(), 2);
+    }
+}
@@ -0,0 +1,200 @@
+// A directory of the meshes imported from text files in the binary format, so that the files are
+// only parsed again after they change
+
+use std::path::{Path, PathBuf};
+
+use super::binary::{self, BinaryError, MeshFile, SourceFile};
+use super::{ImportedMesh, ValidationPolicy};
+
+pub struct MeshCache {
+    dir: PathBuf,
+}
+
+impl MeshCache {
+    /// The directory is created by the first `store`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        MeshCache { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The meshes imported from `source` with `policy`, None if they are not cached or any of
+    /// their source files has changed since
+    /// Fails if the entry is broken, which the next `store` replaces
+    pub fn load(
+        &self,
+        source: &Path,
+        policy: ValidationPolicy,
+    ) -> Result<Option<Vec<ImportedMesh>>, BinaryError> {
+        let path = self.entry_path(source, policy).map_err(BinaryError::Io)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let file = binary::load(&path)?;
+        let current = file.sources.iter().all(SourceFile::is_current);
+        Ok(current.then_some(file.meshes))
+    }
+
+    /// Caches the meshes imported from `source` with `policy`, which are loaded again until
+    /// `source` or any of `dependencies` changes
+    pub fn store(
+        &self,
+        source: &Path,
+        dependencies: &[PathBuf],
+        policy: ValidationPolicy,
+        meshes: &[ImportedMesh],
+    ) -> std::io::Result<()> {
+        let sources = std::iter::once(source)
+            .chain(dependencies.iter().map(PathBuf::as_path))
+            .map(SourceFile::stat)
+            .collect::<std::io::Result<_>>()?;
+
+        std::fs::create_dir_all(&self.dir)?;
+        let file = MeshFile::new(sources, meshes.to_vec());
+        binary::save(&self.entry_path(source, policy)?, &file)
+    }
+
+    // Named by a hash of the contents and the modification time of the source, so that an edit
+    // keeping the size and the modification time is still noticed, and of the policy, as meshes
+    // imported with different policies differ
+    fn entry_path(&self, source: &Path, policy: ValidationPolicy) -> std::io::Result<PathBuf> {
+        let modified = SourceFile::stat(source)?.modified;
+        let mut key = std::fs::read(source)?;
+        key.extend_from_slice(&modified.as_secs().to_le_bytes());
+        key.extend_from_slice(&modified.subsec_nanos().to_le_bytes());
+        key.push(policy as u8);
+
+        Ok(self
+            .dir
+            .join(format!("{:016x}.mesh", binary::checksum(&key))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::mesh::primitives;
+
+    // A cache next to a source file, in a directory removed when the test ends
+    struct Fixture {
+        dir: PathBuf,
+        source: PathBuf,
+        cache: MeshCache,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("sandbox-cache-{name}-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let source = dir.join("mesh.obj");
+            std::fs::write(&source, "o cube\n").unwrap();
+            let cache = MeshCache::new(dir.join("cache"));
+            Fixture { dir, source, cache }
+        }
+
+        fn meshes() -> Vec<ImportedMesh> {
+            vec![ImportedMesh {
+                resource: primitives::cuboid(Vec3::ONE),
+                material: None,
+                transform: glam::Mat4::IDENTITY,
+                repaired: Vec::new(),
+            }]
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn store_and_load() {
+        let f = Fixture::new("store");
+        let policy = ValidationPolicy::Repair;
+        assert!(f.cache.load(&f.source, policy).unwrap().is_none());
+
+        f.cache
+            .store(&f.source, &[], policy, &Fixture::meshes())
+            .unwrap();
+        let meshes = f.cache.load(&f.source, policy).unwrap().unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(
+            meshes[0].resource.positions(),
+            Fixture::meshes()[0].resource.positions()
+        );
+
+        // meshes imported with another policy are not shared
+        assert!(f
+            .cache
+            .load(&f.source, ValidationPolicy::Reject)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn changed_sources_are_not_loaded() {
+        let f = Fixture::new("changed");
+        let policy = ValidationPolicy::Repair;
+        let dependencies = [f.dir.join("mesh.mtl")];
+        let library = &dependencies[0];
+        std::fs::write(library, "newmtl red\n").unwrap();
+        f.cache
+            .store(&f.source, &dependencies, policy, &Fixture::meshes())
+            .unwrap();
+        assert!(f.cache.load(&f.source, policy).unwrap().is_some());
+
+        // the key changes with the contents of the source
+        let entry = f.cache.entry_path(&f.source, policy).unwrap();
+        std::fs::write(&f.source, "o moved\n").unwrap();
+        assert_ne!(f.cache.entry_path(&f.source, policy).unwrap(), entry);
+        assert!(f.cache.load(&f.source, policy).unwrap().is_none());
+
+        // the entry records the dependencies
+        std::fs::write(&f.source, "o cube\n").unwrap();
+        f.cache
+            .store(&f.source, &dependencies, policy, &Fixture::meshes())
+            .unwrap();
+        std::fs::write(library, "newmtl green\n").unwrap();
+        assert!(f.cache.load(&f.source, policy).unwrap().is_none());
+    }
+
+    #[test]
+    fn broken_entries_fail() {
+        let f = Fixture::new("broken");
+        let policy = ValidationPolicy::Repair;
+        f.cache
+            .store(&f.source, &[], policy, &Fixture::meshes())
+            .unwrap();
+
+        let entry = f.cache.entry_path(&f.source, policy).unwrap();
+        let mut bytes = std::fs::read(&entry).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std::fs::write(&entry, &bytes).unwrap();
+        let e = f.cache.load(&f.source, policy).unwrap_err();
+        assert!(matches!(e, BinaryError::Checksum), "{e}");
+
+        std::fs::write(&entry, &bytes[..bytes.len() / 2]).unwrap();
+        let e = f.cache.load(&f.source, policy).unwrap_err();
+        assert!(matches!(e, BinaryError::Truncated), "{e}");
+
+        // and are replaced by the next store
+        f.cache
+            .store(&f.source, &[], policy, &Fixture::meshes())
+            .unwrap();
+        assert!(f.cache.load(&f.source, policy).unwrap().is_some());
+
+        let e = f
+            .cache
+            .load(&f.dir.join("missing.obj"), policy)
+            .unwrap_err();
+        assert!(matches!(e, BinaryError::Io(_)), "{e}");
+    }
+}
@@ -0,0 +1,1521 @@
+// glTF 2.0 importer for .gltf files, whose buffers are external files or data URIs, and .glb files
+// Every triangle primitive of the meshes referenced by the nodes of the default scene is imported,
+// with the world transform of its node and its metallic-roughness material
+// Texture images, skins and morph targets are not supported
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+
+use super::{
+    ImportedMaterial, ImportedMesh, Material, MaterialTextures, MeshResource, ValidationError,
+    ValidationPolicy,
+};
+use crate::json::{self, Value};
+
+const GLB_MAGIC: u32 = 0x4654_6c67; // "glTF"
+const GLB_CHUNK_JSON: u32 = 0x4e4f_534a; // "JSON"
+const GLB_CHUNK_BIN: u32 = 0x004e_4942; // "BIN\0"
+
+const MODE_TRIANGLES: u64 = 4;
+
+const COMPONENT_UNSIGNED_BYTE: u64 = 5121;
+const COMPONENT_UNSIGNED_SHORT: u64 = 5123;
+const COMPONENT_UNSIGNED_INT: u64 = 5125;
+const COMPONENT_FLOAT: u64 = 5126;
+
+#[derive(Debug)]
+pub enum GltfError {
+    Io(PathBuf, std::io::Error),
+    Json(json::ParseError),
+    /// The file does not follow the specification
+    Invalid(String),
+    /// A valid feature that this importer does not handle
+    Unsupported(String),
+    Validation(ValidationError),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GltfError::Io(path, e) => write!(f, "failed to read {}: {e}", path.display()),
+            GltfError::Json(e) => write!(f, "invalid JSON: {e}"),
+            GltfError::Invalid(message) => write!(f, "invalid glTF: {message}"),
+            GltfError::Unsupported(message) => write!(f, "unsupported glTF: {message}"),
+            GltfError::Validation(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GltfError::Io(_, e) => Some(e),
+            GltfError::Json(e) => Some(e),
+            GltfError::Validation(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<json::ParseError> for GltfError {
+    fn from(e: json::ParseError) -> Self {
+        GltfError::Json(e)
+    }
+}
+
+/// Loads a .gltf or .glb file, the names of the meshes are prefixed with `path`
+pub fn load(path: &Path, policy: ValidationPolicy) -> Result<Vec<ImportedMesh>, GltfError> {
+    let bytes = std::fs::read(path).map_err(|e| GltfError::Io(path.to_path_buf(), e))?;
+    let mut meshes = parse(&bytes, path.parent(), policy)?;
+
+    for mesh in &mut meshes {
+        let name = format!("{}:{}", path.display(), mesh.resource.name());
+        mesh.resource.set_name(name);
+    }
+
+    Ok(meshes)
+}
+
+/// Parses the contents of a .gltf or .glb file
+/// External buffers are read from `base_dir`, and cannot be used if it is None
+pub fn parse(
+    bytes: &[u8],
+    base_dir: Option<&Path>,
+    policy: ValidationPolicy,
+) -> Result<Vec<ImportedMesh>, GltfError> {
+    let (text, bin) = if bytes.starts_with(&GLB_MAGIC.to_le_bytes()) {
+        parse_glb(bytes)?
+    } else {
+        (bytes, None)
+    };
+
+    let text = std::str::from_utf8(text)
+        .map_err(|e| GltfError::Invalid(format!("the JSON is not UTF-8: {e}")))?;
+    let doc = json::parse(text)?;
+
+    check_version(&doc)?;
+
+    if let Some(required) = doc.get("extensionsRequired").and_then(Value::as_array) {
+        let names: Vec<_> = required.iter().filter_map(Value::as_str).collect();
+        if !names.is_empty() {
+            return Err(GltfError::Unsupported(format!(
+                "required extensions {}",
+                names.join(", ")
+            )));
+        }
+    }
+
+    let buffers = load_buffers(&doc, bin, base_dir)?;
+    let importer = Importer {
+        doc: &doc,
+        buffers,
+        meshes: Vec::new(),
+        policy,
+    };
+
+    importer.import()
+}
+
+// Returns the JSON chunk and the BIN chunk
+fn parse_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), GltfError> {
+    let u32_at = |offset: usize| -> Result<u32, GltfError> {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| GltfError::Invalid("the GLB file is truncated".to_string()))
+    };
+
+    let version = u32_at(4)?;
+    if version != 2 {
+        return Err(GltfError::Unsupported(format!("GLB version {version}")));
+    }
+
+    let length = (u32_at(8)? as usize).min(bytes.len());
+    let mut json = None;
+    let mut bin = None;
+
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = u32_at(offset)? as usize;
+        let chunk_type = u32_at(offset + 4)?;
+        let start = offset + 8;
+        let Some(chunk) = bytes.get(start..start + chunk_length) else {
+            return Err(GltfError::Invalid("a GLB chunk is truncated".to_string()));
+        };
+
+        match chunk_type {
+            GLB_CHUNK_JSON if json.is_none() => json = Some(chunk),
+            GLB_CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+            // unknown chunks must be ignored
+            _ => (),
+        }
+
+        // chunks are padded to 4 bytes
+        offset = start + chunk_length.next_multiple_of(4);
+    }
+
+    let json = json.ok_or_else(|| GltfError::Invalid("the GLB has no JSON chunk".to_string()))?;
+    Ok((json, bin))
+}
+
+fn check_version(doc: &Value) -> Result<(), GltfError> {
+    let version = doc
+        .get("asset")
+        .and_then(|asset| asset.get("version"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| GltfError::Invalid("asset.version is missing".to_string()))?;
+
+    if version.split('.').next() != Some("2") {
+        return Err(GltfError::Unsupported(format!("glTF version {version}")));
+    }
+
+    Ok(())
+}
+
+fn load_buffers(
+    doc: &Value,
+    bin: Option<&[u8]>,
+    base_dir: Option<&Path>,
+) -> Result<Vec<Vec<u8>>, GltfError> {
+    let buffers = array(doc, "buffers")?;
+
+    buffers
+        .iter()
+        .enumerate()
+        .map(|(i, buffer)| {
+            let path = format!("buffers[{i}]");
+            let byte_length = required_usize(buffer, "byteLength", &path)?;
+
+            let data = match buffer.get("uri").and_then(Value::as_str) {
+                Some(uri) => load_uri(uri, base_dir, &path)?,
+                // only the first buffer of a GLB can refer to the BIN chunk
+                None if i == 0 => bin
+                    .ok_or_else(|| invalid(&path, "no uri and no GLB BIN chunk"))?
+                    .to_vec(),
+                None => return Err(invalid(&path, "no uri")),
+            };
+
+            if data.len() < byte_length {
+                return Err(invalid(
+                    &path,
+                    &format!(
+                        "{} bytes are shorter than byteLength {byte_length}",
+                        data.len()
+                    ),
+                ));
+            }
+
+            Ok(data)
+        })
+        .collect()
+}
+
+fn load_uri(uri: &str, base_dir: Option<&Path>, path: &str) -> Result<Vec<u8>, GltfError> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let Some((_, encoded)) = data.split_once(";base64,") else {
+            return Err(GltfError::Unsupported(format!(
+                "{path}: data URIs must be base64"
+            )));
+        };
+        return decode_base64(encoded).ok_or_else(|| invalid(path, "invalid base64 data"));
+    }
+
+    let Some(base_dir) = base_dir else {
+        return Err(GltfError::Unsupported(format!(
+            "{path}: external buffers need the directory of the file"
+        )));
+    };
+
+    let file = base_dir.join(decode_percent(uri));
+    std::fs::read(&file).map_err(|e| GltfError::Io(file, e))
+}
+
+// The meshes are decoded when a node refers to them first, and copied for other nodes
+struct Importer<'a> {
+    doc: &'a Value,
+    buffers: Vec<Vec<u8>>,
+    meshes: Vec<Option<Vec<Primitive>>>,
+    policy: ValidationPolicy,
+}
+
+#[derive(Clone)]
+struct Primitive {
+    indices: Vec<u32>,
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uv_sets: Vec<Vec<Vec2>>,
+    tangents: Vec<Vec4>,
+    material: ImportedMaterial,
+}
+
+impl Importer<'_> {
+    fn import(mut self) -> Result<Vec<ImportedMesh>, GltfError> {
+        let nodes = array(self.doc, "nodes")?;
+        self.meshes = vec![None; array(self.doc, "meshes")?.len()];
+
+        let roots = self.root_nodes(nodes)?;
+
+        let mut imported = Vec::new();
+        // the node indices from the root, to detect cycles
+        let mut stack: Vec<(usize, Mat4, Vec<usize>)> = roots
+            .into_iter()
+            .rev()
+            .map(|node| (node, Mat4::IDENTITY, Vec::new()))
+            .collect();
+
+        while let Some((index, parent_transform, mut ancestors)) = stack.pop() {
+            let path = format!("nodes[{index}]");
+            let node = nodes
+                .get(index)
+                .ok_or_else(|| invalid(&path, "the node does not exist"))?;
+
+            if ancestors.contains(&index) {
+                return Err(invalid(&path, "the node hierarchy has a cycle"));
+            }
+            ancestors.push(index);
+
+            let transform = parent_transform * node_transform(node, &path)?;
+
+            if let Some(mesh) = optional_usize(node, "mesh", &path)? {
+                let name = node_name(self.doc, node, index, mesh);
+                let primitives = self.mesh(mesh)?;
+                let count = primitives.len();
+
+                for (i, primitive) in primitives.into_iter().enumerate() {
+                    let name = match count {
+                        1 => name.clone(),
+                        _ => format!("{name}[{i}]"),
+                    };
+
+                    let mut resource = MeshResource {
+                        indices: primitive.indices,
+                        positions: primitive.positions,
+                        normals: primitive.normals,
+                        uv_sets: primitive.uv_sets,
+                        tangents: primitive.tangents,
+                        name,
+                    };
+                    // the specification requires flat normals for primitives without them, and
+                    // the tangents to be ignored then
+                    if !resource.has_normals() {
+                        resource.tangents.clear();
+                        resource.compute_flat_normals();
+                    }
+                    resource.generate_missing_tangents();
+                    let repaired = resource
+                        .validate_with(self.policy)
+                        .map_err(GltfError::Validation)?;
+
+                    imported.push(ImportedMesh {
+                        resource,
+                        material: Some(primitive.material),
+                        transform,
+                        repaired,
+                    });
+                }
+            }
+
+            if let Some(children) = node.get("children").and_then(Value::as_array) {
+                for child in children.iter().rev() {
+                    let child = as_usize(child, &format!("{path}.children"))?;
+                    stack.push((child, transform, ancestors.clone()));
+                }
+            }
+        }
+
+        Ok(imported)
+    }
+
+    // The nodes of the default scene, or every node without a parent if there is no scene
+    fn root_nodes(&self, nodes: &[Value]) -> Result<Vec<usize>, GltfError> {
+        let scenes = array(self.doc, "scenes")?;
+
+        if !scenes.is_empty() {
+            let scene = optional_usize(self.doc, "scene", "")?.unwrap_or(0);
+            let path = format!("scenes[{scene}]");
+            let scene = scenes
+                .get(scene)
+                .ok_or_else(|| invalid(&path, "the scene does not exist"))?;
+
+            return array(scene, "nodes")?
+                .iter()
+                .map(|node| as_usize(node, &format!("{path}.nodes")))
+                .collect();
+        }
+
+        let mut is_child = vec![false; nodes.len()];
+        for node in nodes {
+            for child in node
+                .get("children")
+                .and_then(Value::as_array)
+                .unwrap_or(&[])
+            {
+                if let Some(flag) = child.as_f64().and_then(|i| is_child.get_mut(i as usize)) {
+                    *flag = true;
+                }
+            }
+        }
+
+        Ok((0..nodes.len()).filter(|i| !is_child[*i]).collect())
+    }
+
+    fn mesh(&mut self, index: usize) -> Result<Vec<Primitive>, GltfError> {
+        let path = format!("meshes[{index}]");
+        let slot = self
+            .meshes
+            .get(index)
+            .ok_or_else(|| invalid(&path, "the mesh does not exist"))?;
+
+        if let Some(primitives) = slot {
+            return Ok(primitives.clone());
+        }
+
+        let mesh = &array(self.doc, "meshes")?[index];
+        let mut primitives = Vec::new();
+
+        for (i, primitive) in array(mesh, "primitives")?.iter().enumerate() {
+            let path = format!("{path}.primitives[{i}]");
+
+            // points and lines have no surface to render
+            let mode = optional_usize(primitive, "mode", &path)?.unwrap_or(MODE_TRIANGLES as usize);
+            if mode as u64 != MODE_TRIANGLES {
+                continue;
+            }
+
+            primitives.push(self.primitive(primitive, &path)?);
+        }
+
+        self.meshes[index] = Some(primitives.clone());
+        Ok(primitives)
+    }
+
+    fn primitive(&self, primitive: &Value, path: &str) -> Result<Primitive, GltfError> {
+        let attributes = primitive
+            .get("attributes")
+            .ok_or_else(|| invalid(path, "attributes is missing"))?;
+
+        let position = required_usize(attributes, "POSITION", path)?;
+        let positions = self.read_vec3(position)?;
+
+        let normals = match optional_usize(attributes, "NORMAL", path)? {
+            Some(normal) => self.read_vec3(normal)?,
+            None => Vec::new(),
+        };
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return Err(invalid(path, "NORMAL and POSITION have different counts"));
+        }
+
+        let mut uv_sets = Vec::new();
+        while let Some(uv) =
+            optional_usize(attributes, &format!("TEXCOORD_{}", uv_sets.len()), path)?
+        {
+            let uvs: Vec<_> = self
+                .read_vectors::<2>(uv)?
+                .into_iter()
+                .map(Vec2::from_array)
+                .collect();
+            if uvs.len() != positions.len() {
+                return Err(invalid(path, "TEXCOORD and POSITION have different counts"));
+            }
+            uv_sets.push(uvs);
+        }
+
+        let tangents = match optional_usize(attributes, "TANGENT", path)? {
+            Some(tangent) => self
+                .read_vectors::<4>(tangent)?
+                .into_iter()
+                .map(Vec4::from_array)
+                .collect(),
+            None => Vec::new(),
+        };
+        if !tangents.is_empty() && tangents.len() != positions.len() {
+            return Err(invalid(path, "TANGENT and POSITION have different counts"));
+        }
+
+        let indices = match optional_usize(primitive, "indices", path)? {
+            Some(indices) => self.read_indices(indices)?,
+            None => (0..positions.len() as u32).collect(),
+        };
+        if indices.len() % 3 != 0 {
+            return Err(invalid(path, "the index count is not a multiple of 3"));
+        }
+        if let Some(index) = indices.iter().find(|i| **i as usize >= positions.len()) {
+            return Err(invalid(
+                path,
+                &format!(
+                    "the index {index} is out of the {} vertices",
+                    positions.len()
+                ),
+            ));
+        }
+
+        let material = match optional_usize(primitive, "material", path)? {
+            Some(material) => {
+                let materials = array(self.doc, "materials")?;
+                let path = format!("materials[{material}]");
+                let material = materials
+                    .get(material)
+                    .ok_or_else(|| invalid(&path, "the material does not exist"))?;
+                let name = match material.get("name").and_then(Value::as_str) {
+                    Some(name) => name.to_string(),
+                    None => path.clone(),
+                };
+                ImportedMaterial {
+                    name,
+                    material: convert_material(material, &path)?,
+                    textures: MaterialTextures::default(),
+                }
+            }
+            None => ImportedMaterial {
+                name: "default".to_string(),
+                material: convert_material(&Value::Object(Vec::new()), "")?,
+                textures: MaterialTextures::default(),
+            },
+        };
+
+        Ok(Primitive {
+            indices,
+            positions,
+            normals,
+            uv_sets,
+            tangents,
+            material,
+        })
+    }
+
+    fn read_vec3(&self, accessor: usize) -> Result<Vec<Vec3>, GltfError> {
+        if self.accessor(accessor)?.component_type != COMPONENT_FLOAT {
+            return Err(GltfError::Unsupported(format!(
+                "accessors[{accessor}]: only float positions and normals are supported"
+            )));
+        }
+
+        let vectors = self.read_vectors::<3>(accessor)?;
+        Ok(vectors.into_iter().map(Vec3::from_array).collect())
+    }
+
+    // Float vectors, or unsigned normalized integers as allowed for TEXCOORD_n
+    fn read_vectors<const N: usize>(&self, accessor: usize) -> Result<Vec<[f32; N]>, GltfError> {
+        let view = self.accessor(accessor)?;
+        if view.component_count != N {
+            return Err(invalid(
+                &format!("accessors[{accessor}]"),
+                &format!("expected {N} components"),
+            ));
+        }
+
+        let (size, read): (usize, fn(&[u8]) -> f32) = match view.component_type {
+            COMPONENT_FLOAT => (4, |b| f32::from_le_bytes(b.try_into().unwrap())),
+            COMPONENT_UNSIGNED_BYTE => (1, |b| b[0] as f32 / u8::MAX as f32),
+            COMPONENT_UNSIGNED_SHORT => (2, |b| {
+                u16::from_le_bytes(b.try_into().unwrap()) as f32 / u16::MAX as f32
+            }),
+            _ => {
+                return Err(GltfError::Unsupported(format!(
+                    "accessors[{accessor}]: only float and unsigned normalized vertex attributes are supported"
+                )))
+            }
+        };
+
+        Ok(view
+            .elements()
+            .map(|element| {
+                let mut v = [0.0; N];
+                for (c, bytes) in element.chunks_exact(size).enumerate() {
+                    v[c] = read(bytes);
+                }
+                v
+            })
+            .collect())
+    }
+
+    fn read_indices(&self, accessor: usize) -> Result<Vec<u32>, GltfError> {
+        let view = self.accessor(accessor)?;
+        if view.component_count != 1 {
+            return Err(invalid(
+                &format!("accessors[{accessor}]"),
+                "indices must be SCALAR",
+            ));
+        }
+
+        match view.component_type {
+            COMPONENT_UNSIGNED_BYTE | COMPONENT_UNSIGNED_SHORT | COMPONENT_UNSIGNED_INT => (),
+            _ => {
+                return Err(invalid(
+                    &format!("accessors[{accessor}]"),
+                    "indices must be unsigned integers",
+                ))
+            }
+        }
+
+        Ok(view.elements().map(read_unsigned).collect())
+    }
+
+    // The tightly packed elements of an accessor, with the sparse elements substituted
+    fn accessor(&self, index: usize) -> Result<Accessor, GltfError> {
+        let path = format!("accessors[{index}]");
+        let accessor = array(self.doc, "accessors")?
+            .get(index)
+            .ok_or_else(|| invalid(&path, "the accessor does not exist"))?;
+
+        let component_type = required_usize(accessor, "componentType", &path)? as u64;
+        let component_size = match component_type {
+            5120 | COMPONENT_UNSIGNED_BYTE => 1,
+            5122 | COMPONENT_UNSIGNED_SHORT => 2,
+            COMPONENT_UNSIGNED_INT | COMPONENT_FLOAT => 4,
+            _ => return Err(invalid(&path, "unknown componentType")),
+        };
+
+        let component_count = match accessor.get("type").and_then(Value::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(invalid(&path, "unknown type")),
+        };
+
+        let count = required_usize(accessor, "count", &path)?;
+        let element_size = component_size * component_count;
+
+        // the elements are zeros without a buffer view
+        let mut data = vec![0; count * element_size];
+
+        if let Some(view_index) = optional_usize(accessor, "bufferView", &path)? {
+            let (view, stride) = self.buffer_view(view_index)?;
+            let stride = stride.unwrap_or(element_size);
+            let offset = optional_usize(accessor, "byteOffset", &path)?.unwrap_or(0);
+
+            if stride < element_size {
+                return Err(invalid(
+                    &format!("bufferViews[{view_index}]"),
+                    "byteStride is smaller than the element",
+                ));
+            }
+
+            let size = match count {
+                0 => 0,
+                _ => offset + stride * (count - 1) + element_size,
+            };
+            if size > view.len() {
+                return Err(invalid(&path, "the accessor is out of the buffer view"));
+            }
+
+            for (i, element) in data.chunks_exact_mut(element_size).enumerate() {
+                let start = offset + stride * i;
+                element.copy_from_slice(&view[start..start + element_size]);
+            }
+        }
+
+        if let Some(sparse) = accessor.get("sparse") {
+            self.substitute_sparse(sparse, &mut data, element_size, &format!("{path}.sparse"))?;
+        }
+
+        Ok(Accessor {
+            data,
+            element_size,
+            component_type,
+            component_count,
+        })
+    }
+
+    // Overwrites the elements listed by the indices of a sparse accessor with its values
+    fn substitute_sparse(
+        &self,
+        sparse: &Value,
+        data: &mut [u8],
+        element_size: usize,
+        path: &str,
+    ) -> Result<(), GltfError> {
+        let count = required_usize(sparse, "count", path)?;
+
+        let indices_path = format!("{path}.indices");
+        let indices = sparse
+            .get("indices")
+            .ok_or_else(|| invalid(path, "indices is missing"))?;
+        let index_size = match required_usize(indices, "componentType", &indices_path)? as u64 {
+            COMPONENT_UNSIGNED_BYTE => 1,
+            COMPONENT_UNSIGNED_SHORT => 2,
+            COMPONENT_UNSIGNED_INT => 4,
+            _ => return Err(invalid(&indices_path, "indices must be unsigned integers")),
+        };
+        let indices = self.packed_elements(indices, count * index_size, &indices_path)?;
+
+        let values_path = format!("{path}.values");
+        let values = sparse
+            .get("values")
+            .ok_or_else(|| invalid(path, "values is missing"))?;
+        let values = self.packed_elements(values, count * element_size, &values_path)?;
+
+        for (index, value) in indices
+            .chunks_exact(index_size)
+            .zip(values.chunks_exact(element_size))
+        {
+            let index = read_unsigned(index) as usize;
+            let Some(element) = data.get_mut(index * element_size..(index + 1) * element_size)
+            else {
+                return Err(invalid(
+                    &indices_path,
+                    &format!("the index {index} is out of the accessor"),
+                ));
+            };
+            element.copy_from_slice(value);
+        }
+
+        Ok(())
+    }
+
+    // The `size` bytes at the byteOffset of the bufferView of `value`
+    fn packed_elements(&self, value: &Value, size: usize, path: &str) -> Result<&[u8], GltfError> {
+        let (view, _) = self.buffer_view(required_usize(value, "bufferView", path)?)?;
+        let offset = optional_usize(value, "byteOffset", path)?.unwrap_or(0);
+
+        view.get(offset..offset + size)
+            .ok_or_else(|| invalid(path, "the elements are out of the buffer view"))
+    }
+
+    // The bytes of a buffer view, and its byteStride
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), GltfError> {
+        let path = format!("bufferViews[{index}]");
+        let view = array(self.doc, "bufferViews")?
+            .get(index)
+            .ok_or_else(|| invalid(&path, "the buffer view does not exist"))?;
+
+        let buffer = required_usize(view, "buffer", &path)?;
+        let offset = optional_usize(view, "byteOffset", &path)?.unwrap_or(0);
+        let length = required_usize(view, "byteLength", &path)?;
+        let stride = optional_usize(view, "byteStride", &path)?;
+
+        let buffer = self
+            .buffers
+            .get(buffer)
+            .ok_or_else(|| invalid(&path, "the buffer does not exist"))?;
+
+        let bytes = buffer
+            .get(offset..offset + length)
+            .ok_or_else(|| invalid(&path, "the view is out of the buffer"))?;
+
+        Ok((bytes, stride))
+    }
+}
+
+struct Accessor {
+    data: Vec<u8>,
+    element_size: usize,
+    component_type: u64,
+    component_count: usize,
+}
+
+impl Accessor {
+    fn elements(&self) -> std::slice::ChunksExact<'_, u8> {
+        self.data.chunks_exact(self.element_size)
+    }
+}
+
+// Little-endian unsigned integer of 1, 2 or 4 bytes
+fn read_unsigned(bytes: &[u8]) -> u32 {
+    match *bytes {
+        [b] => b as u32,
+        [b0, b1] => u16::from_le_bytes([b0, b1]) as u32,
+        [b0, b1, b2, b3] => u32::from_le_bytes([b0, b1, b2, b3]),
+        _ => unreachable!("unsigned integers have 1, 2 or 4 bytes"),
+    }
+}
+
+fn node_name(doc: &Value, node: &Value, index: usize, mesh: usize) -> String {
+    let mesh_name = doc
+        .get("meshes")
+        .and_then(|meshes| meshes.as_array()?.get(mesh)?.get("name")?.as_str());
+
+    match node.get("name").and_then(Value::as_str).or(mesh_name) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => format!("node{index}"),
+    }
+}
+
+fn node_transform(node: &Value, path: &str) -> Result<Mat4, GltfError> {
+    if let Some(matrix) = node.get("matrix") {
+        // column-major, same as glam
+        let m = numbers::<16>(matrix, &format!("{path}.matrix"))?;
+        return Ok(Mat4::from_cols_array(&m));
+    }
+
+    let translation = match node.get("translation") {
+        Some(t) => Vec3::from_array(numbers(t, &format!("{path}.translation"))?),
+        None => Vec3::ZERO,
+    };
+    let rotation = match node.get("rotation") {
+        Some(r) => Quat::from_array(numbers(r, &format!("{path}.rotation"))?).normalize(),
+        None => Quat::IDENTITY,
+    };
+    let scale = match node.get("scale") {
+        Some(s) => Vec3::from_array(numbers(s, &format!("{path}.scale"))?),
+        None => Vec3::ONE,
+    };
+
+    Ok(Mat4::from_scale_rotation_translation(
+        scale,
+        rotation,
+        translation,
+    ))
+}
+
+// Metals reflect their base color, and the specular term is only used for metals by the BRDF
+fn convert_material(material: &Value, path: &str) -> Result<Material, GltfError> {
+    let pbr = material.get("pbrMetallicRoughness");
+    let factor = |key: &str, default: f32| -> Result<f32, GltfError> {
+        match pbr.and_then(|pbr| pbr.get(key)) {
+            Some(value) => value
+                .as_f64()
+                .map(|v| v as f32)
+                .ok_or_else(|| invalid(&format!("{path}.{key}"), "expected a number")),
+            None => Ok(default),
+        }
+    };
+
+    let base_color = match pbr.and_then(|pbr| pbr.get("baseColorFactor")) {
+        Some(color) => numbers::<4>(color, &format!("{path}.baseColorFactor"))?,
+        None => [1.0; 4],
+    };
+    let base_color = Vec3::new(base_color[0], base_color[1], base_color[2]);
+
+    Ok(Material {
+        base_color,
+        metallic: factor("metallicFactor", 1.0)?.clamp(0.0, 1.0),
+        specular_reflectance: base_color,
+        roughness: factor("roughnessFactor", 1.0)?.clamp(0.0, 1.0),
+        specular_tint: base_color,
+        pad: Default::default(),
+    })
+}
+
+fn numbers<const N: usize>(value: &Value, path: &str) -> Result<[f32; N], GltfError> {
+    let elements = value
+        .as_array()
+        .filter(|elements| elements.len() == N)
+        .ok_or_else(|| invalid(path, &format!("expected an array of {N} numbers")))?;
+
+    let mut numbers = [0.0; N];
+    for (number, element) in numbers.iter_mut().zip(elements) {
+        *number = element
+            .as_f64()
+            .ok_or_else(|| invalid(path, "expected a number"))? as f32;
+    }
+
+    Ok(numbers)
+}
+
+// Missing arrays are empty
+fn array<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], GltfError> {
+    match value.get(key) {
+        Some(array) => array
+            .as_array()
+            .ok_or_else(|| invalid(key, "expected an array")),
+        None => Ok(&[]),
+    }
+}
+
+fn optional_usize(value: &Value, key: &str, path: &str) -> Result<Option<usize>, GltfError> {
+    value
+        .get(key)
+        .map(|v| as_usize(v, &format!("{path}.{key}")))
+        .transpose()
+}
+
+fn required_usize(value: &Value, key: &str, path: &str) -> Result<usize, GltfError> {
+    optional_usize(value, key, path)?.ok_or_else(|| invalid(path, &format!("{key} is missing")))
+}
+
+fn as_usize(value: &Value, path: &str) -> Result<usize, GltfError> {
+    match value.as_f64() {
+        Some(n) if n >= 0.0 && n.fract() == 0.0 && n <= u32::MAX as f64 => Ok(n as usize),
+        _ => Err(invalid(path, "expected a non-negative integer")),
+    }
+}
+
+fn invalid(path: &str, message: &str) -> GltfError {
+    match path {
+        "" => GltfError::Invalid(message.to_string()),
+        _ => GltfError::Invalid(format!("{path}: {message}")),
+    }
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+            b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+            b'+' | b'-' => Some(62),
+            b'/' | b'_' => Some(63),
+            _ => None,
+        }
+    };
+
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+    if encoded.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    for group in encoded.chunks(4) {
+        let mut bits = 0;
+        for (i, c) in group.iter().enumerate() {
+            bits |= value(*c)? << (18 - 6 * i);
+        }
+
+        let bytes = bits.to_be_bytes();
+        decoded.extend_from_slice(&bytes[1..group.len()]);
+    }
+
+    Some(decoded)
+}
+
+// Relative URIs may escape characters such as spaces
+fn decode_percent(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD_POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    const QUAD_INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+    // Builds the buffer views and accessors of a document with a single buffer
+    #[derive(Default)]
+    struct Fixture {
+        bin: Vec<u8>,
+        views: Vec<String>,
+        accessors: Vec<String>,
+    }
+
+    impl Fixture {
+        fn view(&mut self, bytes: &[u8], stride: Option<usize>) -> usize {
+            let offset = self.bin.len();
+            self.bin.extend_from_slice(bytes);
+            self.bin.resize(self.bin.len().next_multiple_of(4), 0);
+
+            let stride = stride
+                .map(|stride| format!(r#","byteStride":{stride}"#))
+                .unwrap_or_default();
+            self.views.push(format!(
+                r#"{{"buffer":0,"byteOffset":{offset},"byteLength":{}{stride}}}"#,
+                bytes.len()
+            ));
+            self.views.len() - 1
+        }
+
+        fn accessor(&mut self, accessor: String) -> usize {
+            self.accessors.push(accessor);
+            self.accessors.len() - 1
+        }
+
+        fn floats<const N: usize>(&mut self, values: &[[f32; N]]) -> usize {
+            let bytes: Vec<u8> = values
+                .iter()
+                .flatten()
+                .flat_map(|v| v.to_le_bytes())
+                .collect();
+            let view = self.view(&bytes, None);
+            let ty = ["SCALAR", "VEC2", "VEC3", "VEC4"][N - 1];
+            self.accessor(format!(
+                r#"{{"bufferView":{view},"componentType":5126,"count":{},"type":"{ty}"}}"#,
+                values.len()
+            ))
+        }
+
+        fn indices(&mut self, indices: &[u16]) -> usize {
+            let bytes: Vec<u8> = indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+            let view = self.view(&bytes, None);
+            self.accessor(format!(
+                r#"{{"bufferView":{view},"componentType":5123,"count":{},"type":"SCALAR"}}"#,
+                indices.len()
+            ))
+        }
+
+        // The accessors of a quad, and the primitive using them
+        fn quad(&mut self) -> String {
+            let position = self.floats(&QUAD_POSITIONS);
+            let normal = self.floats(&[[0.0, 0.0, 1.0]; 4]);
+            let indices = self.indices(&QUAD_INDICES);
+            format!(
+                r#"{{"attributes":{{"POSITION":{position},"NORMAL":{normal}}},"indices":{indices}}}"#
+            )
+        }
+
+        fn document(&self, buffer: &str, members: &str) -> String {
+            format!(
+                r#"{{"asset":{{"version":"2.0"}},"buffers":[{buffer}],"bufferViews":[{}],"accessors":[{}],{members}}}"#,
+                self.views.join(","),
+                self.accessors.join(","),
+            )
+        }
+
+        fn glb(&self, members: &str) -> Vec<u8> {
+            let buffer = format!(r#"{{"byteLength":{}}}"#, self.bin.len());
+            let mut json = self.document(&buffer, members).into_bytes();
+            json.resize(json.len().next_multiple_of(4), b' ');
+
+            let length = 12 + 8 + json.len() + 8 + self.bin.len();
+            let mut glb = Vec::new();
+            for word in [
+                GLB_MAGIC,
+                2,
+                length as u32,
+                json.len() as u32,
+                GLB_CHUNK_JSON,
+            ] {
+                glb.extend_from_slice(&word.to_le_bytes());
+            }
+            glb.extend_from_slice(&json);
+            for word in [self.bin.len() as u32, GLB_CHUNK_BIN] {
+                glb.extend_from_slice(&word.to_le_bytes());
+            }
+            glb.extend_from_slice(&self.bin);
+            glb
+        }
+
+        fn parse(&self, members: &str) -> Result<Vec<ImportedMesh>, GltfError> {
+            parse(&self.glb(members), None, ValidationPolicy::Reject)
+        }
+    }
+
+    fn single_mesh(primitive: &str) -> String {
+        format!(r#""meshes":[{{"name":"mesh","primitives":[{primitive}]}}],"nodes":[{{"mesh":0}}]"#)
+    }
+
+    fn base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        let mut encoded = String::new();
+        for group in bytes.chunks(3) {
+            let mut word = [0; 4];
+            word[1..=group.len()].copy_from_slice(group);
+            let bits = u32::from_be_bytes(word);
+            for i in 0..=group.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+            }
+        }
+        while !encoded.len().is_multiple_of(4) {
+            encoded.push('=');
+        }
+        encoded
+    }
+
+    fn positions(mesh: &ImportedMesh) -> Vec<[f32; 3]> {
+        mesh.resource
+            .positions()
+            .iter()
+            .map(|p| p.to_array())
+            .collect()
+    }
+
+    fn assert_transform(actual: Mat4, expected: Mat4) {
+        assert!(
+            actual.abs_diff_eq(expected, 1e-5),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn glb_quad() {
+        let mut fixture = Fixture::default();
+        let quad = fixture.quad();
+        let meshes = fixture.parse(&single_mesh(&quad)).unwrap();
+
+        assert_eq!(meshes.len(), 1);
+        let mesh = &meshes[0];
+        assert_eq!(mesh.resource.name(), "mesh");
+        assert_eq!(mesh.resource.indices(), [0, 1, 2, 0, 2, 3]);
+        assert_eq!(positions(mesh), QUAD_POSITIONS);
+        assert_eq!(mesh.resource.normals(), [Vec3::Z; 4]);
+        assert!(mesh.resource.uv_sets().is_empty());
+        assert_eq!(mesh.transform, Mat4::IDENTITY);
+    }
+
+    #[test]
+    fn data_uri_buffer() {
+        let mut fixture = Fixture::default();
+        let quad = fixture.quad();
+        let buffer = format!(
+            r#"{{"byteLength":{},"uri":"data:application/octet-stream;base64,{}"}}"#,
+            fixture.bin.len(),
+            base64(&fixture.bin)
+        );
+        let text = fixture.document(&buffer, &single_mesh(&quad));
+
+        let meshes = parse(text.as_bytes(), None, ValidationPolicy::Reject).unwrap();
+        assert_eq!(positions(&meshes[0]), QUAD_POSITIONS);
+    }
+
+    #[test]
+    fn base64_decoding() {
+        assert_eq!(decode_base64("").unwrap(), b"");
+        assert_eq!(decode_base64("Zg==").unwrap(), b"f");
+        assert_eq!(decode_base64("Zm8=").unwrap(), b"fo");
+        assert_eq!(decode_base64("Zm9v").unwrap(), b"foo");
+        assert_eq!(decode_base64("-_8").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode_base64("Z").unwrap_or_default(), b"");
+        assert!(decode_base64("Zm9v!").is_none());
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode_base64(&base64(&bytes)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(decode_percent("a%20b.bin"), "a b.bin");
+        assert_eq!(decode_percent("100%"), "100%");
+        assert_eq!(decode_percent("%zz"), "%zz");
+    }
+
+    #[test]
+    fn interleaved_attributes() {
+        let mut fixture = Fixture::default();
+
+        let mut bytes = Vec::new();
+        for position in QUAD_POSITIONS {
+            for v in position.into_iter().chain([0.0, 0.0, 1.0]) {
+                bytes.extend_from_slice(&f32::to_le_bytes(v));
+            }
+        }
+        let view = fixture.view(&bytes, Some(24));
+        let position = fixture.accessor(format!(
+            r#"{{"bufferView":{view},"componentType":5126,"count":4,"type":"VEC3"}}"#
+        ));
+        let normal = fixture.accessor(format!(
+            r#"{{"bufferView":{view},"byteOffset":12,"componentType":5126,"count":4,"type":"VEC3"}}"#
+        ));
+        let indices = fixture.indices(&QUAD_INDICES);
+
+        let primitive = format!(
+            r#"{{"attributes":{{"POSITION":{position},"NORMAL":{normal}}},"indices":{indices}}}"#
+        );
+        let meshes = fixture.parse(&single_mesh(&primitive)).unwrap();
+
+        assert_eq!(positions(&meshes[0]), QUAD_POSITIONS);
+        assert_eq!(meshes[0].resource.normals(), [Vec3::Z; 4]);
+    }
+
+    #[test]
+    fn index_component_types() {
+        for (component_type, size) in [(5121, 1), (5123, 2), (5125, 4)] {
+            let mut fixture = Fixture::default();
+            let position = fixture.floats(&QUAD_POSITIONS);
+            let normal = fixture.floats(&[[0.0, 0.0, 1.0]; 4]);
+
+            let bytes: Vec<u8> = QUAD_INDICES
+                .iter()
+                .flat_map(|&i| (i as u32).to_le_bytes()[..size].to_vec())
+                .collect();
+            let view = fixture.view(&bytes, None);
+            let indices = fixture.accessor(format!(
+                r#"{{"bufferView":{view},"componentType":{component_type},"count":6,"type":"SCALAR"}}"#
+            ));
+
+            let primitive = format!(
+                r#"{{"attributes":{{"POSITION":{position},"NORMAL":{normal}}},"indices":{indices}}}"#
+            );
+            let meshes = fixture.parse(&single_mesh(&primitive)).unwrap();
+            assert_eq!(meshes[0].resource.indices(), [0, 1, 2, 0, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn non_indexed_primitive() {
+        let mut fixture = Fixture::default();
+        let position = fixture.floats(&[QUAD_POSITIONS[0], QUAD_POSITIONS[1], QUAD_POSITIONS[2]]);
+
+        let primitive = format!(r#"{{"attributes":{{"POSITION":{position}}}}}"#);
+        let meshes = fixture.parse(&single_mesh(&primitive)).unwrap();
+
+        assert_eq!(meshes[0].resource.indices(), [0, 1, 2]);
+        // flat normals are generated for primitives without normals
+        assert_eq!(meshes[0].resource.normals(), [Vec3::Z; 3]);
+    }
+
+    #[test]
+    fn normalized_texture_coordinates() {
+        let mut fixture = Fixture::default();
+        let position = fixture.floats(&QUAD_POSITIONS);
+        let normal = fixture.floats(&[[0.0, 0.0, 1.0]; 4]);
+        let indices = fixture.indices(&QUAD_INDICES);
+
+        let view = fixture.view(&[0, 0, 255, 0, 255, 255, 0, 255], None);
+        let uv0 = fixture.accessor(format!(
+            r#"{{"bufferView":{view},"componentType":5121,"normalized":true,"count":4,"type":"VEC2"}}"#
+        ));
+        let bytes: Vec<u8> = [0u16, 0, 65535, 0, 65535, 32768, 0, 65535]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let view = fixture.view(&bytes, None);
+        let uv1 = fixture.accessor(format!(
+            r#"{{"bufferView":{view},"componentType":5123,"normalized":true,"count":4,"type":"VEC2"}}"#
+        ));
+
+        let primitive = format!(
+            r#"{{"attributes":{{"POSITION":{position},"NORMAL":{normal},"TEXCOORD_0":{uv0},"TEXCOORD_1":{uv1}}},"indices":{indices}}}"#
+        );
+        let meshes = fixture.parse(&single_mesh(&primitive)).unwrap();
+
+        let uv_sets = meshes[0].resource.uv_sets();
+        assert_eq!(uv_sets.len(), 2);
+        assert_eq!(
+            uv_sets[0],
+            [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y].to_vec()
+        );
+        assert_eq!(uv_sets[1][2], Vec2::new(1.0, 32768.0 / 65535.0));
+        // the tangents are generated from the first UV set
+        assert_eq!(meshes[0].resource.tangents().len(), 4);
+    }
+
+    #[test]
+    fn sparse_accessor() {
+        let mut fixture = Fixture::default();
+        let indices = fixture.indices(&QUAD_INDICES);
+
+        // the base positions are all at the origin but the first one, which is not substituted
+        let base = fixture.floats(&[[0.0; 3]; 4]);
+        let base_view = fixture.views.len() - 1;
+        let sparse_indices = fixture.view(&[1, 2, 3, 0], None);
+        let values: Vec<u8> = QUAD_POSITIONS[1..]
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let sparse_values = fixture.view(&values, None);
+        let position = fixture.accessor(format!(
+            r#"{{"bufferView":{base_view},"componentType":5126,"count":4,"type":"VEC3",
+                "sparse":{{"count":3,"indices":{{"bufferView":{sparse_indices},"componentType":5121}},
+                "values":{{"bufferView":{sparse_values}}}}}}}"#
+        ));
+        assert_eq!(position, base + 1);
+        let normal = fixture.floats(&[[0.0, 0.0, 1.0]; 4]);
+
+        let primitive = format!(
+            r#"{{"attributes":{{"POSITION":{position},"NORMAL":{normal}}},"indices":{indices}}}"#
+        );
+        let meshes = fixture.parse(&single_mesh(&primitive)).unwrap();
+        assert_eq!(positions(&meshes[0]), QUAD_POSITIONS);
+    }
+
+    #[test]
+    fn sparse_accessor_without_buffer_view() {
+        let mut fixture = Fixture::default();
+        let indices = fixture.indices(&QUAD_INDICES);
+
+        // the elements that are not substituted are zeros
+        let sparse_indices = fixture.view(&2u16.to_le_bytes(), None);
+        let sparse_values = fixture.view(&[0, 0, 128, 63, 0, 0, 128, 63], None);
+        let uv = fixture.accessor(format!(
+            r#"{{"componentType":5126,"count":4,"type":"VEC2",
+                "sparse":{{"count":1,"indices":{{"bufferView":{sparse_indices},"componentType":5123}},
+                "values":{{"bufferView":{sparse_values}}}}}}}"#
+        ));
+        let position = fixture.floats(&QUAD_POSITIONS);
+        let normal = fixture.floats(&[[0.0, 0.0, 1.0]; 4]);
+
+        let primitive = format!(
+            r#"{{"attributes":{{"POSITION":{position},"NORMAL":{normal},"TEXCOORD_0":{uv}}},"indices":{indices}}}"#
+        );
+        let meshes = fixture.parse(&single_mesh(&primitive)).unwrap();
+        assert_eq!(
+            meshes[0].resource.uv_sets()[0],
+            [Vec2::ZERO, Vec2::ZERO, Vec2::ONE, Vec2::ZERO].to_vec()
+        );
+    }
+
+    #[test]
+    fn sparse_index_out_of_the_accessor() {
+        let mut fixture = Fixture::default();
+        let indices = fixture.indices(&QUAD_INDICES);
+        let base_view = fixture.view(&[0; 48], None);
+        let sparse_indices = fixture.view(&[4], None);
+        let sparse_values = fixture.view(&[0; 12], None);
+        let position = fixture.accessor(format!(
+            r#"{{"bufferView":{base_view},"componentType":5126,"count":4,"type":"VEC3",
+                "sparse":{{"count":1,"indices":{{"bufferView":{sparse_indices},"componentType":5121}},
+                "values":{{"bufferView":{sparse_values}}}}}}}"#
+        ));
+
+        let primitive =
+            format!(r#"{{"attributes":{{"POSITION":{position}}},"indices":{indices}}}"#);
+        match fixture.parse(&single_mesh(&primitive)) {
+            Err(GltfError::Invalid(message)) => assert_eq!(
+                message,
+                "accessors[1].sparse.indices: the index 4 is out of the accessor"
+            ),
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn node_transforms() {
+        let mut fixture = Fixture::default();
+        let quad = fixture.quad();
+
+        // the parent translates its child, which is scaled and rotated by 90 degrees around Z
+        let (sin, cos) = std::f32::consts::FRAC_PI_4.sin_cos();
+        let members = format!(
+            r#""meshes":[{{"primitives":[{quad}]}}],
+            "nodes":[
+                {{"children":[1],"translation":[1,2,3]}},
+                {{"mesh":0,"scale":[2,2,2],"rotation":[0,0,{sin},{cos}]}},
+                {{"mesh":0,"matrix":[1,0,0,0, 0,1,0,0, 0,0,1,0, 4,5,6,1]}}
+            ]"#
+        );
+        let meshes = fixture.parse(&members).unwrap();
+        assert_eq!(meshes.len(), 2);
+
+        let rotation = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        assert_transform(
+            meshes[0].transform,
+            Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0))
+                * Mat4::from_scale_rotation_translation(Vec3::splat(2.0), rotation, Vec3::ZERO),
+        );
+        assert_transform(
+            meshes[0].transform,
+            Mat4::from_scale_rotation_translation(
+                Vec3::splat(2.0),
+                rotation,
+                Vec3::new(1.0, 2.0, 3.0),
+            ),
+        );
+        assert_transform(
+            meshes[1].transform,
+            Mat4::from_translation(Vec3::new(4.0, 5.0, 6.0)),
+        );
+
+        // the mesh is shared by the nodes, which are named after their index without names
+        assert_eq!(meshes[0].resource.name(), "node1");
+        assert_eq!(meshes[1].resource.name(), "node2");
+        assert_eq!(positions(&meshes[1]), QUAD_POSITIONS);
+    }
+
+    #[test]
+    fn default_scene() {
+        let mut fixture = Fixture::default();
+        let quad = fixture.quad();
+        let meshes = format!(r#""meshes":[{{"primitives":[{quad}]}}]"#);
+        let nodes = r#""nodes":[{"name":"a","mesh":0},{"name":"b","mesh":0},{"name":"c","mesh":0,"children":[0]}]"#;
+
+        let names = |members: &str| -> Vec<String> {
+            fixture
+                .parse(members)
+                .unwrap()
+                .iter()
+                .map(|mesh| mesh.resource.name().to_string())
+                .collect()
+        };
+
+        // without scenes, every node without a parent is a root
+        assert_eq!(names(&format!("{meshes},{nodes}")), ["b", "c", "a"]);
+        assert_eq!(
+            names(&format!(
+                r#"{meshes},{nodes},"scenes":[{{"nodes":[0]}},{{"nodes":[1,0]}}],"scene":1"#
+            )),
+            ["b", "a"]
+        );
+        assert_eq!(
+            names(&format!(r#"{meshes},{nodes},"scenes":[{{"nodes":[0]}}]"#)),
+            ["a"]
+        );
+    }
+
+    #[test]
+    fn node_cycle() {
+        let mut fixture = Fixture::default();
+        let quad = fixture.quad();
+        let members = format!(
+            r#""meshes":[{{"primitives":[{quad}]}}],"nodes":[{{"children":[1]}},{{"mesh":0,"children":[0]}}],"scenes":[{{"nodes":[0]}}]"#
+        );
+
+        match fixture.parse(&members) {
+            Err(GltfError::Invalid(message)) => {
+                assert_eq!(message, "nodes[0]: the node hierarchy has a cycle")
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn materials() {
+        let mut fixture = Fixture::default();
+        let quad = fixture.quad();
+        let members = format!(
+            r#""meshes":[{{"primitives":[{quad}]}}, {{"primitives":[
+                {{"attributes":{{"POSITION":0}},"indices":2,"material":0}},
+                {{"attributes":{{"POSITION":0}},"indices":2,"material":1}}
+            ]}}],
+            "materials":[
+                {{"name":"red","pbrMetallicRoughness":{{"baseColorFactor":[1,0,0,0.5],"metallicFactor":0.25,"roughnessFactor":2}}}},
+                {{}}
+            ],
+            "nodes":[{{"name":"default","mesh":0}},{{"name":"material","mesh":1}}]"#
+        );
+        let meshes = fixture.parse(&members).unwrap();
+        assert_eq!(meshes.len(), 3);
+        assert_eq!(meshes[1].resource.name(), "material[0]");
+        assert_eq!(meshes[2].resource.name(), "material[1]");
+
+        // without a material, and with an empty one, the factors are the default ones
+        let default = meshes[0].material.as_ref().unwrap();
+        assert_eq!(default.name, "default");
+        assert_eq!(default.material.base_color, Vec3::ONE);
+        assert_eq!(default.material.metallic, 1.0);
+        assert_eq!(default.material.roughness, 1.0);
+        assert_eq!(default.textures, MaterialTextures::default());
+
+        let red = meshes[1].material.as_ref().unwrap();
+        assert_eq!(red.name, "red");
+        assert_eq!(red.material.base_color, Vec3::X);
+        assert_eq!(red.material.specular_reflectance, Vec3::X);
+        assert_eq!(red.material.specular_tint, Vec3::X);
+        assert_eq!(red.material.metallic, 0.25);
+        // the factors are clamped to their range
+        assert_eq!(red.material.roughness, 1.0);
+
+        let unnamed = meshes[2].material.as_ref().unwrap();
+        assert_eq!(unnamed.name, "materials[1]");
+        assert_eq!(unnamed.material, default.material);
+    }
+
+    #[test]
+    fn non_triangle_primitives_are_skipped() {
+        let mut fixture = Fixture::default();
+        let quad = fixture.quad();
+        let members = format!(
+            r#""meshes":[{{"primitives":[{{"attributes":{{"POSITION":0}},"mode":1}},{quad}]}}],"nodes":[{{"mesh":0}}]"#
+        );
+
+        let meshes = fixture.parse(&members).unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].resource.indices().len(), 6);
+    }
+
+    #[test]
+    fn errors() {
+        let mut fixture = Fixture::default();
+        let quad = fixture.quad();
+        let out_of_view = fixture.accessor(
+            r#"{"bufferView":0,"byteOffset":4,"componentType":5126,"count":4,"type":"VEC3"}"#
+                .to_string(),
+        );
+        let short_positions = fixture.accessor(
+            r#"{"bufferView":2,"componentType":5123,"count":2,"type":"VEC3"}"#.to_string(),
+        );
+
+        let message = |result: Result<Vec<ImportedMesh>, GltfError>| match result {
+            Err(GltfError::Invalid(message)) => format!("invalid: {message}"),
+            Err(GltfError::Unsupported(message)) => format!("unsupported: {message}"),
+            other => panic!("unexpected result {other:?}"),
+        };
+        let primitive = |attributes: &str, indices: usize| {
+            single_mesh(&format!(
+                r#"{{"attributes":{{{attributes}}},"indices":{indices}}}"#
+            ))
+        };
+
+        assert_eq!(
+            message(fixture.parse(&primitive(&format!(r#""POSITION":{out_of_view}"#), 2))),
+            "invalid: accessors[3]: the accessor is out of the buffer view"
+        );
+        assert_eq!(
+            message(fixture.parse(&primitive(&format!(r#""POSITION":{short_positions}"#), 2))),
+            "unsupported: accessors[4]: only float positions and normals are supported"
+        );
+        assert_eq!(
+            message(fixture.parse(&primitive(r#""POSITION":0,"TEXCOORD_0":1"#, 2))),
+            "invalid: accessors[1]: expected 2 components"
+        );
+        assert_eq!(
+            message(fixture.parse(&primitive(r#""NORMAL":1"#, 2))),
+            "invalid: meshes[0].primitives[0]: POSITION is missing"
+        );
+        assert_eq!(
+            message(fixture.parse(&primitive(r#""POSITION":0"#, 0))),
+            "invalid: accessors[0]: indices must be SCALAR"
+        );
+        assert_eq!(
+            message(fixture.parse(&primitive(r#""POSITION":0"#, 9))),
+            "invalid: accessors[9]: the accessor does not exist"
+        );
+        assert_eq!(
+            message(fixture.parse(&format!(
+                r#"{},"extensionsRequired":["KHR_draco_mesh_compression"]"#,
+                single_mesh(&quad)
+            ))),
+            "unsupported: required extensions KHR_draco_mesh_compression"
+        );
+
+        let text = r#"{"asset":{"version":"1.0"}}"#;
+        assert_eq!(
+            message(parse(text.as_bytes(), None, ValidationPolicy::Reject)),
+            "unsupported: glTF version 1.0"
+        );
+
+        let text = r#"{"asset":{"version":"2.0"},"buffers":[{"byteLength":4,"uri":"a.bin"}]}"#;
+        assert_eq!(
+            message(parse(text.as_bytes(), None, ValidationPolicy::Reject)),
+            "unsupported: buffers[0]: external buffers need the directory of the file"
+        );
+
+        let mut glb = fixture.glb(&single_mesh(&quad));
+        glb.truncate(glb.len() - 4);
+        assert_eq!(
+            message(parse(&glb, None, ValidationPolicy::Reject)),
+            "invalid: a GLB chunk is truncated"
+        );
+    }
+
+    #[test]
+    fn out_of_range_indices() {
+        let mut fixture = Fixture::default();
+        let position = fixture.floats(&QUAD_POSITIONS);
+        let indices = fixture.indices(&[0, 1, 4]);
+        let primitive =
+            format!(r#"{{"attributes":{{"POSITION":{position}}},"indices":{indices}}}"#);
+
+        match fixture.parse(&single_mesh(&primitive)) {
+            Err(GltfError::Invalid(message)) => assert_eq!(
+                message,
+                "meshes[0].primitives[0]: the index 4 is out of the 4 vertices"
+            ),
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+}
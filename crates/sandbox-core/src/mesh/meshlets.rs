@@ -0,0 +1,367 @@
+// Splitting of a mesh into small clusters of triangles for mesh shaders, with the bounds to cull
+// each cluster before its triangles are processed
+
+use glam::Vec3;
+
+use super::MeshResource;
+
+/// The limits recommended for mesh shaders, which keep the output of a meshlet within the 16KB
+/// of a thread group
+pub const MAX_MESHLET_VERTICES: usize = 64;
+pub const MAX_MESHLET_TRIANGLES: usize = 124;
+
+// read by mesh shaders from a structured buffer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct Meshlet {
+    /// The first element of `Meshlets::vertices`
+    pub vertex_offset: u32,
+    /// The first element of `Meshlets::triangles`
+    pub triangle_offset: u32,
+    pub vertex_count: u32,
+    pub triangle_count: u32,
+}
+
+// read by mesh or amplification shaders from a structured buffer
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[repr(C)]
+pub struct MeshletBounds {
+    pub center: Vec3,
+    pub radius: f32,
+    /// The average direction of the triangles
+    pub cone_axis: Vec3,
+    /// The sine of the angle between the axis and the farthest triangle normal, 1 if the normals
+    /// spread too much to ever cull the meshlet
+    /// The meshlet faces away from a camera at `eye` if
+    /// `dot(center - eye, cone_axis) >= cone_cutoff * length(center - eye) + radius`
+    pub cone_cutoff: f32,
+}
+
+/// The meshlets of a mesh, whose arrays are uploaded as they are
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Meshlets {
+    pub meshlets: Vec<Meshlet>,
+    pub bounds: Vec<MeshletBounds>,
+    /// The vertices of the mesh used by each meshlet
+    pub vertices: Vec<u32>,
+    /// The three indices into the vertices of the meshlet in the low 24 bits, 8 bits each
+    pub triangles: Vec<u32>,
+}
+
+impl MeshResource {
+    /// Splits the triangles into meshlets of at most `MAX_MESHLET_VERTICES` vertices and
+    /// `MAX_MESHLET_TRIANGLES` triangles, growing each meshlet with the triangles that add the
+    /// fewest vertices to it
+    /// Every triangle is in exactly one meshlet, with its winding kept
+    pub fn build_meshlets(&self) -> Meshlets {
+        let triangle_count = self.indices.len() / 3;
+        let vertex_count = self.positions.len();
+        let triangles = &self.indices[..triangle_count * 3];
+
+        // the triangles around each vertex, packed in one array
+        let mut offsets = vec![0; vertex_count + 1];
+        for &index in triangles {
+            offsets[index as usize + 1] += 1;
+        }
+        for vertex in 0..vertex_count {
+            offsets[vertex + 1] += offsets[vertex];
+        }
+        let mut adjacency = vec![0; triangles.len()];
+        let mut filled = vec![0; vertex_count];
+        for (triangle, indices) in triangles.chunks_exact(3).enumerate() {
+            for &index in indices {
+                let vertex = index as usize;
+                adjacency[offsets[vertex] + filled[vertex]] = triangle;
+                filled[vertex] += 1;
+            }
+        }
+
+        let mut meshlets = Meshlets::default();
+        let mut builder = MeshletBuilder {
+            local_indices: vec![u8::MAX; vertex_count],
+            vertices: Vec::with_capacity(MAX_MESHLET_VERTICES),
+            triangles: Vec::with_capacity(MAX_MESHLET_TRIANGLES),
+        };
+        let mut emitted = vec![false; triangle_count];
+        // scans the triangles in order when no triangle around the meshlet fits
+        let mut cursor = 0;
+
+        loop {
+            let mut best: Option<(usize, usize)> = None;
+            for &vertex in &builder.vertices {
+                let vertex = vertex as usize;
+                for &triangle in &adjacency[offsets[vertex]..offsets[vertex + 1]] {
+                    if emitted[triangle] {
+                        continue;
+                    }
+                    let new_vertices = builder.new_vertices(&triangles[triangle * 3..][..3]);
+                    let better = best.is_none_or(|(t, n)| (new_vertices, triangle) < (n, t));
+                    if builder.fits(new_vertices) && better {
+                        best = Some((triangle, new_vertices));
+                    }
+                }
+            }
+
+            let triangle = match best {
+                Some((triangle, _)) => triangle,
+                None => {
+                    while cursor < triangle_count && emitted[cursor] {
+                        cursor += 1;
+                    }
+                    if cursor == triangle_count {
+                        break;
+                    }
+                    // another part of the mesh, which starts a new meshlet if it does not fit
+                    let indices = &triangles[cursor * 3..][..3];
+                    if !builder.fits(builder.new_vertices(indices)) {
+                        builder.flush(self, &mut meshlets);
+                    }
+                    cursor
+                }
+            };
+
+            builder.add(&triangles[triangle * 3..][..3]);
+            emitted[triangle] = true;
+
+            if builder.triangles.len() == MAX_MESHLET_TRIANGLES {
+                builder.flush(self, &mut meshlets);
+            }
+        }
+        builder.flush(self, &mut meshlets);
+
+        meshlets
+    }
+}
+
+// The meshlet being built
+struct MeshletBuilder {
+    // the index of each vertex of the mesh in the meshlet, u8::MAX if it is not in the meshlet
+    local_indices: Vec<u8>,
+    vertices: Vec<u32>,
+    triangles: Vec<[u8; 3]>,
+}
+
+impl MeshletBuilder {
+    fn new_vertices(&self, indices: &[u32]) -> usize {
+        let mut count = 0;
+        for (corner, &index) in indices.iter().enumerate() {
+            // a vertex repeated in a triangle is only added once
+            let repeated = indices[..corner].contains(&index);
+            if self.local_indices[index as usize] == u8::MAX && !repeated {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    fn fits(&self, new_vertices: usize) -> bool {
+        self.vertices.len() + new_vertices <= MAX_MESHLET_VERTICES
+            && self.triangles.len() < MAX_MESHLET_TRIANGLES
+    }
+
+    fn add(&mut self, indices: &[u32]) {
+        let triangle = [0, 1, 2].map(|corner| {
+            let index = indices[corner];
+            let local = &mut self.local_indices[index as usize];
+            if *local == u8::MAX {
+                *local = self.vertices.len() as u8;
+                self.vertices.push(index);
+            }
+            *local
+        });
+        self.triangles.push(triangle);
+    }
+
+    fn flush(&mut self, mesh: &MeshResource, meshlets: &mut Meshlets) {
+        if self.triangles.is_empty() {
+            return;
+        }
+
+        meshlets.meshlets.push(Meshlet {
+            vertex_offset: meshlets.vertices.len() as u32,
+            triangle_offset: meshlets.triangles.len() as u32,
+            vertex_count: self.vertices.len() as u32,
+            triangle_count: self.triangles.len() as u32,
+        });
+        meshlets.bounds.push(self.bounds(mesh));
+        meshlets.vertices.extend_from_slice(&self.vertices);
+        meshlets.triangles.extend(
+            self.triangles
+                .iter()
+                .map(|t| t[0] as u32 | (t[1] as u32) << 8 | (t[2] as u32) << 16),
+        );
+
+        for &vertex in &self.vertices {
+            self.local_indices[vertex as usize] = u8::MAX;
+        }
+        self.vertices.clear();
+        self.triangles.clear();
+    }
+
+    fn bounds(&self, mesh: &MeshResource) -> MeshletBounds {
+        let positions = self.vertices.iter().map(|&v| mesh.positions[v as usize]);
+        let (center, radius) = super::bounding_sphere(positions);
+
+        let normals: Vec<Vec3> = self
+            .triangles
+            .iter()
+            .map(|t| {
+                let [p0, p1, p2] = t.map(|i| mesh.positions[self.vertices[i as usize] as usize]);
+                (p1 - p0).cross(p2 - p0).normalize_or_zero()
+            })
+            .filter(|&normal| normal != Vec3::ZERO)
+            .collect();
+
+        let cone_axis = normals.iter().sum::<Vec3>().normalize_or_zero();
+        let min_cos = normals.iter().map(|n| n.dot(cone_axis)).fold(1.0, f32::min);
+
+        // every triangle faces away from the view directions within 90 degrees minus the spread of
+        // the normals around the axis, and there are none if the spread reaches 90 degrees
+        let cone_cutoff = match cone_axis != Vec3::ZERO && min_cos > 0.0 {
+            true => (1.0 - min_cos * min_cos).sqrt(),
+            false => 1.0,
+        };
+
+        MeshletBounds {
+            center,
+            radius,
+            cone_axis,
+            cone_cutoff,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::primitives;
+
+    // The triangles of each meshlet as indices into the mesh
+    fn decoded_triangles(meshlets: &Meshlets) -> Vec<[u32; 3]> {
+        let mut decoded = Vec::new();
+        for meshlet in &meshlets.meshlets {
+            let vertices = &meshlets.vertices[meshlet.vertex_offset as usize..]
+                [..meshlet.vertex_count as usize];
+            let triangles = &meshlets.triangles[meshlet.triangle_offset as usize..]
+                [..meshlet.triangle_count as usize];
+            for &packed in triangles {
+                assert_eq!(packed >> 24, 0);
+                decoded.push([0, 8, 16].map(|shift| vertices[(packed >> shift & 0xff) as usize]));
+            }
+        }
+        decoded
+    }
+
+    fn assert_limits(meshlets: &Meshlets) {
+        assert_eq!(meshlets.meshlets.len(), meshlets.bounds.len());
+        let mut vertex_offset = 0;
+        let mut triangle_offset = 0;
+        for meshlet in &meshlets.meshlets {
+            assert!((1..=MAX_MESHLET_VERTICES as u32).contains(&meshlet.vertex_count));
+            assert!((1..=MAX_MESHLET_TRIANGLES as u32).contains(&meshlet.triangle_count));
+            assert_eq!(meshlet.vertex_offset, vertex_offset);
+            assert_eq!(meshlet.triangle_offset, triangle_offset);
+            vertex_offset += meshlet.vertex_count;
+            triangle_offset += meshlet.triangle_count;
+        }
+        assert_eq!(meshlets.vertices.len(), vertex_offset as usize);
+        assert_eq!(meshlets.triangles.len(), triangle_offset as usize);
+    }
+
+    // Every triangle of the mesh is in exactly one meshlet, starting from the same corner
+    fn assert_covered(mesh: &MeshResource, meshlets: &Meshlets) {
+        let mut expected: Vec<[u32; 3]> = mesh
+            .indices()
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        let mut actual = decoded_triangles(meshlets);
+        expected.sort_unstable();
+        actual.sort_unstable();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn sphere() {
+        let mesh = primitives::uv_sphere(1.0, 64, 32);
+        let meshlets = mesh.build_meshlets();
+
+        assert_limits(&meshlets);
+        assert_covered(&mesh, &meshlets);
+
+        // a meshlet only holds the vertices of its triangles, each once
+        for meshlet in &meshlets.meshlets {
+            let vertices = &meshlets.vertices[meshlet.vertex_offset as usize..]
+                [..meshlet.vertex_count as usize];
+            let mut unique = vertices.to_vec();
+            unique.sort_unstable();
+            unique.dedup();
+            assert_eq!(unique.len(), vertices.len());
+        }
+    }
+
+    #[test]
+    fn vertex_limit() {
+        // disjoint triangles, of which a meshlet only holds 21 before running out of vertices
+        let positions: Vec<Vec3> = (0..300)
+            .map(|i| {
+                Vec3::new(
+                    (i / 3) as f32,
+                    (i % 3 == 1) as u32 as f32,
+                    (i % 3 == 2) as u32 as f32,
+                )
+            })
+            .collect();
+        let indices: Vec<u32> = (0..300).collect();
+        let mesh = MeshResource::new(
+            &indices,
+            &positions,
+            &[Vec3::X; 300],
+            "disjoint".to_string(),
+        );
+        let meshlets = mesh.build_meshlets();
+
+        assert_limits(&meshlets);
+        assert_covered(&mesh, &meshlets);
+        assert_eq!(meshlets.meshlets.len(), 5);
+        assert_eq!(meshlets.meshlets[0].vertex_count, 63);
+        assert_eq!(meshlets.meshlets[0].triangle_count, 21);
+    }
+
+    #[test]
+    fn triangle_limit() {
+        // the same vertices in every triangle, so only the triangle count limits the meshlets
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::Y];
+        let indices: Vec<u32> = [0, 1, 2].repeat(200);
+        let mesh = MeshResource::new(&indices, &positions, &[Vec3::Z; 3], "stacked".to_string());
+        let meshlets = mesh.build_meshlets();
+
+        assert_limits(&meshlets);
+        assert_covered(&mesh, &meshlets);
+        let counts: Vec<u32> = meshlets.meshlets.iter().map(|m| m.triangle_count).collect();
+        assert_eq!(counts, [124, 76]);
+    }
+
+    #[test]
+    fn bounds() {
+        let mesh = primitives::uv_sphere(1.0, 32, 16);
+        let meshlets = mesh.build_meshlets();
+
+        for (meshlet, bounds) in meshlets.meshlets.iter().zip(&meshlets.bounds) {
+            let vertices = &meshlets.vertices[meshlet.vertex_offset as usize..]
+                [..meshlet.vertex_count as usize];
+            for &vertex in vertices {
+                let distance = mesh.positions()[vertex as usize].distance(bounds.center);
+                assert!(distance <= bounds.radius * 1.0001);
+            }
+            assert!(bounds.cone_axis.is_normalized());
+            assert!((0.0..=1.0).contains(&bounds.cone_cutoff));
+        }
+    }
+
+    #[test]
+    fn empty_mesh() {
+        let mesh = MeshResource::new(&[], &[Vec3::ZERO], &[Vec3::Z], "empty".to_string());
+        assert_eq!(mesh.build_meshlets(), Meshlets::default());
+    }
+}
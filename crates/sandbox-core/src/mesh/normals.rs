@@ -0,0 +1,318 @@
+// Vertex normal generation for meshes whose files have no normals
+
+use glam::Vec3;
+
+use super::MeshResource;
+
+/// How the normals of the triangles around a vertex are averaged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalWeight {
+    /// Large triangles contribute more, which is cheap but depends on the tessellation
+    Area,
+    /// Triangles contribute by their angle at the vertex [Thürmer and Wüthrich 1998]
+    Angle,
+}
+
+/// Crease angle used when a loader generates the normals of a file without them
+pub const DEFAULT_CREASE_ANGLE: f32 = std::f32::consts::FRAC_PI_3;
+
+// Used for vertices that only belong to degenerate triangles or to none
+const FALLBACK_NORMAL: Vec3 = Vec3::Y;
+
+impl MeshResource {
+    /// Whether there is a normal for each position
+    pub fn has_normals(&self) -> bool {
+        self.normals.len() == self.positions.len()
+    }
+
+    /// Replaces the normals with the average of the triangles around each vertex
+    pub fn compute_smooth_normals(&mut self, weight: NormalWeight) {
+        let mut normals = vec![Vec3::ZERO; self.positions.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            for corner in 0..3 {
+                let normal = corner_normal(&self.positions, triangle, corner, weight);
+                normals[triangle[corner] as usize] += normal;
+            }
+        }
+
+        self.normals = normals.into_iter().map(normalize).collect();
+    }
+
+    /// Replaces the normals with those of the triangles, so that each triangle gets its own
+    /// vertices
+    pub fn compute_flat_normals(&mut self) {
+        let normals: Vec<_> = self
+            .indices
+            .chunks_exact(3)
+            .flat_map(|triangle| [normalize(face_normal(&self.positions, triangle)); 3])
+            .collect();
+
+        let vertices = self.indices[..normals.len()].to_vec();
+        self.gather_vertices(&vertices);
+        self.indices = (0..normals.len() as u32).collect();
+        self.normals = normals;
+    }
+
+    /// Replaces the normals with smooth normals that only average triangles whose normals are
+    /// within `crease_angle` radians of each other
+    /// A vertex on a crease is split into a vertex for each side, and the other vertices keep
+    /// their index
+    pub fn compute_crease_normals(&mut self, crease_angle: f32, weight: NormalWeight) {
+        let min_cos = crease_angle.cos();
+
+        // the indices are rewritten below to point to the split vertices
+        let indices = self.indices.clone();
+        let triangle_count = indices.len() / 3;
+        let face_normals: Vec<_> = indices
+            .chunks_exact(3)
+            .map(|triangle| normalize(face_normal(&self.positions, triangle)))
+            .collect();
+
+        // the corners around each vertex as indices into `indices`
+        let mut corners = vec![Vec::new(); self.positions.len()];
+        for (corner, &index) in indices[..triangle_count * 3].iter().enumerate() {
+            corners[index as usize].push(corner);
+        }
+
+        self.normals = vec![FALLBACK_NORMAL; self.positions.len()];
+        // the normal of each side of a crease around a vertex, and the vertex of the side
+        let mut sides: Vec<(Vec3, u32)> = Vec::new();
+
+        for (vertex, around) in corners.iter().enumerate() {
+            sides.clear();
+
+            for &corner in around {
+                let face_normal = face_normals[corner / 3];
+
+                let mut sum = Vec3::ZERO;
+                for &other in around {
+                    if face_normals[other / 3].dot(face_normal) >= min_cos {
+                        let triangle = &indices[other / 3 * 3..other / 3 * 3 + 3];
+                        sum += corner_normal(&self.positions, triangle, other % 3, weight);
+                    }
+                }
+                let normal = normalize(sum);
+
+                // the same set of triangles is summed in the same order, so the sides of a
+                // crease are told apart by exact comparison
+                let side = sides.iter().find(|(n, _)| *n == normal).map(|(_, v)| *v);
+                let index = match side {
+                    Some(index) => index,
+                    None => {
+                        // the first side keeps the vertex
+                        let index = match sides.is_empty() {
+                            true => vertex as u32,
+                            false => self.duplicate_vertex(vertex),
+                        };
+                        self.normals[index as usize] = normal;
+                        sides.push((normal, index));
+                        index
+                    }
+                };
+                self.indices[corner] = index;
+            }
+        }
+    }
+
+    // Called by the loaders for files without normals or with a normal count that does not
+    // match the positions
+    pub(super) fn generate_missing_normals(&mut self) {
+        if !self.has_normals() {
+            self.compute_crease_normals(DEFAULT_CREASE_ANGLE, NormalWeight::Angle);
+        }
+    }
+}
+
+// The cross product of the edges, whose length is twice the area of the triangle
+fn face_normal(positions: &[Vec3], triangle: &[u32]) -> Vec3 {
+    let [p0, p1, p2] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+    (p1 - p0).cross(p2 - p0)
+}
+
+// The contribution of `triangle` to the normal of its vertex at `corner`
+fn corner_normal(
+    positions: &[Vec3],
+    triangle: &[u32],
+    corner: usize,
+    weight: NormalWeight,
+) -> Vec3 {
+    let normal = face_normal(positions, triangle);
+
+    match weight {
+        NormalWeight::Area => normal,
+        NormalWeight::Angle => {
+            let p = |i: usize| positions[triangle[(corner + i) % 3] as usize];
+            let (e0, e1) = (p(1) - p(0), p(2) - p(0));
+            let angle = e0
+                .normalize_or_zero()
+                .dot(e1.normalize_or_zero())
+                .clamp(-1.0, 1.0)
+                .acos();
+            normal.normalize_or_zero() * angle
+        }
+    }
+}
+
+fn normalize(normal: Vec3) -> Vec3 {
+    match normal.normalize_or_zero() {
+        Vec3::ZERO => FALLBACK_NORMAL,
+        normal => normal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A quad in the XY plane facing +Z
+    fn quad() -> MeshResource {
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y];
+        MeshResource::new(&[0, 1, 2, 0, 2, 3], &positions, &[], "quad".to_string())
+    }
+
+    // Two faces of a cube meeting at the edge from the origin to +Y, facing +Z and +X
+    fn cube_edge() -> MeshResource {
+        let positions = [Vec3::ZERO, Vec3::Y, Vec3::X, Vec3::Z];
+        MeshResource::new(
+            &[0, 2, 1, 0, 1, 3],
+            &positions,
+            &[],
+            "cube edge".to_string(),
+        )
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(actual.abs_diff_eq(expected, 1e-5), "{actual} != {expected}");
+    }
+
+    // The normal at each corner of the triangles
+    fn corner_normals(mesh: &MeshResource) -> Vec<Vec3> {
+        mesh.indices()
+            .iter()
+            .map(|&i| mesh.normals()[i as usize])
+            .collect()
+    }
+
+    #[test]
+    fn flat_quad() {
+        let mut mesh = quad();
+        mesh.compute_flat_normals();
+
+        assert_eq!(mesh.positions().len(), 6);
+        assert_eq!(mesh.indices(), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(mesh.positions()[4], Vec3::new(1.0, 1.0, 0.0));
+        for normal in mesh.normals() {
+            assert_close(*normal, Vec3::Z);
+        }
+
+        // nothing to split on a plane
+        let mut mesh = quad();
+        mesh.compute_crease_normals(DEFAULT_CREASE_ANGLE, NormalWeight::Angle);
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.indices(), [0, 1, 2, 0, 2, 3]);
+        for normal in mesh.normals() {
+            assert_close(*normal, Vec3::Z);
+        }
+    }
+
+    #[test]
+    fn smooth_normals_are_averaged() {
+        for weight in [NormalWeight::Area, NormalWeight::Angle] {
+            let mut mesh = cube_edge();
+            mesh.compute_smooth_normals(weight);
+
+            assert!(mesh.has_normals());
+            let shared = Vec3::new(1.0, 0.0, 1.0).normalize();
+            assert_close(mesh.normals()[0], shared);
+            assert_close(mesh.normals()[1], shared);
+            assert_close(mesh.normals()[2], Vec3::Z);
+            assert_close(mesh.normals()[3], Vec3::X);
+        }
+    }
+
+    #[test]
+    fn triangles_are_weighted() {
+        // a long thin triangle facing +Z and a small one facing +X around the origin, both with a
+        // right angle at the origin
+        let positions = [Vec3::ZERO, Vec3::Y, 4.0 * Vec3::X, Vec3::Z];
+        let mesh = MeshResource::new(&[0, 2, 1, 0, 1, 3], &positions, &[], "".to_string());
+
+        let mut area = mesh.clone();
+        area.compute_smooth_normals(NormalWeight::Area);
+        assert_close(area.normals()[0], Vec3::new(1.0, 0.0, 4.0).normalize());
+
+        let mut angle = mesh;
+        angle.compute_smooth_normals(NormalWeight::Angle);
+        assert_close(angle.normals()[0], Vec3::new(1.0, 0.0, 1.0).normalize());
+    }
+
+    #[test]
+    fn cube_edge_is_split_above_the_crease_angle() {
+        let mut mesh = cube_edge();
+        mesh.compute_crease_normals(DEFAULT_CREASE_ANGLE, NormalWeight::Angle);
+
+        // the vertices of the edge are duplicated for the second face
+        assert_eq!(mesh.positions().len(), 6);
+        assert_eq!(mesh.indices(), [0, 2, 1, 4, 5, 3]);
+        assert_eq!(mesh.positions()[4], Vec3::ZERO);
+        assert_eq!(mesh.positions()[5], Vec3::Y);
+
+        let normals = corner_normals(&mesh);
+        for normal in &normals[..3] {
+            assert_close(*normal, Vec3::Z);
+        }
+        for normal in &normals[3..] {
+            assert_close(*normal, Vec3::X);
+        }
+    }
+
+    #[test]
+    fn cube_edge_is_smooth_below_the_crease_angle() {
+        let mut mesh = cube_edge();
+        mesh.compute_crease_normals(100f32.to_radians(), NormalWeight::Angle);
+
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.indices(), [0, 2, 1, 0, 1, 3]);
+
+        let mut smooth = cube_edge();
+        smooth.compute_smooth_normals(NormalWeight::Angle);
+        for (normal, expected) in mesh.normals().iter().zip(smooth.normals()) {
+            assert_close(*normal, *expected);
+        }
+    }
+
+    #[test]
+    fn degenerate_triangles() {
+        // a triangle, a triangle with a repeated vertex and one with collinear vertices
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::Y, 2.0 * Vec3::X, Vec3::Z];
+        let indices = [0, 1, 2, 1, 1, 4, 0, 1, 3];
+        let mesh = MeshResource::new(&indices, &positions, &[], "degenerate".to_string());
+
+        let mut flat = mesh.clone();
+        flat.compute_flat_normals();
+        let mut area = mesh.clone();
+        area.compute_smooth_normals(NormalWeight::Area);
+        let mut angle = mesh.clone();
+        angle.compute_smooth_normals(NormalWeight::Angle);
+        let mut crease = mesh;
+        crease.compute_crease_normals(DEFAULT_CREASE_ANGLE, NormalWeight::Angle);
+
+        for mesh in [&flat, &area, &angle, &crease] {
+            assert!(mesh.has_normals());
+            for normal in mesh.normals() {
+                assert!(normal.is_normalized(), "{normal}");
+            }
+        }
+
+        // the vertices of the triangle are not affected by the degenerate ones
+        for mesh in [&area, &angle, &crease] {
+            for vertex in 0..3 {
+                assert_close(mesh.normals()[vertex], Vec3::Z);
+            }
+            // only in degenerate triangles
+            assert_eq!(mesh.normals()[3], FALLBACK_NORMAL);
+            assert_eq!(mesh.normals()[4], FALLBACK_NORMAL);
+        }
+        assert_eq!(flat.normals()[3..], [FALLBACK_NORMAL; 6]);
+    }
+}
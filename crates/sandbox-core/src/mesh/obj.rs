@@ -0,0 +1,454 @@
+// Wavefront OBJ importer, with the materials of the MTL files referred to by `mtllib`
+// A model is made for each object and each material used in it
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use glam::{Vec2, Vec3};
+
+use super::binary::BinaryError;
+use super::cache::MeshCache;
+use super::{
+    ImportedMaterial, ImportedMesh, Material, MaterialTextures, MeshResource, ValidationError,
+    ValidationPolicy,
+};
+
+// the diffuse color of MTL materials without Kd
+const DEFAULT_DIFFUSE: Vec3 = Vec3::splat(0.8);
+
+#[derive(Debug)]
+pub enum ObjError {
+    Load(tobj::LoadError),
+    Validation(ValidationError),
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Load(e) => write!(f, "{e}"),
+            ObjError::Validation(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Load(e) => Some(e),
+            ObjError::Validation(e) => Some(e),
+        }
+    }
+}
+
+/// The meshes of an OBJ file, with what failed without preventing them from being loaded
+#[derive(Debug)]
+pub struct ObjFile {
+    pub meshes: Vec<ImportedMesh>,
+    /// Why the MTL files could not be loaded, the meshes have no material then
+    pub material_error: Option<tobj::LoadError>,
+    /// Why the cached meshes could not be loaded, or the imported meshes could not be cached
+    pub cache_error: Option<BinaryError>,
+}
+
+/// The meshes of an OBJ file, whose material is None if the file has no MTL or the model uses no
+/// material
+/// The meshes are taken from `cache` while the file and its MTL files are unchanged, and cached
+/// after they are imported otherwise
+pub fn load(
+    path: &str,
+    policy: ValidationPolicy,
+    cache: Option<&MeshCache>,
+) -> Result<ObjFile, ObjError> {
+    let Some(cache) = cache else {
+        return import(path, policy);
+    };
+    // a broken entry is imported again and replaced
+    let load_error = match cache.load(Path::new(path), policy) {
+        Ok(Some(meshes)) => {
+            return Ok(ObjFile {
+                meshes,
+                material_error: None,
+                cache_error: None,
+            })
+        }
+        Ok(None) => None,
+        Err(e) => Some(e),
+    };
+
+    let mut file = import(path, policy)?;
+    let dependencies = material_libraries(Path::new(path));
+    let store_error = cache
+        .store(Path::new(path), &dependencies, policy, &file.meshes)
+        .err()
+        .map(BinaryError::Io);
+    file.cache_error = load_error.or(store_error);
+    Ok(file)
+}
+
+// The MTL files referred to by `mtllib` that exist, as tobj does not tell which ones it read
+fn material_libraries(path: &Path) -> Vec<PathBuf> {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    text.lines()
+        .filter_map(|line| line.trim_start().strip_prefix("mtllib "))
+        .map(|file| dir.join(file.trim()))
+        .filter(|file| file.is_file())
+        .collect()
+}
+
+fn import(path: &str, policy: ValidationPolicy) -> Result<ObjFile, ObjError> {
+    let (models, materials) =
+        tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS).map_err(ObjError::Load)?;
+
+    // a missing MTL file does not prevent the geometry from being used
+    let (materials, material_error) = match materials {
+        Ok(materials) => (materials, None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let materials: Vec<_> = materials
+        .iter()
+        .map(|material| convert_material(material, dir))
+        .collect();
+
+    let meshes = models
+        .iter()
+        .map(|model| {
+            let material = model
+                .mesh
+                .material_id
+                .and_then(|id| materials.get(id))
+                .cloned();
+
+            // an object using several materials is split into models of the same name
+            let is_split = models.iter().filter(|m| m.name == model.name).count() > 1;
+
+            let mut name = if model.name.is_empty() {
+                path.to_string()
+            } else {
+                format!("{path}:{}", model.name)
+            };
+            if let (true, Some(material)) = (is_split, &material) {
+                name = format!("{name}:{}", material.name);
+            }
+
+            let mesh = &model.mesh;
+            let mut positions = Vec::new();
+            for i in (0..mesh.positions.len()).step_by(3) {
+                let p = Vec3::new(
+                    mesh.positions[i],
+                    mesh.positions[i + 1],
+                    mesh.positions[i + 2],
+                );
+                positions.push(p);
+            }
+
+            let mut normals = Vec::new();
+            for i in (0..mesh.normals.len()).step_by(3) {
+                let n = Vec3::new(mesh.normals[i], mesh.normals[i + 1], mesh.normals[i + 2]);
+                normals.push(n);
+            }
+
+            // OBJ puts the origin of UVs at the bottom left
+            // UVs that only some of the faces have are not used
+            let uv_sets = match mesh.texcoords.len() == positions.len() * 2 {
+                false => Vec::new(),
+                true => vec![mesh
+                    .texcoords
+                    .chunks_exact(2)
+                    .map(|uv| Vec2::new(uv[0], 1.0 - uv[1]))
+                    .collect()],
+            };
+
+            let mut resource = MeshResource {
+                indices: model.mesh.indices.clone(),
+                positions,
+                normals,
+                uv_sets,
+                tangents: Vec::new(),
+                name,
+            };
+            resource.generate_missing_normals();
+            resource.generate_missing_tangents();
+            let repaired = resource
+                .validate_with(policy)
+                .map_err(ObjError::Validation)?;
+
+            Ok(ImportedMesh {
+                resource,
+                material,
+                transform: glam::Mat4::IDENTITY,
+                repaired,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(ObjFile {
+        meshes,
+        material_error,
+        cache_error: None,
+    })
+}
+
+/// Maps an MTL material onto `Material`, textures are relative to `dir`
+/// Pm and Pr of the PBR extension are used when present. Otherwise, the metallic factor is the
+/// share of Ks in the reflectance, and the roughness is converted from the Phong exponent Ns
+pub fn convert_material(material: &tobj::Material, dir: &Path) -> ImportedMaterial {
+    let param = |key: &str| -> Option<f32> {
+        material
+            .unknown_param
+            .get(key)
+            .and_then(|value| value.split_whitespace().next()?.parse().ok())
+    };
+
+    let diffuse = material.diffuse.map_or(DEFAULT_DIFFUSE, Vec3::from_array);
+    let specular = material.specular.map_or(Vec3::ZERO, Vec3::from_array);
+
+    let (metallic, specular_reflectance) = match param("Pm") {
+        // metals reflect their base color, as in glTF
+        Some(metallic) => (metallic, diffuse),
+        None => {
+            let total = diffuse.max_element() + specular.max_element();
+            let metallic = match total {
+                0.0 => 0.0,
+                _ => specular.max_element() / total,
+            };
+            (metallic, specular)
+        }
+    };
+
+    let roughness = match (param("Pr"), material.shininess) {
+        (Some(roughness), _) => roughness,
+        (None, Some(shininess)) => phong_exponent_to_roughness(shininess),
+        (None, None) => 1.0,
+    };
+
+    let texture = |path: Option<&String>| -> Option<PathBuf> {
+        // options such as `-bm 0.5` come before the file name
+        let file = path?.split_whitespace().last()?;
+        Some(dir.join(file))
+    };
+
+    let textures = MaterialTextures {
+        base_color: texture(material.diffuse_texture.as_ref()),
+        specular: texture(material.specular_texture.as_ref()),
+        roughness: texture(material.unknown_param.get("map_Pr"))
+            .or_else(|| texture(material.shininess_texture.as_ref())),
+        metallic: texture(material.unknown_param.get("map_Pm")),
+        normal: texture(material.unknown_param.get("norm"))
+            .or_else(|| texture(material.normal_texture.as_ref())),
+    };
+
+    ImportedMaterial {
+        name: material.name.clone(),
+        material: Material {
+            base_color: diffuse,
+            metallic: metallic.clamp(0.0, 1.0),
+            specular_reflectance,
+            roughness: roughness.clamp(0.0, 1.0),
+            specular_tint: specular_reflectance,
+            pad: Default::default(),
+        },
+        textures,
+    }
+}
+
+/// The roughness whose GGX distribution is closest to a Blinn-Phong lobe of `exponent`
+/// alpha = sqrt(2 / (exponent + 2)) [Walter et al. 2007], and alpha = roughness^2 in the BRDF
+pub fn phong_exponent_to_roughness(exponent: f32) -> f32 {
+    let alpha = (2.0 / (exponent.max(0.0) + 2.0)).sqrt();
+    alpha.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OBJ: &str = "\
+mtllib scene.mtl
+o quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vn 0 0 1
+usemtl red
+f 1//1 2//1 3//1
+usemtl metal
+f 1//1 3//1 4//1
+o triangle
+v 0 0 1
+v 1 0 1
+v 0 1 1
+usemtl default
+f 5 6 7
+";
+
+    const MTL: &str = "\
+newmtl red
+Kd 0.8 0.1 0.1
+Ks 0.2 0.2 0.2
+Ns 98
+map_Kd -bm 0.5 textures/red.png
+map_Ks textures/red_specular.png
+norm -bm 0.5 textures/red_normal.png
+
+newmtl metal
+Kd 0.9 0.8 0.5
+Ks 0.04 0.04 0.04
+Pm 1.0
+Pr 0.3
+map_Pr -s 1 1 1 textures/roughness.png
+
+newmtl default
+";
+
+    // An OBJ file in a directory removed when the test ends
+    struct Fixture {
+        dir: PathBuf,
+        obj: String,
+    }
+
+    impl Fixture {
+        fn new(name: &str, obj: &str, mtl: Option<&str>) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("sandbox-obj-{name}-{}", std::process::id()));
+            let models = dir.join("models");
+            std::fs::create_dir_all(&models).unwrap();
+
+            std::fs::write(models.join("scene.obj"), obj).unwrap();
+            if let Some(mtl) = mtl {
+                std::fs::write(models.join("scene.mtl"), mtl).unwrap();
+            }
+
+            let obj = models.join("scene.obj").to_str().unwrap().to_string();
+            Fixture { dir, obj }
+        }
+
+        fn load(&self) -> ObjFile {
+            load(&self.obj, ValidationPolicy::Repair, None).unwrap()
+        }
+
+        fn texture(&self, file: &str) -> Option<PathBuf> {
+            Some(self.dir.join("models").join(file))
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn material<'a>(file: &'a ObjFile, name: &str) -> &'a ImportedMaterial {
+        file.meshes
+            .iter()
+            .filter_map(|mesh| mesh.material.as_ref())
+            .find(|material| material.name == name)
+            .unwrap()
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn phong_materials() {
+        let f = Fixture::new("phong", OBJ, Some(MTL));
+        let file = f.load();
+        assert!(file.material_error.is_none());
+
+        let red = &material(&file, "red").material;
+        assert_eq!(red.base_color, Vec3::new(0.8, 0.1, 0.1));
+        // the share of Ks in the reflectance
+        assert_close(red.metallic, 0.2);
+        assert_eq!(red.specular_reflectance, Vec3::splat(0.2));
+        assert_eq!(red.specular_tint, Vec3::splat(0.2));
+        assert_close(red.roughness, phong_exponent_to_roughness(98.0));
+        assert_close(red.roughness, 0.02f32.sqrt().sqrt());
+
+        // without Kd, Ks and Ns
+        let default = &material(&file, "default").material;
+        assert_eq!(default.base_color, DEFAULT_DIFFUSE);
+        assert_eq!(default.metallic, 0.0);
+        assert_eq!(default.specular_reflectance, Vec3::ZERO);
+        assert_eq!(default.roughness, 1.0);
+    }
+
+    #[test]
+    fn pbr_materials() {
+        let f = Fixture::new("pbr", OBJ, Some(MTL));
+        let file = f.load();
+
+        // Pm and Pr replace Ks and Ns, and metals reflect their base color
+        let metal = &material(&file, "metal").material;
+        assert_eq!(metal.base_color, Vec3::new(0.9, 0.8, 0.5));
+        assert_eq!(metal.metallic, 1.0);
+        assert_eq!(metal.specular_reflectance, metal.base_color);
+        assert_close(metal.roughness, 0.3);
+    }
+
+    #[test]
+    fn textures_are_relative_to_the_obj() {
+        let f = Fixture::new("textures", OBJ, Some(MTL));
+        let file = f.load();
+
+        // the options before the file names are dropped
+        let red = &material(&file, "red").textures;
+        assert_eq!(red.base_color, f.texture("textures/red.png"));
+        assert_eq!(red.specular, f.texture("textures/red_specular.png"));
+        assert_eq!(red.normal, f.texture("textures/red_normal.png"));
+        assert_eq!(red.roughness, None);
+        assert_eq!(red.metallic, None);
+
+        let metal = &material(&file, "metal").textures;
+        assert_eq!(metal.roughness, f.texture("textures/roughness.png"));
+        assert_eq!(metal.base_color, None);
+
+        assert_eq!(
+            material(&file, "default").textures,
+            MaterialTextures::default()
+        );
+    }
+
+    #[test]
+    fn objects_are_split_by_material() {
+        let f = Fixture::new("split", OBJ, Some(MTL));
+        let file = f.load();
+
+        let names: Vec<_> = file
+            .meshes
+            .iter()
+            .map(|mesh| mesh.resource.name().strip_prefix(&f.obj).unwrap())
+            .collect();
+        assert_eq!(names, [":quad:red", ":quad:metal", ":triangle"]);
+
+        let materials: Vec<_> = file
+            .meshes
+            .iter()
+            .map(|mesh| mesh.material.as_ref().unwrap().name.as_str())
+            .collect();
+        assert_eq!(materials, ["red", "metal", "default"]);
+
+        for mesh in &file.meshes {
+            assert_eq!(mesh.resource.indices().len(), 3);
+            assert!(mesh.resource.has_normals());
+        }
+    }
+
+    #[test]
+    fn missing_mtl() {
+        let f = Fixture::new("missing", OBJ, None);
+        let file = f.load();
+
+        // the geometry is still loaded, without splitting the quad for unknown materials
+        assert!(file.material_error.is_some());
+        assert_eq!(file.meshes.len(), 2);
+        assert_eq!(file.meshes[0].resource.indices().len(), 6);
+        assert!(file.meshes.iter().all(|mesh| mesh.material.is_none()));
+    }
+}
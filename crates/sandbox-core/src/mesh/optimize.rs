@@ -0,0 +1,501 @@
+// Optimizations of the vertex and index order for the GPU, which keep the rendered triangles
+// Each step expects a valid mesh, see `MeshResource::validate`
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use super::MeshResource;
+
+/// The vertices of the FIFO post-transform cache of `MeshResource::stats` and the reports
+pub const STATS_CACHE_SIZE: usize = 16;
+
+// The vertices of the LRU cache simulated by the cache optimization, for which the scoring is made
+// Simulating it gives a better order for the FIFO cache than simulating the FIFO cache itself
+const OPTIMIZER_CACHE_SIZE: usize = 32;
+
+// The scoring of "Linear-Speed Vertex Cache Optimisation" by Tom Forsyth
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshStats {
+    pub vertex_count: usize,
+    /// Average cache miss ratio, the transformed vertices per triangle with a FIFO cache of
+    /// `STATS_CACHE_SIZE` vertices
+    pub acmr: f32,
+}
+
+impl fmt::Display for MeshStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} vertices, ACMR {:.3}", self.vertex_count, self.acmr)
+    }
+}
+
+/// The statistics after each step of `MeshResource::optimize`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptimizationReport {
+    pub input: MeshStats,
+    pub welded: MeshStats,
+    pub cache_optimized: MeshStats,
+    pub fetch_optimized: MeshStats,
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "input:           {}", self.input)?;
+        writeln!(f, "welded:          {}", self.welded)?;
+        writeln!(f, "cache optimized: {}", self.cache_optimized)?;
+        write!(f, "fetch optimized: {}", self.fetch_optimized)
+    }
+}
+
+impl MeshResource {
+    /// Runs every step in the order of welding, cache optimization and fetch optimization
+    pub fn optimize(&mut self) -> OptimizationReport {
+        let input = self.stats();
+        self.weld_vertices();
+        let welded = self.stats();
+        self.optimize_vertex_cache();
+        let cache_optimized = self.stats();
+        self.optimize_vertex_fetch();
+        let fetch_optimized = self.stats();
+
+        OptimizationReport {
+            input,
+            welded,
+            cache_optimized,
+            fetch_optimized,
+        }
+    }
+
+    pub fn stats(&self) -> MeshStats {
+        MeshStats {
+            vertex_count: self.positions.len(),
+            acmr: self.acmr(STATS_CACHE_SIZE),
+        }
+    }
+
+    /// Average cache miss ratio of the indices with a FIFO cache of `cache_size` vertices
+    /// 3 is the worst, and 0.5 is the best for large regular meshes
+    pub fn acmr(&self, cache_size: usize) -> f32 {
+        let triangle_count = self.indices.len() / 3;
+        if triangle_count == 0 {
+            return 0.0;
+        }
+
+        let mut cache = VecDeque::with_capacity(cache_size + 1);
+        let mut misses = 0;
+        for &index in &self.indices[..triangle_count * 3] {
+            if !cache.contains(&index) {
+                misses += 1;
+                cache.push_back(index);
+                if cache.len() > cache_size {
+                    cache.pop_front();
+                }
+            }
+        }
+
+        misses as f32 / triangle_count as f32
+    }
+
+    /// Merges the vertices whose attributes are bitwise identical, and returns the number of the
+    /// removed vertices
+    pub fn weld_vertices(&mut self) -> usize {
+        let vertex_count = self.positions.len();
+
+        let mut unique = HashMap::with_capacity(vertex_count);
+        let mut vertices = Vec::with_capacity(vertex_count);
+        let remap: Vec<u32> = (0..vertex_count)
+            .map(|vertex| {
+                let key = self.vertex_key(vertex);
+                *unique.entry(key).or_insert_with(|| {
+                    vertices.push(vertex as u32);
+                    vertices.len() as u32 - 1
+                })
+            })
+            .collect();
+
+        if vertices.len() == vertex_count {
+            return 0;
+        }
+
+        self.gather_vertices(&vertices);
+        for index in &mut self.indices {
+            *index = remap[*index as usize];
+        }
+
+        vertex_count - vertices.len()
+    }
+
+    /// Reorders the triangles so that their vertices are reused while they are in the
+    /// post-transform cache, with the algorithm of Tom Forsyth
+    pub fn optimize_vertex_cache(&mut self) {
+        self.indices = cache_order(&self.indices, self.positions.len());
+    }
+
+    /// Reorders the vertices in the order of their first use, so that the vertex fetches are
+    /// close in memory. Vertices that no triangle uses are removed
+    pub fn optimize_vertex_fetch(&mut self) {
+        let mut remap = vec![u32::MAX; self.positions.len()];
+        let mut vertices = Vec::with_capacity(self.positions.len());
+
+        for index in &mut self.indices {
+            let new_index = &mut remap[*index as usize];
+            if *new_index == u32::MAX {
+                *new_index = vertices.len() as u32;
+                vertices.push(*index);
+            }
+            *index = *new_index;
+        }
+
+        self.gather_vertices(&vertices);
+    }
+
+    /// The indices as u16 if every vertex can be addressed by them, which halves the index buffer
+    /// 0xffff is left out as it cuts strips when primitive restart is enabled
+    pub fn narrow_indices(&self) -> Option<Vec<u16>> {
+        if self.positions.len() > u16::MAX as usize {
+            return None;
+        }
+        Some(self.indices.iter().map(|&i| i as u16).collect())
+    }
+
+    // The bits of every attribute, with negative zeros made positive so that they are welded
+    fn vertex_key(&self, vertex: usize) -> Vec<u32> {
+        let mut key = Vec::new();
+        let mut push = |values: &[f32]| key.extend(values.iter().map(|v| (v + 0.0).to_bits()));
+
+        push(&self.positions[vertex].to_array());
+        if let Some(normal) = self.normals.get(vertex) {
+            push(&normal.to_array());
+        }
+        for uvs in &self.uv_sets {
+            push(&uvs[vertex].to_array());
+        }
+        if let Some(tangent) = self.tangents.get(vertex) {
+            push(&tangent.to_array());
+        }
+
+        key
+    }
+}
+
+// The triangles of `indices` in the order of `MeshResource::optimize_vertex_cache`, which is also
+// used for the LODs
+pub(super) fn cache_order(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    let triangles = &indices[..triangle_count * 3];
+
+    // the triangles that are not emitted yet around each vertex, packed in one array
+    let mut offsets = vec![0; vertex_count + 1];
+    for &index in triangles {
+        offsets[index as usize + 1] += 1;
+    }
+    for vertex in 0..vertex_count {
+        offsets[vertex + 1] += offsets[vertex];
+    }
+    let mut remaining: Vec<usize> = (0..vertex_count)
+        .map(|vertex| offsets[vertex + 1] - offsets[vertex])
+        .collect();
+    let mut adjacency = vec![0; triangles.len()];
+    let mut filled = vec![0; vertex_count];
+    for (triangle, indices) in triangles.chunks_exact(3).enumerate() {
+        for &index in indices {
+            let vertex = index as usize;
+            adjacency[offsets[vertex] + filled[vertex]] = triangle;
+            filled[vertex] += 1;
+        }
+    }
+
+    let mut vertex_scores: Vec<f32> = remaining
+        .iter()
+        .map(|&remaining| vertex_score(None, remaining))
+        .collect();
+    let triangle_scores: Vec<f32> = triangles
+        .chunks_exact(3)
+        .map(|indices| indices.iter().map(|&i| vertex_scores[i as usize]).sum())
+        .collect();
+    let mut emitted = vec![false; triangle_count];
+
+    let mut output = Vec::with_capacity(triangles.len());
+    // the vertices of the emitted triangles, most recent first
+    let mut cache: Vec<u32> = Vec::with_capacity(OPTIMIZER_CACHE_SIZE + 3);
+    let mut next_cache = Vec::with_capacity(OPTIMIZER_CACHE_SIZE + 3);
+    // scans the triangles in order when no triangle around the cache is left
+    let mut cursor = 0;
+
+    let mut best =
+        (0..triangle_count).max_by(|&a, &b| triangle_scores[a].total_cmp(&triangle_scores[b]));
+
+    while let Some(triangle) = best {
+        let indices = &triangles[triangle * 3..triangle * 3 + 3];
+        output.extend_from_slice(indices);
+        emitted[triangle] = true;
+
+        for &index in indices {
+            let vertex = index as usize;
+            let around = &mut adjacency[offsets[vertex]..offsets[vertex] + remaining[vertex]];
+            let position = around.iter().position(|&t| t == triangle).unwrap();
+            around.swap(position, remaining[vertex] - 1);
+            remaining[vertex] -= 1;
+        }
+
+        next_cache.clear();
+        next_cache.extend_from_slice(indices);
+        next_cache.extend(cache.iter().filter(|i| !indices.contains(i)));
+
+        // the vertices pushed out of the cache lose their cache score
+        for (position, &index) in next_cache.iter().enumerate() {
+            let vertex = index as usize;
+            let cache_position = (position < OPTIMIZER_CACHE_SIZE).then_some(position);
+            vertex_scores[vertex] = vertex_score(cache_position, remaining[vertex]);
+        }
+
+        best = None;
+        let mut best_score = f32::MIN;
+        for &index in &next_cache {
+            let vertex = index as usize;
+            for &t in &adjacency[offsets[vertex]..offsets[vertex] + remaining[vertex]] {
+                let score: f32 = triangles[t * 3..t * 3 + 3]
+                    .iter()
+                    .map(|&i| vertex_scores[i as usize])
+                    .sum();
+                if score > best_score {
+                    best = Some(t);
+                    best_score = score;
+                }
+            }
+        }
+
+        next_cache.truncate(OPTIMIZER_CACHE_SIZE);
+        std::mem::swap(&mut cache, &mut next_cache);
+
+        if best.is_none() {
+            while cursor < triangle_count && emitted[cursor] {
+                cursor += 1;
+            }
+            best = (cursor < triangle_count).then_some(cursor);
+        }
+    }
+
+    output
+}
+
+// Vertices with few triangles left are preferred so that they do not need to be transformed again
+// later, and vertices without triangles are never chosen
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        None => 0.0,
+        // the last triangle is penalized as its vertices are likely to be in the cache anyway
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (OPTIMIZER_CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        }
+    };
+
+    cache_score + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::super::primitives;
+    use super::*;
+
+    // The triangles as positions, starting at their smallest vertex so that the order of the
+    // indices and vertices does not matter
+    fn triangles(mesh: &MeshResource) -> Vec<[[u32; 3]; 3]> {
+        let mut triangles: Vec<_> = mesh
+            .indices()
+            .chunks_exact(3)
+            .map(|triangle| {
+                let mut corners = [0, 1, 2].map(|i| {
+                    mesh.positions()[triangle[i] as usize]
+                        .to_array()
+                        .map(f32::to_bits)
+                });
+                let first = (0..3).min_by_key(|&i| corners[i]).unwrap();
+                corners.rotate_left(first);
+                corners
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    // A deterministic shuffle of the triangles
+    fn shuffle_triangles(mesh: &mut MeshResource) {
+        let mut triangles: Vec<[u32; 3]> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        let mut state = 0x2545_f491_u32;
+        for i in (1..triangles.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            triangles.swap(i, state as usize % (i + 1));
+        }
+        mesh.indices = triangles.concat();
+    }
+
+    #[test]
+    fn acmr() {
+        let mut mesh = MeshResource::new(&[], &[Vec3::ZERO; 4], &[], "mesh".to_string());
+        assert_eq!(mesh.acmr(STATS_CACHE_SIZE), 0.0);
+
+        mesh.indices = vec![0, 1, 2];
+        assert_eq!(mesh.acmr(STATS_CACHE_SIZE), 3.0);
+
+        mesh.indices = vec![0, 1, 2, 2, 1, 3];
+        assert_eq!(mesh.acmr(STATS_CACHE_SIZE), 2.0);
+
+        // 0 is pushed out of a cache of 3 vertices by 3
+        mesh.indices = vec![0, 1, 2, 2, 1, 3, 0, 1, 3];
+        assert_eq!(mesh.acmr(3), 2.0);
+        assert_eq!(mesh.acmr(4), 4.0 / 3.0);
+    }
+
+    #[test]
+    fn weld_vertices() {
+        // a quad made of two triangles without shared vertices, one of them at -0
+        let positions = [
+            Vec3::ZERO,
+            Vec3::X,
+            Vec3::ONE,
+            Vec3::new(-0.0, 0.0, 0.0),
+            Vec3::ONE,
+            Vec3::Y,
+        ];
+        let mut mesh = MeshResource::new(
+            &[0, 1, 2, 3, 4, 5],
+            &positions,
+            &[Vec3::Z; 6],
+            "quad".to_string(),
+        );
+        let before = mesh.stats();
+        let triangles_before = triangles(&mesh);
+
+        assert_eq!(mesh.weld_vertices(), 2);
+
+        let after = mesh.stats();
+        assert_eq!(before.vertex_count, 6);
+        assert_eq!(before.acmr, 3.0);
+        assert_eq!(after.vertex_count, 4);
+        assert_eq!(after.acmr, 2.0);
+        assert_eq!(mesh.indices(), [0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.normals().len(), 4);
+
+        // welding again changes nothing
+        assert_eq!(mesh.weld_vertices(), 0);
+        assert_eq!(mesh.stats(), after);
+        assert_eq!(triangles(&mesh).len(), triangles_before.len());
+    }
+
+    #[test]
+    fn weld_keeps_different_attributes() {
+        let mut mesh = primitives::cuboid(Vec3::ONE);
+        let before = mesh.stats();
+
+        // the corners of a cube have a normal for each face
+        assert_eq!(mesh.weld_vertices(), 0);
+        assert_eq!(mesh.stats(), before);
+    }
+
+    #[test]
+    fn optimize_vertex_cache() {
+        let mut mesh = primitives::uv_sphere(1.0, 32, 16);
+        shuffle_triangles(&mut mesh);
+        let triangles_before = triangles(&mesh);
+        let before = mesh.stats();
+
+        mesh.optimize_vertex_cache();
+        let after = mesh.stats();
+
+        assert_eq!(after.vertex_count, before.vertex_count);
+        assert!(before.acmr > 1.9, "{before}");
+        assert!(after.acmr < 0.8, "{after}");
+        assert_eq!(triangles(&mesh), triangles_before);
+    }
+
+    #[test]
+    fn optimize_vertex_fetch() {
+        let mut mesh = primitives::uv_sphere(1.0, 16, 8);
+        mesh.optimize_vertex_cache();
+        // an unused vertex is removed
+        mesh.positions.push(Vec3::ZERO);
+        mesh.normals.push(Vec3::Z);
+        for uvs in &mut mesh.uv_sets {
+            uvs.push(glam::Vec2::ZERO);
+        }
+        mesh.tangents.push(glam::Vec4::X);
+
+        let triangles_before = triangles(&mesh);
+        let before = mesh.stats();
+
+        mesh.optimize_vertex_fetch();
+        let after = mesh.stats();
+
+        assert_eq!(after.vertex_count, before.vertex_count - 1);
+        assert_eq!(after.acmr, before.acmr);
+        assert_eq!(triangles(&mesh), triangles_before);
+        assert!(mesh.has_tangents());
+
+        // the vertices are in the order of their first use
+        let mut next = 0;
+        for &index in mesh.indices() {
+            assert!(index <= next);
+            if index == next {
+                next += 1;
+            }
+        }
+        assert_eq!(next as usize, mesh.positions().len());
+    }
+
+    #[test]
+    fn optimization_report() {
+        let mut mesh = primitives::uv_sphere(1.0, 64, 32);
+        let input = mesh.acmr(16);
+        let triangles_before = triangles(&mesh);
+
+        let report = mesh.optimize();
+
+        // the report simulates the same cache as `acmr`
+        assert_eq!(report.input.acmr, input);
+        assert_eq!(report.cache_optimized.acmr, mesh.acmr(16));
+        assert_eq!(report.fetch_optimized.acmr, mesh.acmr(16));
+        assert_eq!(report.fetch_optimized, mesh.stats());
+
+        assert_eq!(report.welded.vertex_count, report.input.vertex_count);
+        assert!(report.cache_optimized.acmr < report.welded.acmr);
+        assert!(report.cache_optimized.acmr < 0.75, "{report}");
+        assert_eq!(triangles(&mesh), triangles_before);
+
+        let text = report.to_string();
+        assert_eq!(text.lines().count(), 4);
+        assert!(text.starts_with(&format!("input:           {}", report.input)));
+    }
+
+    #[test]
+    fn narrow_indices() {
+        let mesh = |vertex_count: usize| {
+            let positions = vec![Vec3::ZERO; vertex_count];
+            let last = vertex_count as u32 - 1;
+            MeshResource::new(&[0, last / 2, last], &positions, &[], "mesh".to_string())
+        };
+
+        assert_eq!(mesh(3).narrow_indices(), Some(vec![0, 1, 2]));
+        assert_eq!(mesh(65535).narrow_indices(), Some(vec![0, 32767, 65534]));
+        // 0xffff would cut a strip
+        assert_eq!(mesh(65536).narrow_indices(), None);
+    }
+}
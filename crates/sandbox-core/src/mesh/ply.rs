@@ -0,0 +1,749 @@
+// Stanford PLY importer for ASCII, binary little endian and binary big endian files
+// The positions and the optional normals of the vertex element and the faces are imported,
+// polygons are triangulated as fans, and the other elements and properties are skipped
+
+use std::fmt;
+use std::path::Path;
+
+use glam::{Mat4, Vec3};
+
+use super::{ImportedMesh, MeshResource, ValidationError, ValidationPolicy};
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io(std::io::Error),
+    /// The file does not follow the format
+    Invalid(String),
+    /// A valid feature that this importer does not handle
+    Unsupported(String),
+    Validation(ValidationError),
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlyError::Io(e) => write!(f, "failed to read: {e}"),
+            PlyError::Invalid(message) => write!(f, "invalid PLY: {message}"),
+            PlyError::Unsupported(message) => write!(f, "unsupported PLY: {message}"),
+            PlyError::Validation(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for PlyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PlyError::Io(e) => Some(e),
+            PlyError::Validation(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Loads a PLY file as a mesh named `path`, which has no material
+pub fn load(path: &Path, policy: ValidationPolicy) -> Result<ImportedMesh, PlyError> {
+    let bytes = std::fs::read(path).map_err(PlyError::Io)?;
+    parse(&bytes, path.display().to_string(), policy)
+}
+
+/// Parses the contents of a PLY file
+/// The normals are generated unless the vertices have all of nx, ny and nz
+pub fn parse(
+    bytes: &[u8],
+    name: String,
+    policy: ValidationPolicy,
+) -> Result<ImportedMesh, PlyError> {
+    let (header, body) = parse_header(bytes)?;
+
+    let mut body = match header.format {
+        Format::Ascii => {
+            let text = std::str::from_utf8(body)
+                .map_err(|e| PlyError::Invalid(format!("the ASCII body is not UTF-8: {e}")))?;
+            Body::Ascii(text.split_ascii_whitespace())
+        }
+        Format::BinaryLittleEndian => Body::Binary {
+            bytes: body,
+            big_endian: false,
+        },
+        Format::BinaryBigEndian => Body::Binary {
+            bytes: body,
+            big_endian: true,
+        },
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::new();
+    let mut vertex_count = None;
+
+    for element in &header.elements {
+        let find = |name: &str| element.properties.iter().position(|p| p.name == name);
+
+        match element.name.as_str() {
+            "vertex" => {
+                let position = [find("x"), find("y"), find("z")];
+                let [Some(x), Some(y), Some(z)] = position else {
+                    return Err(PlyError::Invalid(
+                        "the vertices do not have x, y and z".to_string(),
+                    ));
+                };
+                let normal = match [find("nx"), find("ny"), find("nz")] {
+                    [Some(x), Some(y), Some(z)] => Some([x, y, z]),
+                    _ => None,
+                };
+
+                let mut values = vec![0.0; element.properties.len()];
+                for _ in 0..element.count {
+                    for (value, property) in values.iter_mut().zip(&element.properties) {
+                        *value = match property.kind {
+                            PropertyKind::Scalar(scalar) => body.read(scalar)?,
+                            // lists such as texture numbers are not used
+                            PropertyKind::List { .. } => {
+                                body.skip(property)?;
+                                0.0
+                            }
+                        };
+                    }
+
+                    let vec3 = |[x, y, z]: [usize; 3]| {
+                        Vec3::new(values[x] as f32, values[y] as f32, values[z] as f32)
+                    };
+                    positions.push(vec3([x, y, z]));
+                    if let Some(normal) = normal {
+                        normals.push(vec3(normal));
+                    }
+                }
+
+                vertex_count = Some(element.count);
+            }
+            "face" => {
+                let list = find("vertex_indices").or_else(|| find("vertex_index"));
+                let Some(list) = list else {
+                    return Err(PlyError::Invalid(
+                        "the faces do not have vertex_indices".to_string(),
+                    ));
+                };
+                let PropertyKind::List { count, item } = element.properties[list].kind else {
+                    return Err(PlyError::Invalid(
+                        "vertex_indices is not a list".to_string(),
+                    ));
+                };
+
+                let mut polygon = Vec::new();
+                for _ in 0..element.count {
+                    for (i, property) in element.properties.iter().enumerate() {
+                        if i != list {
+                            body.skip(property)?;
+                            continue;
+                        }
+
+                        polygon.clear();
+                        let len = body.read_index(count)?;
+                        for _ in 0..len {
+                            polygon.push(body.read_index(item)?);
+                        }
+                    }
+
+                    // points and lines have no area to render
+                    for i in 2..polygon.len() {
+                        indices.extend([polygon[0], polygon[i - 1], polygon[i]]);
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        body.skip(property)?;
+                    }
+                }
+            }
+        }
+    }
+
+    let vertex_count =
+        vertex_count.ok_or_else(|| PlyError::Invalid("there is no vertex element".to_string()))?;
+    if let Some(index) = indices.iter().find(|&&i| i as usize >= vertex_count) {
+        return Err(PlyError::Invalid(format!(
+            "the index {index} is out of the {vertex_count} vertices"
+        )));
+    }
+
+    let mut resource = MeshResource {
+        indices,
+        positions,
+        normals,
+        uv_sets: Vec::new(),
+        tangents: Vec::new(),
+        name,
+    };
+    resource.generate_missing_normals();
+    let repaired = resource
+        .validate_with(policy)
+        .map_err(PlyError::Validation)?;
+
+    Ok(ImportedMesh {
+        resource,
+        material: None,
+        transform: Mat4::IDENTITY,
+        repaired,
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Clone, Copy)]
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self, PlyError> {
+        match name {
+            "char" | "int8" => Ok(Scalar::I8),
+            "uchar" | "uint8" => Ok(Scalar::U8),
+            "short" | "int16" => Ok(Scalar::I16),
+            "ushort" | "uint16" => Ok(Scalar::U16),
+            "int" | "int32" => Ok(Scalar::I32),
+            "uint" | "uint32" => Ok(Scalar::U32),
+            "float" | "float32" => Ok(Scalar::F32),
+            "double" | "float64" => Ok(Scalar::F64),
+            _ => Err(PlyError::Invalid(format!("unknown type {name}"))),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    fn is_integer(self) -> bool {
+        !matches!(self, Scalar::F32 | Scalar::F64)
+    }
+}
+
+// Returns the header and the bytes after end_header
+fn parse_header(bytes: &[u8]) -> Result<(Header, &[u8]), PlyError> {
+    let mut rest = bytes;
+    let mut next_line = || -> Result<&str, PlyError> {
+        let end = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| PlyError::Invalid("the header has no end_header".to_string()))?;
+        let line = std::str::from_utf8(&rest[..end])
+            .map_err(|_| PlyError::Invalid("the header is not ASCII".to_string()))?;
+        rest = &rest[end + 1..];
+        Ok(line.trim_end_matches('\r'))
+    };
+
+    if next_line().ok() != Some("ply") {
+        return Err(PlyError::Invalid(
+            "the file does not start with ply".to_string(),
+        ));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    loop {
+        let line = next_line()?;
+        let words: Vec<_> = line.split_ascii_whitespace().collect();
+
+        match words.as_slice() {
+            ["end_header"] => break,
+            [] | ["comment" | "obj_info", ..] => {}
+            ["format", name, version] => {
+                if *version != "1.0" {
+                    return Err(PlyError::Unsupported(format!("version {version}")));
+                }
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(PlyError::Invalid(format!("unknown format {name}"))),
+                });
+            }
+            ["element", name, count] => {
+                let count = count
+                    .parse()
+                    .map_err(|_| PlyError::Invalid(format!("invalid count of {name}: {count}")))?;
+                elements.push(Element {
+                    name: name.to_string(),
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            ["property", "list", count, item, name] => {
+                let count = Scalar::parse(count)?;
+                let item = Scalar::parse(item)?;
+                if !count.is_integer() {
+                    return Err(PlyError::Invalid(format!(
+                        "the count of {name} is not an integer"
+                    )));
+                }
+                push_property(&mut elements, name, PropertyKind::List { count, item })?;
+            }
+            ["property", scalar, name] => {
+                let scalar = Scalar::parse(scalar)?;
+                push_property(&mut elements, name, PropertyKind::Scalar(scalar))?;
+            }
+            _ => return Err(PlyError::Invalid(format!("invalid header line \"{line}\""))),
+        }
+    }
+
+    let format = format.ok_or_else(|| PlyError::Invalid("the format is missing".to_string()))?;
+
+    Ok((Header { format, elements }, rest))
+}
+
+fn push_property(elements: &mut [Element], name: &str, kind: PropertyKind) -> Result<(), PlyError> {
+    let element = elements
+        .last_mut()
+        .ok_or_else(|| PlyError::Invalid(format!("the property {name} has no element")))?;
+    element.properties.push(Property {
+        name: name.to_string(),
+        kind,
+    });
+    Ok(())
+}
+
+enum Body<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl Body<'_> {
+    // Every type is exactly representable by f64
+    fn read(&mut self, scalar: Scalar) -> Result<f64, PlyError> {
+        let truncated = || PlyError::Invalid("the file is truncated".to_string());
+
+        match self {
+            Body::Ascii(words) => {
+                let word = words.next().ok_or_else(truncated)?;
+                let value: f64 = word
+                    .parse()
+                    .map_err(|_| PlyError::Invalid(format!("invalid number {word}")))?;
+                if scalar.is_integer() && value.fract() != 0.0 {
+                    return Err(PlyError::Invalid(format!("{word} is not an integer")));
+                }
+                Ok(value)
+            }
+            Body::Binary { bytes, big_endian } => {
+                let size = scalar.size();
+                if bytes.len() < size {
+                    return Err(truncated());
+                }
+                let (value, rest) = bytes.split_at(size);
+                *bytes = rest;
+
+                let mut b = [0; 8];
+                b[..size].copy_from_slice(value);
+                if *big_endian {
+                    b[..size].reverse();
+                }
+
+                Ok(match scalar {
+                    Scalar::I8 => i8::from_le_bytes([b[0]]) as f64,
+                    Scalar::U8 => b[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    Scalar::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    Scalar::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    Scalar::F64 => f64::from_le_bytes(b),
+                })
+            }
+        }
+    }
+
+    fn read_index(&mut self, scalar: Scalar) -> Result<u32, PlyError> {
+        let value = self.read(scalar)?;
+        if !scalar.is_integer() || !(0.0..=u32::MAX as f64).contains(&value) {
+            return Err(PlyError::Invalid(format!("invalid index or count {value}")));
+        }
+        Ok(value as u32)
+    }
+
+    fn skip(&mut self, property: &Property) -> Result<(), PlyError> {
+        match property.kind {
+            PropertyKind::Scalar(scalar) => {
+                self.read(scalar)?;
+            }
+            PropertyKind::List { count, item } => {
+                let len = self.read_index(count)?;
+                for _ in 0..len {
+                    self.read(item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::MeshProblem;
+    use super::*;
+
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+
+    // A quad whose vertices have an extra color and list property, followed by an element
+    // that is not imported
+    const HEADER: &str = "element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property list uchar int vertex_data
+element face 1
+property uchar flags
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+";
+
+    const ASCII_BODY: &str = "0 0 0 0 0 1 255 0
+1 0 0 0 0 1 255 1 7
+1 1 0 0 0 1 255 2 7 7
+0 1 0 0 0 1 255 0
+0 4 0 1 2 3
+0 1
+";
+
+    fn parse_str(text: &str) -> Result<ImportedMesh, PlyError> {
+        parse(
+            text.as_bytes(),
+            "test".to_string(),
+            ValidationPolicy::Reject,
+        )
+    }
+
+    // The same quad as ASCII_BODY in a binary format
+    fn binary(big_endian: bool) -> Vec<u8> {
+        let format = match big_endian {
+            true => "binary_big_endian",
+            false => "binary_little_endian",
+        };
+        let mut bytes = format!("ply\r\nformat {format} 1.0\r\n{HEADER}").into_bytes();
+
+        let mut push = |value: &[u8]| match big_endian {
+            true => bytes.extend(value.iter().rev()),
+            false => bytes.extend(value),
+        };
+
+        for (i, position) in POSITIONS.iter().enumerate() {
+            for v in position.iter().chain(&[0.0, 0.0, 1.0]) {
+                push(&v.to_le_bytes());
+            }
+            push(&[255]);
+            push(&[i as u8 % 3]);
+            for _ in 0..i % 3 {
+                push(&7i32.to_le_bytes());
+            }
+        }
+
+        push(&[0]);
+        push(&[4]);
+        for i in 0..4i32 {
+            push(&i.to_le_bytes());
+        }
+
+        push(&0i32.to_le_bytes());
+        push(&1i32.to_le_bytes());
+        bytes
+    }
+
+    fn assert_quad(mesh: &ImportedMesh) {
+        assert!(mesh.material.is_none());
+        assert!(mesh.repaired.is_empty());
+        let mesh = &mesh.resource;
+        assert_eq!(mesh.indices(), [0, 1, 2, 0, 2, 3]);
+        let positions: Vec<_> = mesh.positions().iter().map(|p| p.to_array()).collect();
+        assert_eq!(positions, POSITIONS);
+        assert_eq!(mesh.normals(), [Vec3::Z; 4]);
+        assert_eq!(mesh.name(), "test");
+    }
+
+    fn error(result: Result<ImportedMesh, PlyError>) -> String {
+        match result {
+            Err(e @ (PlyError::Invalid(_) | PlyError::Unsupported(_))) => e.to_string(),
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let text = format!("ply\nformat ascii 1.0\ncomment made by hand\n{HEADER}{ASCII_BODY}");
+        assert_quad(&parse_str(&text).unwrap());
+    }
+
+    #[test]
+    fn binary_little_endian() {
+        let bytes = binary(false);
+        assert_quad(&parse(&bytes, "test".to_string(), ValidationPolicy::Reject).unwrap());
+    }
+
+    #[test]
+    fn binary_big_endian() {
+        let bytes = binary(true);
+        assert_quad(&parse(&bytes, "test".to_string(), ValidationPolicy::Reject).unwrap());
+    }
+
+    #[test]
+    fn scalar_types() {
+        let text = "ply
+format binary_big_endian 1.0
+element vertex 1
+property char x
+property ushort y
+property double z
+element face 0
+property list ushort uint vertex_index
+end_header
+";
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.push(-2i8 as u8);
+        bytes.extend(65535u16.to_be_bytes());
+        bytes.extend(0.5f64.to_be_bytes());
+
+        // a mesh without triangles is rejected by the validation, so only the header and the
+        // body are parsed
+        let (header, body) = parse_header(&bytes).unwrap();
+        let mut body = Body::Binary {
+            bytes: body,
+            big_endian: true,
+        };
+        assert!(matches!(header.format, Format::BinaryBigEndian));
+        assert_eq!(header.elements.len(), 2);
+
+        let values: Vec<f64> = header.elements[0]
+            .properties
+            .iter()
+            .map(|property| match property.kind {
+                PropertyKind::Scalar(scalar) => body.read(scalar).unwrap(),
+                PropertyKind::List { .. } => unreachable!(),
+            })
+            .collect();
+        assert_eq!(values, [-2.0, 65535.0, 0.5]);
+        assert!(body.read(Scalar::U8).is_err());
+    }
+
+    #[test]
+    fn missing_normals() {
+        let text = "ply
+format ascii 1.0
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+element face 2
+property list uchar uint vertex_indices
+end_header
+0 0 0 1
+1 0 0 1
+1 1 0 1
+0 1 0 1
+3 0 1 2
+3 0 2 3
+";
+        // the normals are generated unless the vertices have nx, ny and nz
+        let mesh = parse_str(text).unwrap().resource;
+        assert_eq!(mesh.indices(), [0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.positions().len(), 4);
+        for normal in mesh.normals() {
+            assert!(normal.abs_diff_eq(Vec3::Z, 1e-6), "{normal}");
+        }
+    }
+
+    #[test]
+    fn points_and_lines_are_skipped() {
+        let text = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 3
+property list uchar uint vertex_indices
+end_header
+0 0 0
+1 0 0
+0 1 0
+1 0
+2 0 1
+3 0 1 2
+";
+        assert_eq!(parse_str(text).unwrap().resource.indices(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn repaired_problems() {
+        let text = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 2
+property list uchar uint vertex_indices
+end_header
+0 0 0
+1 0 0
+0 1 0
+3 0 1 2
+3 0 1 1
+";
+        let repaired = vec![MeshProblem::DegenerateTriangle { triangle: 1 }];
+
+        let mesh = parse(
+            text.as_bytes(),
+            "test".to_string(),
+            ValidationPolicy::Repair,
+        )
+        .unwrap();
+        assert_eq!(mesh.resource.indices(), [0, 1, 2]);
+        assert_eq!(mesh.repaired, repaired);
+
+        match parse_str(text) {
+            Err(PlyError::Validation(e)) => assert_eq!(e.problems, repaired),
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let header = |body: &str| format!("ply\nformat ascii 1.0\n{body}");
+        let quad = |vertices: &str, faces: &str| {
+            header(&format!(
+                "element vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+                 element face 1\nproperty list uchar int vertex_indices\nend_header\n{vertices}\n{faces}\n"
+            ))
+        };
+
+        assert_eq!(
+            error(parse_str("format ascii 1.0\n")),
+            "invalid PLY: the file does not start with ply"
+        );
+        assert_eq!(
+            error(parse_str("ply\nformat ascii 2.0\nend_header\n")),
+            "unsupported PLY: version 2.0"
+        );
+        assert_eq!(
+            error(parse_str("ply\nformat binary 1.0\nend_header\n")),
+            "invalid PLY: unknown format binary"
+        );
+        assert_eq!(
+            error(parse_str("ply\nend_header\n")),
+            "invalid PLY: the format is missing"
+        );
+        assert_eq!(
+            error(parse_str(&header("element vertex 3\n"))),
+            "invalid PLY: the header has no end_header"
+        );
+        assert_eq!(
+            error(parse_str(&header("property float x\nend_header\n"))),
+            "invalid PLY: the property x has no element"
+        );
+        assert_eq!(
+            error(parse_str(&header(
+                "element vertex 1\nproperty half x\nend_header\n"
+            ))),
+            "invalid PLY: unknown type half"
+        );
+        assert_eq!(
+            error(parse_str(&header("element vertex many\nend_header\n"))),
+            "invalid PLY: invalid count of vertex: many"
+        );
+        assert_eq!(
+            error(parse_str(&header(
+                "element face 0\nproperty list float int vertex_indices\nend_header\n"
+            ))),
+            "invalid PLY: the count of vertex_indices is not an integer"
+        );
+        assert_eq!(
+            error(parse_str(&header("end_header\n"))),
+            "invalid PLY: there is no vertex element"
+        );
+        assert_eq!(
+            error(parse_str(&header(
+                "element vertex 1\nproperty float x\nproperty float y\nend_header\n0 0\n"
+            ))),
+            "invalid PLY: the vertices do not have x, y and z"
+        );
+        assert_eq!(
+            error(parse_str(&quad("0 0 0 1 0 0 0 1", ""))),
+            "invalid PLY: the file is truncated"
+        );
+        assert_eq!(
+            error(parse_str(&quad("0 0 0 1 0 0 0 1 x", ""))),
+            "invalid PLY: invalid number x"
+        );
+        assert_eq!(
+            error(parse_str(&quad("0 0 0 1 0 0 0 1 0", "3 0 1 3"))),
+            "invalid PLY: the index 3 is out of the 3 vertices"
+        );
+        assert_eq!(
+            error(parse_str(&quad("0 0 0 1 0 0 0 1 0", "3 0 1 1.5"))),
+            "invalid PLY: 1.5 is not an integer"
+        );
+        assert_eq!(
+            error(parse_str(&quad("0 0 0 1 0 0 0 1 0", "3 0 1 -1"))),
+            "invalid PLY: invalid index or count -1"
+        );
+
+        let mut bytes = binary(false);
+        bytes.pop();
+        assert_eq!(
+            error(parse(&bytes, "test".to_string(), ValidationPolicy::Reject)),
+            "invalid PLY: the file is truncated"
+        );
+    }
+}
@@ -0,0 +1,624 @@
+// Procedural meshes for test scenes, with normals, a UV set and tangents
+//
+// The triangles are wound so that `cross(p1 - p0, p2 - p0)` points outside, which is clockwise on
+// the screen as D3D12 expects. The UVs are not mirrored seen from outside in the left-handed view
+// space, which MikkTSpace takes as mirrored, so the tangents have a W of -1
+// Curved surfaces repeat the vertices on the UV seam, and the poles have a vertex for each segment
+
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+use glam::{Mat4, Quat, Vec2, Vec3, Vec3Swizzles};
+
+use super::{ImportedMaterial, ImportedMesh, Material, MaterialTextures, MeshResource};
+
+/// A primitive with its parameters, which the scene files can refer to by name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Primitive {
+    Plane {
+        size: f32,
+        subdivisions: u32,
+    },
+    Box {
+        size: Vec3,
+    },
+    UvSphere {
+        radius: f32,
+        segments: u32,
+        rings: u32,
+    },
+    Icosphere {
+        radius: f32,
+        subdivisions: u32,
+    },
+    Cylinder {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    Cone {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    Torus {
+        radius: f32,
+        tube_radius: f32,
+        segments: u32,
+        sides: u32,
+    },
+    CornellBox {
+        size: f32,
+    },
+}
+
+impl Primitive {
+    /// A mesh for each material, which only the Cornell box has
+    pub fn meshes(&self) -> Vec<ImportedMesh> {
+        let resource = match *self {
+            Primitive::Plane { size, subdivisions } => plane(size, subdivisions),
+            Primitive::Box { size } => cuboid(size),
+            Primitive::UvSphere {
+                radius,
+                segments,
+                rings,
+            } => uv_sphere(radius, segments, rings),
+            Primitive::Icosphere {
+                radius,
+                subdivisions,
+            } => icosphere(radius, subdivisions),
+            Primitive::Cylinder {
+                radius,
+                height,
+                segments,
+            } => cylinder(radius, height, segments),
+            Primitive::Cone {
+                radius,
+                height,
+                segments,
+            } => cone(radius, height, segments),
+            Primitive::Torus {
+                radius,
+                tube_radius,
+                segments,
+                sides,
+            } => torus(radius, tube_radius, segments, sides),
+            Primitive::CornellBox { size } => return cornell_box(size),
+        };
+
+        vec![ImportedMesh {
+            resource,
+            material: None,
+            transform: Mat4::IDENTITY,
+            repaired: Vec::new(),
+        }]
+    }
+}
+
+/// A square on the XZ plane facing +Y, centered at the origin, with `subdivisions` quads along
+/// each side. V points to -Z
+pub fn plane(size: f32, subdivisions: u32) -> MeshResource {
+    let subdivisions = subdivisions.max(1);
+    let mut builder = Builder::default();
+    builder.quad(
+        Vec3::ZERO,
+        Vec3::Y,
+        Vec3::Z,
+        Vec2::splat(size),
+        subdivisions,
+    );
+    builder.build("plane")
+}
+
+/// A box centered at the origin, whose faces have their own vertices and the whole UV square
+pub fn cuboid(size: Vec3) -> MeshResource {
+    let mut builder = Builder::default();
+    for (normal, up) in BOX_FACES {
+        let extent = Vec2::new(face_right(normal, up).abs().dot(size), up.abs().dot(size));
+        let center = normal * normal.abs().dot(size) * 0.5;
+        builder.quad(center, normal, up, extent, 1);
+    }
+    builder.build("box")
+}
+
+/// A sphere of `segments` around Y and `rings` from the top to the bottom, at least 3 and 2
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> MeshResource {
+    let mut builder = Builder::default();
+    let segments = segments.max(3);
+    builder.grid(segments, rings.max(2), |uv| {
+        let uv = pole_uv(uv, segments);
+        let normal = sphere_direction(uv.x * TAU, uv.y * PI);
+        (normal * radius, normal, uv)
+    });
+    builder.build("uv_sphere")
+}
+
+/// A subdivided icosahedron, whose triangles are all about the same size unlike `uv_sphere`
+/// Each subdivision splits a triangle into 4. The UVs are those of `uv_sphere`
+pub fn icosphere(radius: f32, subdivisions: u32) -> MeshResource {
+    let t = (1.0 + 5f32.sqrt()) * 0.5;
+    #[rustfmt::skip]
+    let mut directions: Vec<Vec3> = [
+        [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+        [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+        [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+    ]
+    .iter()
+    .map(|&p| Vec3::from_array(p).normalize())
+    .collect();
+    #[rustfmt::skip]
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = HashMap::new();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let direction = directions[a as usize] + directions[b as usize];
+                directions.push(direction.normalize());
+                directions.len() as u32 - 1
+            })
+        };
+
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    // the vertices are split where the UVs wrap around and at the poles, whose U is that of
+    // the other corners
+    let mut builder = Builder::default();
+    let mut vertices = HashMap::new();
+    for triangle in &triangles {
+        let mut uvs = triangle.map(|i| sphere_uv(directions[i as usize]));
+        let is_pole = triangle.map(|i| directions[i as usize].xz() == Vec2::ZERO);
+
+        let us = (0..3).filter(|&c| !is_pole[c]).map(|c| uvs[c].x);
+        let (min_u, max_u) = us.fold((1.0f32, 0.0f32), |(min, max), u| (min.min(u), max.max(u)));
+        for (c, uv) in uvs.iter_mut().enumerate() {
+            if max_u - min_u > 0.5 && uv.x < 0.5 && !is_pole[c] {
+                uv.x += 1.0;
+            }
+        }
+        for c in (0..3).filter(|&c| is_pole[c]) {
+            let others = (0..3).filter(|&o| o != c).map(|o| uvs[o].x);
+            uvs[c].x = others.sum::<f32>() * 0.5;
+        }
+
+        let indices = [0, 1, 2].map(|c| {
+            let direction = directions[triangle[c] as usize];
+            let key = (triangle[c], uvs[c].x.to_bits(), uvs[c].y.to_bits());
+            *vertices
+                .entry(key)
+                .or_insert_with(|| builder.vertex(direction * radius, direction, uvs[c]))
+        });
+        builder.indices.extend(indices);
+    }
+    builder.build("icosphere")
+}
+
+/// A cylinder along Y centered at the origin, with caps
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> MeshResource {
+    let segments = segments.max(3);
+    let mut builder = Builder::default();
+
+    builder.grid(segments, 1, |uv| {
+        let normal = sphere_direction(uv.x * TAU, PI * 0.5);
+        let y = height * (0.5 - uv.y);
+        (normal * radius + Vec3::Y * y, normal, uv)
+    });
+    builder.disk(Vec3::Y * height * 0.5, Vec3::Y, radius, segments);
+    builder.disk(Vec3::NEG_Y * height * 0.5, Vec3::NEG_Y, radius, segments);
+
+    builder.build("cylinder")
+}
+
+/// A cone along Y centered at the origin, with the apex at the top and a cap at the bottom
+pub fn cone(radius: f32, height: f32, segments: u32) -> MeshResource {
+    let segments = segments.max(3);
+    let mut builder = Builder::default();
+
+    // the side leans by the slope of the radius over the height
+    let slope = Vec2::new(height, radius).normalize();
+    builder.grid(segments, 1, |uv| {
+        // only the apex is a pole, not the bottom ring
+        let uv = match uv.y {
+            0.0 => pole_uv(uv, segments),
+            _ => uv,
+        };
+        let direction = sphere_direction(uv.x * TAU, PI * 0.5);
+        let normal = direction * slope.x + Vec3::Y * slope.y;
+        let y = height * (0.5 - uv.y);
+        (direction * radius * uv.y + Vec3::Y * y, normal, uv)
+    });
+    builder.disk(Vec3::NEG_Y * height * 0.5, Vec3::NEG_Y, radius, segments);
+
+    builder.build("cone")
+}
+
+/// A torus around Y on the XZ plane, with `segments` around Y and `sides` around the tube
+pub fn torus(radius: f32, tube_radius: f32, segments: u32, sides: u32) -> MeshResource {
+    let mut builder = Builder::default();
+    builder.grid(segments.max(3), sides.max(3), |uv| {
+        let direction = sphere_direction(uv.x * TAU, PI * 0.5);
+        let (sin, cos) = (uv.y * TAU).sin_cos();
+        // V goes down from the outer equator
+        let normal = direction * cos - Vec3::Y * sin;
+        (direction * radius + normal * tube_radius, normal, uv)
+    });
+    builder.build("torus")
+}
+
+/// The Cornell box of `size` on each side, centered at the origin with the open side facing -Z,
+/// as a mesh for each of its white, red and green materials and a mesh for each block
+pub fn cornell_box(size: f32) -> Vec<ImportedMesh> {
+    let half = size * 0.5;
+    let wall = Vec2::splat(size);
+
+    // the walls face inside
+    let mut white = Builder::default();
+    white.quad(Vec3::NEG_Y * half, Vec3::Y, Vec3::Z, wall, 1);
+    white.quad(Vec3::Y * half, Vec3::NEG_Y, Vec3::NEG_Z, wall, 1);
+    white.quad(Vec3::Z * half, Vec3::NEG_Z, Vec3::Y, wall, 1);
+    let mut red = Builder::default();
+    red.quad(Vec3::NEG_X * half, Vec3::X, Vec3::Y, wall, 1);
+    let mut green = Builder::default();
+    green.quad(Vec3::X * half, Vec3::NEG_X, Vec3::Y, wall, 1);
+
+    let white_material = diffuse_material("white", Vec3::splat(0.73));
+    let mesh = |resource: MeshResource, material: &ImportedMaterial, transform| ImportedMesh {
+        resource,
+        material: Some(material.clone()),
+        transform,
+        repaired: Vec::new(),
+    };
+
+    // the blocks stand on the floor, turned toward each other
+    let block = |name: &str, block_size: Vec3, x: f32, z: f32, angle: f32| {
+        let mut resource = cuboid(block_size * size);
+        resource.set_name(format!("cornell_box:{name}"));
+        let translation = Vec3::new(x, block_size.y - 1.0, z) * half;
+        let rotation = Quat::from_rotation_y(angle.to_radians());
+        mesh(
+            resource,
+            &white_material,
+            Mat4::from_rotation_translation(rotation, translation),
+        )
+    };
+
+    vec![
+        mesh(
+            white.build("cornell_box:white"),
+            &white_material,
+            Mat4::IDENTITY,
+        ),
+        mesh(
+            red.build("cornell_box:red"),
+            &diffuse_material("red", Vec3::new(0.65, 0.05, 0.05)),
+            Mat4::IDENTITY,
+        ),
+        mesh(
+            green.build("cornell_box:green"),
+            &diffuse_material("green", Vec3::new(0.12, 0.45, 0.15)),
+            Mat4::IDENTITY,
+        ),
+        block("short_block", Vec3::splat(0.3), 0.35, -0.3, -17.0),
+        block("tall_block", Vec3::new(0.3, 0.6, 0.3), -0.3, 0.3, 17.0),
+    ]
+}
+
+// The outward normal and the up direction of the texture of each face of a box
+const BOX_FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::Y),
+    (Vec3::NEG_X, Vec3::Y),
+    (Vec3::Y, Vec3::Z),
+    (Vec3::NEG_Y, Vec3::NEG_Z),
+    (Vec3::Z, Vec3::Y),
+    (Vec3::NEG_Z, Vec3::Y),
+];
+
+// The right direction of a face seen from outside
+fn face_right(normal: Vec3, up: Vec3) -> Vec3 {
+    up.cross(-normal)
+}
+
+// The direction at the longitude `phi` from +X toward +Z and the angle `theta` from +Y, exact at
+// the poles so that their vertices are at the same position
+fn sphere_direction(phi: f32, theta: f32) -> Vec3 {
+    let (sin_theta, cos_theta) = match theta {
+        0.0 => (0.0, 1.0),
+        PI => (0.0, -1.0),
+        _ => theta.sin_cos(),
+    };
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    Vec3::new(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi)
+}
+
+// The vertices of a grid at V = 0 or 1 on a pole or an apex are each used by a single triangle,
+// whose middle they are moved to
+fn pole_uv(uv: Vec2, segments: u32) -> Vec2 {
+    let offset = 0.5 / segments as f32;
+    match uv.y {
+        0.0 => uv - Vec2::X * offset,
+        1.0 => uv + Vec2::X * offset,
+        _ => uv,
+    }
+}
+
+// The inverse of `sphere_direction` as UVs
+fn sphere_uv(direction: Vec3) -> Vec2 {
+    let u = direction.z.atan2(direction.x) / TAU;
+    let u = if u < 0.0 { u + 1.0 } else { u };
+    let v = direction.xz().length().atan2(direction.y) / PI;
+    Vec2::new(u, v)
+}
+
+fn diffuse_material(name: &str, color: Vec3) -> ImportedMaterial {
+    ImportedMaterial {
+        name: name.to_string(),
+        material: Material {
+            base_color: color,
+            metallic: 0.0,
+            specular_reflectance: Vec3::ZERO,
+            roughness: 1.0,
+            specular_tint: Vec3::ZERO,
+            pad: 0,
+        },
+        textures: MaterialTextures::default(),
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    indices: Vec<u32>,
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+}
+
+impl Builder {
+    fn vertex(&mut self, position: Vec3, normal: Vec3, uv: Vec2) -> u32 {
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.uvs.push(uv);
+        self.positions.len() as u32 - 1
+    }
+
+    // A grid of `columns` by `rows` quads over the UV square, whose positions, normals and UVs
+    // are given by `vertex`, which must make `dp/du x dp/dv` point outside
+    // Triangles whose corners are at the same position, such as at the poles, are skipped, which
+    // leaves the vertices only used by them to `build`
+    fn grid(&mut self, columns: u32, rows: u32, vertex: impl Fn(Vec2) -> (Vec3, Vec3, Vec2)) {
+        let first = self.positions.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let uv = Vec2::new(column as f32 / columns as f32, row as f32 / rows as f32);
+                let (position, normal, uv) = vertex(uv);
+                self.vertex(position, normal, uv);
+            }
+        }
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = first + row * (columns + 1) + column;
+                let (b, c) = (a + 1, a + columns + 1);
+                let d = c + 1;
+                for triangle in [[a, b, c], [b, d, c]] {
+                    let [p0, p1, p2] = triangle.map(|i| self.positions[i as usize]);
+                    if p0 != p1 && p1 != p2 && p2 != p0 {
+                        self.indices.extend(triangle);
+                    }
+                }
+            }
+        }
+    }
+
+    // A rectangle of `size` along the right and up directions seen from the side of `normal`
+    fn quad(&mut self, center: Vec3, normal: Vec3, up: Vec3, size: Vec2, subdivisions: u32) {
+        let right = face_right(normal, up);
+        self.grid(subdivisions, subdivisions, |uv| {
+            let offset = right * (uv.x - 0.5) * size.x + up * (0.5 - uv.y) * size.y;
+            (center + offset, normal, uv)
+        });
+    }
+
+    // A fan of `segments` triangles around `center` on the plane of `normal`, which is +Y or -Y
+    fn disk(&mut self, center: Vec3, normal: Vec3, radius: f32, segments: u32) {
+        // V points to -Z on the top, and to +Z on the bottom, as on the faces of `cuboid`
+        let planar_uv = |offset: Vec3| {
+            Vec2::new(0.5, 0.5) + Vec2::new(offset.x, -offset.z * normal.y) * 0.5 / radius
+        };
+
+        let center_index = self.vertex(center, normal, Vec2::splat(0.5));
+        let first = self.positions.len() as u32;
+        for segment in 0..segments {
+            let offset =
+                sphere_direction(segment as f32 / segments as f32 * TAU, PI * 0.5) * radius;
+            self.vertex(center + offset, normal, planar_uv(offset));
+        }
+
+        for segment in 0..segments {
+            let a = first + segment;
+            let b = first + (segment + 1) % segments;
+            // the segments go from +X toward +Z, which is clockwise seen from +Y
+            match normal.y > 0.0 {
+                true => self.indices.extend([center_index, b, a]),
+                false => self.indices.extend([center_index, a, b]),
+            }
+        }
+    }
+
+    fn build(self, name: &str) -> MeshResource {
+        // drops the vertices without triangles
+        let mut remap = vec![u32::MAX; self.positions.len()];
+        let mut vertices = Vec::new();
+        let indices = self
+            .indices
+            .iter()
+            .map(|&index| {
+                let new = &mut remap[index as usize];
+                if *new == u32::MAX {
+                    *new = vertices.len() as u32;
+                    vertices.push(index as usize);
+                }
+                *new
+            })
+            .collect();
+
+        let mut mesh = MeshResource {
+            indices,
+            positions: vertices.iter().map(|&v| self.positions[v]).collect(),
+            normals: vertices.iter().map(|&v| self.normals[v]).collect(),
+            uv_sets: vec![vertices.iter().map(|&v| self.uvs[v]).collect()],
+            tangents: Vec::new(),
+            name: name.to_string(),
+        };
+        mesh.compute_tangents();
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangles(mesh: &MeshResource) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        let positions = mesh.positions();
+        mesh.indices()
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]].map(|i| positions[i as usize]))
+    }
+
+    // Positive if the triangles face outside
+    fn signed_volume(mesh: &MeshResource) -> f32 {
+        triangles(mesh)
+            .map(|[p0, p1, p2]| p0.dot(p1.cross(p2)) / 6.0)
+            .sum()
+    }
+
+    // The edges not shared with a triangle going the other way, after welding the vertices that
+    // are split on the UV seams, which are at the same position up to rounding
+    fn open_edges(mesh: &MeshResource) -> usize {
+        let key = |p: Vec3| (p * 1e4).round().to_array().map(|c| c as i32);
+        let mut edges: HashMap<_, i32> = HashMap::new();
+        for triangle in triangles(mesh) {
+            let [a, b, c] = triangle.map(key);
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                *edges.entry((from, to)).or_default() += 1;
+                *edges.entry((to, from)).or_default() -= 1;
+            }
+        }
+        edges.values().filter(|&&count| count != 0).count() / 2
+    }
+
+    // The shading normals are on the side the triangles face
+    fn assert_normals_agree(mesh: &MeshResource) {
+        for t in mesh.indices().chunks_exact(3) {
+            let [p0, p1, p2] = [t[0], t[1], t[2]].map(|i| mesh.positions()[i as usize]);
+            let face = (p1 - p0).cross(p2 - p0);
+            for &i in t {
+                let normal = mesh.normals()[i as usize];
+                assert!(normal.dot(face) > 0.0, "{}: vertex {i}", mesh.name());
+            }
+        }
+    }
+
+    fn assert_closed(mesh: &MeshResource, volume: f32, tolerance: f32) {
+        assert_eq!(open_edges(mesh), 0, "{}", mesh.name());
+        let actual = signed_volume(mesh);
+        assert!(
+            (actual - volume).abs() <= volume * tolerance,
+            "{}: {actual} != {volume}",
+            mesh.name()
+        );
+        assert_normals_agree(mesh);
+        mesh.validate().unwrap();
+        assert!(mesh.has_tangents());
+    }
+
+    #[test]
+    fn closed_primitives() {
+        assert_closed(&cuboid(Vec3::new(1.0, 2.0, 3.0)), 6.0, 1e-5);
+        assert_closed(&uv_sphere(1.0, 64, 32), 4.0 / 3.0 * PI, 0.01);
+        assert_closed(&icosphere(1.0, 3), 4.0 / 3.0 * PI, 0.01);
+        assert_closed(&cylinder(0.5, 2.0, 64), PI * 0.25 * 2.0, 0.01);
+        assert_closed(&cone(0.5, 2.0, 64), PI * 0.25 * 2.0 / 3.0, 0.01);
+        assert_closed(&torus(1.0, 0.25, 64, 32), 2.0 * PI * PI * 0.0625, 0.01);
+    }
+
+    #[test]
+    fn coarse_primitives_are_closed() {
+        // the fewest segments, where the poles and the seams are closest to each other
+        for mesh in [
+            uv_sphere(1.0, 3, 2),
+            icosphere(1.0, 0),
+            cylinder(1.0, 1.0, 3),
+            cone(1.0, 1.0, 3),
+            torus(1.0, 0.5, 3, 3),
+        ] {
+            assert_eq!(open_edges(&mesh), 0, "{}", mesh.name());
+            assert!(signed_volume(&mesh) > 0.0, "{}", mesh.name());
+            assert_normals_agree(&mesh);
+        }
+    }
+
+    #[test]
+    fn plane() {
+        let mesh = super::plane(2.0, 4);
+        assert_eq!(mesh.indices().len(), 4 * 4 * 6);
+        assert_eq!(mesh.positions().len(), 5 * 5);
+        assert_normals_agree(&mesh);
+        assert!(triangles(&mesh).all(|[p0, p1, p2]| (p1 - p0).cross(p2 - p0).y > 0.0));
+        // only the border is open
+        assert_eq!(open_edges(&mesh), 4 * 4);
+    }
+
+    #[test]
+    fn cornell_walls_face_inward() {
+        let meshes = cornell_box(2.0);
+        let names: Vec<&str> = meshes.iter().map(|m| m.resource.name()).collect();
+        assert_eq!(
+            names,
+            [
+                "cornell_box:white",
+                "cornell_box:red",
+                "cornell_box:green",
+                "cornell_box:short_block",
+                "cornell_box:tall_block"
+            ]
+        );
+
+        for wall in &meshes[..3] {
+            assert_eq!(wall.transform, Mat4::IDENTITY);
+            assert_normals_agree(&wall.resource);
+            for [p0, p1, p2] in triangles(&wall.resource) {
+                let center = (p0 + p1 + p2) / 3.0;
+                assert!((p1 - p0).cross(p2 - p0).dot(-center) > 0.0);
+            }
+        }
+        // seen from the center, the walls enclose a negative volume as an inside out mesh does
+        let walls: f32 = meshes[..3].iter().map(|m| signed_volume(&m.resource)).sum();
+        assert!(walls < 0.0);
+
+        // the blocks are closed and stand on the floor
+        for block in &meshes[3..] {
+            let resource = &block.resource;
+            assert_eq!(open_edges(resource), 0);
+            assert!(signed_volume(resource) > 0.0);
+            let bottom = resource
+                .positions()
+                .iter()
+                .map(|&p| block.transform.transform_point3(p).y)
+                .fold(f32::MAX, f32::min);
+            assert!((bottom + 1.0).abs() < 1e-5, "{bottom}");
+        }
+    }
+}
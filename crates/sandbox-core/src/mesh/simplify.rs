@@ -0,0 +1,589 @@
+// Simplification by edge collapses ordered by the quadric error metric [Garland and Heckbert 1997]
+// The vertices are kept, so that every level of detail indexes the vertex buffers of the mesh
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use glam::DVec3;
+
+use super::MeshResource;
+
+/// The LODs of `MeshResource::lod_chain` stop before they have fewer triangles than this
+pub const MIN_LOD_TRIANGLES: usize = 64;
+
+// A LOD is dropped if it does not remove this fraction of the triangles of the previous one
+const MIN_LOD_REDUCTION: f32 = 0.1;
+
+// Collapses that turn a triangle by more than about 80 degrees are rejected, as they fold the
+// surface over itself
+const MIN_NORMAL_COS: f64 = 0.2;
+
+/// A level of detail, which indexes the vertices of the mesh it is simplified from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lod {
+    pub indices: Vec<u32>,
+    /// The largest quadric error of the collapses, in the units of the positions: the root mean
+    /// square distance from a moved vertex to the planes of the triangles merged into it, weighted
+    /// by their area. It estimates how far the surface moved, but is not a bound on the distance
+    pub error: f32,
+}
+
+impl Lod {
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+impl MeshResource {
+    /// Collapses edges until at most `target_triangle_count` triangles are left, or until the
+    /// error of the next collapse would exceed `max_error` times the size of the mesh, see
+    /// `Lod::error`
+    /// Vertices on borders and on attribute seams are kept, so that holes do not grow and the
+    /// UVs and normals do not tear
+    pub fn simplify(&self, target_triangle_count: usize, max_error: f32) -> Lod {
+        let mut simplifier = Simplifier::new(self);
+        simplifier.run(target_triangle_count, max_error);
+        simplifier.lod()
+    }
+
+    /// The LODs that halve the triangles of the previous one within `max_error` times the size of
+    /// the mesh, coarsest last. The mesh itself is not included
+    /// Each LOD continues the collapses of the previous one, so the errors never decrease
+    pub fn lod_chain(&self, max_error: f32) -> Vec<Lod> {
+        let mut simplifier = Simplifier::new(self);
+        let mut lods: Vec<Lod> = Vec::new();
+        let mut triangle_count = self.indices.len() / 3;
+
+        while triangle_count / 2 >= MIN_LOD_TRIANGLES {
+            simplifier.run(triangle_count / 2, max_error);
+
+            let reduced = triangle_count - simplifier.triangle_count;
+            if (reduced as f32) < triangle_count as f32 * MIN_LOD_REDUCTION {
+                break;
+            }
+            triangle_count = simplifier.triangle_count;
+
+            let mut lod = simplifier.lod();
+            lod.indices = super::optimize::cache_order(&lod.indices, self.positions.len());
+            lods.push(lod);
+        }
+
+        lods
+    }
+}
+
+// A symmetric 4x4 matrix as its upper triangle, and the total weight of the planes summed in it
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric {
+    m: [f64; 10],
+    weight: f64,
+}
+
+impl Quadric {
+    // The squared distance to the plane of a triangle, weighted by its area
+    fn from_triangle(p0: DVec3, p1: DVec3, p2: DVec3) -> Self {
+        let normal = (p1 - p0).cross(p2 - p0);
+        let area = normal.length() * 0.5;
+        let n = normal.normalize_or_zero();
+        let d = -n.dot(p0);
+
+        let m = [
+            n.x * n.x,
+            n.x * n.y,
+            n.x * n.z,
+            n.x * d,
+            n.y * n.y,
+            n.y * n.z,
+            n.y * d,
+            n.z * n.z,
+            n.z * d,
+            d * d,
+        ];
+
+        Quadric {
+            m: m.map(|v| v * area),
+            weight: area,
+        }
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.m.iter_mut().zip(other.m) {
+            *a += b;
+        }
+        self.weight += other.weight;
+    }
+
+    // The mean squared distance from `p` to the planes, weighted by their area
+    fn error(&self, p: DVec3) -> f64 {
+        let m = &self.m;
+        let squared = m[0] * p.x * p.x
+            + 2.0 * m[1] * p.x * p.y
+            + 2.0 * m[2] * p.x * p.z
+            + 2.0 * m[3] * p.x
+            + m[4] * p.y * p.y
+            + 2.0 * m[5] * p.y * p.z
+            + 2.0 * m[6] * p.y
+            + m[7] * p.z * p.z
+            + 2.0 * m[8] * p.z
+            + m[9];
+
+        match self.weight > 0.0 {
+            true => squared.max(0.0) / self.weight,
+            false => 0.0,
+        }
+    }
+}
+
+// Moving vertex `from` onto vertex `to`, the versions invalidate the entries of moved vertices
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    versions: [u32; 2],
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Reversed so that `BinaryHeap` pops the cheapest collapse first
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Simplifier {
+    positions: Vec<DVec3>,
+    triangles: Vec<[u32; 3]>,
+    removed: Vec<bool>,
+    triangle_count: usize,
+
+    // the triangles around each vertex, which may include removed ones
+    vertex_triangles: Vec<Vec<u32>>,
+    quadrics: Vec<Quadric>,
+    locked: Vec<bool>,
+    collapsed: Vec<bool>,
+    versions: Vec<u32>,
+
+    heap: BinaryHeap<Collapse>,
+    // the size the errors are relative to
+    extent: f64,
+    error: f64,
+}
+
+impl Simplifier {
+    fn new(mesh: &MeshResource) -> Self {
+        let positions: Vec<DVec3> = mesh.positions.iter().map(|p| p.as_dvec3()).collect();
+        let vertex_count = positions.len();
+
+        let triangles: Vec<[u32; 3]> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+
+        let mut vertex_triangles = vec![Vec::new(); vertex_count];
+        let mut quadrics = vec![Quadric::default(); vertex_count];
+        for (triangle, indices) in triangles.iter().enumerate() {
+            let [p0, p1, p2] = indices.map(|i| positions[i as usize]);
+            let quadric = Quadric::from_triangle(p0, p1, p2);
+            for &index in indices {
+                vertex_triangles[index as usize].push(triangle as u32);
+                quadrics[index as usize].add(&quadric);
+            }
+        }
+
+        // an edge used by one triangle is on a border, and the vertices split for different
+        // attributes at the same position make a border as well
+        let mut edges = std::collections::HashMap::new();
+        for indices in &triangles {
+            for corner in 0..3 {
+                let (a, b) = (indices[corner], indices[(corner + 1) % 3]);
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+        let mut locked = vec![false; vertex_count];
+        for (&(a, b), &count) in &edges {
+            if count != 2 {
+                locked[a as usize] = true;
+                locked[b as usize] = true;
+            }
+        }
+
+        let (min, max) = positions.iter().fold(
+            (DVec3::splat(f64::MAX), DVec3::splat(f64::MIN)),
+            |(min, max), &p| (min.min(p), max.max(p)),
+        );
+        let extent = match triangles.is_empty() {
+            true => 0.0,
+            false => (max - min).length(),
+        };
+
+        let mut simplifier = Simplifier {
+            positions,
+            removed: vec![false; triangles.len()],
+            triangle_count: triangles.len(),
+            triangles,
+            vertex_triangles,
+            quadrics,
+            locked,
+            collapsed: vec![false; vertex_count],
+            versions: vec![0; vertex_count],
+            heap: BinaryHeap::new(),
+            extent,
+            error: 0.0,
+        };
+
+        for vertex in 0..vertex_count as u32 {
+            for neighbor in simplifier.neighbors(vertex) {
+                simplifier.push(vertex, neighbor);
+            }
+        }
+
+        simplifier
+    }
+
+    fn run(&mut self, target_triangle_count: usize, max_error: f32) {
+        let max_cost = (max_error as f64 * self.extent).powi(2);
+
+        while self.triangle_count > target_triangle_count {
+            let Some(collapse) = self.heap.pop() else {
+                break;
+            };
+            if collapse.cost > max_cost {
+                // kept for the next run, which may allow a larger error
+                self.heap.push(collapse);
+                break;
+            }
+
+            let (from, to) = (collapse.from, collapse.to);
+            let current = collapse.versions == [from, to].map(|v| self.versions[v as usize]);
+            if !current || self.collapsed[from as usize] || self.collapsed[to as usize] {
+                continue;
+            }
+            if self.is_valid(from, to) {
+                self.collapse(from, to);
+                self.error = self.error.max(collapse.cost.sqrt());
+            }
+        }
+    }
+
+    fn lod(&self) -> Lod {
+        let indices = self
+            .triangles
+            .iter()
+            .zip(&self.removed)
+            .filter(|(_, &removed)| !removed)
+            .flat_map(|(indices, _)| *indices)
+            .collect();
+
+        Lod {
+            indices,
+            error: self.error as f32,
+        }
+    }
+
+    fn push(&mut self, from: u32, to: u32) {
+        if self.locked[from as usize] {
+            return;
+        }
+
+        let mut quadric = self.quadrics[from as usize];
+        quadric.add(&self.quadrics[to as usize]);
+
+        self.heap.push(Collapse {
+            cost: quadric.error(self.positions[to as usize]),
+            from,
+            to,
+            versions: [from, to].map(|v| self.versions[v as usize]),
+        });
+    }
+
+    fn live_triangles(&self, vertex: u32) -> impl Iterator<Item = u32> + '_ {
+        self.vertex_triangles[vertex as usize]
+            .iter()
+            .copied()
+            .filter(|&t| !self.removed[t as usize])
+    }
+
+    fn neighbors(&self, vertex: u32) -> Vec<u32> {
+        let mut neighbors: Vec<u32> = self
+            .live_triangles(vertex)
+            .flat_map(|t| self.triangles[t as usize])
+            .filter(|&v| v != vertex)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+    // The collapse must keep the surface a manifold and must not flip any triangle
+    fn is_valid(&self, from: u32, to: u32) -> bool {
+        let shared = self
+            .live_triangles(from)
+            .filter(|&t| self.triangles[t as usize].contains(&to))
+            .count();
+        if shared == 0 {
+            return false;
+        }
+
+        // the link condition: the vertices around both ends are only those of the triangles on
+        // the edge, otherwise the collapse pinches the surface
+        let to_neighbors = self.neighbors(to);
+        let common = self
+            .neighbors(from)
+            .iter()
+            .filter(|v| to_neighbors.binary_search(v).is_ok())
+            .count();
+        if common != shared {
+            return false;
+        }
+
+        let target = self.positions[to as usize];
+        self.live_triangles(from)
+            .map(|t| self.triangles[t as usize])
+            .filter(|indices| !indices.contains(&to))
+            .all(|indices| {
+                let [p0, p1, p2] = indices.map(|i| self.positions[i as usize]);
+                let before = (p1 - p0).cross(p2 - p0);
+                let [q0, q1, q2] = indices.map(|i| match i == from {
+                    true => target,
+                    false => self.positions[i as usize],
+                });
+                let after = (q1 - q0).cross(q2 - q0);
+
+                let length = before.length() * after.length();
+                length > 0.0 && before.dot(after) >= MIN_NORMAL_COS * length
+            })
+    }
+
+    fn collapse(&mut self, from: u32, to: u32) {
+        let around = std::mem::take(&mut self.vertex_triangles[from as usize]);
+        for t in around {
+            if self.removed[t as usize] {
+                continue;
+            }
+
+            let indices = &mut self.triangles[t as usize];
+            if indices.contains(&to) {
+                self.removed[t as usize] = true;
+                self.triangle_count -= 1;
+            } else {
+                for index in indices.iter_mut().filter(|i| **i == from) {
+                    *index = to;
+                }
+                self.vertex_triangles[to as usize].push(t);
+            }
+        }
+        self.vertex_triangles[to as usize].retain(|&t| !self.removed[t as usize]);
+
+        let quadric = self.quadrics[from as usize];
+        self.quadrics[to as usize].add(&quadric);
+        self.collapsed[from as usize] = true;
+
+        // the costs of the edges at `to` changed with its quadric
+        self.versions[to as usize] += 1;
+        for neighbor in self.neighbors(to) {
+            self.push(neighbor, to);
+            self.push(to, neighbor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use glam::Vec3;
+
+    use super::super::primitives;
+    use super::*;
+
+    // An icosphere whose vertices are welded across the UV seams, so that it is closed
+    fn closed_sphere(subdivisions: u32) -> MeshResource {
+        let sphere = primitives::icosphere(1.0, subdivisions);
+
+        let mut vertices = HashMap::new();
+        let mut positions = Vec::new();
+        let indices: Vec<u32> = sphere
+            .indices()
+            .iter()
+            .map(|&i| {
+                let p = sphere.positions()[i as usize];
+                *vertices
+                    .entry(p.to_array().map(f32::to_bits))
+                    .or_insert_with(|| {
+                        positions.push(p);
+                        positions.len() as u32 - 1
+                    })
+            })
+            .collect();
+
+        let normals: Vec<Vec3> = positions.iter().map(|p| p.normalize()).collect();
+        MeshResource::new(&indices, &positions, &normals, "sphere".to_string())
+    }
+
+    // The number of triangles using each directed edge
+    fn directed_edges(indices: &[u32]) -> HashMap<(u32, u32), usize> {
+        let mut edges = HashMap::new();
+        for t in indices.chunks_exact(3) {
+            for c in 0..3 {
+                *edges.entry((t[c], t[(c + 1) % 3])).or_insert(0) += 1;
+            }
+        }
+        edges
+    }
+
+    // The undirected edges used by a single triangle
+    fn border_edges(indices: &[u32]) -> HashSet<(u32, u32)> {
+        let mut counts = HashMap::new();
+        for (&(a, b), &count) in &directed_edges(indices) {
+            *counts.entry((a.min(b), a.max(b))).or_insert(0) += count;
+        }
+        counts
+            .into_iter()
+            .filter(|&(_, count)| count == 1)
+            .map(|(edge, _)| edge)
+            .collect()
+    }
+
+    fn euler_characteristic(indices: &[u32]) -> i64 {
+        let vertices: HashSet<_> = indices.iter().collect();
+        let edges: HashSet<_> = directed_edges(indices)
+            .into_keys()
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect();
+        vertices.len() as i64 - edges.len() as i64 + (indices.len() / 3) as i64
+    }
+
+    fn assert_valid_triangles(mesh: &MeshResource, lod: &Lod) {
+        for t in lod.indices.chunks_exact(3) {
+            assert!(t[0] != t[1] && t[1] != t[2] && t[2] != t[0], "{t:?}");
+            let [p0, p1, p2] = [0, 1, 2].map(|c| mesh.positions()[t[c] as usize]);
+            assert!((p1 - p0).cross(p2 - p0).length() > 0.0, "{t:?}");
+        }
+    }
+
+    #[test]
+    fn closed_mesh_stays_closed() {
+        let mesh = closed_sphere(3);
+        assert_eq!(euler_characteristic(mesh.indices()), 2);
+
+        let lod = mesh.simplify(mesh.indices().len() / 3 / 4, 1.0);
+        assert!(lod.triangle_count() <= mesh.indices().len() / 3 / 4);
+        assert_valid_triangles(&mesh, &lod);
+
+        // every edge is used once in each direction, so the surface has no hole, no fold and the
+        // same orientation
+        let edges = directed_edges(&lod.indices);
+        for (&(a, b), &count) in &edges {
+            assert_eq!(count, 1, "{a} {b}");
+            assert_eq!(edges.get(&(b, a)), Some(&1), "{a} {b}");
+        }
+        assert_eq!(euler_characteristic(&lod.indices), 2);
+
+        // the triangles still face outward
+        for t in lod.indices.chunks_exact(3) {
+            let [p0, p1, p2] = [0, 1, 2].map(|c| mesh.positions()[t[c] as usize]);
+            assert!((p1 - p0).cross(p2 - p0).dot(p0 + p1 + p2) > 0.0);
+        }
+    }
+
+    #[test]
+    fn borders_and_seams_are_kept() {
+        let mesh = primitives::uv_sphere(1.0, 32, 16);
+        let borders = border_edges(mesh.indices());
+        assert!(!borders.is_empty());
+
+        let lod = mesh.simplify(0, 1.0);
+        assert!(lod.triangle_count() < mesh.indices().len() / 3 / 2);
+        assert_valid_triangles(&mesh, &lod);
+
+        assert_eq!(border_edges(&lod.indices), borders);
+        assert_eq!(
+            euler_characteristic(&lod.indices),
+            euler_characteristic(mesh.indices())
+        );
+        assert!(directed_edges(&lod.indices)
+            .values()
+            .all(|&count| count == 1));
+    }
+
+    #[test]
+    fn flat_surfaces_collapse_without_error() {
+        let mesh = primitives::plane(2.0, 8);
+        let lod = mesh.simplify(0, 0.0);
+
+        // the inner vertices are collapsed onto the border, which is kept
+        assert_eq!(lod.error, 0.0);
+        assert!(lod.triangle_count() < 128 / 2, "{}", lod.triangle_count());
+        assert_eq!(border_edges(&lod.indices), border_edges(mesh.indices()));
+
+        let area: f32 = lod
+            .indices
+            .chunks_exact(3)
+            .map(|t| {
+                let [p0, p1, p2] = [0, 1, 2].map(|c| mesh.positions()[t[c] as usize]);
+                (p1 - p0).cross(p2 - p0).length() * 0.5
+            })
+            .sum();
+        assert!((area - 4.0).abs() < 1e-4, "{area}");
+    }
+
+    #[test]
+    fn max_error_stops_the_collapses() {
+        let mesh = closed_sphere(3);
+        // the diagonal of the bounding box
+        let extent = 2.0 * 3f32.sqrt();
+
+        let exact = mesh.simplify(0, 0.0);
+        assert_eq!(exact.triangle_count(), mesh.indices().len() / 3);
+        assert_eq!(exact.error, 0.0);
+
+        let coarse = mesh.simplify(0, 0.01);
+        assert!(coarse.triangle_count() < exact.triangle_count());
+        assert!(coarse.error > 0.0);
+        assert!(coarse.error <= 0.01 * extent * 1.0001, "{}", coarse.error);
+
+        let coarser = mesh.simplify(0, 0.05);
+        assert!(coarser.triangle_count() < coarse.triangle_count());
+        assert!(coarser.error >= coarse.error);
+    }
+
+    #[test]
+    fn errors_increase_along_the_chain() {
+        let mesh = closed_sphere(4);
+        let extent = 2.0 * 3f32.sqrt();
+        let triangle_count = mesh.indices().len() / 3;
+        let lods = mesh.lod_chain(0.05);
+        assert!(lods.len() >= 3, "{}", lods.len());
+
+        let mut previous = (triangle_count, 0.0);
+        for lod in &lods {
+            assert!(lod.triangle_count() <= previous.0 / 2);
+            assert!(lod.triangle_count() >= MIN_LOD_TRIANGLES);
+            assert!(lod.error >= previous.1, "{} < {}", lod.error, previous.1);
+            assert!(lod.error <= 0.05 * extent * 1.0001);
+            assert_valid_triangles(&mesh, lod);
+            assert_eq!(euler_characteristic(&lod.indices), 2);
+            previous = (lod.triangle_count(), lod.error);
+        }
+    }
+
+    #[test]
+    fn small_meshes_have_no_lods() {
+        let mesh = primitives::cuboid(Vec3::ONE);
+        assert!(mesh.lod_chain(1.0).is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+// Tangent generation with MikkTSpace [Mikkelsen 2008], so that normal maps baked by other tools
+// are reproduced
+
+use bevy_mikktspace::Geometry;
+use glam::Vec4;
+
+use super::MeshResource;
+
+// The triangles of a mesh as seen by MikkTSpace, which returns a tangent for each corner
+struct Corners<'a> {
+    mesh: &'a MeshResource,
+    tangents: Vec<Vec4>,
+}
+
+impl Corners<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        self.mesh.indices[face * 3 + vert] as usize
+    }
+}
+
+impl Geometry for Corners<'_> {
+    fn num_faces(&self) -> usize {
+        self.mesh.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.mesh.positions[self.vertex(face, vert)].to_array()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.mesh.normals[self.vertex(face, vert)].to_array()
+    }
+
+    // V points up in the tools baking normal maps, which is also how the tangents of glTF files
+    // are generated
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let uv = self.mesh.uv_sets[0][self.vertex(face, vert)];
+        [uv.x, 1.0 - uv.y]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = Vec4::from_array(tangent);
+    }
+}
+
+impl MeshResource {
+    /// Whether there is a tangent for each position
+    pub fn has_tangents(&self) -> bool {
+        self.tangents.len() == self.positions.len()
+    }
+
+    /// Replaces the tangents with those of MikkTSpace for the first UV set
+    /// A vertex whose corners get different tangents, such as on the seam between triangles whose
+    /// UVs are mirrored to each other, is split into a vertex for each tangent. Returns false
+    /// without changing anything if the normals or the UVs are missing
+    pub fn compute_tangents(&mut self) -> bool {
+        let uv_count = self.uv_sets.first().map_or(0, Vec::len);
+        if uv_count != self.positions.len() || !self.has_normals() {
+            return false;
+        }
+
+        let triangle_count = self.indices.len() / 3;
+        let mut corners = Corners {
+            mesh: self,
+            tangents: vec![Vec4::ZERO; triangle_count * 3],
+        };
+        if !bevy_mikktspace::generate_tangents(&mut corners) {
+            return false;
+        }
+        let corner_tangents = corners.tangents;
+
+        // vertices not used by any triangle keep an arbitrary tangent
+        self.tangents = self
+            .normals
+            .iter()
+            .map(|normal| normal.any_orthonormal_vector().extend(1.0))
+            .collect();
+
+        // the tangents given to each vertex so far, with the vertex holding each of them
+        let mut splits: Vec<Vec<(Vec4, u32)>> = vec![Vec::new(); self.positions.len()];
+
+        for (corner, &tangent) in corner_tangents.iter().enumerate() {
+            let vertex = self.indices[corner] as usize;
+
+            let split = splits[vertex].iter().find(|(t, _)| *t == tangent);
+            let index = match split {
+                Some(&(_, index)) => index,
+                None => {
+                    // the first tangent keeps the vertex
+                    let index = match splits[vertex].is_empty() {
+                        true => vertex as u32,
+                        false => self.duplicate_vertex(vertex),
+                    };
+                    self.tangents[index as usize] = tangent;
+                    splits[vertex].push((tangent, index));
+                    index
+                }
+            };
+            self.indices[corner] = index;
+        }
+
+        true
+    }
+
+    // Called by the loaders for files with UVs but without tangents
+    pub(super) fn generate_missing_tangents(&mut self) {
+        if !self.has_tangents() {
+            self.compute_tangents();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec2, Vec3};
+
+    use super::*;
+
+    // A quad in the XY plane facing +Z, with the origin of its UVs at the top left
+    fn quad() -> MeshResource {
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y];
+        let mut mesh = MeshResource::new(
+            &[0, 1, 2, 0, 2, 3],
+            &positions,
+            &[Vec3::Z; 4],
+            "quad".to_string(),
+        );
+        mesh.add_uv_set(vec![Vec2::Y, Vec2::ONE, Vec2::X, Vec2::ZERO]);
+        mesh
+    }
+
+    fn bitangent(mesh: &MeshResource, vertex: usize) -> Vec3 {
+        let tangent = mesh.tangents()[vertex];
+        tangent.w * mesh.normals()[vertex].cross(tangent.truncate())
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(actual.abs_diff_eq(expected, 1e-5), "{actual} != {expected}");
+    }
+
+    #[test]
+    fn quad_tangents() {
+        let mut mesh = quad();
+        assert!(mesh.compute_tangents());
+
+        // U goes right and V goes down, so the bitangent goes up as in the glTF sample models
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.indices(), [0, 1, 2, 0, 2, 3]);
+        for vertex in 0..4 {
+            assert_close(mesh.tangents()[vertex].truncate(), Vec3::X);
+            assert_eq!(mesh.tangents()[vertex].w, 1.0);
+            assert_close(bitangent(&mesh, vertex), Vec3::Y);
+        }
+    }
+
+    #[test]
+    fn flipped_v() {
+        let mut mesh = quad();
+        mesh.uv_sets[0] = vec![Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y];
+        assert!(mesh.compute_tangents());
+
+        for vertex in 0..4 {
+            assert_close(mesh.tangents()[vertex].truncate(), Vec3::X);
+            assert_eq!(mesh.tangents()[vertex].w, -1.0);
+            assert_close(bitangent(&mesh, vertex), -Vec3::Y);
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs() {
+        // two quads side by side, whose UVs are mirrored on the shared edge from 1 to 4
+        let positions = [
+            Vec3::ZERO,
+            Vec3::X,
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::Y,
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(2.0, 1.0, 0.0),
+        ];
+        let mut mesh = MeshResource::new(
+            &[0, 1, 4, 0, 4, 3, 1, 2, 5, 1, 5, 4],
+            &positions,
+            &[Vec3::Z; 6],
+            "mirrored".to_string(),
+        );
+        mesh.add_uv_set(vec![
+            Vec2::Y,
+            Vec2::ONE,
+            Vec2::Y,
+            Vec2::ZERO,
+            Vec2::X,
+            Vec2::ZERO,
+        ]);
+        assert!(mesh.compute_tangents());
+
+        // the vertices on the shared edge are split, the copies of 1 and 4 being 6 and 7
+        assert_eq!(mesh.positions().len(), 8);
+        assert_eq!(mesh.indices(), [0, 1, 4, 0, 4, 3, 6, 2, 5, 6, 5, 7]);
+        assert_eq!(mesh.positions()[6], mesh.positions()[1]);
+        assert_eq!(mesh.positions()[7], mesh.positions()[4]);
+        assert_eq!(mesh.uv_sets()[0][6], mesh.uv_sets()[0][1]);
+
+        for vertex in [0, 1, 3, 4] {
+            assert_close(mesh.tangents()[vertex].truncate(), Vec3::X);
+            assert_eq!(mesh.tangents()[vertex].w, 1.0);
+        }
+        // U goes left on the mirrored side, whose bitangent still goes up
+        for vertex in [2, 5, 6, 7] {
+            assert_close(mesh.tangents()[vertex].truncate(), -Vec3::X);
+            assert_eq!(mesh.tangents()[vertex].w, -1.0);
+            assert_close(bitangent(&mesh, vertex), Vec3::Y);
+        }
+    }
+
+    #[test]
+    fn missing_attributes() {
+        let mut mesh = quad();
+        mesh.uv_sets.clear();
+        assert!(!mesh.compute_tangents());
+        assert!(mesh.tangents().is_empty());
+
+        let mut mesh = quad();
+        mesh.normals.clear();
+        assert!(!mesh.compute_tangents());
+        assert!(mesh.tangents().is_empty());
+    }
+
+    #[test]
+    fn unused_vertices() {
+        let mut mesh = quad();
+        mesh.indices.truncate(3);
+        assert!(mesh.compute_tangents());
+
+        // the tangent of the unused vertex is orthogonal to its normal
+        assert_eq!(mesh.positions().len(), 4);
+        let tangent = mesh.tangents()[3];
+        assert!(tangent.truncate().dot(Vec3::Z).abs() < 1e-6);
+        assert!(tangent.truncate().is_normalized());
+    }
+
+    #[test]
+    fn existing_tangents_are_kept() {
+        let mut mesh = quad();
+        mesh.set_tangents(vec![Vec4::new(0.0, 1.0, 0.0, -1.0); 4]);
+        mesh.generate_missing_tangents();
+        assert_eq!(mesh.tangents(), [Vec4::new(0.0, 1.0, 0.0, -1.0); 4]);
+    }
+}
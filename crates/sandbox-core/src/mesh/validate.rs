@@ -0,0 +1,500 @@
+// Checks of the mesh data that would otherwise hang the GPU or panic when the mesh is uploaded
+
+use std::fmt;
+
+use glam::Vec3;
+
+use super::MeshResource;
+
+/// What the loaders do with meshes that fail `MeshResource::validate`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValidationPolicy {
+    /// Fails to load the mesh
+    Reject,
+    /// Drops the broken triangles and regenerates the broken attributes, failing only if no
+    /// triangle is left
+    #[default]
+    Repair,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    Normal,
+    Uv(usize),
+    Tangent,
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Attribute::Normal => write!(f, "normals"),
+            Attribute::Uv(set) => write!(f, "UV set {set}"),
+            Attribute::Tangent => write!(f, "tangents"),
+        }
+    }
+}
+
+/// A problem found by `MeshResource::validate`, with the triangle or the vertex it is in
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeshProblem {
+    NoTriangles,
+    /// The indices after the last complete triangle are not used
+    IndexCount(usize),
+    IndexOutOfRange {
+        triangle: usize,
+        index: u32,
+    },
+    DegenerateTriangle {
+        triangle: usize,
+    },
+    NonFinitePosition {
+        vertex: usize,
+    },
+    /// Not finite, or too short to be normalized
+    InvalidNormal {
+        vertex: usize,
+    },
+    /// The attribute does not have a value for each position
+    AttributeCount {
+        attribute: Attribute,
+        count: usize,
+        expected: usize,
+    },
+}
+
+impl fmt::Display for MeshProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshProblem::NoTriangles => write!(f, "there are no triangles"),
+            MeshProblem::IndexCount(count) => {
+                write!(f, "the index count {count} is not a multiple of 3")
+            }
+            MeshProblem::IndexOutOfRange { triangle, index } => {
+                write!(f, "triangle {triangle}: the index {index} is out of range")
+            }
+            MeshProblem::DegenerateTriangle { triangle } => {
+                write!(f, "triangle {triangle}: the area is zero")
+            }
+            MeshProblem::NonFinitePosition { vertex } => {
+                write!(f, "vertex {vertex}: the position is not finite")
+            }
+            MeshProblem::InvalidNormal { vertex } => {
+                write!(f, "vertex {vertex}: the normal is not a direction")
+            }
+            MeshProblem::AttributeCount {
+                attribute,
+                count,
+                expected,
+            } => write!(f, "there are {count} {attribute} for {expected} positions"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub mesh: String,
+    pub problems: Vec<MeshProblem>,
+}
+
+// Broken files can have a problem for every triangle
+const MAX_DISPLAYED_PROBLEMS: usize = 8;
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.problems.len() {
+            1 => write!(f, "{} has a problem:", self.mesh)?,
+            count => write!(f, "{} has {count} problems:", self.mesh)?,
+        }
+        for problem in self.problems.iter().take(MAX_DISPLAYED_PROBLEMS) {
+            write!(f, "\n  {problem}")?;
+        }
+        if self.problems.len() > MAX_DISPLAYED_PROBLEMS {
+            let rest = self.problems.len() - MAX_DISPLAYED_PROBLEMS;
+            write!(f, "\n  and {rest} more")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl MeshResource {
+    /// Lists every problem of the mesh, an empty attribute other than the positions is not one
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let problems = self.problems();
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(ValidationError {
+                mesh: self.name.clone(),
+                problems,
+            }),
+        }
+    }
+
+    /// Validates the mesh, and repairs it if the policy allows
+    /// Returns the problems that have been repaired
+    pub fn validate_with(
+        &mut self,
+        policy: ValidationPolicy,
+    ) -> Result<Vec<MeshProblem>, ValidationError> {
+        match policy {
+            ValidationPolicy::Reject => self.validate().map(|_| Vec::new()),
+            ValidationPolicy::Repair => {
+                let problems = self.problems();
+                if !problems.is_empty() {
+                    self.repair();
+                    self.validate()?;
+                }
+                Ok(problems)
+            }
+        }
+    }
+
+    /// Drops the incomplete, out of range, degenerate and non-finite triangles and the UV sets
+    /// of a wrong count, and regenerates broken normals and tangents
+    pub fn repair(&mut self) {
+        let triangle_count = self.indices.len() / 3;
+        self.indices.truncate(triangle_count * 3);
+
+        let positions = &self.positions;
+        let mut triangles = Vec::with_capacity(self.indices.len());
+        for triangle in self.indices.chunks_exact(3) {
+            let valid = triangle.iter().all(|&i| (i as usize) < positions.len())
+                && triangle.iter().all(|&i| positions[i as usize].is_finite())
+                && !is_degenerate(positions, triangle);
+            if valid {
+                triangles.extend_from_slice(triangle);
+            }
+        }
+        self.indices = triangles;
+
+        // only unused vertices can be left, which must not break bounding volumes
+        for position in &mut self.positions {
+            if !position.is_finite() {
+                *position = Vec3::ZERO;
+            }
+        }
+
+        let vertex_count = self.positions.len();
+        self.uv_sets.retain(|uvs| uvs.len() == vertex_count);
+
+        let invalid_normals = self.normals.iter().any(|&n| !is_direction(n));
+        if !self.has_normals() || invalid_normals {
+            self.normals.clear();
+            self.tangents.clear();
+            self.generate_missing_normals();
+        }
+        if !self.tangents.is_empty() && !self.has_tangents() {
+            self.tangents.clear();
+        }
+        if self.tangents.is_empty() {
+            self.generate_missing_tangents();
+        }
+    }
+
+    fn problems(&self) -> Vec<MeshProblem> {
+        let mut problems = Vec::new();
+        let vertex_count = self.positions.len();
+
+        if !self.indices.len().is_multiple_of(3) {
+            problems.push(MeshProblem::IndexCount(self.indices.len()));
+        }
+
+        let triangles = self.indices.chunks_exact(3);
+        if triangles.len() == 0 {
+            problems.push(MeshProblem::NoTriangles);
+        }
+
+        for (triangle, indices) in triangles.enumerate() {
+            let out_of_range = indices.iter().find(|&&i| i as usize >= vertex_count);
+            if let Some(&index) = out_of_range {
+                problems.push(MeshProblem::IndexOutOfRange { triangle, index });
+                continue;
+            }
+
+            // non-finite positions are reported by vertex
+            let finite = indices
+                .iter()
+                .all(|&i| self.positions[i as usize].is_finite());
+            if finite && is_degenerate(&self.positions, indices) {
+                problems.push(MeshProblem::DegenerateTriangle { triangle });
+            }
+        }
+
+        for (vertex, position) in self.positions.iter().enumerate() {
+            if !position.is_finite() {
+                problems.push(MeshProblem::NonFinitePosition { vertex });
+            }
+        }
+
+        if !self.has_normals() {
+            problems.push(MeshProblem::AttributeCount {
+                attribute: Attribute::Normal,
+                count: self.normals.len(),
+                expected: vertex_count,
+            });
+        } else {
+            for (vertex, &normal) in self.normals.iter().enumerate() {
+                if !is_direction(normal) {
+                    problems.push(MeshProblem::InvalidNormal { vertex });
+                }
+            }
+        }
+
+        let uv_counts = self.uv_sets.iter().map(Vec::len).enumerate();
+        for (set, count) in uv_counts.filter(|&(_, count)| count != vertex_count) {
+            problems.push(MeshProblem::AttributeCount {
+                attribute: Attribute::Uv(set),
+                count,
+                expected: vertex_count,
+            });
+        }
+
+        if !self.tangents.is_empty() && !self.has_tangents() {
+            problems.push(MeshProblem::AttributeCount {
+                attribute: Attribute::Tangent,
+                count: self.tangents.len(),
+                expected: vertex_count,
+            });
+        }
+
+        problems
+    }
+}
+
+fn is_degenerate(positions: &[Vec3], triangle: &[u32]) -> bool {
+    let [p0, p1, p2] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+    (p1 - p0).cross(p2 - p0).length_squared() <= f32::MIN_POSITIVE
+}
+
+fn is_direction(normal: Vec3) -> bool {
+    normal.is_finite() && normal.length_squared() > f32::MIN_POSITIVE
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec2, Vec4};
+
+    use super::*;
+
+    // Two triangles of a quad facing +Z
+    fn quad() -> MeshResource {
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y];
+        MeshResource::new(
+            &[0, 1, 2, 0, 2, 3],
+            &positions,
+            &[Vec3::Z; 4],
+            "quad".to_string(),
+        )
+    }
+
+    fn problems(mesh: &MeshResource) -> Vec<MeshProblem> {
+        match mesh.validate() {
+            Ok(()) => Vec::new(),
+            Err(e) => {
+                assert_eq!(e.mesh, mesh.name());
+                e.problems
+            }
+        }
+    }
+
+    #[test]
+    fn valid_mesh() {
+        let mut mesh = quad();
+        assert_eq!(mesh.validate(), Ok(()));
+        assert_eq!(mesh.validate_with(ValidationPolicy::Repair), Ok(Vec::new()));
+        assert_eq!(mesh.validate_with(ValidationPolicy::Reject), Ok(Vec::new()));
+
+        // empty UVs and tangents are not problems
+        mesh.add_uv_set(vec![Vec2::ZERO; 4]);
+        mesh.set_tangents(vec![Vec4::X; 4]);
+        assert_eq!(mesh.validate(), Ok(()));
+    }
+
+    #[test]
+    fn index_problems() {
+        let mut mesh = quad();
+        mesh.indices = vec![0, 1, 2, 0, 4, 3, 1, 1, 2, 0];
+        assert_eq!(
+            problems(&mesh),
+            [
+                MeshProblem::IndexCount(10),
+                MeshProblem::IndexOutOfRange {
+                    triangle: 1,
+                    index: 4
+                },
+                MeshProblem::DegenerateTriangle { triangle: 2 },
+            ]
+        );
+
+        mesh.indices = vec![0, 1];
+        assert_eq!(
+            problems(&mesh),
+            [MeshProblem::IndexCount(2), MeshProblem::NoTriangles]
+        );
+    }
+
+    #[test]
+    fn vertex_problems() {
+        let mut mesh = quad();
+        mesh.positions[3] = Vec3::new(f32::NAN, 0.0, 0.0);
+        mesh.normals[1] = Vec3::ZERO;
+        mesh.normals[2] = Vec3::splat(f32::INFINITY);
+
+        // the triangle with a non-finite position is not degenerate
+        assert_eq!(
+            problems(&mesh),
+            [
+                MeshProblem::NonFinitePosition { vertex: 3 },
+                MeshProblem::InvalidNormal { vertex: 1 },
+                MeshProblem::InvalidNormal { vertex: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn attribute_counts() {
+        let mut mesh = quad();
+        mesh.normals.pop();
+        mesh.add_uv_set(vec![Vec2::ZERO; 4]);
+        mesh.add_uv_set(vec![Vec2::ZERO; 5]);
+        mesh.set_tangents(vec![Vec4::X; 2]);
+
+        assert_eq!(
+            problems(&mesh),
+            [
+                MeshProblem::AttributeCount {
+                    attribute: Attribute::Normal,
+                    count: 3,
+                    expected: 4
+                },
+                MeshProblem::AttributeCount {
+                    attribute: Attribute::Uv(1),
+                    count: 5,
+                    expected: 4
+                },
+                MeshProblem::AttributeCount {
+                    attribute: Attribute::Tangent,
+                    count: 2,
+                    expected: 4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_keeps_the_mesh() {
+        let mut mesh = quad();
+        mesh.indices.extend([0, 0, 1]);
+        let before = mesh.clone();
+
+        let e = mesh.validate_with(ValidationPolicy::Reject).unwrap_err();
+        assert_eq!(
+            e.problems,
+            [MeshProblem::DegenerateTriangle { triangle: 2 }]
+        );
+        assert_eq!(mesh.indices(), before.indices());
+    }
+
+    #[test]
+    fn repair_drops_broken_triangles() {
+        let mut mesh = quad();
+        mesh.positions.push(Vec3::splat(f32::NAN));
+        mesh.normals.push(Vec3::Z);
+        mesh.indices = vec![0, 1, 2, 0, 9, 3, 1, 1, 2, 0, 2, 4, 0, 2, 3, 0];
+
+        let repaired = mesh.validate_with(ValidationPolicy::Repair).unwrap();
+        assert_eq!(
+            repaired,
+            [
+                MeshProblem::IndexCount(16),
+                MeshProblem::IndexOutOfRange {
+                    triangle: 1,
+                    index: 9
+                },
+                MeshProblem::DegenerateTriangle { triangle: 2 },
+                MeshProblem::NonFinitePosition { vertex: 4 },
+            ]
+        );
+
+        assert_eq!(mesh.indices(), [0, 1, 2, 0, 2, 3]);
+        // the unused vertex is kept at the origin
+        assert_eq!(mesh.positions().len(), 5);
+        assert_eq!(mesh.positions()[4], Vec3::ZERO);
+        assert_eq!(mesh.validate(), Ok(()));
+    }
+
+    #[test]
+    fn repair_regenerates_attributes() {
+        let mut mesh = quad();
+        mesh.normals[2] = Vec3::ZERO;
+        mesh.add_uv_set(vec![Vec2::Y, Vec2::ONE, Vec2::X, Vec2::ZERO]);
+        mesh.add_uv_set(vec![Vec2::ZERO; 3]);
+        mesh.set_tangents(vec![Vec4::X; 4]);
+
+        let repaired = mesh.validate_with(ValidationPolicy::Repair).unwrap();
+        assert_eq!(repaired.len(), 2);
+
+        // the normals are generated again with the tangents, and the broken UV set is dropped
+        assert_eq!(mesh.uv_sets().len(), 1);
+        for normal in mesh.normals() {
+            assert!(normal.abs_diff_eq(Vec3::Z, 1e-6), "{normal}");
+        }
+        assert!(mesh.has_tangents());
+        for tangent in mesh.tangents() {
+            assert!(tangent.abs_diff_eq(Vec4::new(1.0, 0.0, 0.0, 1.0), 1e-6));
+        }
+        assert_eq!(mesh.validate(), Ok(()));
+    }
+
+    #[test]
+    fn repair_replaces_tangents_of_a_wrong_count() {
+        let mut mesh = quad();
+        mesh.set_tangents(vec![Vec4::X; 3]);
+        mesh.validate_with(ValidationPolicy::Repair).unwrap();
+
+        // without UVs, no tangents are generated
+        assert!(mesh.tangents().is_empty());
+        assert_eq!(mesh.validate(), Ok(()));
+    }
+
+    #[test]
+    fn repair_fails_without_triangles() {
+        let mut mesh = quad();
+        mesh.indices = vec![0, 0, 1, 2, 3];
+
+        let e = mesh.validate_with(ValidationPolicy::Repair).unwrap_err();
+        assert_eq!(e.mesh, "quad");
+        assert_eq!(e.problems, [MeshProblem::NoTriangles]);
+        assert!(mesh.indices().is_empty());
+    }
+
+    #[test]
+    fn error_display() {
+        let problem = MeshProblem::DegenerateTriangle { triangle: 3 };
+        let e = ValidationError {
+            mesh: "mesh".to_string(),
+            problems: vec![problem.clone()],
+        };
+        assert_eq!(
+            e.to_string(),
+            "mesh has a problem:\n  triangle 3: the area is zero"
+        );
+
+        let e = ValidationError {
+            mesh: "mesh".to_string(),
+            problems: vec![problem; 10],
+        };
+        let lines: Vec<_> = e.to_string().lines().map(str::to_string).collect();
+        assert_eq!(lines.len(), 1 + MAX_DISPLAYED_PROBLEMS + 1);
+        assert_eq!(lines[0], "mesh has 10 problems:");
+        assert_eq!(lines.last().unwrap(), "  and 2 more");
+
+        let problem = MeshProblem::AttributeCount {
+            attribute: Attribute::Uv(1),
+            count: 3,
+            expected: 4,
+        };
+        assert_eq!(problem.to_string(), "there are 3 UV set 1 for 4 positions");
+    }
+}
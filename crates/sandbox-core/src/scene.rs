@@ -0,0 +1,1138 @@
+// Description of the meshes, materials, light and camera of a scene, loaded from a JSON file so that
+// setups can be tried without recompiling
+//
+// {
+//   "camera": { "eye": [0, 5, -10], "target": [0, 0, 0], "fov": 20 },
+//   "light": { "position": [3, 10, -3], "target": [0, 0, 0], "angle": 60, "intensity": 500 },
+//   "materials": {
+//     "gold": { "base_color": [1, 0.97, 0.73], "metallic": 0.75, "roughness": 0.5 }
+//   },
+//   "objects": [
+//     {
+//       "mesh": { "file": "bunny.obj" },
+//       "material": "gold",
+//       "transform": { "translation": [0, 0, 0], "rotation": [0, 0, 0], "scale": 1 },
+//       "animation": { "spin": { "axis": [0, 1, 0], "speed": 90 } },
+//       "lod": { "max_error": 0.01 }
+//     }
+//   ]
+// }
+//
+// Angles are in degrees and speeds in degrees per second
+// Mesh files are .obj, .ply, .gltf or .glb, and "material" is optional if the file has materials
+// Instead of "file", a mesh can be one of the primitives, whose counts are optional:
+//   "plane": { "size": 10, "subdivisions": 1 }, "box": { "size": [1, 2, 1] } or { "size": 1 },
+//   "sphere": { "radius": 1, "segments": 32, "rings": 16 },
+//   "icosphere": { "radius": 1, "subdivisions": 3 },
+//   "cylinder": { "radius": 1, "height": 2, "segments": 32 },
+//   "cone": { "radius": 1, "height": 2, "segments": 32 },
+//   "torus": { "radius": 1, "tube_radius": 0.25, "segments": 48, "sides": 24 },
+//   "cornell_box": { "size": 10 }, which has its own materials
+// "lod" simplifies the mesh into LODs within "max_error" times its size, which defaults to 0.01
+// "mesh_validation" is "repair" (default) to drop broken triangles of the mesh files, or "reject"
+// "mesh_cache" is a directory to keep the OBJ meshes in a binary format, so that they load faster
+// Relative mesh files and the cache are resolved against the directory of the scene file
+
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use super::camera::Camera;
+use super::json::{self, Value};
+use super::light::SpotLight;
+use super::math::*;
+use super::mesh::primitives::Primitive;
+use super::mesh::{gltf::GltfError, obj::ObjError, ply::PlyError, Material, ValidationPolicy};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneDesc {
+    pub camera: CameraDesc,
+    pub light: LightDesc,
+    pub objects: Vec<ObjectDesc>,
+    /// Applied to the meshes of the files
+    pub mesh_validation: ValidationPolicy,
+    /// Where the meshes imported from OBJ files are cached, None to always import them
+    pub mesh_cache: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraDesc {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    /// Vertical field of view in degrees
+    pub fov: f32,
+}
+
+impl CameraDesc {
+    pub fn camera(&self, viewport_width: u32, viewport_height: u32) -> Camera {
+        let mut camera = Camera::new(viewport_width, viewport_height);
+        camera.look_at(self.eye, self.target, self.up, self.fov.to_radians());
+        camera
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightDesc {
+    pub position: Vec3,
+    /// Normalized
+    pub direction: Vec3,
+    /// The full angle of the cone in degrees
+    pub angle: f32,
+    pub intensity: f32,
+}
+
+impl LightDesc {
+    pub fn spot_light(&self) -> SpotLight {
+        SpotLight::new(
+            self.position,
+            self.intensity,
+            self.direction,
+            self.angle.to_radians(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectDesc {
+    pub name: Option<String>,
+    pub mesh: MeshDesc,
+    /// Overrides the materials of the mesh file, required if the file has none
+    pub material: Option<Material>,
+    pub transform: Transform,
+    pub animation: Animation,
+    /// None to always draw the mesh itself
+    pub lod: Option<LodDesc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodDesc {
+    /// Relative to the size of the mesh, see `MeshResource::lod_chain`
+    pub max_error: f32,
+}
+
+impl Default for LodDesc {
+    fn default() -> Self {
+        Self { max_error: 0.01 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeshDesc {
+    /// An .obj, .ply, .gltf or .glb file
+    File(PathBuf),
+    /// A procedural mesh, see `mesh::primitives`
+    Primitive(Primitive),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    /// Euler angles in degrees, applied in the order of X, Y and Z
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Vec3::ZERO,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Mat4 {
+        let rotation = self.rotation * std::f32::consts::PI / 180.0;
+        let rotation = Quat::from_euler(EulerRot::XYZ, rotation.x, rotation.y, rotation.z);
+        Mat4::from_scale_rotation_translation(self.scale, rotation, self.translation)
+    }
+}
+
+/// Applied in the object space, before `Transform`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Animation {
+    #[default]
+    Static,
+    /// Rotates around `axis` at `speed` degrees per second
+    Spin { axis: Vec3, speed: f32 },
+}
+
+impl Animation {
+    pub fn matrix(&self, time: f64) -> Mat4 {
+        match self {
+            Animation::Static => Mat4::IDENTITY,
+            Animation::Spin { axis, speed } => {
+                let angle = (time * *speed as f64) % 360.0;
+                Mat4::from_axis_angle(axis.normalize(), angle.to_radians() as f32)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(PathBuf, std::io::Error),
+    Parse(json::ParseError),
+    /// A mesh file of the scene failed to load
+    Obj(PathBuf, ObjError),
+    Gltf(PathBuf, GltfError),
+    Ply(PathBuf, PlyError),
+    /// `path` is where the invalid value is in the document, such as `objects[1].material`
+    Invalid {
+        path: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(path, e) => write!(f, "Failed to read {}: {e}", path.display()),
+            SceneError::Parse(e) => write!(f, "Invalid JSON: {e}"),
+            SceneError::Obj(path, e) => write!(f, "Failed to load {}: {e}", path.display()),
+            SceneError::Gltf(path, e) => write!(f, "Failed to load {}: {e}", path.display()),
+            SceneError::Ply(path, e) => write!(f, "Failed to load {}: {e}", path.display()),
+            SceneError::Invalid { path, message } => write!(f, "{path}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io(_, e) => Some(e),
+            SceneError::Parse(e) => Some(e),
+            SceneError::Obj(_, e) => Some(e),
+            SceneError::Gltf(_, e) => Some(e),
+            SceneError::Ply(_, e) => Some(e),
+            SceneError::Invalid { .. } => None,
+        }
+    }
+}
+
+impl From<json::ParseError> for SceneError {
+    fn from(e: json::ParseError) -> Self {
+        SceneError::Parse(e)
+    }
+}
+
+/// Reads a scene file and resolves its relative mesh files and mesh cache against the directory of
+/// the file
+pub fn load(path: &Path) -> Result<SceneDesc, SceneError> {
+    let text = std::fs::read_to_string(path).map_err(|e| SceneError::Io(path.to_path_buf(), e))?;
+    let mut scene = parse(&text)?;
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let files = scene
+        .objects
+        .iter_mut()
+        .filter_map(|object| match &mut object.mesh {
+            MeshDesc::File(file) => Some(file),
+            _ => None,
+        });
+    for file in files.chain(&mut scene.mesh_cache) {
+        if file.is_relative() {
+            *file = dir.join(&*file);
+        }
+    }
+
+    Ok(scene)
+}
+
+/// Parses and validates a scene, mesh files are kept as written
+pub fn parse(text: &str) -> Result<SceneDesc, SceneError> {
+    let root = json::parse(text)?;
+    let members = object(
+        &root,
+        "",
+        &[
+            "camera",
+            "light",
+            "materials",
+            "objects",
+            "mesh_validation",
+            "mesh_cache",
+        ],
+    )?;
+
+    let materials = match members.get("materials") {
+        Some(value) => materials(value, "materials")?,
+        None => Vec::new(),
+    };
+
+    let objects_value = members.required("objects")?;
+    let objects = array(objects_value, "objects")?
+        .iter()
+        .enumerate()
+        .map(|(i, value)| object_desc(value, &format!("objects[{i}]"), &materials))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if objects.is_empty() {
+        return Err(invalid("objects", "the scene has no object"));
+    }
+
+    let mesh_validation = match members.get("mesh_validation") {
+        Some(value) => match string(value, "mesh_validation")? {
+            "reject" => ValidationPolicy::Reject,
+            "repair" => ValidationPolicy::Repair,
+            _ => {
+                return Err(invalid(
+                    "mesh_validation",
+                    "must be \"reject\" or \"repair\"",
+                ))
+            }
+        },
+        None => ValidationPolicy::default(),
+    };
+
+    let mesh_cache = match members.get("mesh_cache") {
+        Some(value) => Some(PathBuf::from(string(value, "mesh_cache")?)),
+        None => None,
+    };
+
+    Ok(SceneDesc {
+        camera: camera_desc(members.required("camera")?, "camera")?,
+        light: light_desc(members.required("light")?, "light")?,
+        objects,
+        mesh_validation,
+        mesh_cache,
+    })
+}
+
+fn camera_desc(value: &Value, path: &str) -> Result<CameraDesc, SceneError> {
+    let members = object(value, path, &["eye", "target", "up", "fov"])?;
+
+    let eye = vec3(members.required("eye")?, &members.path("eye"))?;
+    let target = vec3(members.required("target")?, &members.path("target"))?;
+    let up = match members.get("up") {
+        Some(up) => vec3(up, &members.path("up"))?,
+        None => Vec3::Y,
+    };
+    let fov = number(members.required("fov")?, &members.path("fov"))?;
+
+    if eye == target {
+        return Err(invalid(
+            path,
+            "the eye and the target are at the same position",
+        ));
+    }
+    if up.cross(target - eye).length_squared() == 0.0 {
+        return Err(invalid(
+            &members.path("up"),
+            "must not be zero or parallel to the view direction",
+        ));
+    }
+    check_angle(fov, &members.path("fov"))?;
+
+    Ok(CameraDesc {
+        eye,
+        target,
+        up,
+        fov,
+    })
+}
+
+fn light_desc(value: &Value, path: &str) -> Result<LightDesc, SceneError> {
+    let members = object(
+        value,
+        path,
+        &["position", "target", "direction", "angle", "intensity"],
+    )?;
+
+    let position = vec3(members.required("position")?, &members.path("position"))?;
+
+    let direction = match (members.get("target"), members.get("direction")) {
+        (Some(target), None) => vec3(target, &members.path("target"))? - position,
+        (None, Some(direction)) => vec3(direction, &members.path("direction"))?,
+        _ => {
+            return Err(invalid(
+                path,
+                "either \"target\" or \"direction\" must be specified",
+            ))
+        }
+    };
+    if direction.length_squared() == 0.0 {
+        return Err(invalid(path, "the direction of the light is zero"));
+    }
+
+    let angle = number(members.required("angle")?, &members.path("angle"))?;
+    check_angle(angle, &members.path("angle"))?;
+
+    let intensity = number(members.required("intensity")?, &members.path("intensity"))?;
+    if intensity < 0.0 {
+        return Err(invalid(&members.path("intensity"), "must not be negative"));
+    }
+
+    Ok(LightDesc {
+        position,
+        direction: direction.normalize(),
+        angle,
+        intensity,
+    })
+}
+
+fn materials(value: &Value, path: &str) -> Result<Vec<(String, Material)>, SceneError> {
+    let Some(members) = value.as_object() else {
+        return Err(type_error(value, path, "an object"));
+    };
+
+    members
+        .iter()
+        .map(|(name, value)| Ok((name.clone(), material(value, &member_path(path, name))?)))
+        .collect()
+}
+
+fn material(value: &Value, path: &str) -> Result<Material, SceneError> {
+    let members = object(
+        value,
+        path,
+        &[
+            "base_color",
+            "metallic",
+            "specular_reflectance",
+            "roughness",
+            "specular_tint",
+        ],
+    )?;
+
+    let color = |key: &str| -> Result<Vec3, SceneError> {
+        let Some(value) = members.get(key) else {
+            return Ok(Vec3::ZERO);
+        };
+        let color = vec3(value, &members.path(key))?;
+        if color.min_element() < 0.0 {
+            return Err(invalid(&members.path(key), "must not be negative"));
+        }
+        Ok(color)
+    };
+
+    let factor = |key: &str, default: f32| -> Result<f32, SceneError> {
+        let Some(value) = members.get(key) else {
+            return Ok(default);
+        };
+        let factor = number(value, &members.path(key))?;
+        if !(0.0..=1.0).contains(&factor) {
+            return Err(invalid(&members.path(key), "must be between 0 and 1"));
+        }
+        Ok(factor)
+    };
+
+    // defaults to a rough dielectric
+    Ok(Material {
+        base_color: color("base_color")?,
+        metallic: factor("metallic", 0.0)?,
+        specular_reflectance: color("specular_reflectance")?,
+        roughness: factor("roughness", 1.0)?,
+        specular_tint: color("specular_tint")?,
+        pad: Default::default(),
+    })
+}
+
+fn object_desc(
+    value: &Value,
+    path: &str,
+    materials: &[(String, Material)],
+) -> Result<ObjectDesc, SceneError> {
+    let members = object(
+        value,
+        path,
+        &["name", "mesh", "material", "transform", "animation", "lod"],
+    )?;
+
+    let name = match members.get("name") {
+        Some(name) => Some(string(name, &members.path("name"))?.to_string()),
+        None => None,
+    };
+
+    let mesh = mesh_desc(members.required("mesh")?, &members.path("mesh"))?;
+
+    let material = match members.get("material") {
+        Some(material) => {
+            let material_path = members.path("material");
+            let material_name = string(material, &material_path)?;
+            let Some((_, material)) = materials.iter().find(|(name, _)| name == material_name)
+            else {
+                return Err(invalid(
+                    &material_path,
+                    &format!("unknown material \"{material_name}\""),
+                ));
+            };
+            Some(material.clone())
+        }
+        None => None,
+    };
+
+    let transform = match members.get("transform") {
+        Some(transform) => transform_desc(transform, &members.path("transform"))?,
+        None => Transform::default(),
+    };
+
+    let animation = match members.get("animation") {
+        Some(animation) => animation_desc(animation, &members.path("animation"))?,
+        None => Animation::Static,
+    };
+
+    let lod = match members.get("lod") {
+        Some(lod) => Some(lod_desc(lod, &members.path("lod"))?),
+        None => None,
+    };
+
+    Ok(ObjectDesc {
+        name,
+        mesh,
+        material,
+        transform,
+        animation,
+        lod,
+    })
+}
+
+const MESH_KINDS: [&str; 9] = [
+    "file",
+    "plane",
+    "box",
+    "sphere",
+    "icosphere",
+    "cylinder",
+    "cone",
+    "torus",
+    "cornell_box",
+];
+
+// Enough for any test scene, while keeping the meshes within memory
+const MAX_SEGMENTS: u32 = 1024;
+
+fn mesh_desc(value: &Value, path: &str) -> Result<MeshDesc, SceneError> {
+    let members = object(value, path, &MESH_KINDS)?;
+
+    let [(kind, value)] = members.members else {
+        return Err(invalid(
+            path,
+            &format!("exactly one of {} must be specified", MESH_KINDS.join(", ")),
+        ));
+    };
+    let path = members.path(kind);
+
+    if kind == "file" {
+        let file = string(value, &path)?;
+        return Ok(MeshDesc::File(PathBuf::from(file)));
+    }
+
+    let primitive = match kind.as_str() {
+        "plane" => {
+            let plane = object(value, &path, &["size", "subdivisions"])?;
+            Primitive::Plane {
+                size: positive(plane.required("size")?, &plane.path("size"))?,
+                subdivisions: count(&plane, "subdivisions", 1, 1..=MAX_SEGMENTS)?,
+            }
+        }
+        "box" => {
+            let cuboid = object(value, &path, &["size"])?;
+            let size = cuboid.required("size")?;
+            let size = match size.as_array() {
+                Some(_) => vec3(size, &cuboid.path("size"))?,
+                None => Vec3::splat(number(size, &cuboid.path("size"))?),
+            };
+            if size.min_element() <= 0.0 {
+                return Err(invalid(&cuboid.path("size"), "must be positive"));
+            }
+            Primitive::Box { size }
+        }
+        "sphere" => {
+            let sphere = object(value, &path, &["radius", "segments", "rings"])?;
+            Primitive::UvSphere {
+                radius: positive(sphere.required("radius")?, &sphere.path("radius"))?,
+                segments: count(&sphere, "segments", 32, 3..=MAX_SEGMENTS)?,
+                rings: count(&sphere, "rings", 16, 2..=MAX_SEGMENTS)?,
+            }
+        }
+        "icosphere" => {
+            let sphere = object(value, &path, &["radius", "subdivisions"])?;
+            Primitive::Icosphere {
+                radius: positive(sphere.required("radius")?, &sphere.path("radius"))?,
+                subdivisions: count(&sphere, "subdivisions", 3, 0..=8)?,
+            }
+        }
+        "cylinder" | "cone" => {
+            let shape = object(value, &path, &["radius", "height", "segments"])?;
+            let radius = positive(shape.required("radius")?, &shape.path("radius"))?;
+            let height = positive(shape.required("height")?, &shape.path("height"))?;
+            let segments = count(&shape, "segments", 32, 3..=MAX_SEGMENTS)?;
+            match kind == "cylinder" {
+                true => Primitive::Cylinder {
+                    radius,
+                    height,
+                    segments,
+                },
+                false => Primitive::Cone {
+                    radius,
+                    height,
+                    segments,
+                },
+            }
+        }
+        "torus" => {
+            let torus = object(
+                value,
+                &path,
+                &["radius", "tube_radius", "segments", "sides"],
+            )?;
+            Primitive::Torus {
+                radius: positive(torus.required("radius")?, &torus.path("radius"))?,
+                tube_radius: positive(torus.required("tube_radius")?, &torus.path("tube_radius"))?,
+                segments: count(&torus, "segments", 48, 3..=MAX_SEGMENTS)?,
+                sides: count(&torus, "sides", 24, 3..=MAX_SEGMENTS)?,
+            }
+        }
+        _ => {
+            let cornell_box = object(value, &path, &["size"])?;
+            Primitive::CornellBox {
+                size: positive(cornell_box.required("size")?, &cornell_box.path("size"))?,
+            }
+        }
+    };
+
+    Ok(MeshDesc::Primitive(primitive))
+}
+
+fn lod_desc(value: &Value, path: &str) -> Result<LodDesc, SceneError> {
+    let members = object(value, path, &["max_error"])?;
+    let mut lod = LodDesc::default();
+
+    if let Some(max_error) = members.get("max_error") {
+        lod.max_error = number(max_error, &members.path("max_error"))?;
+        if lod.max_error <= 0.0 {
+            return Err(invalid(&members.path("max_error"), "must be positive"));
+        }
+    }
+
+    Ok(lod)
+}
+
+fn transform_desc(value: &Value, path: &str) -> Result<Transform, SceneError> {
+    let members = object(value, path, &["translation", "rotation", "scale"])?;
+    let mut transform = Transform::default();
+
+    if let Some(translation) = members.get("translation") {
+        transform.translation = vec3(translation, &members.path("translation"))?;
+    }
+    if let Some(rotation) = members.get("rotation") {
+        transform.rotation = vec3(rotation, &members.path("rotation"))?;
+    }
+    if let Some(scale) = members.get("scale") {
+        // a single number scales uniformly
+        let scale_path = members.path("scale");
+        transform.scale = match scale {
+            Value::Number(_) => Vec3::splat(number(scale, &scale_path)?),
+            _ => vec3(scale, &scale_path)?,
+        };
+        if transform.scale.cmpeq(Vec3::ZERO).any() {
+            return Err(invalid(&scale_path, "must not be zero"));
+        }
+    }
+
+    Ok(transform)
+}
+
+fn animation_desc(value: &Value, path: &str) -> Result<Animation, SceneError> {
+    let members = object(value, path, &["spin"])?;
+
+    let Some(spin) = members.get("spin") else {
+        return Ok(Animation::Static);
+    };
+
+    let path = members.path("spin");
+    let spin = object(spin, &path, &["axis", "speed"])?;
+
+    let axis = match spin.get("axis") {
+        Some(axis) => vec3(axis, &spin.path("axis"))?,
+        None => Vec3::Y,
+    };
+    if axis.length_squared() == 0.0 {
+        return Err(invalid(&spin.path("axis"), "must not be zero"));
+    }
+
+    let speed = number(spin.required("speed")?, &spin.path("speed"))?;
+
+    Ok(Animation::Spin { axis, speed })
+}
+
+// The members of a JSON object, which remembers where it is in the document for error messages
+struct Members<'a> {
+    path: &'a str,
+    members: &'a [(String, Value)],
+}
+
+impl<'a> Members<'a> {
+    fn get(&self, key: &str) -> Option<&'a Value> {
+        self.members.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    fn required(&self, key: &str) -> Result<&'a Value, SceneError> {
+        self.get(key)
+            .ok_or_else(|| invalid(self.path, &format!("\"{key}\" is missing")))
+    }
+
+    fn path(&self, key: &str) -> String {
+        member_path(self.path, key)
+    }
+}
+
+// Unknown keys are errors, so that typos are not silently ignored
+fn object<'a>(value: &'a Value, path: &'a str, keys: &[&str]) -> Result<Members<'a>, SceneError> {
+    let Some(members) = value.as_object() else {
+        return Err(type_error(value, path, "an object"));
+    };
+
+    if let Some((key, _)) = members
+        .iter()
+        .find(|(key, _)| !keys.contains(&key.as_str()))
+    {
+        return Err(invalid(
+            path,
+            &format!("unknown key \"{key}\", expected one of {}", keys.join(", ")),
+        ));
+    }
+
+    Ok(Members { path, members })
+}
+
+fn array<'a>(value: &'a Value, path: &str) -> Result<&'a [Value], SceneError> {
+    value
+        .as_array()
+        .ok_or_else(|| type_error(value, path, "an array"))
+}
+
+fn string<'a>(value: &'a Value, path: &str) -> Result<&'a str, SceneError> {
+    value
+        .as_str()
+        .ok_or_else(|| type_error(value, path, "a string"))
+}
+
+fn number(value: &Value, path: &str) -> Result<f32, SceneError> {
+    let number = value
+        .as_f64()
+        .ok_or_else(|| type_error(value, path, "a number"))? as f32;
+
+    if !number.is_finite() {
+        return Err(invalid(path, "the number is out of range"));
+    }
+
+    Ok(number)
+}
+
+fn positive(value: &Value, path: &str) -> Result<f32, SceneError> {
+    let number = number(value, path)?;
+    if number <= 0.0 {
+        return Err(invalid(path, "must be positive"));
+    }
+
+    Ok(number)
+}
+
+// An optional integer within `range`
+fn count(
+    members: &Members,
+    key: &str,
+    default: u32,
+    range: RangeInclusive<u32>,
+) -> Result<u32, SceneError> {
+    let Some(value) = members.get(key) else {
+        return Ok(default);
+    };
+
+    let path = members.path(key);
+    let count = number(value, &path)?;
+    let (min, max) = (*range.start(), *range.end());
+    if count.fract() != 0.0 || count < min as f32 || count > max as f32 {
+        return Err(invalid(
+            &path,
+            &format!("must be an integer between {min} and {max}"),
+        ));
+    }
+
+    Ok(count as u32)
+}
+
+fn vec3(value: &Value, path: &str) -> Result<Vec3, SceneError> {
+    let elements = value
+        .as_array()
+        .filter(|elements| elements.len() == 3)
+        .ok_or_else(|| type_error(value, path, "an array of 3 numbers"))?;
+
+    let mut v = [0.0; 3];
+    for (i, element) in elements.iter().enumerate() {
+        v[i] = number(element, &format!("{path}[{i}]"))?;
+    }
+
+    Ok(Vec3::from_array(v))
+}
+
+fn check_angle(angle: f32, path: &str) -> Result<(), SceneError> {
+    if angle > 0.0 && angle < 180.0 {
+        Ok(())
+    } else {
+        Err(invalid(path, "must be between 0 and 180 degrees"))
+    }
+}
+
+fn member_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn type_error(value: &Value, path: &str, expected: &str) -> SceneError {
+    invalid(
+        path,
+        &format!("expected {expected}, found {}", value.type_name()),
+    )
+}
+
+fn invalid(path: &str, message: &str) -> SceneError {
+    let path = if path.is_empty() { "<root>" } else { path };
+
+    SceneError::Invalid {
+        path: path.to_string(),
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str = r#""camera": { "eye": [0, 5, -10], "target": [0, 0, 0], "fov": 20 }"#;
+    const LIGHT: &str = r#""light": { "position": [3, 10, -3], "target": [3, 0, -3], "angle": 60, "intensity": 500 }"#;
+
+    fn scene(objects: &str) -> String {
+        format!(r#"{{ {CAMERA}, {LIGHT}, "objects": [{objects}] }}"#)
+    }
+
+    // the scene with a single object and the given members added to the root
+    fn scene_with(members: &str, object: &str) -> String {
+        format!(r#"{{ {CAMERA}, {LIGHT}, {members}, "objects": [{object}] }}"#)
+    }
+
+    fn mesh(objects: &str) -> MeshDesc {
+        parse(&scene(objects)).unwrap().objects[0].mesh.clone()
+    }
+
+    // the path and the message of the validation error
+    fn invalid(text: &str) -> (String, String) {
+        match parse(text) {
+            Err(SceneError::Invalid { path, message }) => (path, message),
+            result => panic!("expected a validation error, found {result:?}"),
+        }
+    }
+
+    #[test]
+    fn minimal_scene_has_defaults() {
+        let scene = parse(&scene(r#"{ "mesh": { "file": "bunny.obj" } }"#)).unwrap();
+
+        assert_eq!(
+            scene.camera,
+            CameraDesc {
+                eye: Vec3::new(0.0, 5.0, -10.0),
+                target: Vec3::ZERO,
+                up: Vec3::Y,
+                fov: 20.0,
+            }
+        );
+        assert_eq!(
+            scene.light,
+            LightDesc {
+                position: Vec3::new(3.0, 10.0, -3.0),
+                direction: Vec3::NEG_Y,
+                angle: 60.0,
+                intensity: 500.0,
+            }
+        );
+        assert_eq!(
+            scene.objects,
+            [ObjectDesc {
+                name: None,
+                mesh: MeshDesc::File(PathBuf::from("bunny.obj")),
+                material: None,
+                transform: Transform::default(),
+                animation: Animation::Static,
+                lod: None,
+            }]
+        );
+        assert_eq!(scene.mesh_validation, ValidationPolicy::Repair);
+        assert_eq!(scene.mesh_cache, None);
+    }
+
+    #[test]
+    fn full_object() {
+        let text = scene_with(
+            r#""materials": {
+                "gold": { "base_color": [1, 0.97, 0.73], "metallic": 0.75, "roughness": 0.5 }
+            },
+            "mesh_validation": "reject",
+            "mesh_cache": "cache""#,
+            r#"{
+                "name": "bunny",
+                "mesh": { "file": "bunny.obj" },
+                "material": "gold",
+                "transform": { "translation": [1, 2, 3], "rotation": [0, 90, 0], "scale": 2 },
+                "animation": { "spin": { "axis": [0, 0, 2], "speed": 90 } },
+                "lod": {}
+            }"#,
+        );
+        let scene = parse(&text).unwrap();
+
+        let object = &scene.objects[0];
+        assert_eq!(object.name.as_deref(), Some("bunny"));
+        assert_eq!(
+            object.material,
+            Some(Material {
+                base_color: Vec3::new(1.0, 0.97, 0.73),
+                metallic: 0.75,
+                specular_reflectance: Vec3::ZERO,
+                roughness: 0.5,
+                specular_tint: Vec3::ZERO,
+                pad: 0,
+            })
+        );
+        assert_eq!(
+            object.transform,
+            Transform {
+                translation: Vec3::new(1.0, 2.0, 3.0),
+                rotation: Vec3::new(0.0, 90.0, 0.0),
+                scale: Vec3::splat(2.0),
+            }
+        );
+        assert_eq!(
+            object.animation,
+            Animation::Spin {
+                axis: Vec3::new(0.0, 0.0, 2.0),
+                speed: 90.0,
+            }
+        );
+        assert_eq!(object.lod, Some(LodDesc { max_error: 0.01 }));
+        assert_eq!(scene.mesh_validation, ValidationPolicy::Reject);
+        assert_eq!(scene.mesh_cache, Some(PathBuf::from("cache")));
+    }
+
+    #[test]
+    fn primitives() {
+        assert_eq!(
+            mesh(r#"{ "mesh": { "sphere": { "radius": 2 } } }"#),
+            MeshDesc::Primitive(Primitive::UvSphere {
+                radius: 2.0,
+                segments: 32,
+                rings: 16,
+            })
+        );
+        assert_eq!(
+            mesh(r#"{ "mesh": { "box": { "size": 2 } } }"#),
+            MeshDesc::Primitive(Primitive::Box {
+                size: Vec3::splat(2.0),
+            })
+        );
+        assert_eq!(
+            mesh(r#"{ "mesh": { "box": { "size": [1, 2, 3] } } }"#),
+            MeshDesc::Primitive(Primitive::Box {
+                size: Vec3::new(1.0, 2.0, 3.0),
+            })
+        );
+        assert_eq!(
+            mesh(r#"{ "mesh": { "torus": { "radius": 1, "tube_radius": 0.25, "sides": 8 } } }"#),
+            MeshDesc::Primitive(Primitive::Torus {
+                radius: 1.0,
+                tube_radius: 0.25,
+                segments: 48,
+                sides: 8,
+            })
+        );
+        assert_eq!(
+            mesh(r#"{ "mesh": { "cone": { "radius": 1, "height": 2, "segments": 3 } } }"#),
+            MeshDesc::Primitive(Primitive::Cone {
+                radius: 1.0,
+                height: 2.0,
+                segments: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn light_direction() {
+        let light = r#""light": { "position": [0, 1, 0], "direction": [2, 0, 0], "angle": 30, "intensity": 1 }"#;
+        let text =
+            format!(r#"{{ {CAMERA}, {light}, "objects": [{{ "mesh": {{ "file": "a.obj" }} }}] }}"#);
+        assert_eq!(parse(&text).unwrap().light.direction, Vec3::X);
+
+        let light = r#""light": { "position": [0, 1, 0], "target": [1, 0, 0], "direction": [1, 0, 0], "angle": 30, "intensity": 1 }"#;
+        let text =
+            format!(r#"{{ {CAMERA}, {light}, "objects": [{{ "mesh": {{ "file": "a.obj" }} }}] }}"#);
+        assert_eq!(
+            invalid(&text),
+            (
+                "light".to_string(),
+                "either \"target\" or \"direction\" must be specified".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn validation_errors_have_paths() {
+        let cases = [
+            (
+                scene(r#"{ "mesh": { "file": "a.obj" }, "colour": 1 }"#),
+                "objects[0]",
+                "unknown key \"colour\", expected one of name, mesh, material, transform, animation, lod",
+            ),
+            (
+                scene(r#"{ "mesh": { "file": "a.obj" }, "material": "gold" }"#),
+                "objects[0].material",
+                "unknown material \"gold\"",
+            ),
+            (
+                scene(r#"{ "mesh": { "file": "a.obj", "box": { "size": 1 } } }"#),
+                "objects[0].mesh",
+                "exactly one of file, plane, box, sphere, icosphere, cylinder, cone, torus, cornell_box must be specified",
+            ),
+            (
+                scene(r#"{ "mesh": { "sphere": { "radius": 1, "segments": 2.5 } } }"#),
+                "objects[0].mesh.sphere.segments",
+                "must be an integer between 3 and 1024",
+            ),
+            (
+                scene(r#"{ "mesh": { "plane": { "size": 0 } } }"#),
+                "objects[0].mesh.plane.size",
+                "must be positive",
+            ),
+            (
+                scene(r#"{ "mesh": { "file": "a.obj" }, "transform": { "scale": [1, 0, 1] } }"#),
+                "objects[0].transform.scale",
+                "must not be zero",
+            ),
+            (
+                scene(r#"{ "mesh": { "file": "a.obj" }, "transform": { "rotation": [0, "90", 0] } }"#),
+                "objects[0].transform.rotation[1]",
+                "expected a number, found a string",
+            ),
+            (
+                scene(r#"{ "mesh": { "file": "a.obj" }, "lod": { "max_error": -1 } }"#),
+                "objects[0].lod.max_error",
+                "must be positive",
+            ),
+            (
+                scene(r#"{ "mesh": { "file": "a.obj" }, "animation": { "spin": { "axis": [0, 0, 0], "speed": 1 } } }"#),
+                "objects[0].animation.spin.axis",
+                "must not be zero",
+            ),
+            (scene(""), "objects", "the scene has no object"),
+            (
+                format!(r#"{{ {CAMERA}, {LIGHT} }}"#),
+                "<root>",
+                "\"objects\" is missing",
+            ),
+            (
+                scene_with(
+                    r#""mesh_validation": "ignore""#,
+                    r#"{ "mesh": { "file": "a.obj" } }"#,
+                ),
+                "mesh_validation",
+                "must be \"reject\" or \"repair\"",
+            ),
+            (
+                scene_with(
+                    r#""materials": { "red": { "base_color": [1, 0, 0], "roughness": 2 } }"#,
+                    r#"{ "mesh": { "file": "a.obj" } }"#,
+                ),
+                "materials.red.roughness",
+                "must be between 0 and 1",
+            ),
+            ("[]".to_string(), "<root>", "expected an object, found an array"),
+        ];
+
+        for (text, path, message) in cases {
+            assert_eq!(invalid(&text), (path.to_string(), message.to_string()));
+        }
+    }
+
+    #[test]
+    fn camera_errors() {
+        let text = |camera: &str| {
+            format!(
+                r#"{{ "camera": {camera}, {LIGHT}, "objects": [{{ "mesh": {{ "file": "a.obj" }} }}] }}"#
+            )
+        };
+
+        let cases = [
+            (
+                r#"{ "eye": [0, 0, 1], "target": [0, 0, 1], "fov": 20 }"#,
+                "camera",
+                "the eye and the target are at the same position",
+            ),
+            (
+                r#"{ "eye": [0, 1, 0], "target": [0, 0, 0], "fov": 20 }"#,
+                "camera.up",
+                "must not be zero or parallel to the view direction",
+            ),
+            (
+                r#"{ "eye": [0, 0, 1], "target": [0, 0, 0], "fov": 180 }"#,
+                "camera.fov",
+                "must be between 0 and 180 degrees",
+            ),
+            (
+                r#"{ "eye": [0, 0], "target": [0, 0, 0], "fov": 20 }"#,
+                "camera.eye",
+                "expected an array of 3 numbers, found an array",
+            ),
+        ];
+
+        for (camera, path, message) in cases {
+            assert_eq!(
+                invalid(&text(camera)),
+                (path.to_string(), message.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn json_errors() {
+        let e = parse(r#"{ "objects": [1, 2,] }"#).unwrap_err();
+        assert!(matches!(e, SceneError::Parse(_)), "{e}");
+
+        let e = parse(r#"{ "objects": [], "objects": [] }"#).unwrap_err();
+        assert!(e.to_string().contains("duplicated key \"objects\""), "{e}");
+    }
+
+    #[test]
+    fn load_resolves_paths_against_the_scene_file() {
+        let dir = std::env::temp_dir().join(format!("sandbox-scene-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scene.json");
+
+        let absolute = dir.join("absolute.obj");
+        let objects = format!(
+            r#"{{ "mesh": {{ "file": "meshes/a.obj" }} }}, {{ "mesh": {{ "file": {} }} }}, {{ "mesh": {{ "plane": {{ "size": 1 }} }} }}"#,
+            json::quote(absolute.to_str().unwrap())
+        );
+        let text =
+            format!(r#"{{ {CAMERA}, {LIGHT}, "mesh_cache": "cache", "objects": [{objects}] }}"#);
+        std::fs::write(&path, text).unwrap();
+
+        let scene = load(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let scene = scene.unwrap();
+
+        assert_eq!(
+            scene.objects[0].mesh,
+            MeshDesc::File(dir.join("meshes/a.obj"))
+        );
+        assert_eq!(scene.objects[1].mesh, MeshDesc::File(absolute));
+        assert_eq!(scene.mesh_cache, Some(dir.join("cache")));
+
+        let e = load(&dir.join("missing.json")).unwrap_err();
+        assert!(matches!(e, SceneError::Io(..)), "{e}");
+    }
+}